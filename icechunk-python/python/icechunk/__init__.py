@@ -1,10 +1,14 @@
 # module
 
 from icechunk._icechunk_python import (
+    ArchiveSummary,
     AzureCredentials,
     AzureStaticCredentials,
     BasicConflictSolver,
+    BranchPolicy,
     CachingConfig,
+    ChunksWithinShape,
+    CommitValidator,
     CompressionAlgorithm,
     CompressionConfig,
     Conflict,
@@ -14,24 +18,34 @@ from icechunk._icechunk_python import (
     ConflictType,
     Credentials,
     Diff,
+    DimensionNamesRequired,
     GcsBearerCredential,
     GcsCredentials,
     GcsStaticCredentials,
     GCSummary,
+    HistoryEdit,
     IcechunkError,
     ManifestConfig,
     ManifestPreloadCondition,
     ManifestPreloadConfig,
     ObjectStoreConfig,
+    PeriodicRetention,
     RebaseFailedData,
     RepositoryConfig,
+    RequiredAttributes,
+    RetentionPeriod,
+    RetentionPolicy,
     S3Credentials,
     S3Options,
     S3StaticCredentials,
+    ArrayStorageStats,
     SnapshotInfo,
+    SpillConfig,
     Storage,
     StorageConcurrencySettings,
     StorageSettings,
+    StorageStats,
+    StorageUsage,
     VersionSelection,
     VirtualChunkContainer,
     VirtualChunkSpec,
@@ -83,10 +97,15 @@ __all__ = [
     "AnyGcsStaticCredential",
     "AnyObjectStoreConfig",
     "AnyS3Credential",
+    "ArchiveSummary",
+    "ArrayStorageStats",
     "AzureCredentials",
     "AzureStaticCredentials",
     "BasicConflictSolver",
+    "BranchPolicy",
     "CachingConfig",
+    "ChunksWithinShape",
+    "CommitValidator",
     "CompressionAlgorithm",
     "CompressionConfig",
     "Conflict",
@@ -97,28 +116,37 @@ __all__ = [
     "ConflictType",
     "Credentials",
     "Diff",
+    "DimensionNamesRequired",
     "GCSummary",
     "GcsBearerCredential",
     "GcsCredentials",
     "GcsStaticCredentials",
+    "HistoryEdit",
     "IcechunkError",
     "IcechunkStore",
     "ManifestConfig",
     "ManifestPreloadCondition",
     "ManifestPreloadConfig",
     "ObjectStoreConfig",
+    "PeriodicRetention",
     "RebaseFailedData",
     "RebaseFailedError",
     "Repository",
     "RepositoryConfig",
+    "RequiredAttributes",
+    "RetentionPeriod",
+    "RetentionPolicy",
     "S3Credentials",
     "S3Options",
     "S3StaticCredentials",
     "Session",
     "SnapshotInfo",
+    "SpillConfig",
     "Storage",
     "StorageConcurrencySettings",
     "StorageSettings",
+    "StorageStats",
+    "StorageUsage",
     "VersionSelection",
     "VirtualChunkContainer",
     "VirtualChunkSpec",
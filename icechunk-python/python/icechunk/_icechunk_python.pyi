@@ -643,6 +643,165 @@ class StorageSettings:
         """True if Icechunk will write object metadata in the object store"""
         ...
 
+class BranchPolicy:
+    """Write protection rules for a branch"""
+
+    def __init__(
+        self,
+        forbid_delete: bool | None = None,
+        forbid_non_fast_forward: bool | None = None,
+        required_metadata_keys: list[str] | None = None,
+    ) -> None:
+        """
+        Create a new `BranchPolicy` object
+
+        Parameters
+        ----------
+        forbid_delete: bool | None
+            The branch cannot be deleted.
+        forbid_non_fast_forward: bool | None
+            The branch cannot be reset to a snapshot that doesn't have the current tip in its ancestry.
+        required_metadata_keys: list[str] | None
+            Every commit to the branch must include these keys in its metadata.
+        """
+        ...
+    @property
+    def forbid_delete(self) -> bool | None:
+        """The branch cannot be deleted."""
+        ...
+    @forbid_delete.setter
+    def forbid_delete(self, value: bool | None) -> None: ...
+    @property
+    def forbid_non_fast_forward(self) -> bool | None:
+        """The branch cannot be reset to a snapshot that doesn't have the current tip in its ancestry."""
+        ...
+    @forbid_non_fast_forward.setter
+    def forbid_non_fast_forward(self, value: bool | None) -> None: ...
+    @property
+    def required_metadata_keys(self) -> list[str] | None:
+        """Every commit to the branch must include these keys in its metadata."""
+        ...
+    @required_metadata_keys.setter
+    def required_metadata_keys(self, value: list[str] | None) -> None: ...
+
+class SpillConfig:
+    """Bounds the memory used by sessions with many chunk writes"""
+
+    def __init__(self, max_in_memory_chunk_refs: int | None = None) -> None:
+        """
+        Create a new `SpillConfig` object
+
+        Parameters
+        ----------
+        max_in_memory_chunk_refs: int | None
+            Above this number of modified chunk references, sessions move them to temporary files. If not set, they are never moved to disk.
+        """
+        ...
+    @property
+    def max_in_memory_chunk_refs(self) -> int | None:
+        """Above this number of modified chunk references, sessions move them to temporary files."""
+        ...
+    @max_in_memory_chunk_refs.setter
+    def max_in_memory_chunk_refs(self, value: int | None) -> None: ...
+
+class RetentionPeriod(Enum):
+    """The periods a `PeriodicRetention` rule keeps one snapshot for
+
+    Attributes
+    ----------
+    Day: int
+    Week: int
+    Month: int
+    Year: int
+    """
+
+    Day = 0
+    Week = 1
+    Month = 2
+    Year = 3
+
+class PeriodicRetention:
+    """Keep the newest snapshot of every period, for example, one per day"""
+
+    def __init__(self, every: RetentionPeriod, periods: int | None = None) -> None:
+        """
+        Create a new `PeriodicRetention` object
+
+        Parameters
+        ----------
+        every: RetentionPeriod
+            Keep the newest snapshot in each of these periods.
+        periods: int | None
+            How many periods back from now the rule applies. If not set, it applies forever.
+        """
+        ...
+    @property
+    def every(self) -> RetentionPeriod:
+        """Keep the newest snapshot in each of these periods."""
+        ...
+    @every.setter
+    def every(self, value: RetentionPeriod) -> None: ...
+    @property
+    def periods(self) -> int | None:
+        """How many periods back from now the rule applies."""
+        ...
+    @periods.setter
+    def periods(self, value: int | None) -> None: ...
+
+class RetentionPolicy:
+    """Which snapshots are kept in the history of each ref when retention is applied
+
+    A snapshot is kept if any rule keeps it. The snapshots pointed by refs, and the first
+    snapshot of the repository, are always kept.
+    """
+
+    def __init__(
+        self,
+        keep_all_days: int | None = None,
+        keep_periodic: list[PeriodicRetention] | None = None,
+        keep_last: int | None = None,
+        keep_with_metadata_keys: list[str] | None = None,
+    ) -> None:
+        """
+        Create a new `RetentionPolicy` object
+
+        Parameters
+        ----------
+        keep_all_days: int | None
+            Keep every snapshot younger than this many days.
+        keep_periodic: list[PeriodicRetention] | None
+            Keep the newest snapshot of every period.
+        keep_last: int | None
+            Keep this many of the newest snapshots of each ref.
+        keep_with_metadata_keys: list[str] | None
+            Keep the snapshots that have any of these keys in their metadata.
+        """
+        ...
+    @property
+    def keep_all_days(self) -> int | None:
+        """Keep every snapshot younger than this many days."""
+        ...
+    @keep_all_days.setter
+    def keep_all_days(self, value: int | None) -> None: ...
+    @property
+    def keep_periodic(self) -> list[PeriodicRetention] | None:
+        """Keep the newest snapshot of every period."""
+        ...
+    @keep_periodic.setter
+    def keep_periodic(self, value: list[PeriodicRetention] | None) -> None: ...
+    @property
+    def keep_last(self) -> int | None:
+        """Keep this many of the newest snapshots of each ref."""
+        ...
+    @keep_last.setter
+    def keep_last(self, value: int | None) -> None: ...
+    @property
+    def keep_with_metadata_keys(self) -> list[str] | None:
+        """Keep the snapshots that have any of these keys in their metadata."""
+        ...
+    @keep_with_metadata_keys.setter
+    def keep_with_metadata_keys(self, value: list[str] | None) -> None: ...
+
 class RepositoryConfig:
     """Configuration for an Icechunk repository"""
 
@@ -655,6 +814,10 @@ class RepositoryConfig:
         storage: StorageSettings | None = None,
         virtual_chunk_containers: dict[str, VirtualChunkContainer] | None = None,
         manifest: ManifestConfig | None = None,
+        branch_policies: dict[str, BranchPolicy] | None = None,
+        spill: SpillConfig | None = None,
+        retention: RetentionPolicy | None = None,
+        session_lease_seconds: int | None = None,
     ) -> None:
         """
         Create a new `RepositoryConfig` object
@@ -675,6 +838,14 @@ class RepositoryConfig:
             The virtual chunk containers for the repository.
         manifest: ManifestConfig | None
             The manifest configuration for the repository.
+        branch_policies: dict[str, BranchPolicy] | None
+            The write protection rules of the branches, by branch name.
+        spill: SpillConfig | None
+            How sessions with many chunk writes bound their memory use.
+        retention: RetentionPolicy | None
+            Which snapshots are kept when retention is applied.
+        session_lease_seconds: int | None
+            If set, writable sessions hold a lease, valid for this many seconds, that protects the objects they write from garbage collection until they commit.
         """
         ...
     @staticmethod
@@ -844,6 +1015,106 @@ class RepositoryConfig:
         Clear all virtual chunk containers from the repository.
         """
         ...
+    @property
+    def branch_policies(self) -> dict[str, BranchPolicy] | None:
+        """
+        The write protection rules of the branches, by branch name.
+
+        Returns
+        -------
+        dict[str, BranchPolicy] | None
+            The write protection rules of the branches, by branch name.
+        """
+        ...
+    @branch_policies.setter
+    def branch_policies(self, value: dict[str, BranchPolicy] | None) -> None:
+        """
+        Set the write protection rules of the branches, by branch name.
+
+        Parameters
+        ----------
+        value: dict[str, BranchPolicy] | None
+            The write protection rules of the branches, by branch name.
+        """
+        ...
+    def set_branch_policy(self, branch: str, policy: BranchPolicy) -> None:
+        """
+        Set the write protection rules of a branch.
+
+        Parameters
+        ----------
+        branch: str
+            The name of the branch.
+        policy: BranchPolicy
+            The rules for the branch.
+        """
+        ...
+    @property
+    def spill(self) -> SpillConfig | None:
+        """
+        How sessions with many chunk writes bound their memory use.
+
+        Returns
+        -------
+        SpillConfig | None
+            How sessions with many chunk writes bound their memory use.
+        """
+        ...
+    @spill.setter
+    def spill(self, value: SpillConfig | None) -> None:
+        """
+        Set how sessions with many chunk writes bound their memory use.
+
+        Parameters
+        ----------
+        value: SpillConfig | None
+            How sessions with many chunk writes bound their memory use.
+        """
+        ...
+    @property
+    def retention(self) -> RetentionPolicy | None:
+        """
+        Which snapshots are kept when retention is applied.
+
+        Returns
+        -------
+        RetentionPolicy | None
+            Which snapshots are kept when retention is applied.
+        """
+        ...
+    @retention.setter
+    def retention(self, value: RetentionPolicy | None) -> None:
+        """
+        Set which snapshots are kept when retention is applied.
+
+        Parameters
+        ----------
+        value: RetentionPolicy | None
+            Which snapshots are kept when retention is applied.
+        """
+        ...
+    @property
+    def session_lease_seconds(self) -> int | None:
+        """
+        How long the leases of writable sessions last, protecting the objects they write from garbage collection.
+
+        Returns
+        -------
+        int | None
+            The lease duration in seconds, sessions don't take leases if not set.
+        """
+        ...
+    @session_lease_seconds.setter
+    def session_lease_seconds(self, value: int | None) -> None:
+        """
+        Set how long the leases of writable sessions last.
+
+        Parameters
+        ----------
+        value: int | None
+            The lease duration in seconds, sessions don't take leases if not set.
+        """
+        ...
 
 class Diff:
     """The result of comparing two snapshots"""
@@ -928,6 +1199,92 @@ class GCSummary:
         How many transaction logs were deleted.
         """
         ...
+    @property
+    def tag_delete_markers_deleted(self) -> int:
+        """
+        How many deleted tags had their delete markers removed, freeing their names.
+        """
+        ...
+
+class ArchiveSummary:
+    """Summarizes the results of moving chunks to an archive storage"""
+    @property
+    def chunks_archived(self) -> int:
+        """
+        How many chunks were moved to the archive.
+        """
+        ...
+    @property
+    def bytes_archived(self) -> int:
+        """
+        How many bytes were moved to the archive.
+        """
+        ...
+
+class StorageUsage:
+    """Number of objects and their total size in bytes"""
+    @property
+    def count(self) -> int: ...
+    @property
+    def bytes(self) -> int: ...
+
+class ArrayStorageStats:
+    """Chunks of one array, by where they are stored"""
+    @property
+    def native_chunks(self) -> StorageUsage:
+        """
+        Chunks stored in the repository, as their own objects.
+        """
+        ...
+    @property
+    def inline_chunks(self) -> StorageUsage:
+        """
+        Chunks stored inside manifests.
+        """
+        ...
+    @property
+    def virtual_chunks(self) -> StorageUsage:
+        """
+        Chunks stored outside of the repository.
+        """
+        ...
+
+class StorageStats:
+    """Storage used by the objects reachable from any branch or tag of an icechunk repo"""
+    @property
+    def arrays(self) -> dict[str, ArrayStorageStats]:
+        """
+        Chunks of each array, by path. Moved arrays are reported under their latest path.
+        """
+        ...
+    @property
+    def snapshots(self) -> StorageUsage: ...
+    @property
+    def manifests(self) -> StorageUsage: ...
+    @property
+    def branches(self) -> dict[str, int]:
+        """
+        Bytes only reachable from each branch, deleting it and running garbage collection would free them.
+        """
+        ...
+    @property
+    def tags(self) -> dict[str, int]:
+        """
+        Bytes only reachable from each tag.
+        """
+        ...
+    @property
+    def native_chunk_bytes(self) -> int:
+        """
+        Total size of the native chunks, as reported by `total_chunks_storage`.
+        """
+        ...
+    @property
+    def metadata_bytes(self) -> int:
+        """
+        Total size of snapshots and manifests.
+        """
+        ...
 
 class PyRepository:
     @classmethod
@@ -964,6 +1321,10 @@ class PyRepository:
     def save_config(self) -> None: ...
     def config(self) -> RepositoryConfig: ...
     def storage(self) -> Storage: ...
+    def set_archive_storage(self, archive: Storage) -> None: ...
+    def archive_storage(self) -> Storage | None: ...
+    def add_commit_validator(self, validator: CommitValidator) -> None: ...
+    def commit_validators(self) -> list[str]: ...
     def set_default_commit_metadata(self, metadata: dict[str, Any]) -> None: ...
     def default_commit_metadata(self) -> dict[str, Any]: ...
     def async_ancestry(
@@ -1001,6 +1362,9 @@ class PyRepository:
         as_of: datetime.datetime | None = None,
     ) -> PySession: ...
     def writable_session(self, branch: str) -> PySession: ...
+    def saved_sessions(self) -> list[str]: ...
+    def resume_session(self, name: str) -> PySession: ...
+    def discard_saved_session(self, name: str) -> None: ...
     def expire_snapshots(
         self,
         older_than: datetime.datetime,
@@ -1011,7 +1375,11 @@ class PyRepository:
     def garbage_collect(
         self, delete_object_older_than: datetime.datetime
     ) -> GCSummary: ...
+    def archive_chunks(
+        self, archive: Storage, older_than: datetime.datetime
+    ) -> ArchiveSummary: ...
     def total_chunks_storage(self) -> int: ...
+    def storage_stats(self) -> StorageStats: ...
 
 class PySession:
     @classmethod
@@ -1034,7 +1402,12 @@ class PySession:
     ) -> AsyncIterator[list[list[int]]]: ...
     @property
     def store(self) -> PyStore: ...
+    def fork(self) -> PySession: ...
     def merge(self, other: PySession) -> None: ...
+    def checkpoint(self) -> None: ...
+    def renew_lease(self) -> None: ...
+    def set_spill_directory(self, directory: str | None = None) -> None: ...
+    def save(self, name: str) -> None: ...
     def commit(self, message: str, metadata: dict[str, Any] | None = None) -> str: ...
     def rebase(self, solver: ConflictSolver) -> None: ...
 
@@ -1125,6 +1498,27 @@ class SnapshotInfo:
         The metadata of the snapshot
         """
         ...
+    @property
+    def history_edit(self) -> HistoryEdit | None:
+        """
+        How expiration changed the parent of the snapshot, None if it never did
+        """
+        ...
+
+class HistoryEdit:
+    """Recorded for snapshots whose parent was changed by expiration"""
+    @property
+    def original_parent_id(self) -> str:
+        """The parent the snapshot was committed with"""
+        ...
+    @property
+    def edited_at(self) -> datetime.datetime:
+        """The last time the parent was changed"""
+        ...
+    @property
+    def skipped_snapshots(self) -> int:
+        """Number of snapshots removed from the ancestry between the snapshot and its current parent, over all edits"""
+        ...
 
 class PyAsyncSnapshotGenerator(AsyncGenerator[SnapshotInfo, None], metaclass=abc.ABCMeta):
     def __aiter__(self) -> PyAsyncSnapshotGenerator: ...
@@ -1482,6 +1876,7 @@ class BasicConflictSolver(ConflictSolver):
     - When a chunk conflict is encountered, the behavior is determined by the `on_chunk_conflict` option
     - When an array is deleted that has been updated, `fail_on_delete_of_updated_array` will determine whether to fail the rebase operation
     - When a group is deleted that has been updated, `fail_on_delete_of_updated_group` will determine whether to fail the rebase operation
+    - When the metadata of a node is updated in both snapshots, `merge_attributes` will determine whether to merge the changes key by key
     """
 
     def __init__(
@@ -1490,6 +1885,7 @@ class BasicConflictSolver(ConflictSolver):
         on_chunk_conflict: VersionSelection = VersionSelection.UseOurs,
         fail_on_delete_of_updated_array: bool = False,
         fail_on_delete_of_updated_group: bool = False,
+        merge_attributes: bool = False,
     ) -> None:
         """Create a BasicConflictSolver object with the given configuration options
 
@@ -1501,6 +1897,8 @@ class BasicConflictSolver(ConflictSolver):
             Whether to fail when a chunk is deleted that has been updated, by default False
         fail_on_delete_of_updated_group: bool
             Whether to fail when a group is deleted that has been updated, by default False
+        merge_attributes: bool
+            Whether to merge concurrent metadata updates, conflicting only when the same attribute is changed to different values, by default False
         """
         ...
 
@@ -1515,6 +1913,32 @@ class ConflictDetector(ConflictSolver):
 
     def __init__(self) -> None: ...
 
+class CommitValidator:
+    """A check that can reject a commit before its snapshot is published
+
+    This should never be used directly, use one of its subclasses instead
+    """
+
+    @property
+    def name(self) -> str:
+        """The name that identifies the validator in rejected commits"""
+        ...
+
+class RequiredAttributes(CommitValidator):
+    """Requires the given keys in the attributes of every array created or updated"""
+
+    def __init__(self, keys: list[str]) -> None: ...
+
+class DimensionNamesRequired(CommitValidator):
+    """Requires a name for every dimension of the arrays created or updated"""
+
+    def __init__(self) -> None: ...
+
+class ChunksWithinShape(CommitValidator):
+    """Rejects chunks written outside of the final shape of their array"""
+
+    def __init__(self) -> None: ...
+
 class IcechunkError(Exception):
     """Base class for all Icechunk errors"""
 
@@ -1576,6 +2000,10 @@ class ConflictType(Enum):
             A delete is attempted on an updated array
         DeleteOfUpdatedGroup: tuple[int]
             A delete is attempted on an updated group
+        NewNodeInDeletedGroup: tuple[int]
+            A new node is in a group deleted by a concurrent commit
+        DeleteOfGroupWithNewChildren: tuple[int]
+            A delete is attempted on a group that got new children in a concurrent commit
     """
 
     NewNodeConflictsWithExistingNode = (1,)
@@ -1588,6 +2016,8 @@ class ConflictType(Enum):
     ChunksUpdatedInUpdatedArray = (8,)
     DeleteOfUpdatedArray = (9,)
     DeleteOfUpdatedGroup = (10,)
+    NewNodeInDeletedGroup = (11,)
+    DeleteOfGroupWithNewChildren = (12,)
 
 class Conflict:
     """A conflict detected between snapshots"""
@@ -3,12 +3,15 @@ from collections.abc import AsyncIterator, Iterator
 from typing import Any, Self, cast
 
 from icechunk._icechunk_python import (
+    ArchiveSummary,
+    CommitValidator,
     Diff,
     GCSummary,
     PyRepository,
     RepositoryConfig,
     SnapshotInfo,
     Storage,
+    StorageStats,
 )
 from icechunk.credentials import AnyCredential
 from icechunk.session import Session
@@ -216,6 +219,62 @@ class Repository:
         """
         return self._repository.storage()
 
+    def set_archive_storage(self, archive: Storage) -> None:
+        """
+        Read the chunks missing from the repository storage from `archive`.
+
+        This is needed to read the versions whose chunks were moved by `archive_chunks`.
+        It also applies to the sessions already open, but it's not persisted: it has to be
+        set again every time the repository is opened.
+
+        Parameters
+        ----------
+        archive : Storage
+            The storage chunks were archived to.
+        """
+        return self._repository.set_archive_storage(archive)
+
+    def archive_storage(self) -> Storage | None:
+        """
+        Get the storage chunks missing from the repository storage are read from.
+
+        Returns
+        -------
+        Storage | None
+            The archive storage, if one was set with `set_archive_storage`.
+        """
+        return self._repository.archive_storage()
+
+    def add_commit_validator(self, validator: CommitValidator) -> None:
+        """
+        Register a check to run on every commit of the sessions created from now on.
+
+        If any validator finds a problem the commit fails and nothing is written.
+
+        !!! warning
+            Validators are not pickled with the repository or its sessions. A session
+            unpickled elsewhere refuses to commit, it has to be committed by the process
+            that registered the validators, for example after merging it into a session
+            created there.
+
+        Parameters
+        ----------
+        validator : CommitValidator
+            One of `RequiredAttributes`, `DimensionNamesRequired` or `ChunksWithinShape`.
+        """
+        return self._repository.add_commit_validator(validator)
+
+    def commit_validators(self) -> list[str]:
+        """
+        Get the names of the validators registered with `add_commit_validator`.
+
+        Returns
+        -------
+        list[str]
+            The validator names, in registration order.
+        """
+        return self._repository.commit_validators()
+
     def set_default_commit_metadata(self, metadata: dict[str, Any]) -> None:
         """
         Set the default commit metadata for the repository. This is useful for providing
@@ -564,6 +623,49 @@ class Repository:
         """
         return Session(self._repository.writable_session(branch))
 
+    def saved_sessions(self) -> list[str]:
+        """
+        List the names of the sessions saved with `Session.save`.
+
+        Returns
+        -------
+        list[str]
+            The names of the saved sessions, sorted.
+        """
+        return self._repository.saved_sessions()
+
+    def resume_session(self, name: str) -> Session:
+        """
+        Continue working on a session saved with `Session.save`.
+
+        The resumed session is based on the same snapshot as the saved one. Committing it
+        deletes the saved state.
+
+        Parameters
+        ----------
+        name : str
+            The name the session was saved under.
+
+        Returns
+        -------
+        Session
+            The writable session with the saved changes.
+        """
+        return Session(self._repository.resume_session(name))
+
+    def discard_saved_session(self, name: str) -> None:
+        """
+        Delete the state of a session saved with `Session.save`.
+
+        The chunks written by the session are no longer protected from garbage collection.
+
+        Parameters
+        ----------
+        name : str
+            The name the session was saved under.
+        """
+        self._repository.discard_saved_session(name)
+
     def expire_snapshots(
         self,
         older_than: datetime.datetime,
@@ -634,6 +736,30 @@ class Repository:
 
         return self._repository.garbage_collect(delete_object_older_than)
 
+    def archive_chunks(
+        self, archive: Storage, older_than: datetime.datetime
+    ) -> ArchiveSummary:
+        """Move the chunks only used by old versions to a cheaper storage.
+
+        Chunks are copied to `archive` and then deleted from the repository storage.
+        Chunks used by snapshots flushed after `older_than`, or by saved sessions, are
+        never archived. Use `set_archive_storage` to read the archived versions.
+
+        Parameters
+        ----------
+        archive: Storage
+            Where to move the chunks.
+        older_than: datetime.datetime
+            Archive the chunks only used by snapshots flushed before this time.
+
+        Returns
+        -------
+        ArchiveSummary
+            Summary of the chunks archived.
+        """
+
+        return self._repository.archive_chunks(archive, older_than)
+
     def total_chunks_storage(self) -> int:
         """Calculate the total storage used for chunks, in bytes .
 
@@ -647,3 +773,19 @@ class Repository:
         """
 
         return self._repository.total_chunks_storage()
+
+    def storage_stats(self) -> StorageStats:
+        """Calculate detailed statistics of the storage used by the repository.
+
+        As with `total_chunks_storage`, only snapshots reachable from a branch or tag
+        are included, and every distinct chunk is counted once.
+
+        Returns
+        -------
+        StorageStats
+            Chunk counts and sizes per array, split by native, inline and virtual
+            chunks, the size of snapshots and manifests, and the bytes only reachable
+            from each branch and tag.
+        """
+
+        return self._repository.storage_stats()
@@ -239,10 +239,28 @@ class Session:
             for coord in batch:
                 yield tuple(coord)
 
+    def fork(self) -> "Session":
+        """
+        Create a child session, based on the same snapshot, to write from another worker.
+
+        The child sees the groups and arrays created, updated or deleted in this session,
+        but not its uncommitted chunk writes. Once done writing, bring its changes back
+        with `Session.merge`.
+
+        Returns
+        -------
+        Session
+            The child session.
+        """
+        return Session(self._session.fork(), _allow_pickling=self._allow_pickling)
+
     def merge(self, other: Self) -> None:
         """
         Merge the changes for this session with the changes from another session.
 
+        Both sessions must be based on the same snapshot, and they cannot have written
+        different data to the same nodes or chunks.
+
         Parameters
         ----------
         other : Self
@@ -250,6 +268,57 @@ class Session:
         """
         self._session.merge(other._session)
 
+    def checkpoint(self) -> None:
+        """
+        Write the chunk references modified in this session to provisional manifests.
+
+        Long running sessions can checkpoint periodically to bound their memory use and the
+        time it takes to commit. The branch is not updated, provisional manifests only become
+        part of the repository once the session is committed.
+        """
+        self._session.checkpoint()
+
+    def renew_lease(self) -> None:
+        """
+        Extend the lease this session holds, if any, by `RepositoryConfig.session_lease_seconds`.
+
+        The lease protects the objects the session writes from garbage collection until it
+        commits. Sessions running for longer than the lease duration need to renew it, calling
+        `checkpoint`, `save` or `rebase` renews it too.
+        """
+        self._session.renew_lease()
+
+    def set_spill_directory(self, directory: str | None = None) -> None:
+        """
+        Set the local directory for the chunk references this session moves to disk.
+
+        Sessions move their modified chunk references to temporary files once they hold more
+        than configured in `RepositoryConfig.spill`. The directory is not part of the
+        repository configuration, it's local to each writer.
+
+        Parameters
+        ----------
+        directory : str | None
+            The directory to use, the OS temporary directory if not set.
+        """
+        self._session.set_spill_directory(directory)
+
+    def save(self, name: str) -> None:
+        """
+        Save the state of this writable session in the repository under a name.
+
+        The session can later be resumed with `Repository.resume_session`, even from a
+        different process. Saving again under the same name overwrites the previous state.
+        Chunks written by a saved session are not deleted by garbage collection until the
+        session is committed or discarded with `Repository.discard_saved_session`.
+
+        Parameters
+        ----------
+        name : str
+            The name to save the session under.
+        """
+        self._session.save(name)
+
     def commit(self, message: str, metadata: dict[str, Any] | None = None) -> str:
         """
         Commit the changes in the session to the repository.
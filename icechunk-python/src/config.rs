@@ -12,11 +12,12 @@ use std::{
 use icechunk::{
     ObjectStoreConfig, RepositoryConfig, Storage,
     config::{
-        AzureCredentials, AzureStaticCredentials, CachingConfig, CompressionAlgorithm,
-        CompressionConfig, Credentials, GcsBearerCredential, GcsCredentials,
-        GcsCredentialsFetcher, GcsStaticCredentials, ManifestConfig,
-        ManifestPreloadCondition, ManifestPreloadConfig, S3Credentials,
-        S3CredentialsFetcher, S3Options, S3StaticCredentials,
+        AzureCredentials, AzureStaticCredentials, BranchPolicy, CachingConfig,
+        CompressionAlgorithm, CompressionConfig, Credentials, GcsBearerCredential,
+        GcsCredentials, GcsCredentialsFetcher, GcsStaticCredentials, ManifestConfig,
+        ManifestPreloadCondition, ManifestPreloadConfig, PeriodicRetention,
+        RetentionPeriod, RetentionPolicy, S3Credentials, S3CredentialsFetcher, S3Options,
+        S3StaticCredentials, SpillConfig,
     },
     storage::{self, ConcurrencySettings},
     virtual_chunks::VirtualChunkContainer,
@@ -990,6 +991,252 @@ impl From<ManifestConfig> for PyManifestConfig {
     }
 }
 
+#[pyclass(name = "BranchPolicy", eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PyBranchPolicy {
+    #[pyo3(get, set)]
+    pub forbid_delete: Option<bool>,
+    #[pyo3(get, set)]
+    pub forbid_non_fast_forward: Option<bool>,
+    #[pyo3(get, set)]
+    pub required_metadata_keys: Option<Vec<String>>,
+}
+
+#[pymethods]
+impl PyBranchPolicy {
+    #[new]
+    #[pyo3(signature = (forbid_delete=None, forbid_non_fast_forward=None, required_metadata_keys=None))]
+    fn new(
+        forbid_delete: Option<bool>,
+        forbid_non_fast_forward: Option<bool>,
+        required_metadata_keys: Option<Vec<String>>,
+    ) -> Self {
+        Self { forbid_delete, forbid_non_fast_forward, required_metadata_keys }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"BranchPolicy(forbid_delete={del}, forbid_non_fast_forward={ff}, required_metadata_keys={keys})"#,
+            del = format_option_to_string(self.forbid_delete.map(format_bool)),
+            ff = format_option_to_string(self.forbid_non_fast_forward.map(format_bool)),
+            keys = format_option(self.required_metadata_keys.as_ref().map(|keys| {
+                format!(
+                    "[{}]",
+                    keys.iter()
+                        .map(|key| format!("{key:?}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })),
+        )
+    }
+}
+
+impl From<&PyBranchPolicy> for BranchPolicy {
+    fn from(value: &PyBranchPolicy) -> Self {
+        Self {
+            forbid_delete: value.forbid_delete,
+            forbid_non_fast_forward: value.forbid_non_fast_forward,
+            required_metadata_keys: value.required_metadata_keys.clone(),
+        }
+    }
+}
+
+impl From<BranchPolicy> for PyBranchPolicy {
+    fn from(value: BranchPolicy) -> Self {
+        Self {
+            forbid_delete: value.forbid_delete,
+            forbid_non_fast_forward: value.forbid_non_fast_forward,
+            required_metadata_keys: value.required_metadata_keys,
+        }
+    }
+}
+
+#[pyclass(name = "SpillConfig", eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PySpillConfig {
+    #[pyo3(get, set)]
+    pub max_in_memory_chunk_refs: Option<u64>,
+}
+
+#[pymethods]
+impl PySpillConfig {
+    #[new]
+    #[pyo3(signature = (max_in_memory_chunk_refs=None))]
+    fn new(max_in_memory_chunk_refs: Option<u64>) -> Self {
+        Self { max_in_memory_chunk_refs }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"SpillConfig(max_in_memory_chunk_refs={max})"#,
+            max = format_option_to_string(self.max_in_memory_chunk_refs),
+        )
+    }
+}
+
+impl From<&PySpillConfig> for SpillConfig {
+    fn from(value: &PySpillConfig) -> Self {
+        Self { max_in_memory_chunk_refs: value.max_in_memory_chunk_refs }
+    }
+}
+
+impl From<SpillConfig> for PySpillConfig {
+    fn from(value: SpillConfig) -> Self {
+        Self { max_in_memory_chunk_refs: value.max_in_memory_chunk_refs }
+    }
+}
+
+#[pyclass(name = "RetentionPeriod", eq, eq_int)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PyRetentionPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl From<RetentionPeriod> for PyRetentionPeriod {
+    fn from(value: RetentionPeriod) -> Self {
+        match value {
+            RetentionPeriod::Day => PyRetentionPeriod::Day,
+            RetentionPeriod::Week => PyRetentionPeriod::Week,
+            RetentionPeriod::Month => PyRetentionPeriod::Month,
+            RetentionPeriod::Year => PyRetentionPeriod::Year,
+        }
+    }
+}
+
+impl From<PyRetentionPeriod> for RetentionPeriod {
+    fn from(value: PyRetentionPeriod) -> Self {
+        match value {
+            PyRetentionPeriod::Day => RetentionPeriod::Day,
+            PyRetentionPeriod::Week => RetentionPeriod::Week,
+            PyRetentionPeriod::Month => RetentionPeriod::Month,
+            PyRetentionPeriod::Year => RetentionPeriod::Year,
+        }
+    }
+}
+
+#[pyclass(name = "PeriodicRetention", eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PyPeriodicRetention {
+    #[pyo3(get, set)]
+    pub every: PyRetentionPeriod,
+    #[pyo3(get, set)]
+    pub periods: Option<u32>,
+}
+
+#[pymethods]
+impl PyPeriodicRetention {
+    #[new]
+    #[pyo3(signature = (every, periods=None))]
+    fn new(every: PyRetentionPeriod, periods: Option<u32>) -> Self {
+        Self { every, periods }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"PeriodicRetention(every=RetentionPeriod.{every:?}, periods={periods})"#,
+            every = self.every,
+            periods = format_option_to_string(self.periods),
+        )
+    }
+}
+
+impl From<&PyPeriodicRetention> for PeriodicRetention {
+    fn from(value: &PyPeriodicRetention) -> Self {
+        Self { every: value.every.clone().into(), periods: value.periods }
+    }
+}
+
+impl From<PeriodicRetention> for PyPeriodicRetention {
+    fn from(value: PeriodicRetention) -> Self {
+        Self { every: value.every.into(), periods: value.periods }
+    }
+}
+
+#[pyclass(name = "RetentionPolicy", eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PyRetentionPolicy {
+    #[pyo3(get, set)]
+    pub keep_all_days: Option<u32>,
+    #[pyo3(get, set)]
+    pub keep_periodic: Option<Vec<PyPeriodicRetention>>,
+    #[pyo3(get, set)]
+    pub keep_last: Option<u32>,
+    #[pyo3(get, set)]
+    pub keep_with_metadata_keys: Option<Vec<String>>,
+}
+
+#[pymethods]
+impl PyRetentionPolicy {
+    #[new]
+    #[pyo3(signature = (keep_all_days=None, keep_periodic=None, keep_last=None, keep_with_metadata_keys=None))]
+    fn new(
+        keep_all_days: Option<u32>,
+        keep_periodic: Option<Vec<PyPeriodicRetention>>,
+        keep_last: Option<u32>,
+        keep_with_metadata_keys: Option<Vec<String>>,
+    ) -> Self {
+        Self { keep_all_days, keep_periodic, keep_last, keep_with_metadata_keys }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"RetentionPolicy(keep_all_days={days}, keep_periodic={periodic}, keep_last={last}, keep_with_metadata_keys={keys})"#,
+            days = format_option_to_string(self.keep_all_days),
+            periodic = format_option(self.keep_periodic.as_ref().map(|rules| {
+                format!(
+                    "[{}]",
+                    rules
+                        .iter()
+                        .map(|rule| rule.__repr__())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })),
+            last = format_option_to_string(self.keep_last),
+            keys = format_option(self.keep_with_metadata_keys.as_ref().map(|keys| {
+                format!(
+                    "[{}]",
+                    keys.iter()
+                        .map(|key| format!("{key:?}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })),
+        )
+    }
+}
+
+impl From<&PyRetentionPolicy> for RetentionPolicy {
+    fn from(value: &PyRetentionPolicy) -> Self {
+        Self {
+            keep_all_days: value.keep_all_days,
+            keep_periodic: value
+                .keep_periodic
+                .as_ref()
+                .map(|rules| rules.iter().map(|rule| rule.into()).collect()),
+            keep_last: value.keep_last,
+            keep_with_metadata_keys: value.keep_with_metadata_keys.clone(),
+        }
+    }
+}
+
+impl From<RetentionPolicy> for PyRetentionPolicy {
+    fn from(value: RetentionPolicy) -> Self {
+        Self {
+            keep_all_days: value.keep_all_days,
+            keep_periodic: value
+                .keep_periodic
+                .map(|rules| rules.into_iter().map(|rule| rule.into()).collect()),
+            keep_last: value.keep_last,
+            keep_with_metadata_keys: value.keep_with_metadata_keys,
+        }
+    }
+}
+
 #[pyclass(name = "RepositoryConfig", eq)]
 #[derive(Debug)]
 pub struct PyRepositoryConfig {
@@ -1007,6 +1254,14 @@ pub struct PyRepositoryConfig {
     pub virtual_chunk_containers: Option<HashMap<String, PyVirtualChunkContainer>>,
     #[pyo3(get, set)]
     pub manifest: Option<Py<PyManifestConfig>>,
+    #[pyo3(get, set)]
+    pub branch_policies: Option<HashMap<String, PyBranchPolicy>>,
+    #[pyo3(get, set)]
+    pub spill: Option<PySpillConfig>,
+    #[pyo3(get, set)]
+    pub retention: Option<PyRetentionPolicy>,
+    #[pyo3(get, set)]
+    pub session_lease_seconds: Option<u32>,
 }
 
 impl PartialEq for PyRepositoryConfig {
@@ -1029,6 +1284,15 @@ impl From<&PyRepositoryConfig> for RepositoryConfig {
                 c.iter().map(|(name, cont)| (name.clone(), cont.into())).collect()
             }),
             manifest: value.manifest.as_ref().map(|c| (&*c.borrow(py)).into()),
+            branch_policies: value.branch_policies.as_ref().map(|policies| {
+                policies
+                    .iter()
+                    .map(|(branch, policy)| (branch.clone(), policy.into()))
+                    .collect()
+            }),
+            spill: value.spill.as_ref().map(|spill| spill.into()),
+            retention: value.retention.as_ref().map(|retention| retention.into()),
+            session_lease_seconds: value.session_lease_seconds,
         })
     }
 }
@@ -1059,6 +1323,15 @@ impl From<RepositoryConfig> for PyRepositoryConfig {
                 Py::new(py, Into::<PyManifestConfig>::into(c))
                     .expect("Cannot create instance of ManifestConfig")
             }),
+            branch_policies: value.branch_policies.map(|policies| {
+                policies
+                    .into_iter()
+                    .map(|(branch, policy)| (branch, policy.into()))
+                    .collect()
+            }),
+            spill: value.spill.map(|spill| spill.into()),
+            retention: value.retention.map(|retention| retention.into()),
+            session_lease_seconds: value.session_lease_seconds,
         })
     }
 }
@@ -1072,7 +1345,7 @@ impl PyRepositoryConfig {
     }
 
     #[new]
-    #[pyo3(signature = (inline_chunk_threshold_bytes = None, get_partial_values_concurrency = None, compression = None, caching = None, storage = None, virtual_chunk_containers = None, manifest = None))]
+    #[pyo3(signature = (inline_chunk_threshold_bytes = None, get_partial_values_concurrency = None, compression = None, caching = None, storage = None, virtual_chunk_containers = None, manifest = None, branch_policies = None, spill = None, retention = None, session_lease_seconds = None))]
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         inline_chunk_threshold_bytes: Option<u16>,
@@ -1082,6 +1355,10 @@ impl PyRepositoryConfig {
         storage: Option<Py<PyStorageSettings>>,
         virtual_chunk_containers: Option<HashMap<String, PyVirtualChunkContainer>>,
         manifest: Option<Py<PyManifestConfig>>,
+        branch_policies: Option<HashMap<String, PyBranchPolicy>>,
+        spill: Option<PySpillConfig>,
+        retention: Option<PyRetentionPolicy>,
+        session_lease_seconds: Option<u32>,
     ) -> Self {
         Self {
             inline_chunk_threshold_bytes,
@@ -1091,6 +1368,10 @@ impl PyRepositoryConfig {
             storage,
             virtual_chunk_containers,
             manifest,
+            branch_policies,
+            spill,
+            retention,
+            session_lease_seconds,
         }
     }
 
@@ -1104,6 +1385,10 @@ impl PyRepositoryConfig {
             .map(|c| c.into_iter().map(|(s, c)| (s, c.into())).collect());
     }
 
+    pub fn set_branch_policy(&mut self, branch: String, policy: PyBranchPolicy) {
+        self.branch_policies.get_or_insert_default().insert(branch, policy);
+    }
+
     pub fn clear_virtual_chunk_containers(&mut self) {
         let this: &PyRepositoryConfig = &*self;
         let mut c: RepositoryConfig = this.into();
@@ -20,6 +20,8 @@ pub enum PyConflictType {
     ChunksUpdatedInUpdatedArray = 8,
     DeleteOfUpdatedArray = 9,
     DeleteOfUpdatedGroup = 10,
+    NewNodeInDeletedGroup = 11,
+    DeleteOfGroupWithNewChildren = 12,
 }
 
 impl Display for PyConflictType {
@@ -45,6 +47,10 @@ impl Display for PyConflictType {
             }
             PyConflictType::DeleteOfUpdatedArray => "Delete of updated array",
             PyConflictType::DeleteOfUpdatedGroup => "Delete of updated group",
+            PyConflictType::NewNodeInDeletedGroup => "New node in deleted group",
+            PyConflictType::DeleteOfGroupWithNewChildren => {
+                "Delete of group with new children"
+            }
         };
         write!(f, "{}", variant_str)
     }
@@ -142,6 +148,16 @@ impl From<&Conflict> for PyConflict {
                 path: path.to_string(),
                 conflicted_chunks: None,
             },
+            Conflict::NewNodeInDeletedGroup(path) => PyConflict {
+                conflict_type: PyConflictType::NewNodeInDeletedGroup,
+                path: path.to_string(),
+                conflicted_chunks: None,
+            },
+            Conflict::DeleteOfGroupWithNewChildren { path, node_id: _ } => PyConflict {
+                conflict_type: PyConflictType::DeleteOfGroupWithNewChildren,
+                path: path.to_string(),
+                conflicted_chunks: None,
+            },
         }
     }
 }
@@ -181,11 +197,12 @@ pub struct PyBasicConflictSolver;
 #[pymethods]
 impl PyBasicConflictSolver {
     #[new]
-    #[pyo3(signature = (*, on_chunk_conflict=PyVersionSelection::UseOurs, fail_on_delete_of_updated_array = false, fail_on_delete_of_updated_group = false))]
+    #[pyo3(signature = (*, on_chunk_conflict=PyVersionSelection::UseOurs, fail_on_delete_of_updated_array = false, fail_on_delete_of_updated_group = false, merge_attributes = false))]
     fn new(
         on_chunk_conflict: PyVersionSelection,
         fail_on_delete_of_updated_array: bool,
         fail_on_delete_of_updated_group: bool,
+        merge_attributes: bool,
     ) -> (Self, PyConflictSolver) {
         (
             Self,
@@ -193,6 +210,7 @@ impl PyBasicConflictSolver {
                 on_chunk_conflict: on_chunk_conflict.into(),
                 fail_on_delete_of_updated_array,
                 fail_on_delete_of_updated_group,
+                merge_attributes,
             })),
         )
     }
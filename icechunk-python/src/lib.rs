@@ -5,15 +5,17 @@ mod repository;
 mod session;
 mod store;
 mod streams;
+mod validation;
 
 use std::env;
 
 use config::{
-    PyAzureCredentials, PyAzureStaticCredentials, PyCachingConfig,
+    PyAzureCredentials, PyAzureStaticCredentials, PyBranchPolicy, PyCachingConfig,
     PyCompressionAlgorithm, PyCompressionConfig, PyCredentials, PyGcsBearerCredential,
     PyGcsCredentials, PyGcsStaticCredentials, PyManifestConfig,
     PyManifestPreloadCondition, PyManifestPreloadConfig, PyObjectStoreConfig,
-    PyRepositoryConfig, PyS3Credentials, PyS3Options, PyS3StaticCredentials, PyStorage,
+    PyPeriodicRetention, PyRepositoryConfig, PyRetentionPeriod, PyRetentionPolicy,
+    PyS3Credentials, PyS3Options, PyS3StaticCredentials, PySpillConfig, PyStorage,
     PyStorageConcurrencySettings, PyStorageSettings, PyVirtualChunkContainer,
     PythonCredentialsFetcher,
 };
@@ -28,9 +30,16 @@ use errors::{
 use icechunk::{format::format_constants::SpecVersionBin, initialize_tracing};
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
-use repository::{PyDiff, PyGCSummary, PyRepository, PySnapshotInfo};
+use repository::{
+    PyArchiveSummary, PyArrayStorageStats, PyDiff, PyGCSummary, PyHistoryEdit,
+    PyRepository, PySnapshotInfo, PyStorageStats, PyStorageUsage,
+};
 use session::PySession;
 use store::{PyStore, VirtualChunkSpec};
+use validation::{
+    PyChunksWithinShape, PyCommitValidator, PyDimensionNamesRequired,
+    PyRequiredAttributes,
+};
 
 #[cfg(feature = "cli")]
 use clap::Parser;
@@ -93,10 +102,15 @@ fn _icechunk_python(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PySession>()?;
     m.add_class::<PyStore>()?;
     m.add_class::<PySnapshotInfo>()?;
+    m.add_class::<PyHistoryEdit>()?;
     m.add_class::<PyConflictSolver>()?;
     m.add_class::<PyBasicConflictSolver>()?;
     m.add_class::<PyConflictDetector>()?;
     m.add_class::<PyVersionSelection>()?;
+    m.add_class::<PyCommitValidator>()?;
+    m.add_class::<PyRequiredAttributes>()?;
+    m.add_class::<PyDimensionNamesRequired>()?;
+    m.add_class::<PyChunksWithinShape>()?;
     m.add_class::<PyS3StaticCredentials>()?;
     m.add_class::<PythonCredentialsFetcher>()?;
     m.add_class::<PyS3Credentials>()?;
@@ -115,10 +129,19 @@ fn _icechunk_python(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyCachingConfig>()?;
     m.add_class::<PyStorageConcurrencySettings>()?;
     m.add_class::<PyManifestPreloadConfig>()?;
+    m.add_class::<PyBranchPolicy>()?;
+    m.add_class::<PySpillConfig>()?;
+    m.add_class::<PyRetentionPeriod>()?;
+    m.add_class::<PyPeriodicRetention>()?;
+    m.add_class::<PyRetentionPolicy>()?;
     m.add_class::<PyManifestPreloadCondition>()?;
     m.add_class::<PyManifestConfig>()?;
     m.add_class::<PyStorageSettings>()?;
     m.add_class::<PyGCSummary>()?;
+    m.add_class::<PyArchiveSummary>()?;
+    m.add_class::<PyStorageUsage>()?;
+    m.add_class::<PyArrayStorageStats>()?;
+    m.add_class::<PyStorageStats>()?;
     m.add_class::<PyDiff>()?;
     m.add_class::<VirtualChunkSpec>()?;
     m.add_function(wrap_pyfunction!(initialize_logs, m)?)?;
@@ -13,12 +13,16 @@ use icechunk::{
     config::Credentials,
     format::{
         SnapshotId,
-        snapshot::{SnapshotInfo, SnapshotProperties},
+        snapshot::{HistoryEdit, SnapshotInfo, SnapshotProperties},
         transaction_log::Diff,
     },
     ops::{
-        gc::{ExpiredRefAction, GCConfig, GCSummary, expire, garbage_collect},
-        stats::repo_chunks_storage,
+        archive::{ArchiveConfig, ArchiveSummary, archive_chunks},
+        gc::{ExpiredRefAction, GCConfig, GCSummary, garbage_collect},
+        stats::{
+            ArrayStorageStats, StatsConfig, StorageStats, Usage, repo_chunks_storage,
+            repo_storage_stats,
+        },
     },
     repository::{RepositoryErrorKind, VersionInfo},
 };
@@ -38,6 +42,7 @@ use crate::{
     errors::PyIcechunkStoreError,
     session::PySession,
     streams::PyAsyncGenerator,
+    validation::PyCommitValidator,
 };
 
 /// Wrapper needed to implement pyo3 conversion classes
@@ -61,6 +66,41 @@ pub struct PySnapshotInfo {
     message: String,
     #[pyo3(get)]
     metadata: PySnapshotProperties,
+    #[pyo3(get)]
+    history_edit: Option<PyHistoryEdit>,
+}
+
+#[pyclass(name = "HistoryEdit", eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PyHistoryEdit {
+    #[pyo3(get)]
+    original_parent_id: String,
+    #[pyo3(get)]
+    edited_at: DateTime<Utc>,
+    #[pyo3(get)]
+    skipped_snapshots: u64,
+}
+
+impl From<HistoryEdit> for PyHistoryEdit {
+    fn from(value: HistoryEdit) -> Self {
+        PyHistoryEdit {
+            original_parent_id: value.original_parent_id.to_string(),
+            edited_at: value.edited_at,
+            skipped_snapshots: value.skipped_snapshots,
+        }
+    }
+}
+
+#[pymethods]
+impl PyHistoryEdit {
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"HistoryEdit(original_parent_id="{parent}", edited_at={at}, skipped_snapshots={skipped})"#,
+            parent = self.original_parent_id,
+            at = datetime_repr(&self.edited_at),
+            skipped = self.skipped_snapshots,
+        )
+    }
 }
 
 impl<'py> FromPyObject<'py> for PySnapshotProperties {
@@ -164,6 +204,7 @@ impl From<PySnapshotProperties> for SnapshotProperties {
 impl From<SnapshotInfo> for PySnapshotInfo {
     fn from(val: SnapshotInfo) -> Self {
         PySnapshotInfo {
+            history_edit: val.history_edit().map(|edit| edit.into()),
             id: val.id.to_string(),
             parent_id: val.parent_id.map(|id| id.to_string()),
             written_at: val.flushed_at,
@@ -331,6 +372,8 @@ pub struct PyGCSummary {
     pub attributes_deleted: u64,
     #[pyo3(get)]
     pub transaction_logs_deleted: u64,
+    #[pyo3(get)]
+    pub tag_delete_markers_deleted: u64,
 }
 
 impl From<GCSummary> for PyGCSummary {
@@ -342,6 +385,7 @@ impl From<GCSummary> for PyGCSummary {
             snapshots_deleted: value.snapshots_deleted,
             attributes_deleted: value.attributes_deleted,
             transaction_logs_deleted: value.transaction_logs_deleted,
+            tag_delete_markers_deleted: value.tag_delete_markers_deleted,
         }
     }
 }
@@ -350,13 +394,149 @@ impl From<GCSummary> for PyGCSummary {
 impl PyGCSummary {
     pub fn __repr__(&self) -> String {
         format!(
-            r#"GCSummary(bytes_deleted={bytes}, chunks_deleted={chunks}, manifests_deleted={manifests}, snapshots_deleted={snapshots}, attributes_deleted={atts}, transaction_logs_deleted={txs})"#,
+            r#"GCSummary(bytes_deleted={bytes}, chunks_deleted={chunks}, manifests_deleted={manifests}, snapshots_deleted={snapshots}, attributes_deleted={atts}, transaction_logs_deleted={txs}, tag_delete_markers_deleted={tags})"#,
             bytes = self.bytes_deleted,
             chunks = self.chunks_deleted,
             manifests = self.manifests_deleted,
             snapshots = self.snapshots_deleted,
             atts = self.attributes_deleted,
             txs = self.transaction_logs_deleted,
+            tags = self.tag_delete_markers_deleted,
+        )
+    }
+}
+
+#[pyclass(name = "ArchiveSummary", eq)]
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct PyArchiveSummary {
+    #[pyo3(get)]
+    pub chunks_archived: u64,
+    #[pyo3(get)]
+    pub bytes_archived: u64,
+}
+
+impl From<ArchiveSummary> for PyArchiveSummary {
+    fn from(value: ArchiveSummary) -> Self {
+        Self {
+            chunks_archived: value.chunks_archived,
+            bytes_archived: value.bytes_archived,
+        }
+    }
+}
+
+#[pymethods]
+impl PyArchiveSummary {
+    pub fn __repr__(&self) -> String {
+        format!(
+            r#"ArchiveSummary(chunks_archived={chunks}, bytes_archived={bytes})"#,
+            chunks = self.chunks_archived,
+            bytes = self.bytes_archived,
+        )
+    }
+}
+
+#[pyclass(name = "StorageUsage", eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PyStorageUsage {
+    #[pyo3(get)]
+    pub count: u64,
+    #[pyo3(get)]
+    pub bytes: u64,
+}
+
+impl From<Usage> for PyStorageUsage {
+    fn from(value: Usage) -> Self {
+        Self { count: value.count, bytes: value.bytes }
+    }
+}
+
+#[pymethods]
+impl PyStorageUsage {
+    pub fn __repr__(&self) -> String {
+        format!("StorageUsage(count={}, bytes={})", self.count, self.bytes)
+    }
+}
+
+#[pyclass(name = "ArrayStorageStats", eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PyArrayStorageStats {
+    #[pyo3(get)]
+    pub native_chunks: PyStorageUsage,
+    #[pyo3(get)]
+    pub inline_chunks: PyStorageUsage,
+    #[pyo3(get)]
+    pub virtual_chunks: PyStorageUsage,
+}
+
+impl From<ArrayStorageStats> for PyArrayStorageStats {
+    fn from(value: ArrayStorageStats) -> Self {
+        Self {
+            native_chunks: value.native_chunks.into(),
+            inline_chunks: value.inline_chunks.into(),
+            virtual_chunks: value.virtual_chunks.into(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyArrayStorageStats {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "ArrayStorageStats(native_chunks={}, inline_chunks={}, virtual_chunks={})",
+            self.native_chunks.__repr__(),
+            self.inline_chunks.__repr__(),
+            self.virtual_chunks.__repr__(),
+        )
+    }
+}
+
+#[pyclass(name = "StorageStats", eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PyStorageStats {
+    #[pyo3(get)]
+    pub arrays: BTreeMap<String, PyArrayStorageStats>,
+    #[pyo3(get)]
+    pub snapshots: PyStorageUsage,
+    #[pyo3(get)]
+    pub manifests: PyStorageUsage,
+    #[pyo3(get)]
+    pub branches: BTreeMap<String, u64>,
+    #[pyo3(get)]
+    pub tags: BTreeMap<String, u64>,
+    #[pyo3(get)]
+    pub native_chunk_bytes: u64,
+    #[pyo3(get)]
+    pub metadata_bytes: u64,
+}
+
+impl From<StorageStats> for PyStorageStats {
+    fn from(value: StorageStats) -> Self {
+        Self {
+            native_chunk_bytes: value.native_chunk_bytes(),
+            metadata_bytes: value.metadata_bytes(),
+            arrays: value
+                .arrays
+                .into_iter()
+                .map(|(path, array)| (path.to_string(), array.into()))
+                .collect(),
+            snapshots: value.snapshots.into(),
+            manifests: value.manifests.into(),
+            branches: value.branches,
+            tags: value.tags,
+        }
+    }
+}
+
+#[pymethods]
+impl PyStorageStats {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "StorageStats(arrays={}, native_chunk_bytes={}, metadata_bytes={}, branches={:?}, tags={:?})",
+            self.arrays.len(),
+            self.native_chunk_bytes,
+            self.metadata_bytes,
+            self.branches,
+            self.tags,
         )
     }
 }
@@ -556,6 +736,33 @@ impl PyRepository {
         PyStorage(Arc::clone(self.0.blocking_read().storage()))
     }
 
+    pub fn set_archive_storage(&self, py: Python<'_>, archive: PyStorage) {
+        py.allow_threads(move || {
+            self.0.blocking_read().set_archive_storage(archive.0);
+        })
+    }
+
+    pub fn archive_storage(&self) -> Option<PyStorage> {
+        self.0.blocking_read().archive_storage().map(PyStorage)
+    }
+
+    pub fn add_commit_validator(&self, py: Python<'_>, validator: PyCommitValidator) {
+        py.allow_threads(move || {
+            self.0.blocking_write().add_commit_validator(validator.0);
+        })
+    }
+
+    pub fn commit_validators(&self, py: Python<'_>) -> Vec<String> {
+        py.allow_threads(move || {
+            self.0
+                .blocking_read()
+                .commit_validators()
+                .iter()
+                .map(|validator| validator.name().to_string())
+                .collect()
+        })
+    }
+
     pub fn set_default_commit_metadata(
         &self,
         py: Python<'_>,
@@ -882,6 +1089,54 @@ impl PyRepository {
         })
     }
 
+    pub fn saved_sessions(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        // This function calls block_on, so we need to allow other thread python to make progress
+        py.allow_threads(move || {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
+                let names = self
+                    .0
+                    .read()
+                    .await
+                    .saved_sessions()
+                    .await
+                    .map_err(PyIcechunkStoreError::RepositoryError)?;
+                Ok(names)
+            })
+        })
+    }
+
+    pub fn resume_session(&self, py: Python<'_>, name: &str) -> PyResult<PySession> {
+        // This function calls block_on, so we need to allow other thread python to make progress
+        py.allow_threads(move || {
+            let session =
+                pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
+                    self.0
+                        .read()
+                        .await
+                        .resume_session(name)
+                        .await
+                        .map_err(PyIcechunkStoreError::RepositoryError)
+                })?;
+
+            Ok(PySession(Arc::new(RwLock::new(session))))
+        })
+    }
+
+    pub fn discard_saved_session(&self, py: Python<'_>, name: &str) -> PyResult<()> {
+        // This function calls block_on, so we need to allow other thread python to make progress
+        py.allow_threads(move || {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
+                self.0
+                    .read()
+                    .await
+                    .discard_saved_session(name)
+                    .await
+                    .map_err(PyIcechunkStoreError::RepositoryError)?;
+                Ok(())
+            })
+        })
+    }
+
     #[pyo3(signature = (older_than, *, delete_expired_branches = false, delete_expired_tags = false))]
     pub fn expire_snapshots(
         &self,
@@ -894,33 +1149,25 @@ impl PyRepository {
         py.allow_threads(move || {
             let result =
                 pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
-                    let (storage, storage_settings, asset_manager) = {
-                        let lock = self.0.read().await;
-                        (
-                            Arc::clone(lock.storage()),
-                            lock.storage_settings().clone(),
-                            Arc::clone(lock.asset_manager()),
+                    let result = self
+                        .0
+                        .read()
+                        .await
+                        .expire_snapshots(
+                            older_than,
+                            if delete_expired_branches {
+                                ExpiredRefAction::Delete
+                            } else {
+                                ExpiredRefAction::Ignore
+                            },
+                            if delete_expired_tags {
+                                ExpiredRefAction::Delete
+                            } else {
+                                ExpiredRefAction::Ignore
+                            },
                         )
-                    };
-
-                    let result = expire(
-                        storage.as_ref(),
-                        &storage_settings,
-                        asset_manager,
-                        older_than,
-                        if delete_expired_branches {
-                            ExpiredRefAction::Delete
-                        } else {
-                            ExpiredRefAction::Ignore
-                        },
-                        if delete_expired_tags {
-                            ExpiredRefAction::Delete
-                        } else {
-                            ExpiredRefAction::Ignore
-                        },
-                    )
-                    .await
-                    .map_err(PyIcechunkStoreError::GCError)?;
+                        .await
+                        .map_err(PyIcechunkStoreError::GCError)?;
                     Ok::<_, PyIcechunkStoreError>(
                         result
                             .released_snapshots
@@ -971,6 +1218,42 @@ impl PyRepository {
         })
     }
 
+    pub fn archive_chunks(
+        &self,
+        py: Python<'_>,
+        archive: PyStorage,
+        older_than: DateTime<Utc>,
+    ) -> PyResult<PyArchiveSummary> {
+        // This function calls block_on, so we need to allow other thread python to make progress
+        py.allow_threads(move || {
+            let result =
+                pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
+                    let (storage, storage_settings, asset_manager) = {
+                        let lock = self.0.read().await;
+                        (
+                            Arc::clone(lock.storage()),
+                            lock.storage_settings().clone(),
+                            Arc::clone(lock.asset_manager()),
+                        )
+                    };
+                    let archive_settings = archive.0.default_settings();
+                    let result = archive_chunks(
+                        storage.as_ref(),
+                        &storage_settings,
+                        asset_manager,
+                        archive.0.as_ref(),
+                        &archive_settings,
+                        &ArchiveConfig::new(older_than),
+                    )
+                    .await
+                    .map_err(PyIcechunkStoreError::GCError)?;
+                    Ok::<_, PyIcechunkStoreError>(result.into())
+                })?;
+
+            Ok(result)
+        })
+    }
+
     pub fn total_chunks_storage(&self, py: Python<'_>) -> PyResult<u64> {
         // This function calls block_on, so we need to allow other thread python to make progress
         py.allow_threads(move || {
@@ -997,6 +1280,34 @@ impl PyRepository {
             Ok(result)
         })
     }
+
+    pub fn storage_stats(&self, py: Python<'_>) -> PyResult<PyStorageStats> {
+        // This function calls block_on, so we need to allow other thread python to make progress
+        py.allow_threads(move || {
+            let result =
+                pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
+                    let (storage, storage_settings, asset_manager) = {
+                        let lock = self.0.read().await;
+                        (
+                            Arc::clone(lock.storage()),
+                            lock.storage_settings().clone(),
+                            Arc::clone(lock.asset_manager()),
+                        )
+                    };
+                    let result = repo_storage_stats(
+                        storage.as_ref(),
+                        &storage_settings,
+                        asset_manager,
+                        &StatsConfig::default(),
+                    )
+                    .await
+                    .map_err(PyIcechunkStoreError::RepositoryError)?;
+                    Ok::<_, PyIcechunkStoreError>(result.into())
+                })?;
+
+            Ok(result)
+        })
+    }
 }
 
 fn map_credentials(
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, ops::Deref, sync::Arc};
+use std::{borrow::Cow, ops::Deref, path::PathBuf, sync::Arc};
 
 use async_stream::try_stream;
 use futures::{StreamExt, TryStreamExt};
@@ -162,17 +162,82 @@ impl PySession {
         Ok(PyAsyncGenerator::new(prepared_list))
     }
 
+    pub fn fork(&self, py: Python<'_>) -> PyResult<Self> {
+        // This is blocking function, we need to release the Gil
+        py.allow_threads(move || {
+            let session = self
+                .0
+                .blocking_read()
+                .fork()
+                .map_err(PyIcechunkStoreError::SessionError)?;
+            Ok(Self(Arc::new(RwLock::new(session))))
+        })
+    }
+
     pub fn merge(&self, other: &PySession, py: Python<'_>) -> PyResult<()> {
         // This is blocking function, we need to release the Gil
         py.allow_threads(move || {
             // TODO: Bad clone
-            let changes = other.0.blocking_read().deref().changes().clone();
+            let changes = other.0.blocking_read().deref().clone();
 
             pyo3_async_runtimes::tokio::get_runtime().block_on(async move {
                 self.0
                     .write()
                     .await
-                    .merge(changes)
+                    .merge_session(changes)
+                    .await
+                    .map_err(PyIcechunkStoreError::SessionError)?;
+                Ok(())
+            })
+        })
+    }
+
+    pub fn checkpoint(&self, py: Python<'_>) -> PyResult<()> {
+        // This is blocking function, we need to release the Gil
+        py.allow_threads(move || {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(async {
+                self.0
+                    .write()
+                    .await
+                    .checkpoint()
+                    .await
+                    .map_err(PyIcechunkStoreError::SessionError)?;
+                Ok(())
+            })
+        })
+    }
+
+    pub fn renew_lease(&self, py: Python<'_>) -> PyResult<()> {
+        // This is blocking function, we need to release the Gil
+        py.allow_threads(move || {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(async {
+                self.0
+                    .write()
+                    .await
+                    .renew_lease()
+                    .await
+                    .map_err(PyIcechunkStoreError::SessionError)?;
+                Ok(())
+            })
+        })
+    }
+
+    #[pyo3(signature = (directory=None))]
+    pub fn set_spill_directory(&self, py: Python<'_>, directory: Option<PathBuf>) {
+        // This is blocking function, we need to release the Gil
+        py.allow_threads(move || {
+            self.0.blocking_write().set_spill_directory(directory);
+        })
+    }
+
+    pub fn save(&self, py: Python<'_>, name: &str) -> PyResult<()> {
+        // This is blocking function, we need to release the Gil
+        py.allow_threads(move || {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(async {
+                self.0
+                    .write()
+                    .await
+                    .save(name)
                     .await
                     .map_err(PyIcechunkStoreError::SessionError)?;
                 Ok(())
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use icechunk::validation::{
+    ChunksWithinShape, CommitValidator, DimensionNamesRequired, RequiredAttributes,
+};
+use pyo3::prelude::*;
+
+#[pyclass(subclass, name = "CommitValidator")]
+#[derive(Clone)]
+pub struct PyCommitValidator(pub Arc<dyn CommitValidator>);
+
+#[pymethods]
+impl PyCommitValidator {
+    #[getter]
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[pyclass(name = "RequiredAttributes", extends=PyCommitValidator)]
+#[derive(Debug, Clone)]
+pub struct PyRequiredAttributes;
+
+#[pymethods]
+impl PyRequiredAttributes {
+    #[new]
+    fn new(keys: Vec<String>) -> (Self, PyCommitValidator) {
+        (Self, PyCommitValidator(Arc::new(RequiredAttributes::new(keys))))
+    }
+}
+
+#[pyclass(name = "DimensionNamesRequired", extends=PyCommitValidator)]
+#[derive(Debug, Clone)]
+pub struct PyDimensionNamesRequired;
+
+#[pymethods]
+impl PyDimensionNamesRequired {
+    #[new]
+    fn new() -> (Self, PyCommitValidator) {
+        (Self, PyCommitValidator(Arc::new(DimensionNamesRequired)))
+    }
+}
+
+#[pyclass(name = "ChunksWithinShape", extends=PyCommitValidator)]
+#[derive(Debug, Clone)]
+pub struct PyChunksWithinShape;
+
+#[pymethods]
+impl PyChunksWithinShape {
+    #[new]
+    fn new() -> (Self, PyCommitValidator) {
+        (Self, PyCommitValidator(Arc::new(ChunksWithinShape)))
+    }
+}
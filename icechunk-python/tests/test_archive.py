@@ -0,0 +1,47 @@
+from datetime import UTC, datetime
+
+import pytest
+
+import icechunk as ic
+import zarr
+
+
+@pytest.mark.filterwarnings("ignore:datetime.datetime.utcnow")
+def test_archive_chunks() -> None:
+    """We only test the interface, more detailed test is done in Rust"""
+
+    repo = ic.Repository.create(
+        storage=ic.in_memory_storage(),
+        config=ic.RepositoryConfig(inline_chunk_threshold_bytes=0),
+    )
+    session = repo.writable_session("main")
+    store = session.store
+
+    group = zarr.group(store=store, overwrite=True)
+    array = group.create_array(
+        "array",
+        shape=(10),
+        chunks=(1,),
+        dtype="i4",
+        compressors=None,
+    )
+    array[:] = 42
+    first_snapshot = session.commit("commit 1")
+    threshold = datetime.now(UTC)
+
+    session = repo.writable_session("main")
+    array = zarr.open_array(session.store, path="array", mode="a")
+    array[:] = 7
+    session.commit("commit 2")
+
+    archive = ic.in_memory_storage()
+    assert repo.archive_storage() is None
+    summary = repo.archive_chunks(archive, threshold)
+    assert summary.chunks_archived == 10
+    assert summary.bytes_archived == 10 * 4
+
+    repo.set_archive_storage(archive)
+    assert repo.archive_storage() is not None
+    session = repo.readonly_session(snapshot_id=first_snapshot)
+    array = zarr.open_array(session.store, path="array", mode="r")
+    assert (array[:] == 42).all()
@@ -51,3 +51,34 @@ def test_invalid_property_types() -> None:
 
     with pytest.raises(TypeError, match="object cannot be converted"):
         session.commit("some commit", props)
+
+
+def test_commit_validators() -> None:
+    repo = ic.Repository.create(
+        storage=ic.in_memory_storage(),
+    )
+    repo.add_commit_validator(ic.RequiredAttributes(["units"]))
+    repo.add_commit_validator(ic.DimensionNamesRequired())
+    repo.add_commit_validator(ic.ChunksWithinShape())
+    assert repo.commit_validators() == [
+        "required_attributes",
+        "dimension_names_required",
+        "chunks_within_shape",
+    ]
+
+    session = repo.writable_session("main")
+    root = zarr.group(store=session.store)
+    root.create_array("array", shape=(10,), chunks=(1,), dtype="i4")
+    with pytest.raises(ic.IcechunkError, match="required_attributes"):
+        session.commit("no units")
+
+    root.create_array(
+        "array",
+        shape=(10,),
+        chunks=(1,),
+        dtype="i4",
+        dimension_names=["x"],
+        attributes={"units": "m"},
+        overwrite=True,
+    )
+    session.commit("with units")
@@ -213,3 +213,78 @@ def test_can_change_deep_config_values() -> None:
 
 def test_spec_version():
     assert icechunk.spec_version() >= 1
+
+
+def test_branch_policies() -> None:
+    storage = icechunk.in_memory_storage()
+    config = icechunk.RepositoryConfig.default()
+    config.set_branch_policy(
+        "prod",
+        icechunk.BranchPolicy(forbid_delete=True, required_metadata_keys=["author"]),
+    )
+    repo = icechunk.Repository.create(storage=storage, config=config)
+    repo.save_config()
+
+    stored_config = icechunk.Repository.fetch_config(storage)
+    assert stored_config
+    assert stored_config.branch_policies == {
+        "prod": icechunk.BranchPolicy(
+            forbid_delete=True, required_metadata_keys=["author"]
+        )
+    }
+
+    repo.create_branch("prod", repo.lookup_branch("main"))
+    with pytest.raises(icechunk.IcechunkError, match="protected"):
+        repo.delete_branch("prod")
+
+    session = repo.writable_session("prod")
+    zarr.group(store=session.store)
+    with pytest.raises(icechunk.IcechunkError, match="author"):
+        session.commit("no author")
+    session.commit("with author", metadata={"author": "jane"})
+
+
+def test_spill_config() -> None:
+    storage = icechunk.in_memory_storage()
+    config = icechunk.RepositoryConfig.default()
+    config.spill = icechunk.SpillConfig(max_in_memory_chunk_refs=10)
+    repo = icechunk.Repository.create(storage=storage, config=config)
+    repo.save_config()
+
+    stored_config = icechunk.Repository.fetch_config(storage)
+    assert stored_config
+    assert stored_config.spill == icechunk.SpillConfig(max_in_memory_chunk_refs=10)
+
+
+def test_retention_config() -> None:
+    storage = icechunk.in_memory_storage()
+    config = icechunk.RepositoryConfig.default()
+    policy = icechunk.RetentionPolicy(
+        keep_all_days=7,
+        keep_periodic=[
+            icechunk.PeriodicRetention(every=icechunk.RetentionPeriod.Month, periods=12)
+        ],
+        keep_with_metadata_keys=["release"],
+    )
+    config.retention = policy
+    repo = icechunk.Repository.create(storage=storage, config=config)
+    repo.save_config()
+
+    stored_config = icechunk.Repository.fetch_config(storage)
+    assert stored_config
+    assert stored_config.retention == policy
+
+
+def test_session_lease_config() -> None:
+    storage = icechunk.in_memory_storage()
+    config = icechunk.RepositoryConfig.default()
+    config.session_lease_seconds = 3600
+    repo = icechunk.Repository.create(storage=storage, config=config)
+    repo.save_config()
+
+    stored_config = icechunk.Repository.fetch_config(storage)
+    assert stored_config
+    assert stored_config.session_lease_seconds == 3600
+
+    session = repo.writable_session("main")
+    session.renew_lease()
@@ -28,3 +28,38 @@ def test_total_chunks_storage() -> None:
     session.commit("commit 1")
 
     assert repo.total_chunks_storage() == 100 * 4
+
+
+@pytest.mark.filterwarnings("ignore:datetime.datetime.utcnow")
+def test_storage_stats() -> None:
+    """We only test the interface, more detailed test is done in Rust"""
+
+    repo = ic.Repository.create(
+        storage=ic.in_memory_storage(),
+        config=ic.RepositoryConfig(inline_chunk_threshold_bytes=0),
+    )
+    session = repo.writable_session("main")
+    store = session.store
+
+    group = zarr.group(store=store, overwrite=True)
+    array = group.create_array(
+        "array",
+        shape=(100),
+        chunks=(1,),
+        dtype="i4",
+        compressors=None,
+    )
+
+    array[:] = 42
+    session.commit("commit 1")
+
+    stats = repo.storage_stats()
+    assert stats.native_chunk_bytes == repo.total_chunks_storage()
+    assert stats.arrays["/array"].native_chunks.count == 100
+    assert stats.arrays["/array"].native_chunks.bytes == 100 * 4
+    assert stats.arrays["/array"].virtual_chunks.count == 0
+    assert stats.snapshots.count == 2
+    assert stats.metadata_bytes > 0
+    # all the snapshots are in main
+    assert stats.branches["main"] == stats.native_chunk_bytes + stats.metadata_bytes
+    assert stats.tags == {}
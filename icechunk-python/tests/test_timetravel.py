@@ -327,7 +327,7 @@ def test_tag_expiration() -> None:
     session = repo.writable_session("main")
     root = zarr.group(store=session.store, overwrite=True)
     root.create_group("child2")
-    session.commit("c")
+    c = session.commit("c")
 
     repo.create_tag("0", a)
     repo.create_tag("1", b)
@@ -340,6 +340,13 @@ def test_tag_expiration() -> None:
     assert expired == {a, b}
     assert repo.list_tags() == set()
 
+    # the snapshot that lost its parent records it
+    edit = repo.lookup_snapshot(c).history_edit
+    assert edit is not None
+    assert edit.original_parent_id == b
+    assert edit.skipped_snapshots == 2
+    assert repo.lookup_snapshot(b).history_edit is None
+
 
 def test_branch_expiration() -> None:
     repo = ic.Repository.create(storage=ic.in_memory_storage())
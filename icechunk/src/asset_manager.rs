@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     io::{BufReader, Read},
     ops::Range,
-    sync::Arc,
+    sync::{Arc, PoisonError, RwLock},
 };
 use tracing::{Span, debug, instrument, trace};
 
@@ -41,6 +41,9 @@ pub struct AssetManager {
     num_bytes_attributes: u64,
     num_bytes_chunks: u64,
     compression_level: u8,
+    /// Where chunks are looked for when `storage` doesn't have them
+    #[serde(default)]
+    archive: RwLock<Option<(Arc<dyn Storage + Send + Sync>, storage::Settings)>>,
     #[serde(skip)]
     snapshot_cache: Cache<SnapshotId, Arc<Snapshot>, FileWeighter>,
     #[serde(skip)]
@@ -63,11 +66,13 @@ struct AssetManagerSerializer {
     num_bytes_attributes: u64,
     num_bytes_chunks: u64,
     compression_level: u8,
+    #[serde(default)]
+    archive: Option<(Arc<dyn Storage + Send + Sync>, storage::Settings)>,
 }
 
 impl From<AssetManagerSerializer> for AssetManager {
     fn from(value: AssetManagerSerializer) -> Self {
-        AssetManager::new(
+        let manager = AssetManager::new(
             value.storage,
             value.storage_settings,
             value.num_snapshot_nodes,
@@ -76,7 +81,11 @@ impl From<AssetManagerSerializer> for AssetManager {
             value.num_bytes_attributes,
             value.num_bytes_chunks,
             value.compression_level,
-        )
+        );
+        if let Some((archive, archive_settings)) = value.archive {
+            manager.set_archive(archive, archive_settings);
+        }
+        manager
     }
 }
 
@@ -101,6 +110,7 @@ impl AssetManager {
             compression_level,
             storage,
             storage_settings,
+            archive: RwLock::new(None),
             snapshot_cache: Cache::with_weighter(1, num_snapshot_nodes, FileWeighter),
             manifest_cache: Cache::with_weighter(1, num_chunk_refs, FileWeighter),
             transactions_cache: Cache::with_weighter(
@@ -138,6 +148,23 @@ impl AssetManager {
         )
     }
 
+    /// Fetch the chunks missing from the repository storage from `archive`
+    ///
+    /// Used to read chunks moved by [`crate::ops::archive::archive_chunks`]. Cached objects
+    /// are kept, and the change applies to every user of this manager.
+    pub fn set_archive(
+        &self,
+        archive: Arc<dyn Storage + Send + Sync>,
+        archive_settings: storage::Settings,
+    ) {
+        let mut current = self.archive.write().unwrap_or_else(PoisonError::into_inner);
+        *current = Some((archive, archive_settings));
+    }
+
+    pub fn archive(&self) -> Option<(Arc<dyn Storage + Send + Sync>, storage::Settings)> {
+        self.archive.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
     pub fn remove_cached_snapshot(&self, snapshot_id: &SnapshotId) {
         self.snapshot_cache.remove(snapshot_id);
     }
@@ -296,10 +323,25 @@ impl AssetManager {
             Ok(chunk) => Ok(chunk),
             Err(guard) => {
                 trace!(%chunk_id, ?range, "Downloading chunk");
-                let chunk = self
+                let chunk = match self
                     .storage
                     .fetch_chunk(&self.storage_settings, chunk_id, range)
-                    .await?;
+                    .await
+                {
+                    Ok(chunk) => chunk,
+                    Err(err) if err.is_not_found() => match self.archive() {
+                        Some((archive, archive_settings)) => {
+                            debug!(%chunk_id, ?range, "Chunk not found, trying the archive");
+                            // the repository storage error is more useful if both fail
+                            archive
+                                .fetch_chunk(&archive_settings, chunk_id, range)
+                                .await
+                                .map_err(|_| err)?
+                        }
+                        None => return Err(err.into()),
+                    },
+                    Err(err) => return Err(err.into()),
+                };
                 let _fail_is_ok = guard.insert(chunk.clone());
                 Ok(chunk)
             }
@@ -1,22 +1,30 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
-    iter,
-    mem::take,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, io, iter,
+    path::Path as StdPath,
+    sync::Arc,
 };
 
 use bytes::Bytes;
-use itertools::{Either, Itertools as _};
-use serde::{Deserialize, Serialize};
+use itertools::{Either, EitherOrBoth, Itertools as _};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    ser::{Error as _, SerializeMap as _},
+};
+use spill::SpillRun;
 
 use crate::{
     format::{
-        ChunkIndices, NodeId, Path,
-        manifest::{ChunkInfo, ChunkPayload},
-        snapshot::{ArrayShape, DimensionName, NodeData, NodeSnapshot},
+        ChunkIndices, ManifestId, NodeId, Path,
+        manifest::{ChunkInfo, ChunkPayload, ManifestRef},
+        snapshot::{ArrayShape, DimensionName, ManifestFileInfo, NodeData, NodeSnapshot},
     },
     session::SessionResult,
 };
 
+mod spill;
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ArrayData {
     pub shape: ArrayShape,
@@ -24,6 +32,46 @@ pub struct ArrayData {
     pub user_data: Bytes,
 }
 
+/// Changes two [`ChangeSet`]s make differently, see [`ChangeSet::overlapping_writes`]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ChangeSetOverlap {
+    /// Nodes created, updated or deleted differently
+    pub nodes: BTreeSet<NodeId>,
+    /// Chunks written with different references
+    pub chunks: BTreeMap<NodeId, BTreeSet<ChunkIndices>>,
+}
+
+impl ChangeSetOverlap {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.chunks.is_empty()
+    }
+}
+
+/// Chunk changes to an array already written to storage by
+/// [`Session::checkpoint`](crate::session::Session::checkpoint)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointedArray {
+    /// Provisional manifests with every chunk of the array at the time of the checkpoint
+    pub manifests: Vec<ManifestRef>,
+    pub manifest_files: Vec<ManifestFileInfo>,
+    /// Manifests the array had in the snapshot when it was first checkpointed, empty for new
+    /// arrays
+    pub base_manifests: Vec<ManifestRef>,
+    pub base_manifest_files: Vec<ManifestFileInfo>,
+    /// Coordinates of the chunks written in the session up to the checkpoint, their payloads
+    /// are in `manifests`
+    pub written: BTreeSet<ChunkIndices>,
+    /// Coordinates of the chunks deleted in the session up to the checkpoint
+    pub deleted: BTreeSet<ChunkIndices>,
+}
+
+impl CheckpointedArray {
+    /// Coordinates of the chunks modified in the session up to the checkpoint, sorted
+    pub fn modified_coords(&self) -> impl Iterator<Item = &ChunkIndices> {
+        self.written.iter().merge(self.deleted.iter())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct ChangeSet {
     new_groups: HashMap<Path, (NodeId, Bytes)>,
@@ -31,9 +79,17 @@ pub struct ChangeSet {
     updated_arrays: HashMap<NodeId, ArrayData>,
     updated_groups: HashMap<NodeId, Bytes>,
     // It's important we keep these sorted, we use this fact in TransactionLog creation
-    set_chunks: BTreeMap<NodeId, BTreeMap<ChunkIndices, Option<ChunkPayload>>>,
+    set_chunks: ChunkChanges,
     deleted_groups: HashSet<(Path, NodeId)>,
     deleted_arrays: HashSet<(Path, NodeId)>,
+    // Chunk changes moved out of `set_chunks` by checkpoints, newer changes in `set_chunks`
+    // take precedence over them
+    #[serde(default)]
+    checkpointed: BTreeMap<NodeId, CheckpointedArray>,
+    // Node changes a fork inherited from its parent, they are not the fork's own changes,
+    // see [`ChangeSet::fork`]
+    #[serde(default)]
+    fork_base: Option<Box<ChangeSet>>,
 }
 
 impl ChangeSet {
@@ -57,19 +113,128 @@ impl ChangeSet {
         self.deleted_arrays.contains(path_and_id)
     }
 
+    /// Modified chunks, sorted by node id and then by coordinates
+    ///
+    /// Spilled changes are read from disk while iterating, failing to read them yields an
+    /// error.
     pub fn chunk_changes(
         &self,
-    ) -> impl Iterator<Item = (&NodeId, &BTreeMap<ChunkIndices, Option<ChunkPayload>>)>
-    {
-        self.set_chunks.iter()
+    ) -> impl Iterator<
+        Item = (
+            &NodeId,
+            impl Iterator<Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>> + use<'_>,
+        ),
+    > {
+        self.set_chunks
+            .node_ids()
+            .into_iter()
+            .map(|node_id| (node_id, self.set_chunks.node_chunks(node_id)))
     }
 
     pub fn has_chunk_changes(&self, node: &NodeId) -> bool {
-        self.set_chunks.get(node).map(|m| !m.is_empty()).unwrap_or(false)
+        self.set_chunks.has_changes(node)
     }
 
     pub fn arrays_with_chunk_changes(&self) -> impl Iterator<Item = &NodeId> {
-        self.chunk_changes().map(|(node, _)| node)
+        self.set_chunks.node_ids().into_iter()
+    }
+
+    /// Coordinates of the modified chunks held in memory or spilled, sorted by node id and
+    /// then by coordinates
+    ///
+    /// Chunks of checkpointed arrays are not included, see [`CheckpointedArray::modified_coords`].
+    pub fn modified_chunk_coords(
+        &self,
+    ) -> impl Iterator<
+        Item = (&NodeId, impl Iterator<Item = io::Result<ChunkIndices>> + use<'_>),
+    > {
+        self.set_chunks.node_ids().into_iter().map(|node_id| {
+            (node_id, self.set_chunks.node_chunks(node_id).map_ok(|(coord, _)| coord))
+        })
+    }
+
+    pub fn checkpointed_arrays(
+        &self,
+    ) -> impl Iterator<Item = (&NodeId, &CheckpointedArray)> {
+        self.checkpointed.iter()
+    }
+
+    pub fn get_checkpoint(&self, node_id: &NodeId) -> Option<&CheckpointedArray> {
+        self.checkpointed.get(node_id)
+    }
+
+    /// The provisional manifests for the array, if its chunk changes were checkpointed
+    pub fn checkpointed_manifests(&self, node_id: &NodeId) -> Option<&[ManifestRef]> {
+        self.checkpointed.get(node_id).map(|checkpoint| checkpoint.manifests.as_slice())
+    }
+
+    pub fn checkpointed_manifest_file(
+        &self,
+        manifest_id: &ManifestId,
+    ) -> Option<&ManifestFileInfo> {
+        self.checkpointed
+            .values()
+            .flat_map(|checkpoint| checkpoint.manifest_files.iter())
+            .find(|info| &info.id == manifest_id)
+    }
+
+    /// Replace the chunk changes for the array with provisional manifests
+    ///
+    /// `manifests` must contain every chunk of the array, including the ones modified in this
+    /// change set. `base_manifests` are the manifests the array has in the snapshot, they are
+    /// ignored if the array was already checkpointed. Only the coordinates of the modified
+    /// chunks are kept, so commits don't need to read the manifests to find them.
+    pub fn checkpoint_array(
+        &mut self,
+        node_id: &NodeId,
+        manifests: Vec<ManifestRef>,
+        manifest_files: Vec<ManifestFileInfo>,
+        base_manifests: Vec<ManifestRef>,
+        base_manifest_files: Vec<ManifestFileInfo>,
+    ) -> io::Result<()> {
+        let mut checkpoint = match self.checkpointed.remove(node_id) {
+            Some(previous) => CheckpointedArray { manifests, manifest_files, ..previous },
+            None => CheckpointedArray {
+                manifests,
+                manifest_files,
+                base_manifests,
+                base_manifest_files,
+                written: BTreeSet::new(),
+                deleted: BTreeSet::new(),
+            },
+        };
+        // changes since the previous checkpoint are newer
+        for res in self.set_chunks.node_chunks(node_id) {
+            let (coord, payload) = res?;
+            if payload.is_some() {
+                checkpoint.deleted.remove(&coord);
+                checkpoint.written.insert(coord);
+            } else {
+                checkpoint.written.remove(&coord);
+                checkpoint.deleted.insert(coord);
+            }
+        }
+        self.set_chunks.remove_node(node_id);
+        self.checkpointed.insert(node_id.clone(), checkpoint);
+        Ok(())
+    }
+
+    /// Remove the checkpoint for the array, the caller is responsible for bringing its chunk
+    /// changes back into the change set
+    pub fn take_checkpoint(&mut self, node_id: &NodeId) -> Option<CheckpointedArray> {
+        self.checkpointed.remove(node_id)
+    }
+
+    /// Number of modified chunk references held in memory
+    pub fn in_memory_chunk_refs(&self) -> usize {
+        self.set_chunks.in_memory_len()
+    }
+
+    /// Move the modified chunk references to a sorted temporary file in `directory`
+    ///
+    /// The change set works the same after spilling, but lookups and iteration read from disk.
+    pub fn spill_chunk_refs(&mut self, directory: &StdPath) -> io::Result<()> {
+        self.set_chunks.spill(directory)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -137,7 +302,8 @@ impl ChangeSet {
         );
 
         self.updated_arrays.remove(node_id);
-        self.set_chunks.remove(node_id);
+        self.set_chunks.remove_node(node_id);
+        self.checkpointed.remove(node_id);
         if !is_new_array {
             self.deleted_arrays.insert((path, node_id.clone()));
         }
@@ -168,20 +334,15 @@ impl ChangeSet {
     ) {
         // this implementation makes delete idempotent
         // it allows deleting a deleted chunk by repeatedly setting None.
-        self.set_chunks
-            .entry(node_id)
-            .and_modify(|h| {
-                h.insert(coord.clone(), data.clone());
-            })
-            .or_insert(BTreeMap::from([(coord, data)]));
+        self.set_chunks.insert(node_id, coord, data);
     }
 
     pub fn get_chunk_ref(
         &self,
         node_id: &NodeId,
         coords: &ChunkIndices,
-    ) -> Option<&Option<ChunkPayload>> {
-        self.set_chunks.get(node_id).and_then(|h| h.get(coords))
+    ) -> io::Result<Option<Option<ChunkPayload>>> {
+        self.set_chunks.get(node_id, coords)
     }
 
     /// Drop the updated chunk references for the node.
@@ -190,31 +351,27 @@ impl ChangeSet {
         &mut self,
         node_id: &NodeId,
         predicate: impl Fn(&ChunkIndices) -> bool,
-    ) {
-        if let Some(changes) = self.set_chunks.get_mut(node_id) {
-            changes.retain(|coord, _| !predicate(coord));
-        }
+    ) -> io::Result<()> {
+        self.set_chunks.retain(node_id, |coord| !predicate(coord))
     }
 
     pub fn array_chunks_iterator(
         &self,
         node_id: &NodeId,
         node_path: &Path,
-    ) -> impl Iterator<Item = (&ChunkIndices, &Option<ChunkPayload>)> + use<'_> {
+    ) -> impl Iterator<Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>> + use<'_>
+    {
         if self.is_deleted(node_path, node_id) {
             return Either::Left(iter::empty());
         }
-        match self.set_chunks.get(node_id) {
-            None => Either::Left(iter::empty()),
-            Some(h) => Either::Right(h.iter()),
-        }
+        Either::Right(self.set_chunks.node_chunks(node_id))
     }
 
     pub fn new_arrays_chunk_iterator(
         &self,
-    ) -> impl Iterator<Item = (Path, ChunkInfo)> + use<'_> {
+    ) -> impl Iterator<Item = io::Result<(Path, ChunkInfo)>> + use<'_> {
         self.new_arrays.iter().flat_map(|(path, (node_id, _))| {
-            self.new_array_chunk_iterator(node_id, path).map(|ci| (path.clone(), ci))
+            self.new_array_chunk_iterator(node_id, path).map_ok(|ci| (path.clone(), ci))
         })
     }
 
@@ -222,14 +379,10 @@ impl ChangeSet {
         &'a self,
         node_id: &'a NodeId,
         node_path: &Path,
-    ) -> impl Iterator<Item = ChunkInfo> + use<'a> {
-        self.array_chunks_iterator(node_id, node_path).filter_map(
-            move |(coords, payload)| {
-                payload.as_ref().map(|p| ChunkInfo {
-                    node: node_id.clone(),
-                    coord: coords.clone(),
-                    payload: p.clone(),
-                })
+    ) -> impl Iterator<Item = io::Result<ChunkInfo>> + use<'a> {
+        self.array_chunks_iterator(node_id, node_path).filter_map_ok(
+            move |(coord, payload)| {
+                payload.map(|payload| ChunkInfo { node: node_id.clone(), coord, payload })
             },
         )
     }
@@ -246,52 +399,242 @@ impl ChangeSet {
         self.new_arrays.iter().map(|(path, (node_id, _))| (path, node_id))
     }
 
-    pub fn take_chunks(
-        &mut self,
-    ) -> BTreeMap<NodeId, BTreeMap<ChunkIndices, Option<ChunkPayload>>> {
-        take(&mut self.set_chunks)
-    }
-
-    pub fn set_chunks(
-        &mut self,
-        chunks: BTreeMap<NodeId, BTreeMap<ChunkIndices, Option<ChunkPayload>>>,
-    ) {
-        self.set_chunks = chunks
-    }
-
     /// Merge this ChangeSet with `other`.
     ///
     /// Results of the merge are applied to `self`. Changes present in `other` take precedence over
     /// `self` changes.
-    pub fn merge(&mut self, other: ChangeSet) {
+    ///
+    /// Only the changes a fork made itself are applied, the ones it inherited from its parent
+    /// may be outdated.
+    pub fn merge(&mut self, mut other: ChangeSet) -> io::Result<()> {
         // FIXME: this should detect conflict, for example, if different writers added on the same
         // path, different objects, or if the same path is added and deleted, etc.
         // TODO: optimize
+        if let Some(base) = other.fork_base.take() {
+            let nodes = other.node_changes_since(&base);
+            other = ChangeSet {
+                set_chunks: other.set_chunks,
+                checkpointed: other.checkpointed,
+                ..nodes
+            };
+        }
+
+        // new nodes inherited by a fork and deleted there
+        for (path, node_id) in other.deleted_groups {
+            if self.new_groups.get(&path).is_some_and(|(id, _)| id == &node_id) {
+                self.delete_group(path, &node_id);
+            } else {
+                self.deleted_groups.insert((path, node_id));
+            }
+        }
+        for (path, node_id) in other.deleted_arrays {
+            if self.new_arrays.get(&path).is_some_and(|(id, _)| id == &node_id) {
+                self.delete_array(path, &node_id);
+            } else {
+                self.deleted_arrays.insert((path, node_id));
+            }
+        }
         self.new_groups.extend(other.new_groups);
         self.new_arrays.extend(other.new_arrays);
         self.updated_groups.extend(other.updated_groups);
         self.updated_arrays.extend(other.updated_arrays);
-        self.deleted_groups.extend(other.deleted_groups);
-        self.deleted_arrays.extend(other.deleted_arrays);
-
-        for (node, other_chunks) in other.set_chunks.into_iter() {
-            match self.set_chunks.remove(&node) {
-                Some(mut old_value) => {
-                    old_value.extend(other_chunks);
-                    self.set_chunks.insert(node, old_value);
-                }
-                None => {
-                    self.set_chunks.insert(node, other_chunks);
+
+        self.set_chunks.merge(other.set_chunks)?;
+        // arrays checkpointed in both must be restored by the caller before merging, their
+        // provisional manifests cannot be combined
+        self.checkpointed.extend(other.checkpointed);
+        Ok(())
+    }
+
+    /// A copy of the node changes, without the chunk changes
+    ///
+    /// Chunk changes, in memory, spilled or checkpointed, are the bulk of a large change
+    /// set, this is what sessions forked for other writers start from. The fork remembers
+    /// the node changes it inherited, merging it back only compares and applies the changes
+    /// made after forking.
+    pub fn fork(&self) -> ChangeSet {
+        let base = ChangeSet {
+            new_groups: self.new_groups.clone(),
+            new_arrays: self.new_arrays.clone(),
+            updated_arrays: self.updated_arrays.clone(),
+            updated_groups: self.updated_groups.clone(),
+            deleted_groups: self.deleted_groups.clone(),
+            deleted_arrays: self.deleted_arrays.clone(),
+            ..Default::default()
+        };
+        ChangeSet {
+            fork_base: (!base.is_empty()).then(|| Box::new(base.clone())),
+            ..base
+        }
+    }
+
+    /// The node changes made since `base`, the state of a parent when it was forked
+    ///
+    /// New nodes in `base` that are gone were deleted, they are returned as deleted nodes.
+    /// Chunk changes are not included.
+    fn node_changes_since(&self, base: &ChangeSet) -> ChangeSet {
+        fn changed<K: Clone + Eq + std::hash::Hash, V: Clone + PartialEq>(
+            ours: &HashMap<K, V>,
+            base: &HashMap<K, V>,
+        ) -> HashMap<K, V> {
+            ours.iter()
+                .filter(|(key, value)| base.get(key) != Some(value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        }
+        fn deleted<T>(
+            deleted: &HashSet<(Path, NodeId)>,
+            base_deleted: &HashSet<(Path, NodeId)>,
+            new: &HashMap<Path, (NodeId, T)>,
+            base_new: &HashMap<Path, (NodeId, T)>,
+        ) -> HashSet<(Path, NodeId)> {
+            let deleted_new = base_new.iter().filter(|(path, (id, _))| {
+                new.get(*path).is_none_or(|(new_id, _)| new_id != id)
+            });
+            deleted
+                .difference(base_deleted)
+                .cloned()
+                .chain(deleted_new.map(|(path, (id, _))| (path.clone(), id.clone())))
+                .collect()
+        }
+
+        ChangeSet {
+            new_groups: changed(&self.new_groups, &base.new_groups),
+            new_arrays: changed(&self.new_arrays, &base.new_arrays),
+            updated_arrays: changed(&self.updated_arrays, &base.updated_arrays),
+            updated_groups: changed(&self.updated_groups, &base.updated_groups),
+            deleted_groups: deleted(
+                &self.deleted_groups,
+                &base.deleted_groups,
+                &self.new_groups,
+                &base.new_groups,
+            ),
+            deleted_arrays: deleted(
+                &self.deleted_arrays,
+                &base.deleted_arrays,
+                &self.new_arrays,
+                &base.new_arrays,
+            ),
+            ..Default::default()
+        }
+    }
+
+    pub fn merge_many<T: IntoIterator<Item = ChangeSet>>(
+        &mut self,
+        others: T,
+    ) -> io::Result<()> {
+        others.into_iter().try_for_each(|change_set| self.merge(change_set))
+    }
+
+    /// Find the changes `self` and `other` make differently
+    ///
+    /// Changes present in both, with the same value, don't overlap. If `other` is a fork of
+    /// `self`, only the node changes each side made after forking are compared.
+    pub fn overlapping_writes(&self, other: &ChangeSet) -> io::Result<ChangeSetOverlap> {
+        fn differ<'a, K: Eq + std::hash::Hash, V: PartialEq>(
+            ours: &'a HashMap<K, V>,
+            theirs: &'a HashMap<K, V>,
+        ) -> impl Iterator<Item = (&'a K, &'a V)> {
+            ours.iter().filter(|(key, value)| {
+                theirs.get(key).is_some_and(|other_value| other_value != *value)
+            })
+        }
+
+        // the node changes a fork inherited are not its own, they can be outdated
+        let (ours, theirs) = match other.fork_base.as_deref() {
+            Some(base) => (
+                Cow::Owned(self.node_changes_since(base)),
+                Cow::Owned(other.node_changes_since(base)),
+            ),
+            None => (Cow::Borrowed(self), Cow::Borrowed(other)),
+        };
+        let (ours, theirs) = (ours.as_ref(), theirs.as_ref());
+
+        let mut nodes = BTreeSet::new();
+        nodes.extend(differ(&ours.new_groups, &theirs.new_groups).map(|(_, (id, _))| id));
+        nodes.extend(differ(&ours.new_arrays, &theirs.new_arrays).map(|(_, (id, _))| id));
+        nodes.extend(
+            differ(&ours.updated_groups, &theirs.updated_groups).map(|(id, _)| id),
+        );
+        nodes.extend(
+            differ(&ours.updated_arrays, &theirs.updated_arrays).map(|(id, _)| id),
+        );
+
+        // the same path created as a group on one side and as an array on the other
+        for (groups, arrays) in [(ours, theirs), (theirs, ours)] {
+            nodes.extend(groups.new_groups.iter().filter_map(|(path, (id, _))| {
+                arrays.new_arrays.contains_key(path).then_some(id)
+            }));
+        }
+
+        // a node can not be created under a group deleted on the other side
+        let created = |cs: &ChangeSet, path: &Path, id: &NodeId| {
+            cs.new_groups.get(path).is_some_and(|(new_id, _)| new_id == id)
+                || cs.new_arrays.get(path).is_some_and(|(new_id, _)| new_id == id)
+        };
+        for (creator, deleter) in [(ours, theirs), (theirs, ours)] {
+            let deleted: Vec<&Path> = deleter
+                .deleted_groups
+                .iter()
+                .filter(|key| !creator.deleted_groups.contains(*key))
+                .map(|(path, _)| path)
+                .collect();
+            if deleted.is_empty() {
+                continue;
+            }
+            nodes.extend(
+                creator
+                    .new_nodes()
+                    .filter(|(path, id)| !created(deleter, path, id))
+                    .filter(|(path, _)| {
+                        deleted.iter().any(|group| path.starts_with(group))
+                    })
+                    .map(|(_, id)| id),
+            );
+        }
+
+        // a node deleted on one side can not be modified on the other, `cs` has the node
+        // changes and `chunks` the chunk changes of that side
+        let modified = |cs: &ChangeSet, chunks: &ChangeSet, path: &Path, id: &NodeId| {
+            cs.updated_groups.contains_key(id)
+                || cs.updated_arrays.contains_key(id)
+                // a new node inherited by a fork
+                || created(cs, path, id)
+                || chunks.set_chunks.has_changes(id)
+        };
+        for (deleted, modifier, chunks) in [(ours, theirs, other), (theirs, ours, self)] {
+            nodes.extend(
+                deleted
+                    .deleted_groups
+                    .iter()
+                    .chain(deleted.deleted_arrays.iter())
+                    .filter(|(path, id)| modified(modifier, chunks, path, id))
+                    .map(|(_, id)| id),
+            );
+        }
+
+        let mut chunks = BTreeMap::new();
+        for node_id in self.set_chunks.node_ids() {
+            if !other.set_chunks.has_changes(node_id) {
+                continue;
+            }
+            let mut coords = BTreeSet::new();
+            for res in self.set_chunks.node_chunks(node_id) {
+                let (coord, payload) = res?;
+                if other
+                    .set_chunks
+                    .get(node_id, &coord)?
+                    .is_some_and(|other_payload| other_payload != payload)
+                {
+                    coords.insert(coord);
                 }
             }
+            if !coords.is_empty() {
+                chunks.insert(node_id.clone(), coords);
+            }
         }
-    }
 
-    pub fn merge_many<T: IntoIterator<Item = ChangeSet>>(&mut self, others: T) {
-        others.into_iter().fold(self, |res, change_set| {
-            res.merge(change_set);
-            res
-        });
+        Ok(ChangeSetOverlap { nodes: nodes.into_iter().cloned().collect(), chunks })
     }
 
     /// Serialize this ChangeSet
@@ -308,17 +651,22 @@ impl ChangeSet {
         Ok(rmp_serde::from_slice(bytes)?)
     }
 
-    pub fn update_existing_chunks<'a, E>(
+    pub fn update_existing_chunks<'a, E: From<io::Error> + 'a>(
         &'a self,
         node: NodeId,
         chunks: impl Iterator<Item = Result<ChunkInfo, E>> + 'a,
     ) -> impl Iterator<Item = Result<ChunkInfo, E>> + 'a {
-        chunks.filter_map_ok(move |chunk| match self.get_chunk_ref(&node, &chunk.coord) {
-            None => Some(chunk),
-            Some(new_payload) => {
-                new_payload.clone().map(|pl| ChunkInfo { payload: pl, ..chunk })
-            }
-        })
+        chunks
+            .map(move |res| {
+                let chunk = res?;
+                Ok(match self.get_chunk_ref(&node, &chunk.coord)? {
+                    None => Some(chunk),
+                    Some(new_payload) => {
+                        new_payload.map(|pl| ChunkInfo { payload: pl, ..chunk })
+                    }
+                })
+            })
+            .filter_map(Result::transpose)
     }
 
     pub fn get_new_node(&self, path: &Path) -> Option<NodeSnapshot> {
@@ -356,16 +704,9 @@ impl ChangeSet {
     }
 
     pub fn new_nodes_iterator(&self) -> impl Iterator<Item = NodeSnapshot> {
-        self.new_nodes().filter_map(move |(path, node_id)| {
-            if self.is_deleted(path, node_id) {
-                return None;
-            }
-            // we should be able to create the full node because we
-            // know it's a new node
-            #[allow(clippy::expect_used)]
-            let node = self.get_new_node(path).expect("Bug in new_nodes implementation");
-            Some(node)
-        })
+        self.new_nodes()
+            .filter(move |(path, node_id)| !self.is_deleted(path, node_id))
+            .filter_map(move |(path, _)| self.get_new_node(path))
     }
 
     // Applies the changeset to an existing node, yielding a new node if it hasn't been deleted
@@ -404,6 +745,280 @@ impl ChangeSet {
     }
 }
 
+type NodeChunkChanges = BTreeMap<ChunkIndices, Option<ChunkPayload>>;
+
+/// Above this number of spill files, they are compacted into one
+const MAX_SPILL_RUNS: usize = 8;
+
+/// Chunk references modified in a session
+///
+/// Recent changes are kept in memory, older ones can be spilled to sorted files on disk, see
+/// [`ChangeSet::spill_chunk_refs`]. Newer changes take precedence over older ones: memory over
+/// files, and files over the ones written before them.
+#[derive(Clone, Default)]
+struct ChunkChanges {
+    in_memory: BTreeMap<NodeId, NodeChunkChanges>,
+    runs: Vec<Arc<SpillRun>>,
+    // nodes removed after spilling, their changes in the first `n` runs must be ignored
+    masked: HashMap<NodeId, usize>,
+}
+
+/// Merge sources of changes sorted by coordinates, keeping only the newest change, the one
+/// with the lowest age, for each coordinate
+fn newest_changes<I>(
+    sources: Vec<I>,
+) -> impl Iterator<Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>>
+where
+    I: Iterator<Item = io::Result<(usize, ChunkIndices, Option<ChunkPayload>)>>,
+{
+    sources
+        .into_iter()
+        // errors go first, so they are not delayed behind other sources
+        .kmerge_by(|a, b| match (a, b) {
+            (Err(_), _) => true,
+            (_, Err(_)) => false,
+            (Ok((age_a, coord_a, _)), Ok((age_b, coord_b, _))) => {
+                (coord_a, age_a) < (coord_b, age_b)
+            }
+        })
+        .dedup_by(|a, b| {
+            matches!((a, b), (Ok((_, coord_a, _)), Ok((_, coord_b, _))) if coord_a == coord_b)
+        })
+        .map_ok(|(_, coord, payload)| (coord, payload))
+}
+
+impl ChunkChanges {
+    fn runs_for<'a>(
+        &'a self,
+        node_id: &'a NodeId,
+    ) -> impl Iterator<Item = &'a Arc<SpillRun>> {
+        let first = self.masked.get(node_id).copied().unwrap_or(0);
+        self.runs[first..].iter().filter(move |run| run.contains_node(node_id))
+    }
+
+    fn insert(
+        &mut self,
+        node_id: NodeId,
+        coord: ChunkIndices,
+        payload: Option<ChunkPayload>,
+    ) {
+        self.in_memory.entry(node_id).or_default().insert(coord, payload);
+    }
+
+    fn get(
+        &self,
+        node_id: &NodeId,
+        coords: &ChunkIndices,
+    ) -> io::Result<Option<Option<ChunkPayload>>> {
+        if let Some(payload) = self.in_memory.get(node_id).and_then(|h| h.get(coords)) {
+            return Ok(Some(payload.clone()));
+        }
+        // newer runs first
+        for run in self.runs_for(node_id).collect::<Vec<_>>().into_iter().rev() {
+            if let Some(payload) = run.get(node_id, coords)? {
+                return Ok(Some(payload));
+            }
+        }
+        Ok(None)
+    }
+
+    fn has_changes(&self, node_id: &NodeId) -> bool {
+        self.in_memory.get(node_id).is_some_and(|h| !h.is_empty())
+            || self.runs_for(node_id).next().is_some()
+    }
+
+    /// Sorted ids of the nodes with changes
+    fn node_ids(&self) -> BTreeSet<&NodeId> {
+        let mut res: BTreeSet<_> = self.in_memory.keys().collect();
+        for (index, run) in self.runs.iter().enumerate() {
+            res.extend(run.nodes().filter(|node_id| {
+                self.masked.get(*node_id).is_none_or(|first| index >= *first)
+            }));
+        }
+        res
+    }
+
+    /// Changes for the node, sorted by coordinates
+    fn node_chunks<'a>(
+        &'a self,
+        node_id: &NodeId,
+    ) -> impl Iterator<Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>> + use<'a>
+    {
+        let in_memory = self
+            .in_memory
+            .get(node_id)
+            .into_iter()
+            .flatten()
+            .map(|(coord, payload)| Ok((0, coord.clone(), payload.clone())));
+        let runs: Vec<_> = self.runs_for(node_id).cloned().collect();
+        if runs.is_empty() {
+            return Either::Left(
+                in_memory.map_ok(|(_, coord, payload)| (coord, payload)),
+            );
+        }
+
+        // every source is sorted, we merge them tagging each change with its age, so we can
+        // keep only the newest change for each coordinate
+        let sources = iter::once(Either::Left(in_memory))
+            .chain(runs.into_iter().rev().enumerate().map(|(age, run)| {
+                Either::Right(
+                    run.node_chunks(node_id)
+                        .map_ok(move |(coord, payload)| (age + 1, coord, payload)),
+                )
+            }))
+            .collect();
+        Either::Right(newest_changes(sources))
+    }
+
+    fn remove_node(&mut self, node_id: &NodeId) {
+        self.in_memory.remove(node_id);
+        if self.runs.iter().any(|run| run.contains_node(node_id)) {
+            self.masked.insert(node_id.clone(), self.runs.len());
+        }
+    }
+
+    fn retain(
+        &mut self,
+        node_id: &NodeId,
+        predicate: impl Fn(&ChunkIndices) -> bool,
+    ) -> io::Result<()> {
+        if self.runs_for(node_id).next().is_some() {
+            // bring the changes back to memory, they are not in the spill files anymore
+            let changes: NodeChunkChanges =
+                self.node_chunks(node_id).collect::<io::Result<_>>()?;
+            self.remove_node(node_id);
+            self.in_memory.insert(node_id.clone(), changes);
+        }
+        if let Some(changes) = self.in_memory.get_mut(node_id) {
+            changes.retain(|coord, _| predicate(coord));
+        }
+        Ok(())
+    }
+
+    /// Apply the changes in `other`, they take precedence over the changes in `self`
+    fn merge(&mut self, other: ChunkChanges) -> io::Result<()> {
+        if other.runs.is_empty() {
+            for (node_id, changes) in other.in_memory {
+                self.in_memory.entry(node_id).or_default().extend(changes);
+            }
+        } else {
+            for node_id in other.node_ids() {
+                for res in other.node_chunks(node_id) {
+                    let (coord, payload) = res?;
+                    self.insert(node_id.clone(), coord, payload);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn in_memory_len(&self) -> usize {
+        self.in_memory.values().map(|h| h.len()).sum()
+    }
+
+    fn spill(&mut self, directory: &StdPath) -> io::Result<()> {
+        if self.in_memory_len() == 0 {
+            return Ok(());
+        }
+        let entries = self.in_memory.iter().flat_map(|(node_id, changes)| {
+            changes.iter().map(|(coord, payload)| {
+                Ok((node_id.clone(), coord.clone(), payload.clone()))
+            })
+        });
+        let run = SpillRun::write(directory, entries)?;
+        self.runs.push(Arc::new(run));
+        self.in_memory.clear();
+        if self.runs.len() > MAX_SPILL_RUNS {
+            self.compact(directory)?;
+        }
+        Ok(())
+    }
+
+    /// Merge all spill files into one, dropping overwritten and masked changes
+    ///
+    /// Lookups and iteration visit every run, compacting keeps their cost bounded.
+    fn compact(&mut self, directory: &StdPath) -> io::Result<()> {
+        debug_assert!(self.in_memory.is_empty());
+        let entries = self.node_ids().into_iter().flat_map(|node_id| {
+            self.node_chunks(node_id)
+                .map_ok(move |(coord, payload)| (node_id.clone(), coord, payload))
+        });
+        let run = SpillRun::write(directory, entries)?;
+        self.runs = vec![Arc::new(run)];
+        self.masked.clear();
+        Ok(())
+    }
+}
+
+impl PartialEq for ChunkChanges {
+    fn eq(&self, other: &Self) -> bool {
+        let node_ids = self.node_ids();
+        node_ids == other.node_ids()
+            && node_ids.into_iter().all(|node_id| {
+                // unreadable spill files never compare equal
+                self.node_chunks(node_id).zip_longest(other.node_chunks(node_id)).all(
+                    |pair| matches!(pair, EitherOrBoth::Both(Ok(a), Ok(b)) if a == b),
+                )
+            })
+    }
+}
+
+impl Eq for ChunkChanges {}
+
+impl fmt::Debug for ChunkChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkChanges")
+            .field("in_memory", &self.in_memory)
+            .field("spilled", &self.runs.iter().map(|run| run.len()).sum::<usize>())
+            .finish()
+    }
+}
+
+// Spilled changes are serialized inline, with the same format used by in memory changes,
+// streaming them from disk without collecting them in memory
+impl Serialize for ChunkChanges {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node_ids = self.node_ids();
+        let mut map = serializer.serialize_map(Some(node_ids.len()))?;
+        for node_id in node_ids {
+            map.serialize_entry(
+                node_id,
+                &SerializeNodeChunks { changes: self, node_id },
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct SerializeNodeChunks<'a> {
+    changes: &'a ChunkChanges,
+    node_id: &'a NodeId,
+}
+
+impl Serialize for SerializeNodeChunks<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // the length goes first in the serialized map, so the changes are read twice
+        let len = self
+            .changes
+            .node_chunks(self.node_id)
+            .try_fold(0, |len, res| res.map(|_| len + 1))
+            .map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(len))?;
+        for res in self.changes.node_chunks(self.node_id) {
+            let (coord, payload) = res.map_err(S::Error::custom)?;
+            map.serialize_entry(&coord, &payload)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkChanges {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let in_memory = BTreeMap::deserialize(deserializer)?;
+        Ok(Self { in_memory, ..Self::default() })
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
@@ -424,7 +1039,7 @@ mod tests {
     #[test]
     fn test_new_arrays_chunk_iterator() {
         let mut change_set = ChangeSet::default();
-        assert_eq!(None, change_set.new_arrays_chunk_iterator().next());
+        assert!(change_set.new_arrays_chunk_iterator().next().is_none());
 
         let shape = ArrayShape::new(vec![(2, 1), (2, 1), (2, 1)]).unwrap();
         let dimension_names = Some(vec!["x".into(), "y".into(), "t".into()]);
@@ -446,10 +1061,10 @@ mod tests {
             node_id2.clone(),
             array_data.clone(),
         );
-        assert_eq!(None, change_set.new_arrays_chunk_iterator().next());
+        assert!(change_set.new_arrays_chunk_iterator().next().is_none());
 
         change_set.set_chunk_ref(node_id1.clone(), ChunkIndices(vec![0, 1]), None);
-        assert_eq!(None, change_set.new_arrays_chunk_iterator().next());
+        assert!(change_set.new_arrays_chunk_iterator().next().is_none());
 
         change_set.set_chunk_ref(
             node_id1.clone(),
@@ -475,6 +1090,7 @@ mod tests {
         {
             let all_chunks: Vec<_> = change_set
                 .new_arrays_chunk_iterator()
+                .map(|res| res.unwrap())
                 .sorted_by_key(|c| c.1.coord.clone())
                 .collect();
             let expected_chunks: Vec<_> = [
@@ -515,4 +1131,89 @@ mod tests {
             assert_eq!(all_chunks, expected_chunks);
         }
     }
+
+    #[test]
+    fn test_spilled_chunk_refs() {
+        let dir = tempfile::tempdir().unwrap();
+        let node_id1 = NodeId::random();
+        let node_id2 = NodeId::random();
+        let payload = |n: u32| Some(ChunkPayload::Inline(format!("{n}").into()));
+
+        let mut change_set = ChangeSet::default();
+        // enough chunks to span multiple blocks in the spill file
+        for i in 0..10_000 {
+            change_set.set_chunk_ref(node_id1.clone(), ChunkIndices(vec![i]), payload(i));
+        }
+        change_set.set_chunk_ref(node_id2.clone(), ChunkIndices(vec![0]), payload(0));
+        let expected = change_set.clone();
+
+        change_set.spill_chunk_refs(dir.path()).unwrap();
+        assert_eq!(change_set.in_memory_chunk_refs(), 0);
+        assert_eq!(change_set, expected);
+        assert_eq!(
+            change_set.get_chunk_ref(&node_id1, &ChunkIndices(vec![4_242])).unwrap(),
+            Some(payload(4_242))
+        );
+        assert_eq!(
+            change_set.get_chunk_ref(&node_id1, &ChunkIndices(vec![10_000])).unwrap(),
+            None
+        );
+
+        // newer changes take precedence over spilled ones
+        change_set.set_chunk_ref(node_id1.clone(), ChunkIndices(vec![1]), None);
+        change_set.set_chunk_ref(
+            node_id1.clone(),
+            ChunkIndices(vec![10_000]),
+            payload(0),
+        );
+        change_set.spill_chunk_refs(dir.path()).unwrap();
+        change_set.set_chunk_ref(node_id1.clone(), ChunkIndices(vec![2]), payload(42));
+        assert_eq!(
+            change_set.get_chunk_ref(&node_id1, &ChunkIndices(vec![1])).unwrap(),
+            Some(None)
+        );
+        assert_eq!(
+            change_set.get_chunk_ref(&node_id1, &ChunkIndices(vec![2])).unwrap(),
+            Some(payload(42))
+        );
+
+        let chunks: Vec<_> = change_set
+            .array_chunks_iterator(&node_id1, &"/foo".try_into().unwrap())
+            .try_collect()
+            .unwrap();
+        assert_eq!(chunks.len(), 10_001);
+        assert!(chunks.iter().map(|(coord, _)| coord).is_sorted());
+        assert_eq!(chunks[1], (ChunkIndices(vec![1]), None));
+        assert_eq!(chunks[2], (ChunkIndices(vec![2]), payload(42)));
+        assert_eq!(chunks[10_000], (ChunkIndices(vec![10_000]), payload(0)));
+
+        // spilled changes are serialized like in memory ones
+        let bytes = change_set.export_to_bytes().unwrap();
+        let imported = ChangeSet::import_from_bytes(&bytes).unwrap();
+        assert_eq!(imported, change_set);
+        assert_eq!(imported.in_memory_chunk_refs(), 10_002);
+
+        // runs are compacted, keeping only the newest changes
+        let expected = change_set.clone();
+        assert_eq!(change_set.set_chunks.runs.len(), 2);
+        for _ in 0..super::MAX_SPILL_RUNS - 1 {
+            change_set.set_chunk_ref(node_id2.clone(), ChunkIndices(vec![0]), payload(0));
+            change_set.spill_chunk_refs(dir.path()).unwrap();
+        }
+        assert_eq!(change_set.set_chunks.runs.len(), 1);
+        assert_eq!(change_set.set_chunks.runs[0].len(), 10_002);
+        assert_eq!(change_set, expected);
+
+        // deleting the array hides its spilled changes
+        change_set.delete_array("/foo".try_into().unwrap(), &node_id1);
+        assert!(!change_set.has_chunk_changes(&node_id1));
+        assert_eq!(
+            change_set.get_chunk_ref(&node_id1, &ChunkIndices(vec![3])).unwrap(),
+            None
+        );
+        assert_eq!(
+            change_set.arrays_with_chunk_changes().collect::<Vec<_>>(),
+            [&node_id2]
+        );
+    }
 }
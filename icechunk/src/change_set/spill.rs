@@ -0,0 +1,238 @@
+//! Sorted, immutable files of chunk references, used by [`ChangeSet`](super::ChangeSet) to keep
+//! its memory bounded in very large sessions.
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use tokio::runtime::RuntimeFlavor;
+
+use crate::format::{ChunkIndices, NodeId, manifest::ChunkPayload};
+
+type Entry = (NodeId, ChunkIndices, Option<ChunkPayload>);
+
+/// Number of chunk references serialized together, the unit of reads
+const BLOCK_LEN: usize = 4_096;
+
+#[derive(Debug)]
+struct BlockInfo {
+    first_node: NodeId,
+    first_coords: ChunkIndices,
+    offset: u64,
+    len: u64,
+}
+
+/// A file with chunk references sorted by node and coordinates
+///
+/// Only a sparse index is kept in memory, one entry per block of [`BLOCK_LEN`] references.
+/// The file is anonymous, it's removed by the OS once dropped.
+#[derive(Debug)]
+pub(super) struct SpillRun {
+    file: Mutex<File>,
+    blocks: Vec<BlockInfo>,
+    nodes: BTreeSet<NodeId>,
+    len: usize,
+    // lookups and iteration tend to be sequential, so we keep the last block read
+    last_block: Mutex<Option<(usize, Arc<Vec<Entry>>)>>,
+}
+
+fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(err)
+}
+
+// a panic while reading left the file or the cached block in an unknown state
+fn poisoned() -> io::Error {
+    io::Error::other("spill file lock poisoned by a previous failure")
+}
+
+/// Run file IO without stalling the other tasks of a multi threaded tokio runtime
+///
+/// Spill files are read from sync code called by async functions, `block_in_place` lets the
+/// runtime move its other tasks to a different worker while we wait for the disk.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        // current thread runtimes can't hand over their tasks
+        _ => f(),
+    }
+}
+
+impl SpillRun {
+    /// Write `entries`, that must be sorted by node and coordinates, to a new file in `dir`
+    pub(super) fn write(
+        dir: &Path,
+        entries: impl IntoIterator<Item = io::Result<Entry>>,
+    ) -> io::Result<Self> {
+        let mut file = tempfile::tempfile_in(dir)?;
+        let mut blocks = Vec::new();
+        let mut nodes = BTreeSet::new();
+        let mut len = 0;
+        let mut offset = 0;
+        {
+            let mut writer = BufWriter::new(&mut file);
+            let mut entries = entries.into_iter().peekable();
+            while entries.peek().is_some() {
+                let block: Vec<Entry> =
+                    entries.by_ref().take(BLOCK_LEN).collect::<io::Result<_>>()?;
+                let bytes = rmp_serde::to_vec(&block).map_err(to_io_error)?;
+                writer.write_all(&bytes)?;
+                let (first_node, first_coords, _) = &block[0];
+                blocks.push(BlockInfo {
+                    first_node: first_node.clone(),
+                    first_coords: first_coords.clone(),
+                    offset,
+                    len: bytes.len() as u64,
+                });
+                nodes.extend(block.iter().map(|(node_id, _, _)| node_id.clone()));
+                len += block.len();
+                offset += bytes.len() as u64;
+            }
+            writer.flush()?;
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+            blocks,
+            nodes,
+            len,
+            last_block: Mutex::new(None),
+        })
+    }
+
+    /// Number of chunk references in the file
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(super) fn contains_node(&self, node_id: &NodeId) -> bool {
+        self.nodes.contains(node_id)
+    }
+
+    pub(super) fn nodes(&self) -> impl Iterator<Item = &NodeId> {
+        self.nodes.iter()
+    }
+
+    fn read_block(&self, index: usize) -> io::Result<Arc<Vec<Entry>>> {
+        let mut last = self.last_block.lock().map_err(|_| poisoned())?;
+        if let Some((cached_index, block)) = last.as_ref()
+            && *cached_index == index
+        {
+            return Ok(Arc::clone(block));
+        }
+
+        let info = &self.blocks[index];
+        let block: Arc<Vec<Entry>> = Arc::new(blocking(|| {
+            let mut buf = vec![0; info.len as usize];
+            {
+                let mut file = self.file.lock().map_err(|_| poisoned())?;
+                file.seek(SeekFrom::Start(info.offset))?;
+                file.read_exact(&mut buf)?;
+            }
+            rmp_serde::from_slice(&buf).map_err(to_io_error)
+        })?);
+        *last = Some((index, Arc::clone(&block)));
+        Ok(block)
+    }
+
+    /// Index of the first block that can contain references for `node_id`
+    fn first_block_for(&self, node_id: &NodeId) -> usize {
+        // the references for the node can start in the last block that begins before it
+        self.blocks.partition_point(|block| &block.first_node < node_id).saturating_sub(1)
+    }
+
+    pub(super) fn get(
+        &self,
+        node_id: &NodeId,
+        coords: &ChunkIndices,
+    ) -> io::Result<Option<Option<ChunkPayload>>> {
+        if !self.contains_node(node_id) {
+            return Ok(None);
+        }
+        let index = self.blocks.partition_point(|block| {
+            (&block.first_node, &block.first_coords) <= (node_id, coords)
+        });
+        if index == 0 {
+            return Ok(None);
+        }
+        let block = self.read_block(index - 1)?;
+        Ok(block
+            .binary_search_by(|(id, idx, _)| (id, idx).cmp(&(node_id, coords)))
+            .ok()
+            .map(|pos| block[pos].2.clone()))
+    }
+
+    /// Iterate the references for `node_id`, sorted by coordinates
+    pub(super) fn node_chunks(
+        self: &Arc<Self>,
+        node_id: &NodeId,
+    ) -> impl Iterator<Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>> + use<>
+    {
+        NodeChunks {
+            run: Arc::clone(self),
+            node_id: node_id.clone(),
+            block_index: self.first_block_for(node_id),
+            position: 0,
+            block: None,
+            done: !self.contains_node(node_id),
+        }
+    }
+}
+
+struct NodeChunks {
+    run: Arc<SpillRun>,
+    node_id: NodeId,
+    block_index: usize,
+    position: usize,
+    block: Option<Arc<Vec<Entry>>>,
+    done: bool,
+}
+
+impl Iterator for NodeChunks {
+    type Item = io::Result<(ChunkIndices, Option<ChunkPayload>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let block = match &self.block {
+                Some(block) => Arc::clone(block),
+                None => {
+                    if self.block_index >= self.run.blocks.len() {
+                        self.done = true;
+                        return None;
+                    }
+                    match self.run.read_block(self.block_index) {
+                        Ok(block) => {
+                            self.block = Some(Arc::clone(&block));
+                            self.position = 0;
+                            block
+                        }
+                        Err(err) => {
+                            self.done = true;
+                            return Some(Err(err));
+                        }
+                    }
+                }
+            };
+
+            match block.get(self.position) {
+                None => {
+                    self.block = None;
+                    self.block_index += 1;
+                }
+                Some((node_id, coords, payload)) => {
+                    self.position += 1;
+                    if node_id == &self.node_id {
+                        return Some(Ok((coords.clone(), payload.clone())));
+                    } else if node_id > &self.node_id {
+                        self.done = true;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
@@ -1,4 +1,5 @@
 use crate::repository::VersionInfo;
+use chrono::{DateTime, Utc};
 use clap::{Args, Parser, Subcommand};
 use dialoguer::{Input, Select};
 use futures::stream::StreamExt;
@@ -11,6 +12,9 @@ use std::sync::Arc;
 
 use anyhow::{Context, Ok, Result};
 
+use crate::ops::archive::{ArchiveConfig, archive_chunks};
+use crate::ops::stats::{StatsConfig, repo_storage_stats};
+use crate::refs::Ref;
 use crate::storage::{
     new_azure_blob_storage, new_gcs_storage, new_local_filesystem_storage,
     new_tigris_storage,
@@ -36,6 +40,8 @@ enum Command {
     Repo(RepoCommand),
     #[command(subcommand, about = "Manage snapshots")]
     Snapshot(SnapshotCommand),
+    #[command(subcommand, about = "Manage branches and tags")]
+    Ref(RefCommand),
     #[command(subcommand, about = "Manage configuration")]
     Config(ConfigCommand),
 }
@@ -44,6 +50,13 @@ enum Command {
 enum RepoCommand {
     #[clap(name = "create", about = "Create a repository")]
     Create(CreateCommand),
+    #[clap(name = "stats", about = "Show the storage used by a repository")]
+    Stats(StatsCommand),
+    #[clap(
+        name = "archive",
+        about = "Move the chunks only used by old versions to another storage"
+    )]
+    Archive(ArchiveCommand),
 }
 
 #[derive(Debug, Subcommand)]
@@ -52,6 +65,12 @@ enum SnapshotCommand {
     List(ListCommand),
 }
 
+#[derive(Debug, Subcommand)]
+enum RefCommand {
+    #[clap(name = "log", about = "Show the history of movements of a branch or tag")]
+    Log(RefLogCommand),
+}
+
 #[derive(Debug, Subcommand)]
 enum ConfigCommand {
     #[clap(name = "init", about = "Interactively create a new config file.")]
@@ -74,6 +93,28 @@ struct CreateCommand {
     repo: RepositoryAlias,
 }
 
+#[derive(Debug, Args)]
+struct StatsCommand {
+    #[arg(name = "alias", help = "Alias of the repository in the config")]
+    repo: RepositoryAlias,
+}
+
+#[derive(Debug, Args)]
+struct ArchiveCommand {
+    #[arg(name = "alias", help = "Alias of the repository in the config")]
+    repo: RepositoryAlias,
+    #[arg(
+        name = "archive",
+        help = "Alias in the config of the storage to move the chunks to"
+    )]
+    archive: RepositoryAlias,
+    #[arg(
+        long = "older-than",
+        help = "Archive the chunks only used by snapshots flushed before this RFC 3339 time"
+    )]
+    older_than: DateTime<Utc>,
+}
+
 #[derive(Debug, Args)]
 struct InitCommand {
     #[arg(
@@ -105,6 +146,26 @@ struct ListCommand {
     branch: String,
 }
 
+#[derive(Debug, Args)]
+struct RefLogCommand {
+    repo: RepositoryAlias,
+    #[arg(short = 'n', default_value_t = 10, help = "Number of entries to list")]
+    n: usize,
+    #[arg(
+        short = 'b',
+        long = "branch",
+        default_value = "main",
+        help = "Branch to show the reflog of"
+    )]
+    branch: String,
+    #[arg(
+        short = 't',
+        long = "tag",
+        help = "Tag to show the reflog of, overrides --branch"
+    )]
+    tag: Option<String>,
+}
+
 const CONFIG_DIR: &str = "icechunk";
 const CONFIG_NAME: &str = "cli-config.yaml";
 
@@ -220,6 +281,73 @@ async fn repo_create(init_cmd: &CreateCommand, config: &CliConfig) -> Result<()>
     Ok(())
 }
 
+async fn repo_stats(
+    stats_cmd: &StatsCommand,
+    config: &CliConfig,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let repo =
+        config.repos.get(&stats_cmd.repo).context("Repository not found in config")?;
+    let storage = get_storage(repo).await?;
+    let config = Some(repo.get_config().clone());
+
+    let repository = Repository::open(config, Arc::clone(&storage), HashMap::new())
+        .await
+        .context(format!("Failed to open repository {:?}", stats_cmd.repo))?;
+
+    let stats = repo_storage_stats(
+        repository.storage().as_ref(),
+        repository.storage_settings(),
+        Arc::clone(repository.asset_manager()),
+        &StatsConfig::default(),
+    )
+    .await
+    .context("Failed to compute storage statistics")?;
+
+    writeln!(writer, "{}", serde_yaml_ng::to_string(&stats)?)?;
+
+    Ok(())
+}
+
+async fn repo_archive(
+    archive_cmd: &ArchiveCommand,
+    config: &CliConfig,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let repo =
+        config.repos.get(&archive_cmd.repo).context("Repository not found in config")?;
+    let archive_repo = config
+        .repos
+        .get(&archive_cmd.archive)
+        .context("Archive storage not found in config")?;
+    let storage = get_storage(repo).await?;
+    let archive = get_storage(archive_repo).await?;
+    let config = Some(repo.get_config().clone());
+
+    let repository = Repository::open(config, Arc::clone(&storage), HashMap::new())
+        .await
+        .context(format!("Failed to open repository {:?}", archive_cmd.repo))?;
+
+    let summary = archive_chunks(
+        repository.storage().as_ref(),
+        repository.storage_settings(),
+        Arc::clone(repository.asset_manager()),
+        archive.as_ref(),
+        &archive.default_settings(),
+        &ArchiveConfig::new(archive_cmd.older_than),
+    )
+    .await
+    .context("Failed to archive chunks")?;
+
+    writeln!(
+        writer,
+        "✅ Archived {} chunks ({} bytes) to {:?}",
+        summary.chunks_archived, summary.bytes_archived, archive_cmd.archive
+    )?;
+
+    Ok(())
+}
+
 async fn snapshot_list(
     list_cmd: &ListCommand,
     config: &CliConfig,
@@ -246,6 +374,36 @@ async fn snapshot_list(
     Ok(())
 }
 
+async fn ref_log(
+    log_cmd: &RefLogCommand,
+    config: &CliConfig,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let repo =
+        config.repos.get(&log_cmd.repo).context("Repository not found in config")?;
+    let storage = get_storage(repo).await?;
+    let config = Some(repo.get_config().clone());
+
+    let repository = Repository::open(config, Arc::clone(&storage), HashMap::new())
+        .await
+        .context(format!("Failed to open repository {:?}", log_cmd.repo))?;
+
+    let reference = match &log_cmd.tag {
+        Some(tag) => Ref::Tag(tag.clone()),
+        None => Ref::Branch(log_cmd.branch.clone()),
+    };
+    let entries = repository
+        .reflog(&reference, Some(log_cmd.n))
+        .await
+        .context("Failed to get reflog")?;
+
+    for entry in entries {
+        writeln!(writer, "{:?}", entry)?;
+    }
+
+    Ok(())
+}
+
 async fn config_add(add_cmd: &AddCommand, config: &CliConfig) -> Result<CliConfig> {
     if config.repos.contains_key(&add_cmd.repo) {
         return Err(anyhow::anyhow!("Repository {:?} already exists", add_cmd.repo));
@@ -417,9 +575,18 @@ pub async fn run_cli(args: IcechunkCLI) -> Result<()> {
         Command::Repo(RepoCommand::Create(init_cmd)) => {
             repo_create(&init_cmd, &config).await
         }
+        Command::Repo(RepoCommand::Stats(stats_cmd)) => {
+            repo_stats(&stats_cmd, &config, stdout()).await
+        }
+        Command::Repo(RepoCommand::Archive(archive_cmd)) => {
+            repo_archive(&archive_cmd, &config, stdout()).await
+        }
         Command::Snapshot(SnapshotCommand::List(list_cmd)) => {
             snapshot_list(&list_cmd, &config, stdout()).await
         }
+        Command::Ref(RefCommand::Log(log_cmd)) => {
+            ref_log(&log_cmd, &config, stdout()).await
+        }
         Command::Config(ConfigCommand::Init(init_cmd)) => {
             let new_config = config_init(&init_cmd, &config).await?;
             write_config(&new_config)?;
@@ -475,6 +642,77 @@ mod tests {
         assert!(snapshots_contents.next().is_some());
     }
 
+    #[tokio::test]
+    async fn test_repo_stats() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().to_path_buf();
+
+        let repo_alias = RepositoryAlias("test-repo".to_string());
+        let repo = RepositoryDefinition::LocalFileSystem {
+            path: path.clone(),
+            config: RepositoryConfig::default(),
+        };
+
+        let mut repos = HashMap::new();
+        repos.insert(repo_alias.clone(), repo);
+
+        let config = CliConfig { repos };
+
+        let init_cmd = CreateCommand { repo: repo_alias.clone() };
+
+        repo_create(&init_cmd, &config).await.unwrap();
+
+        let stats_cmd = StatsCommand { repo: repo_alias.clone() };
+
+        let mut writer = Vec::new();
+        repo_stats(&stats_cmd, &config.clone(), &mut writer).await.unwrap();
+
+        let output = String::from_utf8(writer).unwrap();
+
+        assert!(output.contains("snapshots:"));
+        assert!(output.contains("main:"));
+    }
+
+    #[tokio::test]
+    async fn test_repo_archive() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().to_path_buf();
+
+        let repo_alias = RepositoryAlias("test-repo".to_string());
+        let archive_alias = RepositoryAlias("test-archive".to_string());
+        let repo = RepositoryDefinition::LocalFileSystem {
+            path: path.join("repo"),
+            config: RepositoryConfig::default(),
+        };
+        let archive = RepositoryDefinition::LocalFileSystem {
+            path: path.join("archive"),
+            config: RepositoryConfig::default(),
+        };
+
+        let mut repos = HashMap::new();
+        repos.insert(repo_alias.clone(), repo);
+        repos.insert(archive_alias.clone(), archive);
+
+        let config = CliConfig { repos };
+
+        let init_cmd = CreateCommand { repo: repo_alias.clone() };
+
+        repo_create(&init_cmd, &config).await.unwrap();
+
+        let archive_cmd = ArchiveCommand {
+            repo: repo_alias.clone(),
+            archive: archive_alias.clone(),
+            older_than: Utc::now(),
+        };
+
+        let mut writer = Vec::new();
+        repo_archive(&archive_cmd, &config.clone(), &mut writer).await.unwrap();
+
+        let output = String::from_utf8(writer).unwrap();
+
+        assert!(output.contains("Archived 0 chunks (0 bytes)"));
+    }
+
     #[tokio::test]
     async fn test_snapshot_list() {
         let temp = assert_fs::TempDir::new().unwrap();
@@ -507,6 +745,42 @@ mod tests {
         assert!(output.contains("SnapshotInfo"));
     }
 
+    #[tokio::test]
+    async fn test_ref_log() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().to_path_buf();
+
+        let repo_alias = RepositoryAlias("test-repo".to_string());
+        let repo = RepositoryDefinition::LocalFileSystem {
+            path: path.clone(),
+            config: RepositoryConfig::default(),
+        };
+
+        let mut repos = HashMap::new();
+        repos.insert(repo_alias.clone(), repo);
+
+        let config = CliConfig { repos };
+
+        let init_cmd = CreateCommand { repo: repo_alias.clone() };
+
+        repo_create(&init_cmd, &config).await.unwrap();
+
+        let log_cmd = RefLogCommand {
+            repo: repo_alias.clone(),
+            n: 10,
+            branch: "main".to_string(),
+            tag: None,
+        };
+
+        let mut writer = Vec::new();
+        ref_log(&log_cmd, &config.clone(), &mut writer).await.unwrap();
+
+        let output = String::from_utf8(writer).unwrap();
+
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("Create"));
+    }
+
     #[tokio::test]
     async fn test_config_list() {
         let temp = assert_fs::TempDir::new().unwrap();
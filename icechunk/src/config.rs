@@ -12,6 +12,7 @@ pub use object_store::gcp::GcpCredential;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    format::snapshot::SnapshotProperties,
     storage,
     virtual_chunks::{ContainerName, VirtualChunkContainer, mk_default_containers},
 };
@@ -222,6 +223,139 @@ impl ManifestConfig {
     }
 }
 
+/// Write protection rules for a single branch
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Default)]
+pub struct BranchPolicy {
+    /// The branch cannot be deleted
+    pub forbid_delete: Option<bool>,
+    /// The branch cannot be reset to a snapshot that doesn't have the current tip in its ancestry
+    pub forbid_non_fast_forward: Option<bool>,
+    /// Every commit to the branch must include these keys in its metadata
+    pub required_metadata_keys: Option<Vec<String>>,
+}
+
+impl BranchPolicy {
+    pub fn forbid_delete(&self) -> bool {
+        self.forbid_delete.unwrap_or(false)
+    }
+
+    pub fn forbid_non_fast_forward(&self) -> bool {
+        self.forbid_non_fast_forward.unwrap_or(false)
+    }
+
+    pub fn required_metadata_keys(&self) -> &[String] {
+        self.required_metadata_keys.as_deref().unwrap_or_default()
+    }
+
+    /// The required keys that commit `metadata` doesn't have
+    pub fn missing_metadata_keys(&self, metadata: &SnapshotProperties) -> Vec<String> {
+        self.required_metadata_keys()
+            .iter()
+            .filter(|key| !metadata.contains_key(key.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn merge(&self, other: Self) -> Self {
+        Self {
+            forbid_delete: other.forbid_delete.or(self.forbid_delete),
+            forbid_non_fast_forward: other
+                .forbid_non_fast_forward
+                .or(self.forbid_non_fast_forward),
+            required_metadata_keys: other
+                .required_metadata_keys
+                .or(self.required_metadata_keys.clone()),
+        }
+    }
+}
+
+/// Bounds the memory used by sessions with many chunk writes
+///
+/// Once a session holds more than `max_in_memory_chunk_refs` modified chunk references, they
+/// are moved to sorted temporary files. The directory is local to each writer, it's set with
+/// [`Session::set_spill_directory`](crate::session::Session::set_spill_directory).
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Default)]
+pub struct SpillConfig {
+    /// If not set, chunk references are never spilled to disk
+    pub max_in_memory_chunk_refs: Option<u64>,
+}
+
+impl SpillConfig {
+    pub fn max_in_memory_chunk_refs(&self) -> Option<u64> {
+        self.max_in_memory_chunk_refs
+    }
+
+    pub fn merge(&self, other: Self) -> Self {
+        Self {
+            max_in_memory_chunk_refs: other
+                .max_in_memory_chunk_refs
+                .or(self.max_in_memory_chunk_refs),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Keep the newest snapshot of every period, for example, one per day
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct PeriodicRetention {
+    pub every: RetentionPeriod,
+    /// How many periods back from now the rule applies, forever if not set
+    pub periods: Option<u32>,
+}
+
+/// Which snapshots [`apply_retention`](crate::ops::apply_retention) keeps in the history of
+/// each ref
+///
+/// A snapshot is kept if any rule keeps it. The snapshots pointed by refs, and the first
+/// snapshot of the repository, are always kept.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Keep every snapshot younger than this
+    pub keep_all_days: Option<u32>,
+    pub keep_periodic: Option<Vec<PeriodicRetention>>,
+    /// Keep this many of the newest snapshots of each ref
+    pub keep_last: Option<u32>,
+    /// Keep the snapshots that have any of these keys in their metadata
+    pub keep_with_metadata_keys: Option<Vec<String>>,
+}
+
+impl RetentionPolicy {
+    pub fn keep_all_days(&self) -> u32 {
+        self.keep_all_days.unwrap_or(0)
+    }
+
+    pub fn keep_periodic(&self) -> &[PeriodicRetention] {
+        self.keep_periodic.as_deref().unwrap_or_default()
+    }
+
+    pub fn keep_last(&self) -> u32 {
+        self.keep_last.unwrap_or(0)
+    }
+
+    pub fn keep_with_metadata_keys(&self) -> &[String] {
+        self.keep_with_metadata_keys.as_deref().unwrap_or_default()
+    }
+
+    pub fn merge(&self, other: Self) -> Self {
+        Self {
+            keep_all_days: other.keep_all_days.or(self.keep_all_days),
+            keep_periodic: other.keep_periodic.or(self.keep_periodic.clone()),
+            keep_last: other.keep_last.or(self.keep_last),
+            keep_with_metadata_keys: other
+                .keep_with_metadata_keys
+                .or(self.keep_with_metadata_keys.clone()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct RepositoryConfig {
     /// Chunks smaller than this will be stored inline in the manifest
@@ -239,6 +373,18 @@ pub struct RepositoryConfig {
     pub virtual_chunk_containers: Option<HashMap<ContainerName, VirtualChunkContainer>>,
 
     pub manifest: Option<ManifestConfig>,
+
+    /// Protection rules, indexed by branch name
+    pub branch_policies: Option<HashMap<String, BranchPolicy>>,
+
+    pub spill: Option<SpillConfig>,
+
+    /// Snapshot history to keep, if not set snapshots are only expired explicitly
+    pub retention: Option<RetentionPolicy>,
+
+    /// If set, writable sessions hold a lease, valid for this many seconds, that protects the
+    /// objects they write from garbage collection until they commit
+    pub session_lease_seconds: Option<u32>,
 }
 
 static DEFAULT_COMPRESSION: OnceLock<CompressionConfig> = OnceLock::new();
@@ -247,6 +393,7 @@ static DEFAULT_VIRTUAL_CHUNK_CONTAINERS: OnceLock<
     HashMap<ContainerName, VirtualChunkContainer>,
 > = OnceLock::new();
 static DEFAULT_MANIFEST_CONFIG: OnceLock<ManifestConfig> = OnceLock::new();
+static DEFAULT_SPILL_CONFIG: OnceLock<SpillConfig> = OnceLock::new();
 
 impl RepositoryConfig {
     pub fn inline_chunk_threshold_bytes(&self) -> u16 {
@@ -276,6 +423,20 @@ impl RepositoryConfig {
         })
     }
 
+    pub fn spill(&self) -> &SpillConfig {
+        self.spill
+            .as_ref()
+            .unwrap_or_else(|| DEFAULT_SPILL_CONFIG.get_or_init(SpillConfig::default))
+    }
+
+    pub fn retention(&self) -> Option<&RetentionPolicy> {
+        self.retention.as_ref()
+    }
+
+    pub fn session_lease_seconds(&self) -> Option<u32> {
+        self.session_lease_seconds
+    }
+
     pub fn merge(&self, other: Self) -> Self {
         Self {
             inline_chunk_threshold_bytes: other
@@ -321,6 +482,37 @@ impl RepositoryConfig {
                 (Some(c), None) => Some(c.clone()),
                 (Some(mine), Some(theirs)) => Some(mine.merge(theirs)),
             },
+            branch_policies: match (&self.branch_policies, other.branch_policies) {
+                (None, None) => None,
+                (None, Some(p)) => Some(p),
+                (Some(p), None) => Some(p.clone()),
+                (Some(mine), Some(theirs)) => {
+                    let mut merged = mine.clone();
+                    for (branch, policy) in theirs {
+                        let policy = match merged.get(&branch) {
+                            Some(existing) => existing.merge(policy),
+                            None => policy,
+                        };
+                        merged.insert(branch, policy);
+                    }
+                    Some(merged)
+                }
+            },
+            spill: match (&self.spill, other.spill) {
+                (None, None) => None,
+                (None, Some(c)) => Some(c),
+                (Some(c), None) => Some(c.clone()),
+                (Some(mine), Some(theirs)) => Some(mine.merge(theirs)),
+            },
+            retention: match (&self.retention, other.retention) {
+                (None, None) => None,
+                (None, Some(c)) => Some(c),
+                (Some(c), None) => Some(c.clone()),
+                (Some(mine), Some(theirs)) => Some(mine.merge(theirs)),
+            },
+            session_lease_seconds: other
+                .session_lease_seconds
+                .or(self.session_lease_seconds),
         }
     }
 }
@@ -365,6 +557,16 @@ impl RepositoryConfig {
     pub fn clear_virtual_chunk_containers(&mut self) {
         self.virtual_chunk_containers = Some(Default::default())
     }
+
+    pub fn branch_policy(&self, branch: &str) -> Option<&BranchPolicy> {
+        self.branch_policies.as_ref().and_then(|policies| policies.get(branch))
+    }
+
+    pub fn set_branch_policy(&mut self, branch: &str, policy: BranchPolicy) {
+        self.branch_policies
+            .get_or_insert_with(Default::default)
+            .insert(branch.to_string(), policy);
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
@@ -1,8 +1,14 @@
 use async_trait::async_trait;
+use bytes::Bytes;
+use serde_json::{Map, Value};
 
 use crate::{
-    change_set::ChangeSet,
-    format::transaction_log::TransactionLog,
+    change_set::{ArrayData, ChangeSet},
+    format::{
+        NodeId, Path,
+        snapshot::{NodeData, NodeSnapshot},
+        transaction_log::TransactionLog,
+    },
     session::{Session, SessionResult},
 };
 
@@ -20,6 +26,12 @@ pub struct BasicConflictSolver {
     pub on_chunk_conflict: VersionSelection,
     pub fail_on_delete_of_updated_array: bool,
     pub fail_on_delete_of_updated_group: bool,
+    /// Solve [`Conflict::ZarrMetadataDoubleUpdate`] with a three-way merge of the Zarr metadata
+    ///
+    /// Keys in `attributes` are merged one by one, so concurrent changes to different
+    /// attributes don't conflict. Only the same key, or any other metadata field, changed
+    /// to different values on both sides is unsolvable.
+    pub merge_attributes: bool,
 }
 
 impl Default for BasicConflictSolver {
@@ -28,6 +40,7 @@ impl Default for BasicConflictSolver {
             on_chunk_conflict: VersionSelection::UseOurs,
             fail_on_delete_of_updated_array: false,
             fail_on_delete_of_updated_group: false,
+            merge_attributes: false,
         }
     }
 }
@@ -61,12 +74,12 @@ impl ConflictSolver for BasicConflictSolver {
 }
 
 impl BasicConflictSolver {
-    async fn solve_conflicts(
+    pub(crate) async fn solve_conflicts(
         &self,
         _previous_change: &TransactionLog,
-        _previous_repo: &Session,
+        previous_repo: &Session,
         current_changes: ChangeSet,
-        _current_repo: &Session,
+        current_repo: &Session,
         conflicts: Vec<Conflict>,
     ) -> SessionResult<ConflictResolution> {
         use Conflict::*;
@@ -75,11 +88,14 @@ impl BasicConflictSolver {
                 conflict,
                 NewNodeConflictsWithExistingNode(_)
                     | NewNodeInInvalidGroup(_)
-                    | ZarrMetadataDoubleUpdate(_)
+                    | NewNodeInDeletedGroup(_)
                     | ZarrMetadataUpdateOfDeletedArray(_)
                     | ZarrMetadataUpdateOfDeletedGroup(_)
                     | ChunksUpdatedInDeletedArray { .. }
                     | ChunksUpdatedInUpdatedArray { .. }
+                    | DeleteOfGroupWithNewChildren { .. }
+            ) || matches!(conflict,
+                ZarrMetadataDoubleUpdate(_) if !self.merge_attributes
             ) || matches!(conflict,
                 ChunkDoubleUpdate{..} if self.on_chunk_conflict == VersionSelection::Fail
             ) || matches!(conflict,
@@ -96,7 +112,36 @@ impl BasicConflictSolver {
             });
         }
 
+        // metadata merges can still fail, so they are computed before modifying the changes
+        let mut merged_metadata = Vec::new();
+        for conflict in conflicts.iter() {
+            if let ZarrMetadataDoubleUpdate(path) = conflict {
+                match merge_metadata(path, previous_repo, &current_changes, current_repo)
+                    .await?
+                {
+                    Some(merged) => merged_metadata.push(merged),
+                    None => {
+                        return Ok(ConflictResolution::Unsolvable {
+                            reason: conflicts,
+                            unmodified: current_changes,
+                        });
+                    }
+                }
+            }
+        }
+
         let mut current_changes = current_changes;
+        for merged in merged_metadata {
+            match merged {
+                MergedMetadata::Array(node_id, path, array_data) => {
+                    current_changes.update_array(&node_id, &path, array_data)
+                }
+                MergedMetadata::Group(node_id, path, user_data) => {
+                    current_changes.update_group(&node_id, &path, user_data)
+                }
+            }
+        }
+
         for conflict in conflicts {
             match conflict {
                 ChunkDoubleUpdate { node_id, chunk_coordinates, .. } => {
@@ -107,7 +152,7 @@ impl BasicConflictSolver {
                         VersionSelection::UseTheirs => current_changes
                             .drop_chunk_changes(&node_id, |coord| {
                                 chunk_coordinates.contains(coord)
-                            }),
+                            })?,
                         // we can panic here because we have returned from the function if there
                         // were any unsolvable conflicts
                         #[allow(clippy::panic)]
@@ -116,6 +161,10 @@ impl BasicConflictSolver {
                         ),
                     }
                 }
+                ZarrMetadataDoubleUpdate(_) => {
+                    assert!(self.merge_attributes);
+                    // already merged above
+                }
                 DeleteOfUpdatedArray { .. } => {
                     assert!(!self.fail_on_delete_of_updated_array);
                     // this is a no-op, the solution is to still delete the array
@@ -134,3 +183,147 @@ impl BasicConflictSolver {
         Ok(ConflictResolution::Patched(current_changes))
     }
 }
+
+enum MergedMetadata {
+    Array(NodeId, Path, ArrayData),
+    Group(NodeId, Path, Bytes),
+}
+
+/// Three-way merge of the metadata of a node updated in both changes
+///
+/// Returns `None` if the changes cannot be merged. `current_repo` must not hold
+/// `current_changes`, so it reads the common ancestor.
+async fn merge_metadata(
+    path: &Path,
+    previous_repo: &Session,
+    current_changes: &ChangeSet,
+    current_repo: &Session,
+) -> SessionResult<Option<MergedMetadata>> {
+    let ancestor = current_repo.get_node(path).await?;
+    let theirs = previous_repo.get_node(path).await?;
+    let NodeSnapshot { id, user_data: ancestor_user_data, node_data, .. } = ancestor;
+    let merged = match (node_data, theirs.node_data) {
+        (
+            NodeData::Array { shape, dimension_names, .. },
+            NodeData::Array {
+                shape: their_shape,
+                dimension_names: their_dimension_names,
+                ..
+            },
+        ) => current_changes.get_updated_array(&id).and_then(|ours| {
+            let (shape, dimension_names) = merge_values(
+                Some(&(shape, dimension_names)),
+                Some(&(ours.shape.clone(), ours.dimension_names.clone())),
+                Some(&(their_shape, their_dimension_names)),
+            )??;
+            let user_data =
+                merge_user_data(&ancestor_user_data, &ours.user_data, &theirs.user_data)?;
+            Some(MergedMetadata::Array(
+                id.clone(),
+                path.clone(),
+                ArrayData { shape, dimension_names, user_data },
+            ))
+        }),
+        (NodeData::Group, NodeData::Group) => {
+            current_changes.get_updated_group(&id).and_then(|ours| {
+                let user_data =
+                    merge_user_data(&ancestor_user_data, ours, &theirs.user_data)?;
+                Some(MergedMetadata::Group(id.clone(), path.clone(), user_data))
+            })
+        }
+        _ => None,
+    };
+    Ok(merged)
+}
+
+/// Three-way merge of Zarr metadata JSON documents, merging `attributes` key by key
+fn merge_user_data(ancestor: &Bytes, ours: &Bytes, theirs: &Bytes) -> Option<Bytes> {
+    if ours == theirs || theirs == ancestor {
+        return Some(ours.clone());
+    }
+    if ours == ancestor {
+        return Some(theirs.clone());
+    }
+
+    let ancestor = parse_json_object(ancestor)?;
+    let ours = parse_json_object(ours)?;
+    let theirs = parse_json_object(theirs)?;
+    let merged = merge_objects(&ancestor, &ours, &theirs, |key, a, o, t| {
+        if key == "attributes" && (o.is_some() || t.is_some()) {
+            let empty = Map::new();
+            if let (Some(a), Some(o), Some(t)) =
+                (as_object(a, &empty), as_object(o, &empty), as_object(t, &empty))
+            {
+                let attributes =
+                    merge_objects(a, o, t, |_, a, o, t| merge_values(a, o, t))?;
+                return Some(Some(Value::Object(attributes)));
+            }
+        }
+        merge_values(a, o, t)
+    })?;
+    serde_json::to_vec(&Value::Object(merged)).ok().map(Bytes::from)
+}
+
+/// A missing value is taken as an empty object
+fn as_object<'a>(
+    value: Option<&'a Value>,
+    empty: &'a Map<String, Value>,
+) -> Option<&'a Map<String, Value>> {
+    match value {
+        None => Some(empty),
+        Some(Value::Object(obj)) => Some(obj),
+        Some(_) => None,
+    }
+}
+
+fn parse_json_object(bytes: &Bytes) -> Option<Map<String, Value>> {
+    if bytes.is_empty() {
+        return Some(Map::new());
+    }
+    match serde_json::from_slice(bytes) {
+        Ok(Value::Object(obj)) => Some(obj),
+        _ => None,
+    }
+}
+
+/// Merge every key of the objects with `merge_key`, which returns `None` on conflict and
+/// `Some(None)` for a key that must be absent from the result
+fn merge_objects<F>(
+    ancestor: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    merge_key: F,
+) -> Option<Map<String, Value>>
+where
+    F: Fn(&str, Option<&Value>, Option<&Value>, Option<&Value>) -> Option<Option<Value>>,
+{
+    let mut merged = Map::new();
+    for key in ancestor.keys().chain(ours.keys()).chain(theirs.keys()) {
+        if merged.contains_key(key) {
+            continue;
+        }
+        if let Some(value) =
+            merge_key(key, ancestor.get(key), ours.get(key), theirs.get(key))?
+        {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Some(merged)
+}
+
+/// Three-way merge of an atomic value, `None` means the value is absent in that version
+///
+/// Returns `None` if both sides changed the value in different ways
+fn merge_values<T: PartialEq + Clone>(
+    ancestor: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+) -> Option<Option<T>> {
+    if ours == theirs || theirs == ancestor {
+        Some(ours.cloned())
+    } else if ours == ancestor {
+        Some(theirs.cloned())
+    } else {
+        None
+    }
+}
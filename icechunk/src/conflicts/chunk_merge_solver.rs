@@ -0,0 +1,192 @@
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::{
+    change_set::ChangeSet,
+    format::{
+        ByteRange, ChunkIndices, NodeId, Path, manifest::ChunkPayload,
+        transaction_log::TransactionLog,
+    },
+    session::{Session, SessionResult, get_chunk},
+};
+
+use super::{
+    Conflict, ConflictResolution, ConflictSolver, basic_solver::BasicConflictSolver,
+    detector::ConflictDetector,
+};
+
+/// A three-way merge function for chunk payloads
+///
+/// It receives the bytes of the chunk in the common ancestor, in our changes, and in the
+/// conflicting commit, in that order. `None` means the chunk doesn't exist in that version.
+/// Returning `None` signals the chunk cannot be merged, the conflict is then left to the
+/// fallback solver.
+pub type ChunkMergeFn =
+    dyn Fn(Option<Bytes>, Option<Bytes>, Option<Bytes>) -> Option<Bytes> + Send + Sync;
+
+/// A [`ConflictSolver`] that resolves [`Conflict::ChunkDoubleUpdate`] by merging chunk bytes
+///
+/// All three versions of each conflicting chunk are fetched and passed to the merge function,
+/// the result is written as a new chunk and replaces our change. Any other conflicts, and
+/// chunks the merge function refuses to merge, are passed to the `fallback` solver.
+///
+/// Example, merging chunks of little endian `u32` counts with elementwise addition:
+/// ```ignore
+/// let solver = ChunkMergeSolver::new(|ancestor, ours, theirs| {
+///     let ancestor = ancestor.unwrap_or_default();
+///     let (ours, theirs) = (ours?, theirs?);
+///     // ours + theirs - ancestor, elementwise
+///     Some(add_counts(&ancestor, &ours, &theirs))
+/// });
+/// session.rebase(&solver).await?;
+/// ```
+#[derive(Clone)]
+pub struct ChunkMergeSolver {
+    pub merge: Arc<ChunkMergeFn>,
+    pub fallback: BasicConflictSolver,
+}
+
+impl fmt::Debug for ChunkMergeSolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkMergeSolver").field("fallback", &self.fallback).finish()
+    }
+}
+
+impl ChunkMergeSolver {
+    pub fn new<F>(merge: F) -> Self
+    where
+        F: Fn(Option<Bytes>, Option<Bytes>, Option<Bytes>) -> Option<Bytes>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self { merge: Arc::new(merge), fallback: BasicConflictSolver::default() }
+    }
+
+    pub fn with_fallback(mut self, fallback: BasicConflictSolver) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Returns the merged payload, or `None` if the merge function refused to merge
+    async fn merge_chunk(
+        &self,
+        previous_repo: &Session,
+        current_changes: &ChangeSet,
+        current_repo: &Session,
+        path: &Path,
+        node_id: &NodeId,
+        coord: &ChunkIndices,
+    ) -> SessionResult<Option<ChunkPayload>> {
+        // current_repo doesn't hold the changes being rebased, so it reads from the
+        // common ancestor
+        let ancestor = async {
+            get_chunk(current_repo.get_chunk_reader(path, coord, &ByteRange::ALL).await?)
+                .await
+        };
+        let ours = async {
+            match current_changes.get_chunk_ref(node_id, coord)? {
+                Some(Some(payload)) => current_repo
+                    .chunk_payload_reader(payload, &ByteRange::ALL)
+                    .await
+                    .map(Some),
+                _ => Ok(None),
+            }
+        };
+        let theirs = async {
+            get_chunk(previous_repo.get_chunk_reader(path, coord, &ByteRange::ALL).await?)
+                .await
+        };
+        let (ancestor, ours, theirs) = futures::try_join!(ancestor, ours, theirs)?;
+
+        match (self.merge)(ancestor, ours, theirs) {
+            Some(merged) => Ok(Some(current_repo.get_chunk_writer()(merged).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl ConflictSolver for ChunkMergeSolver {
+    async fn solve(
+        &self,
+        previous_change: &TransactionLog,
+        previous_repo: &Session,
+        current_changes: ChangeSet,
+        current_repo: &Session,
+    ) -> SessionResult<ConflictResolution> {
+        let (conflicts, current_changes) = match ConflictDetector
+            .solve(previous_change, previous_repo, current_changes, current_repo)
+            .await?
+        {
+            res @ ConflictResolution::Patched(_) => return Ok(res),
+            ConflictResolution::Unsolvable { reason, unmodified } => (reason, unmodified),
+        };
+
+        let mut merged = Vec::new();
+        let mut remaining = Vec::new();
+        for conflict in conflicts {
+            match conflict {
+                Conflict::ChunkDoubleUpdate { path, node_id, chunk_coordinates } => {
+                    let mut unmerged = HashSet::new();
+                    for coord in chunk_coordinates {
+                        match self
+                            .merge_chunk(
+                                previous_repo,
+                                &current_changes,
+                                current_repo,
+                                &path,
+                                &node_id,
+                                &coord,
+                            )
+                            .await?
+                        {
+                            Some(payload) => {
+                                merged.push((node_id.clone(), coord, payload))
+                            }
+                            None => {
+                                unmerged.insert(coord);
+                            }
+                        }
+                    }
+                    if !unmerged.is_empty() {
+                        remaining.push(Conflict::ChunkDoubleUpdate {
+                            path,
+                            node_id,
+                            chunk_coordinates: unmerged,
+                        });
+                    }
+                }
+                other => remaining.push(other),
+            }
+        }
+
+        // merged chunks are only applied if every other conflict could be solved, this way an
+        // unsolvable result returns the changes unmodified
+        let resolution = if remaining.is_empty() {
+            ConflictResolution::Patched(current_changes)
+        } else {
+            self.fallback
+                .solve_conflicts(
+                    previous_change,
+                    previous_repo,
+                    current_changes,
+                    current_repo,
+                    remaining,
+                )
+                .await?
+        };
+
+        match resolution {
+            ConflictResolution::Patched(mut patched) => {
+                for (node_id, coord, payload) in merged {
+                    patched.set_chunk_ref(node_id, coord, Some(payload));
+                }
+                Ok(ConflictResolution::Patched(patched))
+            }
+            unsolvable @ ConflictResolution::Unsolvable { .. } => Ok(unsolvable),
+        }
+    }
+}
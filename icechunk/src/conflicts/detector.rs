@@ -1,11 +1,13 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io,
     ops::DerefMut,
     sync::Mutex,
 };
 
 use async_trait::async_trait;
 use futures::{StreamExt, TryStreamExt, stream};
+use itertools::Itertools as _;
 
 use crate::{
     change_set::ChangeSet,
@@ -144,15 +146,14 @@ impl ConflictSolver for ConflictDetector {
                 if previous_changes.is_empty() {
                     None
                 } else {
-                    let conflicting: HashSet<_> = changes
-                        .keys()
-                        .filter(|coord| previous_changes.contains(coord))
-                        .cloned()
-                        .collect();
-                    if conflicting.is_empty() {
-                        None
-                    } else {
-                        Some(Ok((node_id, conflicting)))
+                    let conflicting = changes
+                        .map_ok(|(coord, _)| coord)
+                        .filter_ok(|coord| previous_changes.contains(coord))
+                        .collect::<io::Result<HashSet<_>>>();
+                    match conflicting {
+                        Ok(conflicting) if conflicting.is_empty() => None,
+                        Ok(conflicting) => Some(Ok((node_id, conflicting))),
+                        Err(err) => Some(Err(SessionError::from(err))),
                     }
                 }
             });
@@ -222,8 +223,23 @@ impl ConflictSolver for ConflictDetector {
             }
         });
 
+        let new_nodes_in_deleted_groups = stream::iter(
+            new_nodes_in_deleted_groups(previous_change, &current_changes, current_repo)
+                .await?
+                .into_iter()
+                .map(Ok),
+        );
+
+        let deletes_of_groups_with_new_children = stream::iter(
+            deletes_of_groups_with_new_children(&current_changes, previous_repo)
+                .await?
+                .into_iter()
+                .map(Ok),
+        );
+
         let all_conflicts: Vec<_> = new_nodes_explicit_conflicts
             .chain(new_nodes_implicit_conflicts)
+            .chain(new_nodes_in_deleted_groups)
             .chain(updated_arrays_already_updated)
             .chain(updated_groups_already_updated)
             .chain(updated_arrays_were_deleted)
@@ -233,6 +249,7 @@ impl ConflictSolver for ConflictDetector {
             .chain(chunks_double_updated)
             .chain(deletes_of_updated_arrays)
             .chain(deletes_of_updated_groups)
+            .chain(deletes_of_groups_with_new_children)
             .try_collect()
             .await?;
 
@@ -247,6 +264,75 @@ impl ConflictSolver for ConflictDetector {
     }
 }
 
+/// New nodes whose ancestor group was deleted by the previous change
+///
+/// `current_repo` must not hold `current_changes`, so it reads the common ancestor
+async fn new_nodes_in_deleted_groups(
+    previous_change: &TransactionLog,
+    current_changes: &ChangeSet,
+    current_repo: &Session,
+) -> SessionResult<Vec<Conflict>> {
+    let mut deleted_groups = BTreeSet::new();
+    for (path, _) in current_changes.new_nodes() {
+        for parent in path.ancestors().skip(1) {
+            if current_changes.get_group(&parent).is_some() {
+                // a group created by the current change, it cannot have been deleted
+                continue;
+            }
+            match current_repo.get_node(&parent).await {
+                Ok(node) if previous_change.group_deleted(&node.id) => {
+                    deleted_groups.insert(parent);
+                    break;
+                }
+                Ok(_)
+                | Err(SessionError {
+                    kind: SessionErrorKind::NodeNotFound { .. }, ..
+                }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    Ok(deleted_groups.into_iter().map(Conflict::NewNodeInDeletedGroup).collect())
+}
+
+/// Groups deleted by the current change, that got new descendants in the previous change
+///
+/// Applying the delete would leave those descendants orphan.
+async fn deletes_of_groups_with_new_children(
+    current_changes: &ChangeSet,
+    previous_repo: &Session,
+) -> SessionResult<Vec<Conflict>> {
+    let deleted_groups: HashMap<&Path, &NodeId> = current_changes
+        .deleted_groups()
+        // a group deleted and then created again in the current change is not deleted
+        .filter(|(path, _)| current_changes.get_group(path).is_none())
+        .map(|(path, node_id)| (path, node_id))
+        .collect();
+    if deleted_groups.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conflicting = BTreeMap::new();
+    for node in previous_repo.list_nodes().await? {
+        let node = node?;
+        if current_changes.is_deleted(&node.path, &node.id) {
+            continue;
+        }
+        if let Some((path, node_id)) = node
+            .path
+            .ancestors()
+            .skip(1)
+            .find_map(|parent| deleted_groups.get_key_value(&parent))
+        {
+            conflicting.insert((*path).clone(), (*node_id).clone());
+        }
+    }
+    Ok(conflicting
+        .into_iter()
+        .map(|(path, node_id)| Conflict::DeleteOfGroupWithNewChildren { path, node_id })
+        .collect())
+}
+
 struct PathFinder<It>(Mutex<(HashMap<NodeId, Path>, Option<It>)>);
 
 impl<It: Iterator<Item = SessionResult<NodeSnapshot>>> PathFinder<It> {
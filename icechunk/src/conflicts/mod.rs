@@ -9,12 +9,14 @@ use crate::{
 };
 
 pub mod basic_solver;
+pub mod chunk_merge_solver;
 pub mod detector;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Conflict {
     NewNodeConflictsWithExistingNode(Path),
     NewNodeInInvalidGroup(Path),
+    NewNodeInDeletedGroup(Path),
     ZarrMetadataDoubleUpdate(Path),
     ZarrMetadataUpdateOfDeletedArray(Path),
     ZarrMetadataUpdateOfDeletedGroup(Path),
@@ -39,8 +41,10 @@ pub enum Conflict {
         path: Path,
         node_id: NodeId,
     },
-    // FIXME: we are missing the case of current change deleting a group and previous change
-    // creating something new under it
+    DeleteOfGroupWithNewChildren {
+        path: Path,
+        node_id: NodeId,
+    },
 }
 
 #[derive(Debug)]
@@ -51,6 +55,11 @@ pub enum ConflictResolution {
 
 #[async_trait]
 pub trait ConflictSolver {
+    /// Solve the conflicts of `current_changes` with one of the commits being rebased on
+    ///
+    /// `previous_change` is the transaction log of that commit, and `previous_repo` the
+    /// repository right after it, not the tip of the branch. `current_repo` is the session
+    /// being rebased, without `current_changes`.
     async fn solve(
         &self,
         previous_change: &TransactionLog,
@@ -7,6 +7,7 @@ use flatbuffers::{FlatBufferBuilder, VerifierOptions};
 use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use serde_with::{TryFromInto, serde_as};
 
 use super::{
     AttributesId, ChunkIndices, IcechunkFormatError, IcechunkFormatErrorKind,
@@ -283,6 +284,44 @@ impl SnapshotInfo {
     pub fn is_initial(&self) -> bool {
         self.parent_id.is_none()
     }
+
+    /// How expiration changed the parent of this snapshot, `None` if it never did
+    pub fn history_edit(&self) -> Option<HistoryEdit> {
+        HistoryEdit::from_metadata(&self.metadata)
+    }
+}
+
+/// Recorded in the metadata of snapshots whose parent was changed by expiration
+///
+/// Expiration and retention policies write it on a kept snapshot when its expired ancestors
+/// are skipped, making the root or an older kept snapshot its new parent. The record lives on
+/// that re-parented child, the snapshot it was attached to is not modified.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEdit {
+    /// The parent the snapshot was committed with
+    #[serde_as(as = "TryFromInto<String>")]
+    pub original_parent_id: SnapshotId,
+    /// The last time the parent was changed
+    pub edited_at: DateTime<Utc>,
+    /// Number of snapshots removed from the ancestry between this snapshot and its current
+    /// parent, over all edits
+    pub skipped_snapshots: u64,
+}
+
+impl HistoryEdit {
+    /// Metadata key holding the record
+    pub const METADATA_KEY: &'static str = "icechunk.history_edit";
+
+    pub fn from_metadata(metadata: &SnapshotProperties) -> Option<Self> {
+        metadata
+            .get(Self::METADATA_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    fn to_metadata_value(&self) -> IcechunkResult<Value> {
+        Ok(serde_json::to_value(self).map_err(std::io::Error::from)?)
+    }
 }
 
 static ROOT_OPTIONS: VerifierOptions = VerifierOptions {
@@ -450,16 +489,43 @@ impl Snapshot {
         self.root().manifest_files().iter().map(|mf| mf.into())
     }
 
-    /// Cretase a new `Snapshot` with all the same data as `new_child` but `self` as parent
-    pub fn adopt(&self, new_child: &Snapshot) -> IcechunkResult<Self> {
+    /// A copy of `new_child` with `self` as parent
+    ///
+    /// `skipped_snapshots` is the number of snapshots between `new_child` and `self` in the
+    /// ancestry of `new_child`, it's recorded with the original parent in a [`HistoryEdit`].
+    pub fn adopt(
+        &self,
+        new_child: &Snapshot,
+        skipped_snapshots: u64,
+        edited_at: DateTime<Utc>,
+    ) -> IcechunkResult<Self> {
         // Rust flatbuffers implementation doesn't allow mutation of scalars, so we need to
         // create a whole new buffer and write to it in full
 
+        let mut metadata = new_child.metadata()?;
+        if let Some(parent_id) = new_child.parent_id() {
+            // if the child was already edited, we keep accumulating on the existing record
+            let edit = match HistoryEdit::from_metadata(&metadata) {
+                Some(previous) => HistoryEdit {
+                    edited_at,
+                    skipped_snapshots: previous.skipped_snapshots + skipped_snapshots,
+                    ..previous
+                },
+                None => HistoryEdit {
+                    original_parent_id: parent_id,
+                    edited_at,
+                    skipped_snapshots,
+                },
+            };
+            metadata
+                .insert(HistoryEdit::METADATA_KEY.to_string(), edit.to_metadata_value()?);
+        }
+
         Snapshot::from_iter(
             Some(new_child.id()),
             Some(self.id()),
             new_child.message().clone(),
-            Some(new_child.metadata()?.clone()),
+            Some(metadata),
             new_child.manifest_files().collect(),
             Some(new_child.flushed_at()?),
             new_child.iter(),
@@ -834,4 +900,56 @@ mod tests {
 
         assert!(shape2.valid_chunk_coord(&coord3));
     }
+
+    #[test]
+    fn test_adopt_records_history_edit() -> Result<(), Box<dyn std::error::Error>> {
+        let snapshot = |parent: &Snapshot| {
+            let nodes: Vec<Result<NodeSnapshot, Infallible>> = Vec::new();
+            Snapshot::from_iter(
+                None,
+                Some(parent.id()),
+                "child".to_string(),
+                None,
+                Vec::new(),
+                None,
+                nodes,
+            )
+        };
+        let root = Snapshot::initial()?;
+        let first = snapshot(&root)?;
+        let second = snapshot(&first)?;
+        let child = snapshot(&second)?;
+
+        let info = SnapshotInfo::try_from(&child)?;
+        assert_eq!(info.history_edit(), None);
+
+        let edited_at = Utc::now();
+        // skip the second snapshot
+        let edited = first.adopt(&child, 1, edited_at)?;
+        let info = SnapshotInfo::try_from(&edited)?;
+        assert_eq!(
+            info.history_edit(),
+            Some(HistoryEdit {
+                original_parent_id: second.id(),
+                edited_at,
+                skipped_snapshots: 1
+            })
+        );
+
+        // editing again keeps the original parent and accumulates the skipped snapshots
+        let edited = root.adopt(&edited, 1, edited_at)?;
+        let info = SnapshotInfo::try_from(&edited)?;
+        assert_eq!(info.parent_id, Some(root.id()));
+        // the record is on the re-parented child, never on its new parent
+        assert_eq!(SnapshotInfo::try_from(&root)?.history_edit(), None);
+        assert_eq!(
+            info.history_edit(),
+            Some(HistoryEdit {
+                original_parent_id: second.id(),
+                edited_at,
+                skipped_snapshots: 2
+            })
+        );
+        Ok(())
+    }
 }
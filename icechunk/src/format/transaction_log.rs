@@ -4,7 +4,7 @@ use std::{
 };
 
 use flatbuffers::VerifierOptions;
-use itertools::{Either, Itertools as _};
+use itertools::{Either, EitherOrBoth, Itertools as _};
 
 use crate::{
     change_set::ChangeSet,
@@ -22,7 +22,7 @@ pub struct TransactionLog {
 }
 
 impl TransactionLog {
-    pub fn new(id: &SnapshotId, cs: &ChangeSet) -> Self {
+    pub fn new(id: &SnapshotId, cs: &ChangeSet) -> IcechunkResult<Self> {
         let mut new_groups: Vec<_> =
             cs.new_groups().map(|(_, id)| generated::ObjectId8::new(&id.0)).collect();
         let mut new_arrays: Vec<_> =
@@ -40,29 +40,57 @@ impl TransactionLog {
         // TODO: what's a good capacity?
         let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(1_024 * 1_024);
 
-        // these come sorted from the change set
-        let updated_chunks = cs
-            .chunk_changes()
-            .map(|(node_id, chunks)| {
+        // these come sorted from the change set, changes after a checkpoint can repeat
+        // checkpointed coordinates
+        let in_memory = cs
+            .modified_chunk_coords()
+            .map(|(node_id, chunks)| (node_id, Either::Left(chunks)));
+        let from_checkpoints = cs.checkpointed_arrays().map(|(node_id, checkpoint)| {
+            (
+                node_id,
+                Either::Right(
+                    checkpoint.modified_coords().cloned().map(Ok::<_, std::io::Error>),
+                ),
+            )
+        });
+        let updated_chunks = in_memory
+            .merge_join_by(from_checkpoints, |(a, _), (b, _)| a.cmp(b))
+            .map(|both| {
+                let (node_id, chunks) = match both {
+                    EitherOrBoth::Left((node_id, chunks))
+                    | EitherOrBoth::Right((node_id, chunks)) => {
+                        (node_id, Either::Left(chunks))
+                    }
+                    EitherOrBoth::Both((node_id, a), (_, b)) => {
+                        // errors go first, so they are not delayed behind the other source
+                        let merged = a
+                            .merge_by(b, |a, b| match (a, b) {
+                                (Err(_), _) => true,
+                                (_, Err(_)) => false,
+                                (Ok(a), Ok(b)) => a <= b,
+                            })
+                            .dedup_by(|a, b| matches!((a, b), (Ok(a), Ok(b)) if a == b));
+                        (node_id, Either::Right(merged))
+                    }
+                };
                 let node_id = generated::ObjectId8::new(&node_id.0);
                 let node_id = Some(&node_id);
                 let chunks = chunks
-                    .keys()
                     .map(|indices| {
-                        let coords = Some(builder.create_vector(indices.0.as_slice()));
-                        generated::ChunkIndices::create(
+                        let coords = Some(builder.create_vector(indices?.0.as_slice()));
+                        Ok(generated::ChunkIndices::create(
                             &mut builder,
                             &generated::ChunkIndicesArgs { coords },
-                        )
+                        ))
                     })
-                    .collect::<Vec<_>>();
+                    .collect::<std::io::Result<Vec<_>>>()?;
                 let chunks = Some(builder.create_vector(chunks.as_slice()));
-                generated::ArrayUpdatedChunks::create(
+                Ok(generated::ArrayUpdatedChunks::create(
                     &mut builder,
                     &generated::ArrayUpdatedChunksArgs { node_id, chunks },
-                )
+                ))
             })
-            .collect::<Vec<_>>();
+            .collect::<IcechunkResult<Vec<_>>>()?;
         let updated_chunks = builder.create_vector(updated_chunks.as_slice());
         let updated_chunks = Some(updated_chunks);
 
@@ -100,7 +128,7 @@ impl TransactionLog {
         let (mut buffer, offset) = builder.collapse();
         buffer.drain(0..offset);
         buffer.shrink_to_fit();
-        Self { buffer }
+        Ok(Self { buffer })
     }
 
     pub fn from_buffer(buffer: Vec<u8>) -> IcechunkResult<TransactionLog> {
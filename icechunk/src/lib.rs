@@ -32,6 +32,7 @@ pub mod storage;
 pub mod store;
 #[cfg(test)]
 pub mod strategies;
+pub mod validation;
 pub mod virtual_chunks;
 
 pub use config::{ObjectStoreConfig, RepositoryConfig};
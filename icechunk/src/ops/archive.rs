@@ -0,0 +1,252 @@
+//! Moving the chunks of old versions to a cheaper storage
+//!
+//! Archived chunks are deleted from the repository storage, repositories configured with
+//! [`crate::Repository::set_archive_storage`] read them from the archive instead.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    future::ready,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use futures::{StreamExt as _, TryStreamExt as _, stream};
+use itertools::{EitherOrBoth, Itertools as _};
+use tracing::instrument;
+
+use crate::{
+    Storage,
+    asset_manager::AssetManager,
+    format::{ChunkId, ManifestId, SnapshotId, manifest::ChunkPayload},
+    ops::{
+        ScanConfig, all_roots,
+        external_sort::ExternalSorter,
+        gc::{
+            GCResult, PlannedDeletion, keep_saved_session_chunks,
+            saved_session_manifests, saved_sessions,
+        },
+    },
+    storage,
+};
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    older_than: DateTime<Utc>,
+    scan: ScanConfig,
+}
+
+impl ArchiveConfig {
+    /// Archive the chunks that only snapshots flushed before `older_than` point to
+    pub fn new(older_than: DateTime<Utc>) -> Self {
+        Self { older_than, scan: ScanConfig::default() }
+    }
+
+    /// Concurrency and memory limits of the archival
+    pub fn with_scan(mut self, scan: ScanConfig) -> Self {
+        self.scan = scan;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct ArchiveSummary {
+    pub chunks_archived: u64,
+    pub bytes_archived: u64,
+}
+
+/// Manifests of the reachable snapshots, split by the age of the snapshots using them
+#[derive(Debug, Default)]
+struct ManifestsByAge {
+    recent: HashMap<ManifestId, u64>,
+    old: HashMap<ManifestId, u64>,
+}
+
+async fn manifests_by_age(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: &AssetManager,
+    roots: &HashSet<SnapshotId>,
+    older_than: &DateTime<Utc>,
+) -> GCResult<ManifestsByAge> {
+    let mut pending: Vec<SnapshotId> =
+        all_roots(storage, storage_settings, roots).await?.try_collect().await?;
+    let mut seen = HashSet::new();
+    let mut res = ManifestsByAge::default();
+    while let Some(snap_id) = pending.pop() {
+        if !seen.insert(snap_id.clone()) {
+            continue;
+        }
+        let snap = asset_manager.fetch_snapshot(&snap_id).await?;
+        let manifests =
+            if &snap.flushed_at()? < older_than { &mut res.old } else { &mut res.recent };
+        manifests.extend(snap.manifest_files().map(|mf| (mf.id, mf.size_bytes)));
+        pending.extend(snap.parent_id());
+    }
+    res.old.retain(|manifest_id, _| !res.recent.contains_key(manifest_id));
+    Ok(res)
+}
+
+/// Push the ids of the native chunks in `manifests` to `sorter`
+async fn push_manifest_chunks(
+    asset_manager: &AssetManager,
+    manifests: &HashMap<ManifestId, u64>,
+    concurrency: usize,
+    sorter: &mut ExternalSorter<ChunkId>,
+) -> GCResult<()> {
+    let mut fetched = stream::iter(manifests.iter())
+        .map(|(manifest_id, size)| asset_manager.fetch_manifest(manifest_id, *size))
+        .buffer_unordered(concurrency);
+    while let Some(manifest) = fetched.try_next().await? {
+        for payload in manifest.chunk_payloads() {
+            if let ChunkPayload::Ref(chunk_ref) = payload? {
+                sorter.push(chunk_ref.id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Chunks still in the repository storage, that only old snapshots point to
+async fn chunks_to_archive(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: &AssetManager,
+    config: &ArchiveConfig,
+) -> GCResult<impl Iterator<Item = io::Result<PlannedDeletion<ChunkId>>> + Send + use<>> {
+    let saved_sessions = saved_sessions(storage, storage_settings).await?;
+    let roots = saved_sessions.iter().map(|saved| saved.snapshot_id.clone()).collect();
+    let mut manifests = manifests_by_age(
+        storage,
+        storage_settings,
+        asset_manager,
+        &roots,
+        &config.older_than,
+    )
+    .await?;
+    // uncommitted changes could be committed any time, they count as recent
+    for (manifest_id, size) in saved_session_manifests(&saved_sessions) {
+        manifests.old.remove(&manifest_id);
+        manifests.recent.insert(manifest_id, size);
+    }
+
+    let mut recent = config.scan.chunk_sorter();
+    push_manifest_chunks(
+        asset_manager,
+        &manifests.recent,
+        config.scan.concurrency(),
+        &mut recent,
+    )
+    .await?;
+    keep_saved_session_chunks(&saved_sessions, &mut recent)?;
+    let mut old = config.scan.chunk_sorter();
+    push_manifest_chunks(
+        asset_manager,
+        &manifests.old,
+        config.scan.concurrency(),
+        &mut old,
+    )
+    .await?;
+
+    let mut listed_chunks = config.scan.chunk_sorter();
+    let mut listed = storage.list_chunks(storage_settings).await?;
+    while let Some(chunk) = listed.try_next().await? {
+        listed_chunks.push(PlannedDeletion::from(chunk))?;
+    }
+    tracing::debug!(
+        old_runs = old.spilled_runs(),
+        recent_runs = recent.spilled_runs(),
+        listed_runs = listed_chunks.spilled_runs(),
+        "Chunks collected"
+    );
+
+    // errors are yielded as soon as they are found
+    let only_old = old
+        .into_sorted_iter()?
+        .merge_join_by(recent.into_sorted_iter()?, |old, recent| match (old, recent) {
+            (Ok(old), Ok(recent)) => old.cmp(recent),
+            (Err(_), _) => Ordering::Less,
+            (_, Err(_)) => Ordering::Greater,
+        })
+        .filter_map(|either| match either {
+            EitherOrBoth::Left(old) => Some(old),
+            EitherOrBoth::Right(Err(err)) => Some(Err(err)),
+            EitherOrBoth::Right(Ok(_)) | EitherOrBoth::Both(_, _) => None,
+        });
+    let merged =
+        listed_chunks.into_sorted_iter()?.merge_join_by(only_old, |listed, old| {
+            match (listed, old) {
+                (Ok(listed), Ok(old)) => listed.id.cmp(old),
+                (Err(_), _) => Ordering::Less,
+                (_, Err(_)) => Ordering::Greater,
+            }
+        });
+    // listed chunks that nobody points to are garbage, not archived
+    Ok(merged.filter_map(|either| match either {
+        EitherOrBoth::Both(listed, _) => Some(listed),
+        EitherOrBoth::Left(Err(err)) | EitherOrBoth::Right(Err(err)) => Some(Err(err)),
+        EitherOrBoth::Left(Ok(_)) | EitherOrBoth::Right(Ok(_)) => None,
+    }))
+}
+
+/// Move the chunks that only snapshots older than the threshold point to, to `archive`
+///
+/// Historical versions stay readable, without being expired, from repositories with
+/// [`crate::Repository::set_archive_storage`]. Every chunk is copied to the archive before
+/// being deleted from the repository storage, if the operation fails midway, running it again
+/// finishes the work.
+///
+/// Chunks pointed to by snapshots newer than the threshold, or by saved sessions, are never
+/// archived. Chunks that are already gone from the repository storage are skipped. Garbage
+/// collection doesn't look at the archive, chunks of expired versions stay there.
+#[instrument(skip(storage, storage_settings, asset_manager, archive, archive_settings))]
+pub async fn archive_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    archive: &(dyn Storage + Send + Sync),
+    archive_settings: &storage::Settings,
+    config: &ArchiveConfig,
+) -> GCResult<ArchiveSummary> {
+    let candidates =
+        chunks_to_archive(storage, storage_settings, &asset_manager, config).await?;
+
+    // archiving stops at the first error, only chunks already copied are deleted
+    let failure = Mutex::new(None);
+    let copied = stream::iter(candidates)
+        .map(|chunk| async move {
+            let chunk = chunk?;
+            let bytes = storage
+                .fetch_chunk(storage_settings, &chunk.id, &(0..chunk.size_bytes))
+                .await?;
+            archive.write_chunk(archive_settings, chunk.id.clone(), bytes).await?;
+            GCResult::Ok((chunk.id, chunk.size_bytes))
+        })
+        .buffer_unordered(config.scan.concurrency())
+        .scan((), |_, copied| {
+            ready(match copied {
+                Ok(chunk) => Some(chunk),
+                Err(err) => {
+                    if let Ok(mut failure) = failure.lock() {
+                        *failure = Some(err);
+                    }
+                    None
+                }
+            })
+        })
+        .boxed();
+    let res = storage.delete_chunks(storage_settings, copied).await?;
+    tracing::info!(
+        chunks = res.deleted_objects,
+        bytes = res.deleted_bytes,
+        "Chunks archived"
+    );
+    match failure.into_inner() {
+        Ok(Some(err)) => Err(err),
+        _ => Ok(ArchiveSummary {
+            chunks_archived: res.deleted_objects,
+            bytes_archived: res.deleted_bytes,
+        }),
+    }
+}
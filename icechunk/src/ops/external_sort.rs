@@ -0,0 +1,190 @@
+//! Sorting of more items than fit in memory, used by garbage collection to handle repositories
+//! with billions of chunks.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Number of items serialized together in a run file
+const BLOCK_LEN: usize = 4_096;
+
+fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(err)
+}
+
+/// Collects items, keeping at most `max_in_memory` of them in memory
+///
+/// When the buffer is full it's sorted and written to an anonymous file in `dir`. Once all
+/// items are pushed, [`ExternalSorter::into_sorted_iter`] merges the files, yielding every
+/// distinct item once, in order.
+#[derive(Debug)]
+pub(crate) struct ExternalSorter<T> {
+    dir: PathBuf,
+    max_in_memory: usize,
+    buffer: Vec<T>,
+    runs: Vec<File>,
+}
+
+impl<T> ExternalSorter<T>
+where
+    T: Ord + Serialize + DeserializeOwned,
+{
+    pub(crate) fn new(dir: &Path, max_in_memory: usize) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            max_in_memory: max_in_memory.max(1),
+            buffer: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) -> io::Result<()> {
+        self.buffer.push(item);
+        if self.buffer.len() >= self.max_in_memory {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn sort_buffer(&mut self) {
+        self.buffer.sort_unstable();
+        self.buffer.dedup();
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.sort_buffer();
+        let mut file = tempfile::tempfile_in(&self.dir)?;
+        {
+            let mut writer = BufWriter::new(&mut file);
+            for block in self.buffer.chunks(BLOCK_LEN) {
+                rmp_serde::encode::write(&mut writer, block).map_err(to_io_error)?;
+            }
+            writer.flush()?;
+        }
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Number of files written so far
+    pub(crate) fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub(crate) fn into_sorted_iter(mut self) -> io::Result<SortedIter<T>> {
+        self.sort_buffer();
+        let mut runs: Vec<RunReader<T>> =
+            self.runs.into_iter().map(RunReader::new).collect();
+        runs.push(RunReader::in_memory(self.buffer));
+
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (index, run) in runs.iter_mut().enumerate() {
+            if let Some(item) = run.next_item()? {
+                heap.push(Reverse((item, index)));
+            }
+        }
+        Ok(SortedIter { runs, heap, failed: false })
+    }
+}
+
+struct RunReader<T> {
+    file: Option<BufReader<File>>,
+    block: std::vec::IntoIter<T>,
+}
+
+impl<T: DeserializeOwned> RunReader<T> {
+    fn new(file: File) -> Self {
+        Self { file: Some(BufReader::new(file)), block: Vec::new().into_iter() }
+    }
+
+    fn in_memory(items: Vec<T>) -> Self {
+        Self { file: None, block: items.into_iter() }
+    }
+
+    fn next_item(&mut self) -> io::Result<Option<T>> {
+        loop {
+            if let Some(item) = self.block.next() {
+                return Ok(Some(item));
+            }
+            let Some(file) = self.file.as_mut() else {
+                return Ok(None);
+            };
+            match rmp_serde::from_read::<_, Vec<T>>(file) {
+                Ok(block) => self.block = block.into_iter(),
+                Err(rmp_serde::decode::Error::InvalidMarkerRead(err))
+                    if err.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    self.file = None;
+                }
+                Err(err) => return Err(to_io_error(err)),
+            }
+        }
+    }
+}
+
+/// Distinct items of an [`ExternalSorter`], in order
+pub(crate) struct SortedIter<T> {
+    runs: Vec<RunReader<T>>,
+    heap: BinaryHeap<Reverse<(T, usize)>>,
+    failed: bool,
+}
+
+impl<T: Ord + DeserializeOwned> SortedIter<T> {
+    fn next_item(&mut self) -> io::Result<Option<T>> {
+        while let Some(Reverse((item, index))) = self.heap.pop() {
+            if let Some(next) = self.runs[index].next_item()? {
+                self.heap.push(Reverse((next, index)));
+            }
+            // runs are deduplicated, but the same item can be in several of them
+            if self.heap.peek().is_some_and(|Reverse((next, _))| next == &item) {
+                continue;
+            }
+            return Ok(Some(item));
+        }
+        Ok(None)
+    }
+}
+
+impl<T: Ord + DeserializeOwned> Iterator for SortedIter<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let res = self.next_item().transpose();
+        self.failed = matches!(res, Some(Err(_)));
+        res
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn test_external_sort() {
+        let dir = std::env::temp_dir();
+        let items = (0..10_000u32).map(|i| (i * 7919) % 3_000);
+        let mut sorter = ExternalSorter::new(&dir, 1_000);
+        for item in items.clone() {
+            sorter.push(item).unwrap();
+        }
+        assert_eq!(sorter.spilled_runs(), 10);
+        let sorted: Vec<_> = sorter.into_sorted_iter().unwrap().try_collect().unwrap();
+        assert_eq!(sorted, items.sorted().dedup().collect::<Vec<_>>());
+
+        let sorter = ExternalSorter::<u32>::new(&dir, 1_000);
+        assert_eq!(sorter.into_sorted_iter().unwrap().count(), 0);
+    }
+}
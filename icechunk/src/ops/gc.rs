@@ -1,30 +1,49 @@
-use std::{collections::HashSet, future::ready, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::ready,
+    io,
+    sync::{Arc, Mutex},
+};
 
 use chrono::{DateTime, Utc};
-use futures::{StreamExt, TryStreamExt, stream};
+use futures::{Stream, StreamExt, TryStreamExt, stream, stream::BoxStream};
+use itertools::{EitherOrBoth, Itertools as _};
+use serde::{Deserialize, Serialize};
 use tokio::pin;
 use tracing::instrument;
 
+use self::checkpoint::Checkpoint;
 use crate::{
     Storage, StorageError,
     asset_manager::AssetManager,
     format::{
-        ChunkId, IcechunkFormatError, IcechunkFormatErrorKind, ManifestId, SnapshotId,
-        manifest::ChunkPayload,
+        AttributesId, ChunkId, IcechunkFormatError, ManifestId, SnapshotId,
+        manifest::ChunkPayload, snapshot::SnapshotInfo,
+    },
+    ops::{ScanConfig, external_sort::ExternalSorter, pointed_snapshots, stats::Usage},
+    refs::{
+        Ref, RefError, RefErrorKind, delete_branch, delete_tag, list_deleted_tags,
+        list_refs, purge_deleted_tag,
+    },
+    repository::{RepositoryError, RepositoryErrorKind},
+    session::{
+        SavedSession,
+        lease::{LeaseError, SessionLease, delete_lease, list_leases},
+        saved::list_saved_sessions,
     },
-    ops::pointed_snapshots,
-    refs::{Ref, RefError, delete_branch, delete_tag, list_refs},
-    repository::RepositoryError,
-    storage::{self, DeleteObjectsResult, ListInfo},
+    storage::{self, DeleteObjectsResult, ListInfo, StorageResult},
 };
 
-#[derive(Debug, PartialEq, Eq)]
+mod checkpoint;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Action {
     Keep,
     DeleteIfCreatedBefore(DateTime<Utc>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GCConfig {
     extra_roots: HashSet<SnapshotId>,
     dangling_chunks: Action,
@@ -32,6 +51,9 @@ pub struct GCConfig {
     dangling_attributes: Action,
     dangling_transaction_logs: Action,
     dangling_snapshots: Action,
+    stale_tag_delete_markers: Action,
+    scan: ScanConfig,
+    use_checkpoint: bool,
 }
 
 impl GCConfig {
@@ -50,6 +72,9 @@ impl GCConfig {
             dangling_attributes,
             dangling_transaction_logs,
             dangling_snapshots,
+            stale_tag_delete_markers: Action::Keep,
+            scan: ScanConfig::default(),
+            use_checkpoint: false,
         }
     }
     pub fn clean_all(
@@ -68,6 +93,37 @@ impl GCConfig {
         )
     }
 
+    /// What to do with the markers left by deleted tags, kept by default, also by
+    /// [`GCConfig::clean_all`]
+    ///
+    /// Deleting a marker frees the name of the tag, it can then be created again pointing to
+    /// a different snapshot. Markers are deleted if they were written before the cutoff.
+    pub fn with_stale_tag_delete_markers(mut self, action: Action) -> Self {
+        self.stale_tag_delete_markers = action;
+        self
+    }
+
+    /// Concurrency and memory limits of the collection
+    ///
+    /// Beyond the in memory limit, the ids of the chunks to keep and of the deletion
+    /// candidates are sorted in temporary files.
+    pub fn with_scan(mut self, scan: ScanConfig) -> Self {
+        self.scan = scan;
+        self
+    }
+
+    /// Use, and update, a reachability checkpoint stored in the repository, disabled by
+    /// default
+    ///
+    /// Collections with a checkpoint only read the snapshots created, or edited by
+    /// expiration, since the previous collection, and the manifests it didn't scan. The
+    /// checkpoint stores the chunk ids of every scanned manifest in the repository storage,
+    /// they are read back one manifest at a time.
+    pub fn with_checkpoint(mut self, use_checkpoint: bool) -> Self {
+        self.use_checkpoint = use_checkpoint;
+        self
+    }
+
     fn action_needed(&self) -> bool {
         [
             &self.dangling_chunks,
@@ -75,6 +131,7 @@ impl GCConfig {
             &self.dangling_attributes,
             &self.dangling_transaction_logs,
             &self.dangling_snapshots,
+            &self.stale_tag_delete_markers,
         ]
         .into_iter()
         .any(|action| action != &Action::Keep)
@@ -100,6 +157,31 @@ impl GCConfig {
         self.dangling_snapshots != Action::Keep
     }
 
+    pub fn deletes_tag_delete_markers(&self) -> bool {
+        self.stale_tag_delete_markers != Action::Keep
+    }
+
+    /// A copy of this configuration that keeps everything `leases` protect
+    fn protecting(&self, leases: &[SessionLease]) -> Self {
+        let Some(watermark) = leases.iter().map(|lease| lease.acquired_at).min() else {
+            return self.clone();
+        };
+        let protect = |action: Action| match action {
+            Action::DeleteIfCreatedBefore(before) => {
+                Action::DeleteIfCreatedBefore(before.min(watermark))
+            }
+            Action::Keep => Action::Keep,
+        };
+        let mut res = self.clone();
+        res.extra_roots.extend(leases.iter().map(|lease| lease.snapshot_id.clone()));
+        res.dangling_chunks = protect(self.dangling_chunks);
+        res.dangling_manifests = protect(self.dangling_manifests);
+        res.dangling_attributes = protect(self.dangling_attributes);
+        res.dangling_transaction_logs = protect(self.dangling_transaction_logs);
+        res.dangling_snapshots = protect(self.dangling_snapshots);
+        res
+    }
+
     fn must_delete_chunk(&self, chunk: &ListInfo<ChunkId>) -> bool {
         match self.dangling_chunks {
             Action::DeleteIfCreatedBefore(before) => chunk.created_at < before,
@@ -127,6 +209,20 @@ impl GCConfig {
             _ => false,
         }
     }
+
+    fn must_delete_attributes(&self, attributes: &ListInfo<AttributesId>) -> bool {
+        match self.dangling_attributes {
+            Action::DeleteIfCreatedBefore(before) => attributes.created_at < before,
+            _ => false,
+        }
+    }
+
+    fn must_delete_tag_delete_marker(&self, deleted_at: &DateTime<Utc>) -> bool {
+        match self.stale_tag_delete_markers {
+            Action::DeleteIfCreatedBefore(before) => deleted_at < &before,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Default)]
@@ -137,6 +233,7 @@ pub struct GCSummary {
     pub snapshots_deleted: u64,
     pub attributes_deleted: u64,
     pub transaction_logs_deleted: u64,
+    pub tag_delete_markers_deleted: u64,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -147,89 +244,229 @@ pub enum GCError {
     Storage(#[from] StorageError),
     #[error("repository error {0}")]
     Repository(#[from] RepositoryError),
+    #[error("session lease error {0}")]
+    Lease(#[from] LeaseError),
     #[error("format error {0}")]
     FormatError(#[from] IcechunkFormatError),
+    #[error(
+        "refs changed since the garbage collection plan was computed at {computed_at}"
+    )]
+    PlanOutdated { computed_at: DateTime<Utc> },
+    #[error("error writing temporary files {0}")]
+    Io(#[from] io::Error),
+    #[error("the repository config has no retention policy")]
+    NoRetentionPolicy,
 }
 
 pub type GCResult<A> = Result<A, GCError>;
 
-#[instrument(skip(asset_manager, storage))]
-pub async fn garbage_collect(
+pub(super) async fn saved_sessions(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> GCResult<Vec<SavedSession>> {
+    let mut res = Vec::new();
+    for name in list_saved_sessions(storage, storage_settings).await? {
+        match SavedSession::fetch(storage, storage_settings, &name).await {
+            Ok(saved) => res.push(saved),
+            // discarded or committed while we were listing
+            Err(RepositoryError {
+                kind: RepositoryErrorKind::SavedSessionNotFound(_),
+                ..
+            }) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(res)
+}
+
+/// Leases of the in-flight sessions, expired leases are deleted if `delete_expired`
+async fn active_leases(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    delete_expired: bool,
+) -> GCResult<Vec<SessionLease>> {
+    let now = Utc::now();
+    let (active, expired): (Vec<_>, Vec<_>) = list_leases(storage, storage_settings)
+        .await?
+        .into_iter()
+        .partition(|(_, lease)| lease.is_active(now));
+    if delete_expired {
+        for (id, lease) in expired {
+            tracing::info!(id, branch = lease.branch, "Deleting expired session lease");
+            delete_lease(storage, storage_settings, &id).await?;
+        }
+    }
+    Ok(active.into_iter().map(|(_, lease)| lease).collect())
+}
+
+/// Objects reachable from the repository roots
+#[derive(Debug)]
+struct RetainedObjects {
+    snapshots: HashSet<SnapshotId>,
+    manifests: HashSet<ManifestId>,
+    /// Only populated if `config` deletes chunks
+    chunks: ExternalSorter<ChunkId>,
+}
+
+/// Objects reachable from the repository roots, with the checkpoint for the next collection
+/// if `config` uses checkpoints
+///
+/// Nothing is written to the checkpoint unless `update_checkpoint`.
+async fn retained_objects(
     storage: &(dyn Storage + Send + Sync),
     storage_settings: &storage::Settings,
     asset_manager: Arc<AssetManager>,
     config: &GCConfig,
-) -> GCResult<GCSummary> {
-    // TODO: this function could have much more parallelism
-    if !config.action_needed() {
-        tracing::info!("No action requested");
-        return Ok(GCSummary::default());
+    update_checkpoint: bool,
+) -> GCResult<(RetainedObjects, Option<Checkpoint>)> {
+    if config.use_checkpoint {
+        let started_at = Utc::now();
+        let previous = Checkpoint::fetch(storage, storage_settings).await?;
+        let (retained, next) = checkpoint::retained_objects(
+            storage,
+            storage_settings,
+            asset_manager,
+            config,
+            previous,
+            started_at,
+            update_checkpoint,
+        )
+        .await?;
+        return Ok((retained, Some(next)));
     }
 
     tracing::info!("Finding GC roots");
-    let all_snaps = pointed_snapshots(
-        storage,
-        storage_settings,
-        Arc::clone(&asset_manager),
-        &config.extra_roots,
-    )
-    .await?;
+    // sessions saved for later are roots too, together with everything they wrote
+    let saved_sessions = saved_sessions(storage, storage_settings).await?;
+    let mut roots = config.extra_roots.clone();
+    roots.extend(saved_sessions.iter().map(|saved| saved.snapshot_id.clone()));
+    let all_snaps =
+        pointed_snapshots(storage, storage_settings, Arc::clone(&asset_manager), &roots)
+            .await?
+            .err_into();
+    let retained =
+        objects_retained_by(all_snaps, &saved_sessions, asset_manager, config).await?;
+    Ok((retained, None))
+}
 
-    // FIXME: add attribute files
-    let mut keep_chunks = HashSet::new();
-    let mut keep_manifests = HashSet::new();
+/// Objects reachable from the snapshots in `all_snaps`, and from `saved_sessions`
+async fn objects_retained_by(
+    all_snaps: impl Stream<Item = GCResult<SnapshotId>>,
+    saved_sessions: &[SavedSession],
+    asset_manager: Arc<AssetManager>,
+    config: &GCConfig,
+) -> GCResult<RetainedObjects> {
+    // attribute files are not referenced by snapshots anymore, all of them are dangling
     let mut keep_snapshots = HashSet::new();
+    // many snapshots share manifests, we collect them to scan each one once
+    let mut keep_manifests: HashMap<ManifestId, u64> = HashMap::new();
 
     tracing::info!("Calculating retained objects");
-    pin!(all_snaps);
-    while let Some(snap_id) = all_snaps.try_next().await? {
-        let snap = asset_manager.fetch_snapshot(&snap_id).await?;
-        if config.deletes_snapshots() && keep_snapshots.insert(snap_id.clone()) {
-            tracing::trace!("Adding snapshot to keep list: {}", &snap_id);
+    {
+        let snapshots = all_snaps
+            .try_filter(|snap_id| ready(keep_snapshots.insert(snap_id.clone())))
+            .map_ok(|snap_id| {
+                let asset_manager = Arc::clone(&asset_manager);
+                async move { Ok(asset_manager.fetch_snapshot(&snap_id).await?) }
+            })
+            .try_buffer_unordered(config.scan.concurrency());
+        pin!(snapshots);
+        while let Some(snap) = snapshots.try_next().await? {
+            keep_manifests.extend(snap.manifest_files().map(|mf| (mf.id, mf.size_bytes)));
         }
+    }
+    keep_manifests.extend(saved_session_manifests(saved_sessions));
 
-        if config.deletes_manifests() {
-            let manifests = snap.manifest_files().map(|mf| mf.id);
-            for mf in manifests {
-                if keep_manifests.insert(mf.clone()) {
-                    tracing::trace!("Adding manifest to keep list: {}", &mf);
+    let mut keep_chunks = config.scan.chunk_sorter();
+    if config.deletes_chunks() {
+        let manifests = stream::iter(keep_manifests.iter())
+            .map(|(manifest_id, size)| {
+                let asset_manager = Arc::clone(&asset_manager);
+                async move { asset_manager.fetch_manifest(manifest_id, *size).await }
+            })
+            .buffer_unordered(config.scan.concurrency());
+        pin!(manifests);
+        while let Some(manifest) = manifests.try_next().await? {
+            for payload in manifest.chunk_payloads() {
+                // a missing id would get a live chunk deleted, so errors are not skipped
+                if let ChunkPayload::Ref(chunk_ref) = payload? {
+                    keep_chunks.push(chunk_ref.id)?;
                 }
             }
         }
+        keep_saved_session_chunks(saved_sessions, &mut keep_chunks)?;
+        tracing::debug!(runs = keep_chunks.spilled_runs(), "Chunks to keep collected");
+    }
 
-        if config.deletes_chunks() {
-            for manifest_id in snap.manifest_files().map(|mf| mf.id) {
-                let manifest_info =
-                    snap.manifest_info(&manifest_id).ok_or_else(|| {
-                        IcechunkFormatError::from(
-                            IcechunkFormatErrorKind::ManifestInfoNotFound {
-                                manifest_id: manifest_id.clone(),
-                            },
-                        )
-                    })?;
-                let manifest = asset_manager
-                    .fetch_manifest(&manifest_id, manifest_info.size_bytes)
-                    .await?;
-                let chunk_ids =
-                    manifest.chunk_payloads().filter_map(|payload| match payload {
-                        Ok(ChunkPayload::Ref(chunk_ref)) => Some(chunk_ref.id.clone()),
-                        Ok(_) => None,
-                        Err(err) => {
-                            tracing::error!(
-                                error = %err,
-                                "Error in chunk payload iterator"
-                            );
-                            None
-                        }
-                    });
-                for chunk in chunk_ids {
-                    if keep_chunks.insert(chunk.clone()) {
-                        tracing::trace!("Adding chunk to keep list: {}", &chunk);
-                    }
+    Ok(RetainedObjects {
+        snapshots: keep_snapshots,
+        manifests: keep_manifests.into_keys().collect(),
+        chunks: keep_chunks,
+    })
+}
+
+/// Manifests written by the checkpoints of `saved_sessions`, and the ones the arrays had
+/// before their first checkpoint, with their sizes
+pub(super) fn saved_session_manifests(
+    saved_sessions: &[SavedSession],
+) -> impl Iterator<Item = (ManifestId, u64)> + '_ {
+    saved_sessions
+        .iter()
+        .flat_map(|saved| saved.change_set.checkpointed_arrays())
+        .flat_map(|(_, checkpoint)| {
+            checkpoint.manifest_files.iter().chain(checkpoint.base_manifest_files.iter())
+        })
+        .map(|mf| (mf.id.clone(), mf.size_bytes))
+}
+
+/// Add the chunks written by `saved_sessions` to `keep_chunks`
+pub(super) fn keep_saved_session_chunks(
+    saved_sessions: &[SavedSession],
+    keep_chunks: &mut ExternalSorter<ChunkId>,
+) -> io::Result<()> {
+    for saved in saved_sessions.iter() {
+        for (_, changes) in saved.change_set.chunk_changes() {
+            for res in changes {
+                if let (_, Some(ChunkPayload::Ref(chunk_ref))) = res? {
+                    keep_chunks.push(chunk_ref.id)?;
                 }
             }
         }
     }
+    Ok(())
+}
+
+#[instrument(skip(asset_manager, storage))]
+pub async fn garbage_collect(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    config: &GCConfig,
+) -> GCResult<GCSummary> {
+    if !config.action_needed() {
+        tracing::info!("No action requested");
+        return Ok(GCSummary::default());
+    }
+
+    // sessions that haven't committed yet can be writing objects nothing points to
+    let leases = active_leases(storage, storage_settings, true).await?;
+    let config = &config.protecting(&leases);
+
+    let (
+        RetainedObjects {
+            snapshots: keep_snapshots,
+            manifests: keep_manifests,
+            chunks: keep_chunks,
+        },
+        checkpoint,
+    ) = retained_objects(
+        storage,
+        storage_settings,
+        Arc::clone(&asset_manager),
+        config,
+        true,
+    )
+    .await?;
 
     let mut summary = GCSummary::default();
 
@@ -271,36 +508,87 @@ pub async fn garbage_collect(
     }
     if config.deletes_chunks() {
         asset_manager.clear_chunk_cache();
-        let res = gc_chunks(storage, storage_settings, config, &keep_chunks).await?;
+        let res = gc_chunks(storage, storage_settings, config, keep_chunks).await?;
         summary.chunks_deleted = res.deleted_objects;
         summary.bytes_deleted += res.deleted_bytes;
     }
+    if config.deletes_attributes() {
+        let res = gc_attributes(storage, storage_settings, config).await?;
+        summary.attributes_deleted = res.deleted_objects;
+        summary.bytes_deleted += res.deleted_bytes;
+    }
+    if config.deletes_tag_delete_markers() {
+        let tags = stale_deleted_tags(storage, storage_settings, config).await?;
+        summary.tag_delete_markers_deleted =
+            purge_deleted_tags(storage, storage_settings, &tags).await?;
+    }
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.write(storage, storage_settings).await?;
+    }
 
     Ok(summary)
 }
 
-#[instrument(skip(storage, storage_settings, config, keep_ids), fields(keep_ids.len = keep_ids.len()))]
+/// Chunks that are old enough to be deleted and are not in `keep`, sorted by id
+///
+/// Both the candidates and `keep` are sorted, possibly on disk, and then merged, memory use is
+/// bounded by [`GCConfig::with_scan`].
+async fn dangling_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    config: &GCConfig,
+    keep: ExternalSorter<ChunkId>,
+) -> GCResult<impl Iterator<Item = io::Result<PlannedDeletion<ChunkId>>> + Send + use<>> {
+    let mut candidates = config.scan.chunk_sorter();
+    let mut listed = storage.list_chunks(storage_settings).await?;
+    while let Some(chunk) = listed.try_next().await? {
+        if config.must_delete_chunk(&chunk) {
+            candidates.push(PlannedDeletion::from(chunk))?;
+        }
+    }
+    tracing::debug!(runs = candidates.spilled_runs(), "Chunk candidates collected");
+
+    let merged = candidates.into_sorted_iter()?.merge_join_by(
+        keep.into_sorted_iter()?,
+        |candidate, keep| match (candidate, keep) {
+            (Ok(candidate), Ok(keep)) => candidate.id.cmp(keep),
+            // errors are yielded as soon as they are found
+            (Err(_), _) => Ordering::Less,
+            (_, Err(_)) => Ordering::Greater,
+        },
+    );
+    Ok(merged.filter_map(|either| match either {
+        EitherOrBoth::Left(candidate) => Some(candidate),
+        EitherOrBoth::Right(Err(err)) => Some(Err(err)),
+        EitherOrBoth::Right(Ok(_)) | EitherOrBoth::Both(_, _) => None,
+    }))
+}
+
+#[instrument(skip(storage, storage_settings, config, keep_ids))]
 async fn gc_chunks(
     storage: &(dyn Storage + Send + Sync),
     storage_settings: &storage::Settings,
     config: &GCConfig,
-    keep_ids: &HashSet<ChunkId>,
+    keep_ids: ExternalSorter<ChunkId>,
 ) -> GCResult<DeleteObjectsResult> {
-    let to_delete = storage
-        .list_chunks(storage_settings)
-        .await?
-        // TODO: don't skip over errors
-        .filter_map(move |chunk| {
-            ready(chunk.ok().and_then(|chunk| {
-                if config.must_delete_chunk(&chunk) && !keep_ids.contains(&chunk.id) {
-                    Some((chunk.id.clone(), chunk.size_bytes))
-                } else {
-                    None
-                }
-            }))
-        })
-        .boxed();
-    Ok(storage.delete_chunks(storage_settings, to_delete).await?)
+    let dangling = dangling_chunks(storage, storage_settings, config, keep_ids).await?;
+    // deletion stops at the first error, chunks after it could be in the keep set
+    let failure = Mutex::new(None);
+    let to_delete = stream::iter(dangling.map_while(|chunk| match chunk {
+        Ok(chunk) => Some((chunk.id, chunk.size_bytes)),
+        Err(err) => {
+            if let Ok(mut failure) = failure.lock() {
+                *failure = Some(err);
+            }
+            None
+        }
+    }))
+    .boxed();
+    let res = storage.delete_chunks(storage_settings, to_delete).await?;
+    match failure.into_inner() {
+        Ok(Some(err)) => Err(err.into()),
+        _ => Ok(res),
+    }
 }
 
 #[instrument(skip(storage, storage_settings, config, keep_ids), fields(keep_ids.len = keep_ids.len()))]
@@ -311,24 +599,23 @@ async fn gc_manifests(
     config: &GCConfig,
     keep_ids: &HashSet<ManifestId>,
 ) -> GCResult<DeleteObjectsResult> {
-    let to_delete = storage
+    let to_delete: Vec<_> = storage
         .list_manifests(storage_settings)
         .await?
-        // TODO: don't skip over errors
-        .filter_map(move |manifest| {
-            ready(manifest.ok().and_then(|manifest| {
-                if config.must_delete_manifest(&manifest)
-                    && !keep_ids.contains(&manifest.id)
-                {
-                    asset_manager.remove_cached_manifest(&manifest.id);
-                    Some((manifest.id.clone(), manifest.size_bytes))
-                } else {
-                    None
-                }
-            }))
+        .try_filter(|manifest| {
+            ready(
+                config.must_delete_manifest(manifest) && !keep_ids.contains(&manifest.id),
+            )
         })
-        .boxed();
-    Ok(storage.delete_manifests(storage_settings, to_delete).await?)
+        .map_ok(|manifest| {
+            asset_manager.remove_cached_manifest(&manifest.id);
+            (manifest.id, manifest.size_bytes)
+        })
+        .try_collect()
+        .await?;
+    Ok(storage
+        .delete_manifests(storage_settings, stream::iter(to_delete).boxed())
+        .await?)
 }
 
 #[instrument(skip(storage, storage_settings, config, keep_ids), fields(keep_ids.len = keep_ids.len()))]
@@ -339,24 +626,23 @@ async fn gc_snapshots(
     config: &GCConfig,
     keep_ids: &HashSet<SnapshotId>,
 ) -> GCResult<DeleteObjectsResult> {
-    let to_delete = storage
+    let to_delete: Vec<_> = storage
         .list_snapshots(storage_settings)
         .await?
-        // TODO: don't skip over errors
-        .filter_map(move |snapshot| {
-            ready(snapshot.ok().and_then(|snapshot| {
-                if config.must_delete_snapshot(&snapshot)
-                    && !keep_ids.contains(&snapshot.id)
-                {
-                    asset_manager.remove_cached_snapshot(&snapshot.id);
-                    Some((snapshot.id.clone(), snapshot.size_bytes))
-                } else {
-                    None
-                }
-            }))
+        .try_filter(|snapshot| {
+            ready(
+                config.must_delete_snapshot(snapshot) && !keep_ids.contains(&snapshot.id),
+            )
+        })
+        .map_ok(|snapshot| {
+            asset_manager.remove_cached_snapshot(&snapshot.id);
+            (snapshot.id, snapshot.size_bytes)
         })
-        .boxed();
-    Ok(storage.delete_snapshots(storage_settings, to_delete).await?)
+        .try_collect()
+        .await?;
+    Ok(storage
+        .delete_snapshots(storage_settings, stream::iter(to_delete).boxed())
+        .await?)
 }
 
 #[instrument(skip(storage, storage_settings, config, keep_ids), fields(keep_ids.len = keep_ids.len()))]
@@ -367,22 +653,454 @@ async fn gc_transaction_logs(
     config: &GCConfig,
     keep_ids: &HashSet<SnapshotId>,
 ) -> GCResult<DeleteObjectsResult> {
-    let to_delete = storage
+    let to_delete: Vec<_> = storage
         .list_transaction_logs(storage_settings)
         .await?
-        // TODO: don't skip over errors
-        .filter_map(move |tx| {
-            ready(tx.ok().and_then(|tx| {
-                if config.must_delete_transaction_log(&tx) && !keep_ids.contains(&tx.id) {
-                    asset_manager.remove_cached_tx_log(&tx.id);
-                    Some((tx.id.clone(), tx.size_bytes))
-                } else {
-                    None
-                }
-            }))
+        .try_filter(|tx| {
+            ready(config.must_delete_transaction_log(tx) && !keep_ids.contains(&tx.id))
         })
-        .boxed();
-    Ok(storage.delete_transaction_logs(storage_settings, to_delete).await?)
+        .map_ok(|tx| {
+            asset_manager.remove_cached_tx_log(&tx.id);
+            (tx.id, tx.size_bytes)
+        })
+        .try_collect()
+        .await?;
+    Ok(storage
+        .delete_transaction_logs(storage_settings, stream::iter(to_delete).boxed())
+        .await?)
+}
+
+#[instrument(skip(storage, storage_settings, config))]
+async fn gc_attributes(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    config: &GCConfig,
+) -> GCResult<DeleteObjectsResult> {
+    // a failed listing is reported instead of deleting nothing
+    let to_delete: Vec<_> = storage
+        .list_attributes(storage_settings)
+        .await?
+        .try_filter(|attributes| ready(config.must_delete_attributes(attributes)))
+        .map_ok(|attributes| (attributes.id, attributes.size_bytes))
+        .try_collect()
+        .await?;
+    Ok(storage
+        .delete_attributes(storage_settings, stream::iter(to_delete).boxed())
+        .await?)
+}
+
+/// Names of the deleted tags with a marker old enough to be deleted
+async fn stale_deleted_tags(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    config: &GCConfig,
+) -> GCResult<Vec<String>> {
+    Ok(list_deleted_tags(storage, storage_settings)
+        .await?
+        .into_iter()
+        .filter(|(_, deleted_at)| config.must_delete_tag_delete_marker(deleted_at))
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Returns the number of tags purged
+async fn purge_deleted_tags(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    tags: &[String],
+) -> GCResult<u64> {
+    let mut purged = 0;
+    for tag in tags {
+        match purge_deleted_tag(storage, storage_settings, tag).await {
+            Ok(()) => purged += 1,
+            // purged concurrently by somebody else
+            Err(RefError { kind: RefErrorKind::RefNotFound(_), .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(purged)
+}
+
+/// An object that a [`GCPlan`] deletes
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PlannedDeletion<Id> {
+    pub id: Id,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl<Id> From<ListInfo<Id>> for PlannedDeletion<Id> {
+    fn from(value: ListInfo<Id>) -> Self {
+        Self { id: value.id, size_bytes: value.size_bytes, created_at: value.created_at }
+    }
+}
+
+/// The refs, and saved sessions, a [`GCPlan`] was computed for
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RefsState {
+    pub branches: BTreeMap<String, SnapshotId>,
+    pub tags: BTreeMap<String, SnapshotId>,
+    pub saved_sessions: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl RefsState {
+    pub async fn fetch(
+        storage: &(dyn Storage + Send + Sync),
+        storage_settings: &storage::Settings,
+    ) -> GCResult<Self> {
+        let mut res = Self::default();
+        for reference in list_refs(storage, storage_settings).await? {
+            let snapshot = reference.fetch(storage, storage_settings).await?.snapshot;
+            match reference {
+                Ref::Branch(name) => res.branches.insert(name, snapshot),
+                Ref::Tag(name) => res.tags.insert(name, snapshot),
+            };
+        }
+        for name in list_saved_sessions(storage, storage_settings).await? {
+            match SavedSession::fetch(storage, storage_settings, &name).await {
+                Ok(saved) => {
+                    res.saved_sessions.insert(name, saved.saved_at);
+                }
+                Err(RepositoryError {
+                    kind: RepositoryErrorKind::SavedSessionNotFound(_),
+                    ..
+                }) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// The objects a garbage collection would delete, computed by [`plan_garbage_collection`]
+///
+/// Plans can be serialized, reviewed, and executed later with [`execute_gc_plan`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct GCPlan {
+    pub computed_at: DateTime<Utc>,
+    pub refs: RefsState,
+    pub snapshots: Vec<PlannedDeletion<SnapshotId>>,
+    pub transaction_logs: Vec<PlannedDeletion<SnapshotId>>,
+    pub manifests: Vec<PlannedDeletion<ManifestId>>,
+    pub chunks: Vec<PlannedDeletion<ChunkId>>,
+    #[serde(default)]
+    pub attributes: Vec<PlannedDeletion<AttributesId>>,
+    /// Names of the deleted tags whose delete markers are removed
+    #[serde(default)]
+    pub tag_delete_markers: Vec<String>,
+}
+
+impl GCPlan {
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+            && self.transaction_logs.is_empty()
+            && self.manifests.is_empty()
+            && self.chunks.is_empty()
+            && self.attributes.is_empty()
+            && self.tag_delete_markers.is_empty()
+    }
+
+    /// The summary [`execute_gc_plan`] would return if every object is deleted
+    pub fn summary(&self) -> GCSummary {
+        fn bytes<Id>(deletions: &[PlannedDeletion<Id>]) -> u64 {
+            deletions.iter().map(|d| d.size_bytes).sum()
+        }
+        GCSummary {
+            bytes_deleted: bytes(&self.snapshots)
+                + bytes(&self.transaction_logs)
+                + bytes(&self.manifests)
+                + bytes(&self.chunks)
+                + bytes(&self.attributes),
+            chunks_deleted: self.chunks.len() as u64,
+            manifests_deleted: self.manifests.len() as u64,
+            snapshots_deleted: self.snapshots.len() as u64,
+            attributes_deleted: self.attributes.len() as u64,
+            transaction_logs_deleted: self.transaction_logs.len() as u64,
+            tag_delete_markers_deleted: self.tag_delete_markers.len() as u64,
+        }
+    }
+}
+
+async fn planned_deletions<Id>(
+    objects: BoxStream<'_, StorageResult<ListInfo<Id>>>,
+    must_delete: impl Fn(&ListInfo<Id>) -> bool,
+) -> GCResult<Vec<PlannedDeletion<Id>>> {
+    Ok(objects
+        .try_filter(|info| ready(must_delete(info)))
+        .map_ok(PlannedDeletion::from)
+        .try_collect()
+        .await?)
+}
+
+/// Compute what [`garbage_collect`] would delete, without deleting anything
+#[instrument(skip(asset_manager, storage))]
+pub async fn plan_garbage_collection(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    config: &GCConfig,
+) -> GCResult<GCPlan> {
+    // refs are captured before finding the retained objects, so any later change to the
+    // refs is detected when executing the plan
+    let refs = RefsState::fetch(storage, storage_settings).await?;
+    let mut plan = GCPlan { computed_at: Utc::now(), refs, ..Default::default() };
+    if !config.action_needed() {
+        return Ok(plan);
+    }
+    let leases = active_leases(storage, storage_settings, false).await?;
+    let config = &config.protecting(&leases);
+    // the checkpoint is only updated when the plan is executed
+    let (keep, _) =
+        retained_objects(storage, storage_settings, asset_manager, config, false).await?;
+    plan_deletions(storage, storage_settings, config, keep, &mut plan).await?;
+    Ok(plan)
+}
+
+/// Fill `plan` with the objects `config` deletes that are not in `keep`
+async fn plan_deletions(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    config: &GCConfig,
+    keep: RetainedObjects,
+    plan: &mut GCPlan,
+) -> GCResult<()> {
+    if config.deletes_snapshots() {
+        plan.snapshots =
+            planned_deletions(storage.list_snapshots(storage_settings).await?, |info| {
+                config.must_delete_snapshot(info) && !keep.snapshots.contains(&info.id)
+            })
+            .await?;
+    }
+    if config.deletes_transaction_logs() {
+        plan.transaction_logs = planned_deletions(
+            storage.list_transaction_logs(storage_settings).await?,
+            |info| {
+                config.must_delete_transaction_log(info)
+                    && !keep.snapshots.contains(&info.id)
+            },
+        )
+        .await?;
+    }
+    if config.deletes_manifests() {
+        plan.manifests =
+            planned_deletions(storage.list_manifests(storage_settings).await?, |info| {
+                config.must_delete_manifest(info) && !keep.manifests.contains(&info.id)
+            })
+            .await?;
+    }
+    if config.deletes_chunks() {
+        plan.chunks = dangling_chunks(storage, storage_settings, config, keep.chunks)
+            .await?
+            .try_collect()?;
+    }
+    if config.deletes_attributes() {
+        plan.attributes =
+            planned_deletions(storage.list_attributes(storage_settings).await?, |info| {
+                config.must_delete_attributes(info)
+            })
+            .await?;
+    }
+    if config.deletes_tag_delete_markers() {
+        plan.tag_delete_markers =
+            stale_deleted_tags(storage, storage_settings, config).await?;
+    }
+    Ok(())
+}
+
+/// What [`expire`] followed by [`garbage_collect`] would free, computed by
+/// [`estimate_expire_and_gc`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExpireAndGCEstimate {
+    /// Refs [`expire`] would delete
+    pub deleted_refs: HashSet<Ref>,
+    pub snapshots: Usage,
+    pub transaction_logs: Usage,
+    pub manifests: Usage,
+    pub chunks: Usage,
+    pub attributes: Usage,
+    pub tag_delete_markers: u64,
+}
+
+impl ExpireAndGCEstimate {
+    pub fn bytes_freed(&self) -> u64 {
+        self.snapshots.bytes
+            + self.transaction_logs.bytes
+            + self.manifests.bytes
+            + self.chunks.bytes
+            + self.attributes.bytes
+    }
+}
+
+impl From<&GCPlan> for ExpireAndGCEstimate {
+    fn from(plan: &GCPlan) -> Self {
+        fn usage<Id>(deletions: &[PlannedDeletion<Id>]) -> Usage {
+            Usage {
+                count: deletions.len() as u64,
+                bytes: deletions.iter().map(|d| d.size_bytes).sum(),
+            }
+        }
+        Self {
+            deleted_refs: HashSet::new(),
+            snapshots: usage(&plan.snapshots),
+            transaction_logs: usage(&plan.transaction_logs),
+            manifests: usage(&plan.manifests),
+            chunks: usage(&plan.chunks),
+            attributes: usage(&plan.attributes),
+            tag_delete_markers: plan.tag_delete_markers.len() as u64,
+        }
+    }
+}
+
+/// Snapshots in `ancestry`, sorted newest first, that [`expire_ref`] keeps in the history
+///
+/// That's every snapshot up to the oldest one not expired, and the root. If the whole
+/// history is expired, the tip and the root are kept.
+fn kept_by_expiration(
+    ancestry: &[SnapshotInfo],
+    older_than: DateTime<Utc>,
+) -> impl Iterator<Item = &SnapshotId> {
+    let last_kept = ancestry.iter().rposition(|info| info.flushed_at >= older_than);
+    ancestry
+        .iter()
+        .take(last_kept.unwrap_or_default() + 1)
+        .chain(ancestry.last())
+        .map(|info| &info.id)
+}
+
+/// Compute what [`expire`] with the same arguments, followed by [`garbage_collect`] with
+/// `config`, would free, without changing anything in the repository
+///
+/// History is followed from the refs as [`expire`] would leave it. Saved sessions and active
+/// session leases keep their whole history, even if expiration would shorten it, so the
+/// estimate can be lower than what is actually freed. On the other hand, [`expire`] writes
+/// the snapshots it edits again, slightly bigger. If they become unreachable, they are only
+/// freed by a `config` that deletes objects created after the expiration.
+#[instrument(skip(asset_manager, storage))]
+pub async fn estimate_expire_and_gc(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    older_than: DateTime<Utc>,
+    expired_branches: ExpiredRefAction,
+    expired_tags: ExpiredRefAction,
+    config: &GCConfig,
+) -> GCResult<ExpireAndGCEstimate> {
+    let mut deleted_refs = HashSet::new();
+    let mut keep_snapshots = Vec::new();
+    for reference in list_refs(storage, storage_settings).await? {
+        let tip = reference.fetch(storage, storage_settings).await?.snapshot;
+        let ancestry: Vec<SnapshotInfo> = Arc::clone(&asset_manager)
+            .snapshot_ancestry(&tip)
+            .await?
+            .try_collect()
+            .await?;
+        let ref_is_expired =
+            ancestry.first().is_some_and(|tip| tip.flushed_at < older_than);
+        let deleted = ref_is_expired
+            && deletes_expired_ref(
+                &reference,
+                &|_| false,
+                expired_branches,
+                expired_tags,
+            );
+        if deleted {
+            deleted_refs.insert(reference);
+        } else {
+            keep_snapshots.extend(kept_by_expiration(&ancestry, older_than).cloned());
+        }
+    }
+
+    let mut plan = GCPlan { computed_at: Utc::now(), ..Default::default() };
+    if config.action_needed() {
+        let leases = active_leases(storage, storage_settings, false).await?;
+        let config = &config.protecting(&leases);
+        let saved_sessions = saved_sessions(storage, storage_settings).await?;
+        let roots = config
+            .extra_roots
+            .iter()
+            .chain(saved_sessions.iter().map(|saved| &saved.snapshot_id));
+        for root in roots {
+            let ancestry = Arc::clone(&asset_manager).snapshot_ancestry(root).await?;
+            keep_snapshots
+                .extend(ancestry.map_ok(|info| info.id).try_collect::<Vec<_>>().await?);
+        }
+
+        let keep = objects_retained_by(
+            stream::iter(keep_snapshots.into_iter().map(Ok)),
+            &saved_sessions,
+            asset_manager,
+            config,
+        )
+        .await?;
+        plan_deletions(storage, storage_settings, config, keep, &mut plan).await?;
+    }
+
+    Ok(ExpireAndGCEstimate { deleted_refs, ..ExpireAndGCEstimate::from(&plan) })
+}
+
+/// Delete exactly the objects in `plan`
+///
+/// Fails with [`GCError::PlanOutdated`], without deleting anything, if refs or saved sessions
+/// changed since the plan was computed: objects in the plan could be reachable now.
+#[instrument(skip(asset_manager, storage, plan))]
+pub async fn execute_gc_plan(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    plan: &GCPlan,
+) -> GCResult<GCSummary> {
+    if RefsState::fetch(storage, storage_settings).await? != plan.refs {
+        return Err(GCError::PlanOutdated { computed_at: plan.computed_at });
+    }
+
+    let mut summary = GCSummary::default();
+    let to_delete = stream::iter(plan.snapshots.iter().map(|d| {
+        asset_manager.remove_cached_snapshot(&d.id);
+        (d.id.clone(), d.size_bytes)
+    }))
+    .boxed();
+    let res = storage.delete_snapshots(storage_settings, to_delete).await?;
+    summary.snapshots_deleted = res.deleted_objects;
+    summary.bytes_deleted += res.deleted_bytes;
+
+    let to_delete = stream::iter(plan.transaction_logs.iter().map(|d| {
+        asset_manager.remove_cached_tx_log(&d.id);
+        (d.id.clone(), d.size_bytes)
+    }))
+    .boxed();
+    let res = storage.delete_transaction_logs(storage_settings, to_delete).await?;
+    summary.transaction_logs_deleted = res.deleted_objects;
+    summary.bytes_deleted += res.deleted_bytes;
+
+    let to_delete = stream::iter(plan.manifests.iter().map(|d| {
+        asset_manager.remove_cached_manifest(&d.id);
+        (d.id.clone(), d.size_bytes)
+    }))
+    .boxed();
+    let res = storage.delete_manifests(storage_settings, to_delete).await?;
+    summary.manifests_deleted = res.deleted_objects;
+    summary.bytes_deleted += res.deleted_bytes;
+
+    if !plan.chunks.is_empty() {
+        asset_manager.clear_chunk_cache();
+    }
+    let to_delete =
+        stream::iter(plan.chunks.iter().map(|d| (d.id.clone(), d.size_bytes))).boxed();
+    let res = storage.delete_chunks(storage_settings, to_delete).await?;
+    summary.chunks_deleted = res.deleted_objects;
+    summary.bytes_deleted += res.deleted_bytes;
+
+    let to_delete =
+        stream::iter(plan.attributes.iter().map(|d| (d.id.clone(), d.size_bytes)))
+            .boxed();
+    let res = storage.delete_attributes(storage_settings, to_delete).await?;
+    summary.attributes_deleted = res.deleted_objects;
+    summary.bytes_deleted += res.deleted_bytes;
+
+    summary.tag_delete_markers_deleted =
+        purge_deleted_tags(storage, storage_settings, &plan.tag_delete_markers).await?;
+
+    Ok(summary)
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -453,16 +1171,22 @@ pub async fn expire_ref(
         }
     }
 
+    // positions in the ancestry, used to count the snapshots skipped by the edit
+    let (mut editable_index, mut root_index) = (0u64, 0u64);
+    let mut index = 0;
     while let Some(parent) = ancestry.try_next().await? {
         if parent.flushed_at >= older_than {
             tracing::debug!(snap = %parent.id, flushed_at = %parent.flushed_at, "Processing non expired snapshot");
             // we are navigating non-expired snaps, last will be kept in editable_snap
             editable_snap = parent.id;
+            editable_index = index;
         } else {
             tracing::debug!(snap = %parent.id, flushed_at = %parent.flushed_at, "Processing expired snapshot");
             released.insert(parent.id.clone());
             root = parent.id;
+            root_index = index;
         }
+        index += 1;
     }
 
     // we counted the root as released, but it's not
@@ -491,8 +1215,8 @@ pub async fn expire_ref(
 
     tracing::info!(root = %root.id(), editable_snap=%editable_snap.id(), "Expiration needed for this ref");
 
-    // TODO: add properties to the snapshot that tell us it was history edited
-    let new_snapshot = Arc::new(root.adopt(&editable_snap)?);
+    let skipped = root_index.saturating_sub(editable_index + 1);
+    let new_snapshot = Arc::new(root.adopt(&editable_snap, skipped, Utc::now())?);
     asset_manager.write_snapshot(new_snapshot).await?;
     tracing::info!("Snapshot overwritten");
 
@@ -516,6 +1240,25 @@ pub struct ExpireResult {
     pub deleted_refs: HashSet<Ref>,
 }
 
+/// Whether [`expire`] deletes `reference` once it's expired
+///
+/// The default branch, and branches for which `keep_branch` returns true, are never deleted.
+fn deletes_expired_ref(
+    reference: &Ref,
+    keep_branch: &(dyn Fn(&str) -> bool + Sync),
+    expired_branches: ExpiredRefAction,
+    expired_tags: ExpiredRefAction,
+) -> bool {
+    match reference {
+        Ref::Tag(_) => expired_tags == ExpiredRefAction::Delete,
+        Ref::Branch(name) => {
+            expired_branches == ExpiredRefAction::Delete
+                && name != Ref::DEFAULT_BRANCH
+                && !keep_branch(name)
+        }
+    }
+}
+
 /// Expire all snapshots older than a threshold.
 ///
 /// This processes snapshots found by navigating all references in
@@ -533,6 +1276,10 @@ pub struct ExpireResult {
 /// available for garbage collection, they could still be pointed by
 /// ether refs.
 ///
+/// Branch policies are not checked here, use
+/// [`Repository::expire_snapshots`](crate::Repository::expire_snapshots) to keep the
+/// branches they protect.
+///
 /// See: https://github.com/earth-mover/icechunk/blob/main/design-docs/007-basic-expiration.md
 #[instrument(skip(asset_manager, storage))]
 pub async fn expire(
@@ -542,6 +1289,30 @@ pub async fn expire(
     older_than: DateTime<Utc>,
     expired_branches: ExpiredRefAction,
     expired_tags: ExpiredRefAction,
+) -> GCResult<ExpireResult> {
+    expire_keeping_branches(
+        storage,
+        storage_settings,
+        asset_manager,
+        older_than,
+        expired_branches,
+        expired_tags,
+        &|_| false,
+    )
+    .await
+}
+
+/// Like [`expire`], but expired branches for which `keep_branch` returns true are not
+/// deleted
+#[instrument(skip(asset_manager, storage, keep_branch))]
+pub(crate) async fn expire_keeping_branches(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    older_than: DateTime<Utc>,
+    expired_branches: ExpiredRefAction,
+    expired_tags: ExpiredRefAction,
+    keep_branch: &(dyn Fn(&str) -> bool + Sync),
 ) -> GCResult<ExpireResult> {
     let all_refs = stream::iter(list_refs(storage, storage_settings).await?);
     let asset_manager = Arc::clone(&asset_manager.clone());
@@ -569,29 +1340,24 @@ pub async fn expire(
                 }
                 ExpireRefResult::NothingToDo { ref_is_expired } => ref_is_expired,
             };
-            if ref_is_expired {
+            if ref_is_expired
+                && deletes_expired_ref(&r, keep_branch, expired_branches, expired_tags)
+            {
                 match &r {
                     Ref::Tag(name) => {
-                        if expired_tags == ExpiredRefAction::Delete {
-                            tracing::info!(name, "Deleting expired tag");
-                            delete_tag(storage, storage_settings, name.as_str())
-                                .await
-                                .map_err(GCError::Ref)?;
-                            result.deleted_refs.insert(r);
-                        }
+                        tracing::info!(name, "Deleting expired tag");
+                        delete_tag(storage, storage_settings, name.as_str())
+                            .await
+                            .map_err(GCError::Ref)?;
                     }
                     Ref::Branch(name) => {
-                        if expired_branches == ExpiredRefAction::Delete
-                            && name != Ref::DEFAULT_BRANCH
-                        {
-                            tracing::info!(name, "Deleting expired branch");
-                            delete_branch(storage, storage_settings, name.as_str())
-                                .await
-                                .map_err(GCError::Ref)?;
-                            result.deleted_refs.insert(r);
-                        }
+                        tracing::info!(name, "Deleting expired branch");
+                        delete_branch(storage, storage_settings, name.as_str(), None)
+                            .await
+                            .map_err(GCError::Ref)?;
                     }
                 }
+                result.deleted_refs.insert(r);
             }
             Ok(result)
         })
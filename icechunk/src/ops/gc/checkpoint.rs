@@ -0,0 +1,575 @@
+//! Reachability checkpoint, it lets garbage collection skip reading the snapshots and
+//! manifests it already read in previous runs.
+//!
+//! Manifests never change, so the chunks they point to can be reused forever. Snapshots are
+//! rewritten when expiration edits their parent, so they are reused only if storage reports
+//! them as not modified since the run that wrote the checkpoint started.
+//!
+//! The checkpoint is an index, with the reachable snapshots, and one object per scanned
+//! manifest with the ids of its chunks. Chunk ids are read one manifest at a time, they
+//! never have to fit in memory together.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{StreamExt as _, TryStreamExt as _, stream, stream::FuturesUnordered};
+use serde::{Deserialize, Serialize};
+use tokio::pin;
+use tracing::instrument;
+
+use crate::{
+    Storage,
+    asset_manager::AssetManager,
+    format::{ChunkId, ManifestId, SnapshotId, manifest::ChunkPayload},
+    ops::all_roots,
+    refs::prefixed::PrefixedObjects,
+    repository::RepositoryError,
+    storage::{self, GC_PREFIX, WriteRefResult},
+};
+
+use super::{
+    GCConfig, GCResult, RetainedObjects, keep_saved_session_chunks,
+    saved_session_manifests, saved_sessions,
+};
+
+const GC_CHECKPOINT_KEY: &str = "checkpoint.msgpack";
+const GC_MANIFESTS_DIR_PREFIX: &str = "manifests/";
+const GC_MANIFEST_CHUNKS_KEY_NAME: &str = "chunks.msgpack";
+
+fn gc_objects<'a>(
+    storage: &'a (dyn Storage + Send + Sync),
+    storage_settings: &'a storage::Settings,
+) -> PrefixedObjects<'a> {
+    PrefixedObjects::new(storage, storage_settings, GC_PREFIX)
+}
+
+/// Store the garbage collection checkpoint, replacing the previous one
+#[instrument(skip(storage, storage_settings, bytes))]
+pub(super) async fn write_gc_checkpoint(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    bytes: Bytes,
+) -> GCResult<()> {
+    // concurrent collections, last write wins, any of the checkpoints is valid
+    gc_objects(storage, storage_settings).overwrite(GC_CHECKPOINT_KEY, bytes).await?;
+    Ok(())
+}
+
+#[instrument(skip(storage, storage_settings))]
+pub(super) async fn fetch_gc_checkpoint(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> GCResult<Option<Bytes>> {
+    Ok(gc_objects(storage, storage_settings).get(GC_CHECKPOINT_KEY).await?)
+}
+
+fn gc_manifest_chunks_key(manifest_id: &ManifestId) -> String {
+    format!("{}{}/{}", GC_MANIFESTS_DIR_PREFIX, manifest_id, GC_MANIFEST_CHUNKS_KEY_NAME)
+}
+
+/// Store the chunks of a manifest, as recorded by the garbage collection checkpoint
+///
+/// Manifests never change, if the object already exists it's left untouched.
+#[instrument(skip(storage, storage_settings, bytes))]
+pub(super) async fn write_gc_manifest_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    manifest_id: &ManifestId,
+    bytes: Bytes,
+) -> GCResult<()> {
+    let key = gc_manifest_chunks_key(manifest_id);
+    match gc_objects(storage, storage_settings).create(key.as_str(), bytes).await? {
+        // a concurrent collection wrote the same content
+        WriteRefResult::Written | WriteRefResult::WontOverwrite => Ok(()),
+    }
+}
+
+#[instrument(skip(storage, storage_settings))]
+pub(super) async fn fetch_gc_manifest_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    manifest_id: &ManifestId,
+) -> GCResult<Option<Bytes>> {
+    let key = gc_manifest_chunks_key(manifest_id);
+    Ok(gc_objects(storage, storage_settings).get(key.as_str()).await?)
+}
+
+/// Manifests with chunks stored by [`write_gc_manifest_chunks`]
+#[instrument(skip(storage, storage_settings))]
+pub(super) async fn list_gc_manifest_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> GCResult<Vec<ManifestId>> {
+    let names =
+        gc_objects(storage, storage_settings).names(GC_MANIFESTS_DIR_PREFIX).await?;
+    Ok(names
+        .iter()
+        .filter_map(|name| match ManifestId::try_from(name.as_str()) {
+            Ok(id) => Some(id),
+            Err(_) => {
+                tracing::warn!(name, "Ignoring unknown object in GC checkpoint");
+                None
+            }
+        })
+        .collect())
+}
+
+#[instrument(skip(storage, storage_settings, manifest_ids))]
+pub(super) async fn delete_gc_manifest_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    manifest_ids: Vec<ManifestId>,
+) -> GCResult<u64> {
+    let keys = manifest_ids.iter().map(gc_manifest_chunks_key).collect();
+    Ok(gc_objects(storage, storage_settings).delete(keys).await?)
+}
+
+/// Snapshots modified this long before the checkpoint was started are read again, to
+/// tolerate clock differences between this process and the object store
+fn clock_skew() -> TimeDelta {
+    TimeDelta::minutes(10)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CheckpointedSnapshot {
+    parent_id: Option<SnapshotId>,
+    manifests: Vec<(ManifestId, u64)>,
+}
+
+/// What a garbage collection found reachable
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Checkpoint {
+    /// When the run that computed the checkpoint started
+    started_at: DateTime<Utc>,
+    snapshots: HashMap<SnapshotId, CheckpointedSnapshot>,
+    /// Only the manifests scanned for chunks, their chunk ids are stored separately
+    manifests: HashSet<ManifestId>,
+}
+
+impl Checkpoint {
+    /// The stored checkpoint, an unreadable one is ignored, and the whole repository is read
+    pub(super) async fn fetch(
+        storage: &(dyn Storage + Send + Sync),
+        storage_settings: &storage::Settings,
+    ) -> GCResult<Self> {
+        let Some(bytes) = fetch_gc_checkpoint(storage, storage_settings).await? else {
+            return Ok(Self::default());
+        };
+        match rmp_serde::from_slice(&bytes) {
+            Ok(checkpoint) => Ok(checkpoint),
+            Err(err) => {
+                tracing::warn!(%err, "Ignoring unreadable GC checkpoint");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Store the checkpoint, and delete the chunk ids of manifests it doesn't use anymore
+    pub(super) async fn write(
+        &self,
+        storage: &(dyn Storage + Send + Sync),
+        storage_settings: &storage::Settings,
+    ) -> GCResult<()> {
+        let bytes = rmp_serde::to_vec(self).map_err(RepositoryError::from)?;
+        write_gc_checkpoint(storage, storage_settings, Bytes::from(bytes)).await?;
+        // a concurrent collection can lose objects its index points to, they are read
+        // from the manifest again
+        let stale = list_gc_manifest_chunks(storage, storage_settings)
+            .await?
+            .into_iter()
+            .filter(|manifest_id| !self.manifests.contains(manifest_id))
+            .collect();
+        delete_gc_manifest_chunks(storage, storage_settings, stale).await?;
+        Ok(())
+    }
+}
+
+/// Ids of the native chunks in a manifest, from the checkpoint if `stored`, and also
+/// recorded in it if `record`
+///
+/// Returns if the ids are stored in the checkpoint after the call.
+async fn manifest_chunks(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: &AssetManager,
+    manifest_id: &ManifestId,
+    size: u64,
+    stored: bool,
+    record: bool,
+) -> GCResult<(Vec<ChunkId>, bool)> {
+    if stored {
+        match fetch_gc_manifest_chunks(storage, storage_settings, manifest_id).await? {
+            Some(bytes) => match rmp_serde::from_slice(&bytes) {
+                Ok(chunks) => return Ok((chunks, true)),
+                Err(err) => {
+                    tracing::warn!(%err, %manifest_id, "Ignoring unreadable GC checkpoint")
+                }
+            },
+            None => tracing::debug!(%manifest_id, "Manifest missing from GC checkpoint"),
+        }
+    }
+
+    let manifest = asset_manager.fetch_manifest(manifest_id, size).await?;
+    let mut chunks = Vec::new();
+    for payload in manifest.chunk_payloads() {
+        // a missing id would get a live chunk deleted, errors are not skipped
+        if let ChunkPayload::Ref(chunk_ref) = payload? {
+            chunks.push(chunk_ref.id);
+        }
+    }
+    if record {
+        let bytes = rmp_serde::to_vec(&chunks).map_err(RepositoryError::from)?;
+        write_gc_manifest_chunks(storage, storage_settings, manifest_id, bytes.into())
+            .await?;
+    }
+    Ok((chunks, record))
+}
+
+/// Like [`super::retained_objects`], but only reading what `previous` doesn't know
+///
+/// Returns the checkpoint for the next run too, started at `started_at`. The chunk ids of
+/// the manifests read are stored right away only if `record`, the index is written by
+/// [`Checkpoint::write`].
+pub(super) async fn retained_objects(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    config: &GCConfig,
+    mut previous: Checkpoint,
+    started_at: DateTime<Utc>,
+    record: bool,
+) -> GCResult<(RetainedObjects, Checkpoint)> {
+    let unchanged_before = previous.started_at - clock_skew();
+    let mut unchanged = HashSet::new();
+    {
+        let listed = storage.list_snapshots(storage_settings).await?;
+        pin!(listed);
+        while let Some(info) = listed.try_next().await? {
+            if info.created_at < unchanged_before {
+                unchanged.insert(info.id);
+            }
+        }
+    }
+
+    let saved_sessions = saved_sessions(storage, storage_settings).await?;
+    let mut roots = config.extra_roots.clone();
+    roots.extend(saved_sessions.iter().map(|saved| saved.snapshot_id.clone()));
+    let mut pending: Vec<SnapshotId> =
+        all_roots(storage, storage_settings, &roots).await?.try_collect().await?;
+
+    let mut next = Checkpoint { started_at, ..Default::default() };
+    let mut seen = HashSet::new();
+    let mut fetching = FuturesUnordered::new();
+    let mut snapshots_read = 0;
+    loop {
+        // reused snapshots are resolved here, the rest are fetched concurrently
+        while fetching.len() < config.scan.concurrency()
+            && let Some(snap_id) = pending.pop()
+        {
+            if !seen.insert(snap_id.clone()) {
+                continue;
+            }
+            let reused = unchanged
+                .contains(&snap_id)
+                .then(|| previous.snapshots.remove(&snap_id))
+                .flatten();
+            match reused {
+                Some(snapshot) => {
+                    pending.extend(snapshot.parent_id.iter().cloned());
+                    next.snapshots.insert(snap_id, snapshot);
+                }
+                None => {
+                    snapshots_read += 1;
+                    let asset_manager = Arc::clone(&asset_manager);
+                    fetching.push(async move {
+                        let snap = asset_manager.fetch_snapshot(&snap_id).await?;
+                        let snapshot = CheckpointedSnapshot {
+                            parent_id: snap.parent_id(),
+                            manifests: snap
+                                .manifest_files()
+                                .map(|mf| (mf.id, mf.size_bytes))
+                                .collect(),
+                        };
+                        GCResult::Ok((snap_id, snapshot))
+                    });
+                }
+            }
+        }
+        match fetching.try_next().await? {
+            Some((snap_id, snapshot)) => {
+                pending.extend(snapshot.parent_id.iter().cloned());
+                next.snapshots.insert(snap_id, snapshot);
+            }
+            None => break,
+        }
+    }
+    tracing::info!(
+        snapshots = next.snapshots.len(),
+        read = snapshots_read,
+        "Reachable snapshots found"
+    );
+
+    let mut keep_manifests: HashMap<ManifestId, u64> = next
+        .snapshots
+        .values()
+        .flat_map(|snapshot| snapshot.manifests.iter().cloned())
+        .collect();
+    keep_manifests.extend(saved_session_manifests(&saved_sessions));
+    // stored manifests go to the next checkpoint, even if chunks are not collected
+    next.manifests = keep_manifests
+        .keys()
+        .filter(|manifest_id| previous.manifests.contains(*manifest_id))
+        .cloned()
+        .collect();
+
+    let mut keep_chunks = config.scan.chunk_sorter();
+    if config.deletes_chunks() {
+        tracing::info!(
+            manifests = keep_manifests.len(),
+            stored = next.manifests.len(),
+            "Scanning manifests"
+        );
+        let mut newly_stored = Vec::new();
+        {
+            let manifests = stream::iter(keep_manifests.iter())
+                .map(|(manifest_id, size)| {
+                    let stored = next.manifests.contains(manifest_id);
+                    let asset_manager = Arc::clone(&asset_manager);
+                    async move {
+                        let (chunks, stored) = manifest_chunks(
+                            storage,
+                            storage_settings,
+                            &asset_manager,
+                            manifest_id,
+                            *size,
+                            stored,
+                            record,
+                        )
+                        .await?;
+                        GCResult::Ok((manifest_id, chunks, stored))
+                    }
+                })
+                .buffer_unordered(config.scan.concurrency());
+            pin!(manifests);
+            while let Some((manifest_id, chunks, stored)) = manifests.try_next().await? {
+                for chunk_id in chunks {
+                    keep_chunks.push(chunk_id)?;
+                }
+                if stored {
+                    newly_stored.push(manifest_id.clone());
+                }
+            }
+        }
+        next.manifests.extend(newly_stored);
+        keep_saved_session_chunks(&saved_sessions, &mut keep_chunks)?;
+        tracing::debug!(runs = keep_chunks.spilled_runs(), "Chunks to keep collected");
+    }
+
+    let retained = RetainedObjects {
+        snapshots: next.snapshots.keys().cloned().collect(),
+        manifests: keep_manifests.into_keys().collect(),
+        chunks: keep_chunks,
+    };
+    Ok((retained, next))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        Repository, RepositoryConfig,
+        format::{ChunkIndices, Path, snapshot::ArrayShape},
+        new_in_memory_storage,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint_reuse() -> Result<(), Box<dyn std::error::Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let storage_settings = storage.default_settings();
+        let repo = Repository::create(
+            Some(RepositoryConfig {
+                inline_chunk_threshold_bytes: Some(0),
+                ..Default::default()
+            }),
+            Arc::clone(&storage),
+            HashMap::new(),
+        )
+        .await?;
+        let path: Path = "/array".try_into().unwrap();
+        let mut session = repo.writable_session("main").await?;
+        session.add_group(Path::root(), Bytes::new()).await?;
+        session
+            .add_array(
+                path.clone(),
+                ArrayShape::new(vec![(5, 1)]).unwrap(),
+                None,
+                Bytes::new(),
+            )
+            .await?;
+        for idx in 0..5 {
+            let payload = session.get_chunk_writer()(Bytes::from_static(b"a")).await?;
+            session
+                .set_chunk_ref(path.clone(), ChunkIndices(vec![idx]), Some(payload))
+                .await?;
+        }
+        session.commit("first", None).await?;
+        let mut session = repo.writable_session("main").await?;
+        let payload = session.get_chunk_writer()(Bytes::from_static(b"b")).await?;
+        session.set_chunk_ref(path.clone(), ChunkIndices(vec![0]), Some(payload)).await?;
+        let tip = session.commit("second", None).await?;
+
+        let now = Utc::now();
+        let config = GCConfig::clean_all(now, now, None).with_checkpoint(true);
+        let retained = |previous: Checkpoint, record: bool| {
+            retained_objects(
+                storage.as_ref(),
+                &storage_settings,
+                Arc::clone(repo.asset_manager()),
+                &config,
+                previous,
+                Utc::now(),
+                record,
+            )
+        };
+        let chunk_count = |retained: RetainedObjects| {
+            retained.chunks.into_sorted_iter().unwrap().count()
+        };
+        let stored_manifests =
+            || list_gc_manifest_chunks(storage.as_ref(), &storage_settings);
+
+        // without recording, nothing is stored
+        let (objects, checkpoint) = retained(Checkpoint::default(), false).await?;
+        assert_eq!(chunk_count(objects), 6);
+        assert!(checkpoint.manifests.is_empty());
+        assert!(stored_manifests().await?.is_empty());
+
+        let (objects, mut checkpoint) = retained(Checkpoint::default(), true).await?;
+        assert_eq!(objects.snapshots.len(), 3);
+        assert_eq!(objects.manifests.len(), 2);
+        assert_eq!(chunk_count(objects), 6);
+        assert_eq!(checkpoint.snapshots.len(), 3);
+        assert_eq!(checkpoint.manifests.len(), 2);
+        assert_eq!(stored_manifests().await?.len(), 2);
+
+        checkpoint.write(storage.as_ref(), &storage_settings).await?;
+        assert_eq!(
+            Checkpoint::fetch(storage.as_ref(), &storage_settings).await?,
+            checkpoint
+        );
+
+        // a checkpoint that claims less than the truth, to see what is read again
+        for snapshot in checkpoint.snapshots.values_mut() {
+            snapshot.parent_id = None;
+        }
+        let manifest_ids: Vec<_> = checkpoint.manifests.iter().cloned().collect();
+        delete_gc_manifest_chunks(
+            storage.as_ref(),
+            &storage_settings,
+            manifest_ids.clone(),
+        )
+        .await?;
+        // the chunks of the tip manifest are lost, the other manifest is recorded as empty
+        let lost =
+            repo.asset_manager().fetch_snapshot(&tip).await?.manifest_files().next();
+        let lost = lost.unwrap().id;
+        for manifest_id in manifest_ids.into_iter().filter(|id| id != &lost) {
+            let empty = rmp_serde::to_vec(&Vec::<ChunkId>::new())?;
+            write_gc_manifest_chunks(
+                storage.as_ref(),
+                &storage_settings,
+                &manifest_id,
+                empty.into(),
+            )
+            .await?;
+        }
+
+        // no snapshot was modified after this checkpoint, only the tip is read, and the
+        // manifest missing from the checkpoint is read again
+        checkpoint.started_at = Utc::now() + TimeDelta::hours(1);
+        let (objects, _) = retained(checkpoint.clone(), true).await?;
+        assert_eq!(objects.snapshots.len(), 1);
+        assert!(objects.manifests.contains(&lost));
+        assert_eq!(chunk_count(objects), 5);
+
+        // all snapshots could have been modified, they are read, but manifests are not
+        checkpoint.started_at = Utc::now() - TimeDelta::hours(1);
+        let (objects, next) = retained(checkpoint, true).await?;
+        assert_eq!(objects.snapshots.len(), 3);
+        assert_eq!(chunk_count(objects), 5);
+        assert_eq!(next.snapshots.len(), 3);
+        assert_eq!(next.manifests.len(), 2);
+
+        // stale chunk ids are deleted with the checkpoint that stops using them
+        Checkpoint::default().write(storage.as_ref(), &storage_settings).await?;
+        assert!(stored_manifests().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc_stores_checkpoint() -> Result<(), Box<dyn std::error::Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let storage_settings = storage.default_settings();
+        let repo = Repository::create(
+            Some(RepositoryConfig {
+                inline_chunk_threshold_bytes: Some(0),
+                ..Default::default()
+            }),
+            Arc::clone(&storage),
+            HashMap::new(),
+        )
+        .await?;
+        let path: Path = "/array".try_into().unwrap();
+        let mut session = repo.writable_session("main").await?;
+        session
+            .add_array(
+                path.clone(),
+                ArrayShape::new(vec![(5, 1)]).unwrap(),
+                None,
+                Bytes::new(),
+            )
+            .await?;
+        let payload = session.get_chunk_writer()(Bytes::from_static(b"a")).await?;
+        session.set_chunk_ref(path, ChunkIndices(vec![0]), Some(payload)).await?;
+        session.commit("first", None).await?;
+
+        let gc = |checkpoint: bool| {
+            let now = Utc::now();
+            let config = GCConfig::clean_all(now, now, None).with_checkpoint(checkpoint);
+            let asset_manager = Arc::clone(repo.asset_manager());
+            let storage_settings = &storage_settings;
+            let storage = Arc::clone(&storage);
+            async move {
+                super::super::garbage_collect(
+                    storage.as_ref(),
+                    storage_settings,
+                    asset_manager,
+                    &config,
+                )
+                .await
+            }
+        };
+
+        gc(false).await?;
+        assert!(
+            fetch_gc_checkpoint(storage.as_ref(), &storage_settings).await?.is_none()
+        );
+
+        gc(true).await?;
+        assert!(
+            fetch_gc_checkpoint(storage.as_ref(), &storage_settings).await?.is_some()
+        );
+        assert_eq!(
+            list_gc_manifest_chunks(storage.as_ref(), &storage_settings).await?.len(),
+            1
+        );
+        Ok(())
+    }
+}
@@ -12,9 +12,17 @@ use crate::{
     storage,
 };
 
+pub mod archive;
+mod external_sort;
 pub mod gc;
+pub mod retention;
+mod scan;
 pub mod stats;
 
+pub use archive::{ArchiveConfig, archive_chunks};
+pub use retention::apply_retention;
+pub use scan::ScanConfig;
+
 pub async fn all_roots<'a>(
     storage: &'a (dyn Storage + Send + Sync),
     storage_settings: &'a storage::Settings,
@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Datelike as _, Days, Months, Utc};
+use futures::TryStreamExt as _;
+use tracing::instrument;
+
+use crate::{
+    Storage,
+    asset_manager::AssetManager,
+    config::{PeriodicRetention, RepositoryConfig, RetentionPeriod, RetentionPolicy},
+    format::{SnapshotId, snapshot::SnapshotInfo},
+    ops::gc::{ExpireResult, GCError, GCResult},
+    refs::list_refs,
+    storage,
+};
+
+impl RetentionPeriod {
+    /// Identifies the period `time` belongs to
+    fn bucket(&self, time: &DateTime<Utc>) -> (i32, u32) {
+        match self {
+            RetentionPeriod::Day => (time.year(), time.ordinal()),
+            RetentionPeriod::Week => {
+                let week = time.iso_week();
+                (week.year(), week.week())
+            }
+            RetentionPeriod::Month => (time.year(), time.month()),
+            RetentionPeriod::Year => (time.year(), 0),
+        }
+    }
+
+    /// The start of the time window covering the last `periods` periods before `now`
+    fn window_start(&self, now: DateTime<Utc>, periods: u32) -> Option<DateTime<Utc>> {
+        match self {
+            RetentionPeriod::Day => now.checked_sub_days(Days::new(periods.into())),
+            RetentionPeriod::Week => {
+                now.checked_sub_days(Days::new(7 * u64::from(periods)))
+            }
+            RetentionPeriod::Month => now.checked_sub_months(Months::new(periods)),
+            RetentionPeriod::Year => {
+                now.checked_sub_months(Months::new(periods.saturating_mul(12)))
+            }
+        }
+    }
+}
+
+impl PeriodicRetention {
+    fn applies_to(&self, now: DateTime<Utc>, time: &DateTime<Utc>) -> bool {
+        match self.periods {
+            None => true,
+            Some(periods) => {
+                self.every.window_start(now, periods).is_none_or(|start| time >= &start)
+            }
+        }
+    }
+}
+
+/// Snapshots of `ancestry`, sorted newest first, that `policy` keeps
+fn retained_in_ancestry<'a>(
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+    ancestry: &'a [SnapshotInfo],
+) -> HashSet<&'a SnapshotId> {
+    let mut keep = HashSet::new();
+    // the tip and the root of the repository are never expired
+    keep.extend(ancestry.first().map(|info| &info.id));
+    keep.extend(ancestry.last().map(|info| &info.id));
+
+    keep.extend(ancestry.iter().take(policy.keep_last() as usize).map(|info| &info.id));
+
+    if let Some(start) = now.checked_sub_days(Days::new(policy.keep_all_days().into())) {
+        keep.extend(
+            ancestry.iter().filter(|info| info.flushed_at >= start).map(|info| &info.id),
+        );
+    }
+
+    let keys = policy.keep_with_metadata_keys();
+    keep.extend(
+        ancestry
+            .iter()
+            .filter(|info| keys.iter().any(|key| info.metadata.contains_key(key)))
+            .map(|info| &info.id),
+    );
+
+    for rule in policy.keep_periodic() {
+        let mut seen = HashSet::new();
+        // ancestry goes from newest to oldest, so the first snapshot seen in each period is
+        // the newest one
+        for info in ancestry.iter().filter(|info| rule.applies_to(now, &info.flushed_at))
+        {
+            if seen.insert(rule.every.bucket(&info.flushed_at)) {
+                keep.insert(&info.id);
+            }
+        }
+    }
+    keep
+}
+
+/// Expire the snapshots that the retention policy of `repo_config` doesn't retain, in the
+/// history of every ref
+///
+/// Fails with [`GCError::NoRetentionPolicy`] if the config doesn't have a policy.
+///
+/// The policy is evaluated for the ancestry of each ref, and a snapshot is kept if it's
+/// retained in any of them. Then, every kept snapshot is edited in place to have the next
+/// older kept snapshot as parent, skipping the expired ones. Since kept snapshots are the
+/// same for every ref, snapshots shared by several refs get the same parent from all of them.
+///
+/// As with [`expire`](crate::ops::gc::expire), released snapshots are not deleted, that's the
+/// job of [`garbage_collect`](crate::ops::gc::garbage_collect), and caches of the edited
+/// snapshots in other [`AssetManager`] instances must be invalidated.
+#[instrument(skip(asset_manager, storage, repo_config))]
+pub async fn apply_retention(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    repo_config: &RepositoryConfig,
+    now: DateTime<Utc>,
+) -> GCResult<ExpireResult> {
+    let policy = repo_config.retention().ok_or(GCError::NoRetentionPolicy)?;
+    let mut ancestries = Vec::new();
+    for reference in list_refs(storage, storage_settings).await? {
+        let tip = reference.fetch(storage, storage_settings).await?.snapshot;
+        let ancestry: Vec<SnapshotInfo> = Arc::clone(&asset_manager)
+            .snapshot_ancestry(&tip)
+            .await?
+            .try_collect()
+            .await?;
+        ancestries.push(ancestry);
+    }
+
+    let kept: HashSet<&SnapshotId> = ancestries
+        .iter()
+        .flat_map(|ancestry| retained_in_ancestry(policy, now, ancestry))
+        .collect();
+
+    let mut result = ExpireResult::default();
+    // new parent for every snapshot that needs it, and the number of snapshots skipped
+    let mut new_parents: HashMap<&SnapshotId, (&SnapshotId, u64)> = HashMap::new();
+    for ancestry in ancestries.iter() {
+        result.released_snapshots.extend(
+            ancestry.iter().filter(|info| !kept.contains(&info.id)).map(|i| i.id.clone()),
+        );
+        let kept_chain: Vec<(usize, &SnapshotInfo)> = ancestry
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| kept.contains(&info.id))
+            .collect();
+        for pair in kept_chain.windows(2) {
+            let ((child_index, child), (parent_index, parent)) = (pair[0], pair[1]);
+            if child.parent_id.as_ref() != Some(&parent.id) {
+                let skipped = (parent_index - child_index - 1) as u64;
+                new_parents.insert(&child.id, (&parent.id, skipped));
+            }
+        }
+    }
+
+    let edited_at = Utc::now();
+    for (child, (parent, skipped)) in new_parents {
+        tracing::debug!(%child, %parent, skipped, "Editing snapshot parent");
+        let child = asset_manager.fetch_snapshot(child).await?;
+        let parent = asset_manager.fetch_snapshot(parent).await?;
+        let new_snapshot = parent.adopt(&child, skipped, edited_at)?;
+        asset_manager.write_snapshot(Arc::new(new_snapshot)).await?;
+        result.edited_snapshots.insert(child.id().clone());
+    }
+
+    tracing::info!(
+        released = result.released_snapshots.len(),
+        edited = result.edited_snapshots.len(),
+        "Retention policy applied"
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use crate::format::snapshot::SnapshotProperties;
+
+    use super::*;
+
+    #[test]
+    fn test_retained_in_ancestry() {
+        let now = Utc.with_ymd_and_hms(2025, 6, 30, 12, 0, 0).unwrap();
+        // one snapshot every 12 hours, for a year, newest first
+        let ancestry: Vec<_> = (0..730)
+            .map(|i| SnapshotInfo {
+                id: SnapshotId::random(),
+                parent_id: None,
+                flushed_at: now - chrono::Duration::hours(12 * i),
+                message: i.to_string(),
+                metadata: if i == 500 {
+                    SnapshotProperties::from_iter([("release".to_string(), true.into())])
+                } else {
+                    Default::default()
+                },
+            })
+            .collect();
+        let kept = |policy: &RetentionPolicy| {
+            let kept = retained_in_ancestry(policy, now, &ancestry);
+            ancestry
+                .iter()
+                .enumerate()
+                .filter(|(_, info)| kept.contains(&info.id))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+
+        // tip and root are always kept
+        assert_eq!(kept(&RetentionPolicy::default()), vec![0, 729]);
+        assert_eq!(
+            kept(&RetentionPolicy { keep_last: Some(3), ..Default::default() }),
+            vec![0, 1, 2, 729]
+        );
+        assert_eq!(
+            kept(&RetentionPolicy { keep_all_days: Some(2), ..Default::default() }),
+            vec![0, 1, 2, 3, 4, 729]
+        );
+        assert_eq!(
+            kept(&RetentionPolicy {
+                keep_with_metadata_keys: Some(vec!["release".to_string()]),
+                ..Default::default()
+            }),
+            vec![0, 500, 729]
+        );
+
+        // the newest snapshot of each of the last 3 days, and of every month
+        let policy = RetentionPolicy {
+            keep_periodic: Some(vec![
+                PeriodicRetention { every: RetentionPeriod::Day, periods: Some(3) },
+                PeriodicRetention { every: RetentionPeriod::Month, periods: None },
+            ]),
+            ..Default::default()
+        };
+        let kept = kept(&policy);
+        assert_eq!(&kept[..5], &[0, 2, 4, 6, 60]);
+        // one per month from june back to july of the previous year, plus the root
+        assert_eq!(kept.len(), 4 + 11 + 1);
+        assert_eq!(kept.last(), Some(&729));
+        for i in &kept[4..kept.len() - 1] {
+            // the newest snapshot of its month
+            assert_ne!(
+                ancestry[*i].flushed_at.month(),
+                ancestry[*i - 1].flushed_at.month()
+            );
+        }
+    }
+}
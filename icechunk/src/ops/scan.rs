@@ -0,0 +1,59 @@
+//! Limits shared by the operations that read every snapshot and manifest in a repository
+
+use std::path::PathBuf;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::external_sort::ExternalSorter;
+
+/// Concurrency and memory limits for garbage collection, statistics and archival
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanConfig {
+    concurrency: usize,
+    max_in_memory_chunks: usize,
+    spill_directory: Option<PathBuf>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self { concurrency: 16, max_in_memory_chunks: 10_000_000, spill_directory: None }
+    }
+}
+
+impl ScanConfig {
+    /// Maximum number of snapshots, manifests or chunks fetched concurrently, 16 by default
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Maximum number of chunks kept in memory, 10 million by default
+    ///
+    /// Beyond that, chunks are sorted in temporary files.
+    pub fn with_max_in_memory_chunks(mut self, max: usize) -> Self {
+        self.max_in_memory_chunks = max;
+        self
+    }
+
+    /// Directory for temporary files, the OS temporary directory by default
+    pub fn with_spill_directory(mut self, directory: PathBuf) -> Self {
+        self.spill_directory = Some(directory);
+        self
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub fn max_in_memory_chunks(&self) -> usize {
+        self.max_in_memory_chunks
+    }
+
+    pub(crate) fn chunk_sorter<T>(&self) -> ExternalSorter<T>
+    where
+        T: Ord + Serialize + DeserializeOwned,
+    {
+        let dir = self.spill_directory.clone().unwrap_or_else(std::env::temp_dir);
+        ExternalSorter::new(&dir, self.max_in_memory_chunks)
+    }
+}
@@ -1,16 +1,28 @@
-use futures::TryStreamExt as _;
-use std::{collections::HashSet, sync::Arc};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt as _, TryStreamExt as _, stream};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 use tokio::pin;
 
 use crate::{
     Storage,
     asset_manager::AssetManager,
-    format::{IcechunkFormatError, IcechunkFormatErrorKind, manifest::ChunkPayload},
+    format::{
+        ChunkId, ChunkIndices, IcechunkFormatError, IcechunkFormatErrorKind, ManifestId,
+        NodeId, Path, SnapshotId,
+        manifest::ChunkPayload,
+        snapshot::{NodeData, SnapshotInfo},
+    },
+    refs::{Ref, list_refs},
     repository::RepositoryResult,
     storage,
 };
 
-use super::pointed_snapshots;
+use super::{ScanConfig, pointed_snapshots};
 
 /// Compute the total size in bytes of all committed repo chunks.
 /// It doesn't include inline or virtual chunks.
@@ -50,20 +62,10 @@ pub async fn repo_chunks_storage(
                     .fetch_manifest(&manifest_id, manifest_info.size_bytes)
                     .await?;
                 for payload in manifest.chunk_payloads() {
-                    match payload {
-                        Ok(ChunkPayload::Ref(chunk_ref)) => {
-                            if seen_chunks.insert(chunk_ref.id) {
-                                size += chunk_ref.length;
-                            }
-                        }
-                        Ok(_) => {}
-                        // TODO: don't skip errors
-                        Err(err) => {
-                            tracing::error!(
-                                error = %err,
-                                "Error in chunk payload iterator"
-                            );
-                        }
+                    if let ChunkPayload::Ref(chunk_ref) = payload?
+                        && seen_chunks.insert(chunk_ref.id)
+                    {
+                        size += chunk_ref.length;
                     }
                 }
 
@@ -73,3 +75,293 @@ pub async fn repo_chunks_storage(
     }
     Ok(size)
 }
+
+/// Number of objects and their total size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Chunks of one array, by where they are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ArrayStorageStats {
+    /// Chunks stored in the repository, as their own objects
+    pub native_chunks: Usage,
+    /// Chunks stored inside manifests
+    pub inline_chunks: Usage,
+    /// Chunks stored outside of the repository
+    pub virtual_chunks: Usage,
+}
+
+/// Storage used by the repository, computed by [`repo_storage_stats`]
+///
+/// Only objects reachable from a branch or tag are included, every distinct chunk is counted
+/// once, even if it's part of many snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StorageStats {
+    /// Chunks of each array, arrays that were moved are reported under their path in the
+    /// newest snapshot that has them
+    pub arrays: BTreeMap<Path, ArrayStorageStats>,
+    pub snapshots: Usage,
+    pub manifests: Usage,
+    /// Bytes of native chunks, manifests and snapshots that only the branch can reach
+    ///
+    /// Deleting the branch and collecting garbage frees at most this much. Garbage collection
+    /// also keeps the objects of saved sessions, session leases and extra roots, those are
+    /// not taken into account here.
+    pub branches: BTreeMap<String, u64>,
+    /// Bytes that only the tag can reach, like for `branches`
+    pub tags: BTreeMap<String, u64>,
+}
+
+impl StorageStats {
+    /// Total size of the chunks stored in the repository, as [`repo_chunks_storage`]
+    pub fn native_chunk_bytes(&self) -> u64 {
+        self.arrays.values().map(|array| array.native_chunks.bytes).sum()
+    }
+
+    /// Total size of snapshots and manifests
+    pub fn metadata_bytes(&self) -> u64 {
+        self.snapshots.bytes + self.manifests.bytes
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsConfig {
+    scan: ScanConfig,
+}
+
+impl StatsConfig {
+    /// Concurrency and memory limits of the computation
+    ///
+    /// Beyond the in memory limit, chunks are sorted in temporary files to find the
+    /// distinct ones.
+    pub fn with_scan(mut self, scan: ScanConfig) -> Self {
+        self.scan = scan;
+        self
+    }
+}
+
+/// The refs that can reach an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum Owner {
+    /// Only the ref with this index
+    One(usize),
+    Many,
+}
+
+impl Owner {
+    fn merge(self, other: Owner) -> Owner {
+        if self == other { self } else { Owner::Many }
+    }
+}
+
+fn own<K: std::hash::Hash + Eq>(owners: &mut HashMap<K, Owner>, key: K, owner: Owner) {
+    owners
+        .entry(key)
+        .and_modify(|current| *current = current.merge(owner))
+        .or_insert(owner);
+}
+
+/// Identifies an inline or virtual chunk without keeping its payload
+fn external_chunk_key(
+    node_id: &NodeId,
+    coords: &ChunkIndices,
+    payload: &ChunkPayload,
+) -> (u64, u64) {
+    let hash = |seed: u64| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        (node_id, coords, payload).hash(&mut hasher);
+        hasher.finish()
+    };
+    (hash(0), hash(1))
+}
+
+/// Compute detailed storage statistics for the repository
+///
+/// Every snapshot reachable from a branch or tag is read, together with all its manifests.
+/// Chunks are deduplicated with external sorting, so memory use is bounded by `config`.
+pub async fn repo_storage_stats(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    asset_manager: Arc<AssetManager>,
+    config: &StatsConfig,
+) -> RepositoryResult<StorageStats> {
+    let refs: Vec<Ref> =
+        list_refs(storage, storage_settings).await?.into_iter().collect();
+
+    let mut snapshot_owners: HashMap<SnapshotId, Owner> = HashMap::new();
+    for (index, reference) in refs.iter().enumerate() {
+        let tip = reference.fetch(storage, storage_settings).await?.snapshot;
+        let ancestry = Arc::clone(&asset_manager).snapshot_ancestry(&tip).await?;
+        pin!(ancestry);
+        while let Some(SnapshotInfo { id, .. }) = ancestry.try_next().await? {
+            own(&mut snapshot_owners, id, Owner::One(index));
+        }
+    }
+
+    let mut snapshot_sizes = HashMap::new();
+    let listed = storage.list_snapshots(storage_settings).await?;
+    pin!(listed);
+    while let Some(info) = listed.try_next().await? {
+        snapshot_sizes.insert(info.id, info.size_bytes);
+    }
+
+    // the path of each array in the newest snapshot that has it
+    let mut paths: HashMap<NodeId, (DateTime<Utc>, Path)> = HashMap::new();
+    // manifests with their size and the arrays they hold
+    let mut manifests: HashMap<ManifestId, u64> = HashMap::new();
+    let mut manifest_nodes: HashMap<ManifestId, HashSet<NodeId>> = HashMap::new();
+    let mut manifest_owners: HashMap<ManifestId, Owner> = HashMap::new();
+    let mut fetched = stream::iter(snapshot_owners.iter())
+        .map(|(snap_id, owner)| {
+            let asset_manager = Arc::clone(&asset_manager);
+            async move {
+                asset_manager.fetch_snapshot(snap_id).await.map(|snap| (snap, *owner))
+            }
+        })
+        .buffer_unordered(config.scan.concurrency());
+    while let Some((snap, owner)) = fetched.try_next().await? {
+        let flushed_at = snap.flushed_at()?;
+        for node in snap.iter() {
+            let node = node?;
+            if let NodeData::Array { manifests: refs, .. } = node.node_data {
+                for manifest_ref in refs {
+                    manifest_nodes
+                        .entry(manifest_ref.object_id)
+                        .or_default()
+                        .insert(node.id.clone());
+                }
+                let path =
+                    paths.entry(node.id).or_insert((flushed_at, node.path.clone()));
+                if path.0 < flushed_at {
+                    *path = (flushed_at, node.path);
+                }
+            }
+        }
+        for info in snap.manifest_files() {
+            manifests.insert(info.id.clone(), info.size_bytes);
+            own(&mut manifest_owners, info.id, owner);
+        }
+    }
+    drop(fetched);
+
+    let mut stats = StorageStats::default();
+    let mut array_paths: Vec<Path> = Vec::new();
+    let mut array_indexes: HashMap<Path, usize> = HashMap::new();
+    // native chunks, with the array that has them, the refs reaching them and their size
+    let mut native_chunks = config.scan.chunk_sorter::<(ChunkId, usize, Owner, u64)>();
+    // inline and virtual chunks by array, with their size, virtual ones are flagged
+    let mut external_chunks =
+        config.scan.chunk_sorter::<(usize, (u64, u64), bool, u64)>();
+    let mut fetched = stream::iter(manifests.iter())
+        .map(|(manifest_id, size)| {
+            let asset_manager = Arc::clone(&asset_manager);
+            async move {
+                asset_manager
+                    .fetch_manifest(manifest_id, *size)
+                    .await
+                    .map(|m| (manifest_id, m))
+            }
+        })
+        .buffer_unordered(config.scan.concurrency());
+    while let Some((manifest_id, manifest)) = fetched.try_next().await? {
+        let owner = manifest_owners[manifest_id];
+        for node_id in manifest_nodes.get(manifest_id).into_iter().flatten() {
+            let Some((_, path)) = paths.get(node_id) else { continue };
+            let array = *array_indexes.entry(path.clone()).or_insert_with(|| {
+                array_paths.push(path.clone());
+                array_paths.len() - 1
+            });
+            for chunk in Arc::clone(&manifest).iter(node_id.clone()) {
+                let (coords, payload) = chunk?;
+                match &payload {
+                    ChunkPayload::Ref(chunk_ref) => native_chunks.push((
+                        chunk_ref.id.clone(),
+                        array,
+                        owner,
+                        chunk_ref.length,
+                    ))?,
+                    ChunkPayload::Inline(bytes) => external_chunks.push((
+                        array,
+                        external_chunk_key(node_id, &coords, &payload),
+                        false,
+                        bytes.len() as u64,
+                    ))?,
+                    ChunkPayload::Virtual(virtual_ref) => external_chunks.push((
+                        array,
+                        external_chunk_key(node_id, &coords, &payload),
+                        true,
+                        virtual_ref.length,
+                    ))?,
+                }
+            }
+        }
+    }
+    drop(fetched);
+
+    let mut arrays = vec![ArrayStorageStats::default(); array_paths.len()];
+    let mut exclusive = vec![0u64; refs.len()];
+    let mut add_native = |(_, array, owner, length): (ChunkId, usize, Owner, u64)| {
+        arrays[array].native_chunks.add(length);
+        if let Owner::One(index) = owner {
+            exclusive[index] += length;
+        }
+    };
+    // the same chunk appears once for each array and owner, these come together
+    let mut current: Option<(ChunkId, usize, Owner, u64)> = None;
+    for item in native_chunks.into_sorted_iter()? {
+        let item = item?;
+        match current.as_mut() {
+            Some(chunk) if chunk.0 == item.0 => chunk.2 = chunk.2.merge(item.2),
+            _ => {
+                if let Some(chunk) = current.replace(item) {
+                    add_native(chunk);
+                }
+            }
+        }
+    }
+    if let Some(chunk) = current {
+        add_native(chunk);
+    }
+    for item in external_chunks.into_sorted_iter()? {
+        let (array, _, is_virtual, length) = item?;
+        if is_virtual {
+            arrays[array].virtual_chunks.add(length);
+        } else {
+            arrays[array].inline_chunks.add(length);
+        }
+    }
+    stats.arrays = array_paths.into_iter().zip(arrays).collect();
+
+    for (snap_id, owner) in snapshot_owners.iter() {
+        let size = snapshot_sizes.get(snap_id).copied().unwrap_or_default();
+        stats.snapshots.add(size);
+        if let Owner::One(index) = owner {
+            exclusive[*index] += size;
+        }
+    }
+    for (manifest_id, size) in manifests.iter() {
+        stats.manifests.add(*size);
+        if let Owner::One(index) = manifest_owners[manifest_id] {
+            exclusive[index] += size;
+        }
+    }
+    for (reference, bytes) in refs.into_iter().zip(exclusive) {
+        match reference {
+            Ref::Branch(name) => stats.branches.insert(name, bytes),
+            Ref::Tag(name) => stats.tags.insert(name, bytes),
+        };
+    }
+
+    Ok(stats)
+}
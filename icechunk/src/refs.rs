@@ -5,12 +5,15 @@ use std::{
 };
 
 use async_recursion::async_recursion;
+use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use futures::{
-    FutureExt, StreamExt,
+    FutureExt, StreamExt, TryStreamExt,
     stream::{FuturesOrdered, FuturesUnordered},
 };
 use itertools::Itertools;
+use rand::{Rng, rng};
 use serde::{Deserialize, Serialize};
 use serde_with::{TryFromInto, serde_as};
 use thiserror::Error;
@@ -18,11 +21,17 @@ use tracing::instrument;
 
 use crate::{
     Storage, StorageError,
+    config::BranchPolicy,
     error::ICError,
-    format::SnapshotId,
-    storage::{self, GetRefResult, StorageErrorKind, VersionInfo, WriteRefResult},
+    format::{SnapshotId, snapshot::SnapshotProperties},
+    storage::{
+        self, GetRefResult, REF_PREFIX, REFLOG_PREFIX, StorageErrorKind, VersionInfo,
+        WriteRefResult,
+    },
 };
 
+pub(crate) mod prefixed;
+
 #[derive(Debug, Error)]
 pub enum RefErrorKind {
     #[error(transparent)]
@@ -45,6 +54,26 @@ pub enum RefErrorKind {
 
     #[error("branch update conflict: `({expected_parent:?}) != ({actual_parent:?})`")]
     Conflict { expected_parent: Option<SnapshotId>, actual_parent: Option<SnapshotId> },
+
+    #[error("cannot write `{0}`, too many concurrent updates")]
+    TooManyConcurrentUpdates(String),
+
+    #[error("branch `{branch}` is protected: {violation}")]
+    BranchPolicyViolation { branch: String, violation: BranchPolicyViolation },
+
+    #[error("cannot check the policy of branch `{branch}`")]
+    BranchPolicyCheck { branch: String, source: Box<dyn std::error::Error + Send + Sync> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum BranchPolicyViolation {
+    #[error("it cannot be deleted")]
+    DeleteForbidden,
+    #[error("cannot reset from `{tip}` to `{target}`, it is not a fast-forward")]
+    NonFastForwardReset { tip: SnapshotId, target: SnapshotId },
+    #[error("commit metadata is missing required keys {0:?}")]
+    MissingCommitMetadata(Vec<String>),
 }
 
 pub type RefError = ICError<RefErrorKind>;
@@ -78,9 +107,9 @@ impl Ref {
     pub const DEFAULT_BRANCH: &'static str = "main";
 
     fn from_path(path: &str) -> RefResult<Self> {
-        match path.strip_prefix("tag.") {
+        match path.strip_prefix(TAG_DIR_PREFIX) {
             Some(name) => Ok(Ref::Tag(name.to_string())),
-            None => match path.strip_prefix("branch.") {
+            None => match path.strip_prefix(BRANCH_DIR_PREFIX) {
                 Some(name) => Ok(Ref::Branch(name.to_string())),
                 None => Err(RefErrorKind::InvalidRefType(path.to_string()).into()),
             },
@@ -101,6 +130,13 @@ impl Ref {
         }
     }
 
+    pub fn name(&self) -> &str {
+        match self {
+            Ref::Tag(name) => name,
+            Ref::Branch(name) => name,
+        }
+    }
+
     pub async fn fetch(
         &self,
         storage: &(dyn Storage + Send + Sync),
@@ -118,32 +154,144 @@ impl Ref {
 pub struct RefData {
     #[serde_as(as = "TryFromInto<String>")]
     pub snapshot: SnapshotId,
+    /// Only present for annotated tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<TagAnnotation>,
 }
 
-const REF_KEY_NAME: &str = "ref.json";
-const TAG_DELETE_MARKER_KEY_NAME: &str = "ref.json.deleted";
+impl RefData {
+    pub fn new(snapshot: SnapshotId) -> Self {
+        Self { snapshot, annotation: None }
+    }
+}
 
-fn tag_key(tag_name: &str) -> RefResult<String> {
-    if tag_name.contains('/') {
-        return Err(RefErrorKind::InvalidRefName(tag_name.to_string()).into());
+/// Extra information stored with an annotated tag, like release notes
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TagAnnotation {
+    pub message: String,
+    pub creator: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub properties: SnapshotProperties,
+}
+
+impl TagAnnotation {
+    pub fn new(
+        message: impl Into<String>,
+        creator: Option<String>,
+        properties: SnapshotProperties,
+    ) -> Self {
+        Self { message: message.into(), creator, created_at: Utc::now(), properties }
     }
+}
 
-    Ok(format!("tag.{}/{}", tag_name, REF_KEY_NAME))
+/// A tag together with its annotation, if it has one
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TagInfo {
+    pub name: String,
+    pub snapshot: SnapshotId,
+    pub annotation: Option<TagAnnotation>,
 }
 
-fn tag_delete_marker_key(tag_name: &str) -> RefResult<String> {
-    if tag_name.contains('/') {
-        return Err(RefErrorKind::InvalidRefName(tag_name.to_string()).into());
+/// The kind of operation that moved a ref
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefOperation {
+    Create,
+    Commit,
+    Reset,
+    Rebase,
+    Delete,
+}
+
+/// A single movement of a branch or tag, as recorded in its reflog
+#[serde_as]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReflogEntry {
+    /// The snapshot the ref pointed to before the operation, `None` if it didn't exist
+    #[serde_as(as = "Option<TryFromInto<String>>")]
+    pub old_snapshot: Option<SnapshotId>,
+    /// The snapshot the ref points to after the operation, `None` if it was deleted
+    #[serde_as(as = "Option<TryFromInto<String>>")]
+    pub new_snapshot: Option<SnapshotId>,
+    pub timestamp: DateTime<Utc>,
+    pub operation: RefOperation,
+    pub user: Option<String>,
+}
+
+impl ReflogEntry {
+    /// The user is taken from the [`REFLOG_USER_METADATA_KEY`] key in `properties`, if present
+    pub fn new(
+        operation: RefOperation,
+        old_snapshot: Option<SnapshotId>,
+        new_snapshot: Option<SnapshotId>,
+        properties: &SnapshotProperties,
+    ) -> Self {
+        let user = properties
+            .get(REFLOG_USER_METADATA_KEY)
+            .and_then(|user| user.as_str())
+            .map(|user| user.to_string());
+        Self { old_snapshot, new_snapshot, timestamp: Utc::now(), operation, user }
     }
+}
+
+/// Commit metadata key used to attribute reflog entries to a user
+pub const REFLOG_USER_METADATA_KEY: &str = "user";
+
+const REF_KEY_NAME: &str = "ref.json";
+const TAG_DELETE_MARKER_KEY_NAME: &str = "ref.json.deleted";
+const BRANCH_DIR_PREFIX: &str = "branch.";
+const TAG_DIR_PREFIX: &str = "tag.";
+
+/// Ref names can be organized in namespaces separated by `/`, like `users/alice/experiment`.
+///
+/// Every part of the name must be non-empty, and cannot be `.`, `..` or start with
+/// `ref.json`, the name of the objects stored inside a ref directory. Otherwise the keys of
+/// one ref would not be listed next to each other, and listings could return it twice.
+///
+/// Older clients only take the first path component of a ref key as its name, so they
+/// don't see nested names like `users/alice/experiment`, or they misread them as the ref
+/// `users`.
+fn validate_ref_name(name: &str) -> RefResult<()> {
+    let valid = name.split('/').all(|part| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && !part.starts_with(REF_KEY_NAME)
+            && !part.contains('\\')
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(RefErrorKind::InvalidRefName(name.to_string()).into())
+    }
+}
 
-    Ok(format!("tag.{}/{}", tag_name, TAG_DELETE_MARKER_KEY_NAME))
+fn tag_key(tag_name: &str) -> RefResult<String> {
+    validate_ref_name(tag_name)?;
+    Ok(format!("{}{}/{}", TAG_DIR_PREFIX, tag_name, REF_KEY_NAME))
+}
+
+fn tag_delete_marker_key(tag_name: &str) -> RefResult<String> {
+    validate_ref_name(tag_name)?;
+    Ok(format!("{}{}/{}", TAG_DIR_PREFIX, tag_name, TAG_DELETE_MARKER_KEY_NAME))
 }
 
 fn branch_key(branch_name: &str) -> RefResult<String> {
-    if branch_name.contains('/') {
-        return Err(RefErrorKind::InvalidRefName(branch_name.to_string()).into());
-    }
-    Ok(format!("branch.{}/{}", branch_name, REF_KEY_NAME))
+    validate_ref_name(branch_name)?;
+    Ok(format!("{}{}/{}", BRANCH_DIR_PREFIX, branch_name, REF_KEY_NAME))
+}
+
+fn reflog_dir(reference: &Ref) -> RefResult<String> {
+    let (kind, name) = match reference {
+        Ref::Tag(name) => (TAG_DIR_PREFIX, name),
+        Ref::Branch(name) => (BRANCH_DIR_PREFIX, name),
+    };
+    validate_ref_name(name)?;
+    // reflogs are kept flat, otherwise listing the entries for `foo` would
+    // also return those of `foo/bar`
+    let name = name.replace('%', "%25").replace('/', "%2F");
+    Ok(format!("{}{}", kind, name))
 }
 
 #[instrument(skip(storage, storage_settings))]
@@ -152,9 +300,21 @@ pub async fn create_tag(
     storage_settings: &storage::Settings,
     name: &str,
     snapshot: SnapshotId,
+) -> RefResult<()> {
+    create_annotated_tag(storage, storage_settings, name, snapshot, None).await
+}
+
+/// Create a tag, storing `annotation` with it if there is one
+#[instrument(skip(storage, storage_settings))]
+pub async fn create_annotated_tag(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    name: &str,
+    snapshot: SnapshotId,
+    annotation: Option<TagAnnotation>,
 ) -> RefResult<()> {
     let key = tag_key(name)?;
-    let data = RefData { snapshot };
+    let data = RefData { snapshot, annotation };
     let content = serde_json::to_vec(&data)?;
     match storage
         .write_ref(
@@ -173,14 +333,34 @@ pub async fn create_tag(
     }
 }
 
+/// Checks moves of a branch against the policy of the branch, see [`update_branch`]
+///
+/// Commits are not checked here, their metadata is checked before the new snapshot is
+/// written, see [`Session::commit`](crate::session::Session::commit).
+#[async_trait]
+pub trait BranchGuard: Send + Sync {
+    /// Fail if branch `branch` cannot move from `tip` to `new_snapshot`
+    async fn check(
+        &self,
+        branch: &str,
+        tip: &SnapshotId,
+        new_snapshot: &SnapshotId,
+    ) -> RefResult<()>;
+}
+
+/// Move branch `name` from `current_snapshot` to `new_snapshot`, `current_snapshot` is
+/// `None` to create it
+///
+/// If there is a `guard`, moves of an existing branch are checked with it.
 #[async_recursion]
-#[instrument(skip(storage, storage_settings))]
+#[instrument(skip(storage, storage_settings, guard))]
 pub async fn update_branch(
     storage: &(dyn Storage + Send + Sync),
     storage_settings: &storage::Settings,
     name: &str,
     new_snapshot: SnapshotId,
     current_snapshot: Option<&SnapshotId>,
+    guard: Option<&dyn BranchGuard>,
 ) -> RefResult<()> {
     let (ref_data, version) = match fetch_branch(storage, storage_settings, name).await {
         Ok((ref_data, version)) => (Some(ref_data), version),
@@ -200,8 +380,12 @@ pub async fn update_branch(
         .into());
     }
 
+    if let (Some(guard), Some(tip)) = (guard, current_snapshot) {
+        guard.check(name, tip, &new_snapshot).await?;
+    }
+
     let key = branch_key(name)?;
-    let data = RefData { snapshot: new_snapshot };
+    let data = RefData::new(new_snapshot);
     let content = serde_json::to_vec(&data)?;
     match storage
         .write_ref(
@@ -222,6 +406,7 @@ pub async fn update_branch(
                 name,
                 data.snapshot,
                 current_snapshot,
+                guard,
             )
             .await
         }
@@ -259,6 +444,116 @@ pub async fn list_refs(
     Ok(candidate_refs.difference(&deleted_tags).cloned().collect())
 }
 
+/// Names of the refs of one kind, under the top level directory `root`, that start with
+/// `prefix`, after the ref `start_after`, at most `limit` of them
+///
+/// Names are sorted by the key of their ref object, `<name>/ref.json`, so nested names
+/// like `users/alice` come before `users`.
+async fn ref_names_after(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    root: &str,
+    dir_prefix: &str,
+    prefix: &str,
+    start_after: Option<&str>,
+    limit: Option<usize>,
+) -> RefResult<Vec<String>> {
+    let start_after_key =
+        start_after.map(|after| format!("{}{}/{}", dir_prefix, after, REF_KEY_NAME));
+    // other objects of the `start_after` ref, like tag delete markers, come after its key
+    let names = storage
+        .ref_names_in(
+            storage_settings,
+            root,
+            format!("{}{}", dir_prefix, prefix).as_str(),
+            start_after_key.as_deref(),
+            limit.map(|limit| limit.saturating_add(1)),
+        )
+        .await?;
+    Ok(names
+        .iter()
+        .filter_map(|path| path.strip_prefix(dir_prefix))
+        .filter(|name| Some(*name) != start_after)
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// List at most `limit` branch names that start with `prefix`.
+///
+/// Names are sorted by their key in storage, `branch.<name>/ref.json`, so nested names like
+/// `users/alice/a` come before `users`. To get the next page pass the last name returned
+/// as `start_after`.
+#[instrument(skip(storage, storage_settings))]
+pub async fn list_branches_page(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    prefix: &str,
+    start_after: Option<&str>,
+    limit: usize,
+) -> RefResult<Vec<String>> {
+    ref_names_after(
+        storage,
+        storage_settings,
+        REF_PREFIX,
+        BRANCH_DIR_PREFIX,
+        prefix,
+        start_after,
+        Some(limit),
+    )
+    .await
+}
+
+/// List at most `limit` tag names that start with `prefix`.
+///
+/// Names are sorted as in [`list_branches_page`]. To get the next page pass the last name
+/// returned as `start_after`.
+#[instrument(skip(storage, storage_settings))]
+pub async fn list_tags_page(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    prefix: &str,
+    start_after: Option<&str>,
+    limit: usize,
+) -> RefResult<Vec<String>> {
+    let mut res = Vec::with_capacity(limit);
+    let mut start_after = start_after.map(|name| name.to_string());
+    // deleted tags are skipped, we keep listing until the page is full
+    while res.len() < limit {
+        let wanted = limit - res.len();
+        let names = ref_names_after(
+            storage,
+            storage_settings,
+            REF_PREFIX,
+            TAG_DIR_PREFIX,
+            prefix,
+            start_after.as_deref(),
+            Some(wanted),
+        )
+        .await?;
+        let listed = names.len();
+        start_after = names.last().cloned();
+        // we only check for delete markers in the candidates we need for the page
+        let futs: FuturesOrdered<_> = names
+            .into_iter()
+            .map(|name| async move {
+                match fetch_tag(storage, storage_settings, name.as_str()).await {
+                    Ok(_) => Ok(Some(name)),
+                    Err(RefError { kind: RefErrorKind::RefNotFound(_), .. }) => Ok(None),
+                    Err(err) => Err(err),
+                }
+            })
+            .collect();
+        res.extend(
+            futs.try_filter_map(|name| ready(Ok(name))).try_collect::<Vec<_>>().await?,
+        );
+        if listed < wanted {
+            break;
+        }
+    }
+    Ok(res)
+}
+
 pub async fn list_tags(
     storage: &(dyn Storage + Send + Sync),
     storage_settings: &storage::Settings,
@@ -291,12 +586,22 @@ pub async fn list_branches(
     Ok(branches)
 }
 
+/// Delete branch `branch`, failing if its `policy` forbids it
 #[instrument(skip(storage, storage_settings))]
 pub async fn delete_branch(
     storage: &(dyn Storage + Send + Sync),
     storage_settings: &storage::Settings,
     branch: &str,
+    policy: Option<&BranchPolicy>,
 ) -> RefResult<()> {
+    if policy.is_some_and(|p| p.forbid_delete()) {
+        return Err(RefErrorKind::BranchPolicyViolation {
+            branch: branch.to_string(),
+            violation: BranchPolicyViolation::DeleteForbidden,
+        }
+        .into());
+    }
+
     // we make sure the branch exists
     _ = fetch_branch_tip(storage, storage_settings, branch).await?;
 
@@ -316,11 +621,12 @@ pub async fn delete_tag(
 
     // no race condition: delete_tag ^ 2 = delete_tag
     let key = tag_delete_marker_key(tag)?;
+    let marker = TagDeleteMarker { deleted_at: Some(Utc::now()) };
     match storage
         .write_ref(
             storage_settings,
             key.as_str(),
-            Bytes::from_static(&[]),
+            Bytes::from(serde_json::to_vec(&marker)?),
             &VersionInfo::for_creation(),
         )
         .await
@@ -410,6 +716,155 @@ pub async fn fetch_branch_tip(
     Ok(fetch_branch(storage, storage_settings, name).await?.0)
 }
 
+/// Append an entry to the reflog of a branch or tag.
+///
+/// Every entry is stored in its own object, so concurrent writers never overwrite each other.
+/// Reflogs live outside of the refs, so listing refs never has to go through them.
+#[instrument(skip(storage, storage_settings))]
+pub async fn append_reflog(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    reference: &Ref,
+    entry: &ReflogEntry,
+) -> RefResult<()> {
+    // entry names sort newest first, the random suffix breaks ties
+    let nanos = entry.timestamp.timestamp_nanos_opt().unwrap_or_default().max(0);
+    let key = format!(
+        "{}/{:019}-{:08x}.json",
+        reflog_dir(reference)?,
+        i64::MAX - nanos,
+        rng().random::<u32>()
+    );
+    let content = serde_json::to_vec(entry)?;
+    storage
+        .write_ref_in(
+            storage_settings,
+            REFLOG_PREFIX,
+            key.as_str(),
+            Bytes::from(content),
+            &VersionInfo::for_creation(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Like [`append_reflog`] but failures are only logged.
+///
+/// Used after the ref has already moved, when failing the whole operation
+/// would misreport its outcome to the caller.
+pub(crate) async fn record_reflog(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    reference: &Ref,
+    entry: ReflogEntry,
+) {
+    if let Err(err) = append_reflog(storage, storage_settings, reference, &entry).await {
+        tracing::error!(error = %err, reference = ?reference, "Cannot write reflog entry");
+    }
+}
+
+/// Fetch the reflog of a branch or tag, newest entries first, at most `limit` of them.
+///
+/// The reflog survives the deletion of the ref, so it can be used to recover it.
+#[instrument(skip(storage, storage_settings))]
+pub async fn fetch_reflog(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    reference: &Ref,
+    limit: Option<usize>,
+) -> RefResult<Vec<ReflogEntry>> {
+    let dir = reflog_dir(reference)?;
+    let limit = limit.unwrap_or(usize::MAX);
+    // not every storage lists in key order, we keep the `limit` newest names as we go
+    let mut names = BTreeSet::new();
+    let mut listed = storage
+        .list_objects(storage_settings, format!("{}/{}/", REFLOG_PREFIX, dir).as_str())
+        .await?;
+    while let Some(info) = listed.try_next().await? {
+        names.insert(info.id);
+        if names.len() > limit {
+            names.pop_last();
+        }
+    }
+
+    let entries: Vec<Option<ReflogEntry>> = futures::stream::iter(names)
+        .map(|name| {
+            let key = format!("{}/{}", dir, name);
+            async move {
+                match storage
+                    .get_ref_in(storage_settings, REFLOG_PREFIX, key.as_str())
+                    .await?
+                {
+                    GetRefResult::Found { bytes, .. } => {
+                        RefResult::Ok(Some(serde_json::from_slice(bytes.as_ref())?))
+                    }
+                    GetRefResult::NotFound => Ok(None),
+                }
+            }
+        })
+        .buffered(
+            storage_settings.concurrency().max_concurrent_requests_for_object().get()
+                as usize,
+        )
+        .try_collect()
+        .await?;
+    Ok(entries.into_iter().flatten().collect())
+}
+
+/// Content of the object that marks a tag as deleted
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TagDeleteMarker {
+    /// Markers written by older versions are empty and don't have a deletion time
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Deleted tags, with the time they were deleted, in name order
+///
+/// The time is when the delete marker was written, markers written by older versions don't
+/// record it in their content.
+#[instrument(skip(storage, storage_settings))]
+pub async fn list_deleted_tags(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> RefResult<Vec<(String, DateTime<Utc>)>> {
+    let suffix = format!("/{TAG_DELETE_MARKER_KEY_NAME}");
+    let mut res: Vec<_> = storage
+        .ref_objects_in(storage_settings, REF_PREFIX, TAG_DIR_PREFIX)
+        .await?
+        .into_iter()
+        .filter_map(|info| {
+            let name =
+                info.id.strip_prefix(TAG_DIR_PREFIX)?.strip_suffix(suffix.as_str())?;
+            Some((name.to_string(), info.created_at))
+        })
+        .collect();
+    res.sort();
+    Ok(res)
+}
+
+/// Remove every trace of a deleted tag, after which a new tag can be created with its name
+///
+/// Fails with [`RefErrorKind::RefNotFound`] if the tag wasn't deleted.
+#[instrument(skip(storage, storage_settings))]
+pub async fn purge_deleted_tag(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    tag: &str,
+) -> RefResult<()> {
+    let marker_key = tag_delete_marker_key(tag)?;
+    if let GetRefResult::NotFound = storage.get_ref(storage_settings, &marker_key).await?
+    {
+        return Err(RefErrorKind::RefNotFound(tag.to_string()).into());
+    }
+    // the marker goes last, if we fail in between the tag still reads as deleted
+    for key in [tag_key(tag)?, marker_key] {
+        storage
+            .delete_refs(storage_settings, futures::stream::iter([key]).boxed())
+            .await?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 #[allow(clippy::panic, clippy::unwrap_used, clippy::expect_used)]
 mod tests {
@@ -485,7 +940,7 @@ mod tests {
 
             // attempting to create a branch that doesn't exist, with a fake parent
             let res =
-                update_branch(storage.as_ref(), &storage_settings, "branch0", s1.clone(), Some(&s2))
+                update_branch(storage.as_ref(), &storage_settings, "branch0", s1.clone(), Some(&s2), None)
                     .await;
             assert!(res.is_err());
             assert_eq!(
@@ -494,12 +949,12 @@ mod tests {
             );
 
             // create a branch successfully
-            update_branch(storage.as_ref(), &storage_settings, "branch1", s1.clone(), None).await?;
+            update_branch(storage.as_ref(), &storage_settings, "branch1", s1.clone(), None, None).await?;
 
 
             assert_eq!(
                 fetch_branch_tip(storage.as_ref(), &storage_settings, "branch1").await?,
-                RefData { snapshot: s1.clone() }
+                RefData::new(s1.clone())
             );
 
 
@@ -518,18 +973,19 @@ mod tests {
                 "branch1",
                 s2.clone(),
                 Some(&s1.clone()),
+                None,
             )
             .await?;
 
             assert_eq!(
                 fetch_branch_tip(storage.as_ref(), &storage_settings, "branch1").await?,
-                RefData { snapshot: s2.clone() }
+                RefData::new(s2.clone())
             );
 
             let sid = SnapshotId::random();
             // update a branch with the wrong parent
             let res =
-                update_branch(storage.as_ref(), &storage_settings, "branch1", sid.clone(), Some(&s1))
+                update_branch(storage.as_ref(), &storage_settings, "branch1", sid.clone(), Some(&s1), None)
                     .await;
             assert!(matches!(res,
                     Err(RefError{kind: RefErrorKind::Conflict { expected_parent, actual_parent }, ..})
@@ -537,17 +993,17 @@ mod tests {
             ));
 
             // update the branch again but now with the right parent
-            update_branch(storage.as_ref(), &storage_settings, "branch1", sid.clone(), Some(&s2))
+            update_branch(storage.as_ref(), &storage_settings, "branch1", sid.clone(), Some(&s2), None)
                 .await?;
 
             assert_eq!(
                 fetch_branch_tip(storage.as_ref(), &storage_settings, "branch1").await?,
-                RefData { snapshot: sid.clone() }
+                RefData::new(sid.clone())
             );
 
 
             // delete a branch
-            delete_branch(storage.as_ref(), &storage_settings, "branch1").await?;
+            delete_branch(storage.as_ref(), &storage_settings, "branch1", None).await?;
             assert!(matches!(
                 fetch_branch_tip(storage.as_ref(), &storage_settings, "branch1").await,
                 Err(RefError{kind: RefErrorKind::RefNotFound(name),..}) if name == "branch1"
@@ -616,4 +1072,238 @@ mod tests {
         res2?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_reflog() -> Result<(), Box<dyn std::error::Error>> {
+        let ((_, res1), (_, res2, _)) = with_test_storages::<
+            Result<(), Box<dyn std::error::Error>>,
+            _,
+            _,
+        >(|storage| async move {
+            let storage_settings = storage.default_settings();
+            let s1 = SnapshotId::random();
+            let s2 = SnapshotId::random();
+            let branch = Ref::Branch("main".to_string());
+
+            assert!(
+                fetch_reflog(storage.as_ref(), &storage_settings, &branch, None)
+                    .await?
+                    .is_empty()
+            );
+
+            update_branch(
+                storage.as_ref(),
+                &storage_settings,
+                "main",
+                s1.clone(),
+                None,
+                None,
+            )
+            .await?;
+            let props = SnapshotProperties::from_iter([(
+                REFLOG_USER_METADATA_KEY.to_string(),
+                "jane".into(),
+            )]);
+            let first =
+                ReflogEntry::new(RefOperation::Create, None, Some(s1.clone()), &props);
+            append_reflog(storage.as_ref(), &storage_settings, &branch, &first).await?;
+            let second = ReflogEntry::new(
+                RefOperation::Reset,
+                Some(s1.clone()),
+                Some(s2.clone()),
+                &SnapshotProperties::default(),
+            );
+            append_reflog(storage.as_ref(), &storage_settings, &branch, &second).await?;
+
+            // newest entries come first
+            let log =
+                fetch_reflog(storage.as_ref(), &storage_settings, &branch, None).await?;
+            assert_eq!(log, vec![second.clone(), first.clone()]);
+            assert_eq!(log[1].user, Some("jane".to_string()));
+            assert_eq!(
+                fetch_reflog(storage.as_ref(), &storage_settings, &branch, Some(1))
+                    .await?,
+                vec![second]
+            );
+
+            // reflogs are outside of the refs, clients that don't know them never see them
+            assert_eq!(
+                storage.ref_names(&storage_settings).await?,
+                vec!["branch.main".to_string()]
+            );
+            // reflogs are not listed as refs and are independent for tags
+            assert_eq!(
+                list_refs(storage.as_ref(), &storage_settings).await?,
+                [branch].into()
+            );
+            assert!(
+                fetch_reflog(
+                    storage.as_ref(),
+                    &storage_settings,
+                    &Ref::Tag("main".to_string()),
+                    None
+                )
+                .await?
+                .is_empty()
+            );
+
+            Ok(())
+        })
+        .await;
+        res1?;
+        res2?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespaced_refs() -> Result<(), Box<dyn std::error::Error>> {
+        let ((_, res1), (_, res2, _)) = with_test_storages::<
+            Result<(), Box<dyn std::error::Error>>,
+            _,
+            _,
+        >(|storage| async move {
+            let storage_settings = storage.default_settings();
+            let sid = SnapshotId::random();
+
+            for name in [
+                "",
+                "/a",
+                "a/",
+                "a//b",
+                "a/./b",
+                "../a",
+                "a/ref.json",
+                "a/ref.json-x",
+                "ref.json.deleted/a",
+            ] {
+                assert!(matches!(
+                    update_branch(
+                        storage.as_ref(),
+                        &storage_settings,
+                        name,
+                        sid.clone(),
+                        None,
+                        None
+                    )
+                    .await,
+                    Err(RefError { kind: RefErrorKind::InvalidRefName(_), .. })
+                ));
+            }
+
+            for name in ["main", "users", "users/alice/a", "users/alice/b", "users/bob/a"]
+            {
+                update_branch(
+                    storage.as_ref(),
+                    &storage_settings,
+                    name,
+                    sid.clone(),
+                    None,
+                    None,
+                )
+                .await?;
+            }
+            for name in ["v1", "releases/v1", "releases/v2", "releases/v3"] {
+                create_tag(storage.as_ref(), &storage_settings, name, sid.clone())
+                    .await?;
+            }
+            delete_tag(storage.as_ref(), &storage_settings, "releases/v2").await?;
+
+            assert_eq!(
+                list_branches(storage.as_ref(), &storage_settings).await?,
+                ["main", "users", "users/alice/a", "users/alice/b", "users/bob/a"]
+                    .map(String::from)
+                    .into()
+            );
+            assert_eq!(
+                fetch_branch_tip(storage.as_ref(), &storage_settings, "users/alice/b")
+                    .await?
+                    .snapshot,
+                sid
+            );
+
+            // listing by prefix
+            let page = |prefix, start_after, limit| {
+                let storage = Arc::clone(&storage);
+                let storage_settings = storage_settings.clone();
+                async move {
+                    list_branches_page(
+                        storage.as_ref(),
+                        &storage_settings,
+                        prefix,
+                        start_after,
+                        limit,
+                    )
+                    .await
+                    .unwrap()
+                }
+            };
+            assert_eq!(
+                page("users/alice/", None, 10).await,
+                ["users/alice/a", "users/alice/b"]
+            );
+            assert_eq!(
+                page("users/al", None, 10).await,
+                ["users/alice/a", "users/alice/b"]
+            );
+            // `users/ref.json` comes after the refs nested under `users/`
+            assert_eq!(page("users", None, 2).await, ["users/alice/a", "users/alice/b"]);
+            assert_eq!(
+                page("users", Some("users/alice/b"), 2).await,
+                ["users/bob/a", "users"]
+            );
+            assert!(page("users", Some("users"), 2).await.is_empty());
+            assert!(page("users", None, 0).await.is_empty());
+            assert!(page("nope/", None, 2).await.is_empty());
+
+            // deleted tags are skipped without shortening the page
+            assert_eq!(
+                list_tags_page(storage.as_ref(), &storage_settings, "releases/", None, 2)
+                    .await?,
+                ["releases/v1", "releases/v3"]
+            );
+            assert_eq!(
+                list_tags_page(
+                    storage.as_ref(),
+                    &storage_settings,
+                    "",
+                    Some("releases/v1"),
+                    5
+                )
+                .await?,
+                ["releases/v3", "v1"]
+            );
+
+            // nested refs have independent reflogs
+            let entry = ReflogEntry::new(
+                RefOperation::Create,
+                None,
+                Some(sid.clone()),
+                &SnapshotProperties::default(),
+            );
+            let users = Ref::Branch("users".to_string());
+            let alice = Ref::Branch("users/alice/a".to_string());
+            append_reflog(storage.as_ref(), &storage_settings, &alice, &entry).await?;
+            assert_eq!(
+                fetch_reflog(storage.as_ref(), &storage_settings, &alice, None)
+                    .await?
+                    .len(),
+                1
+            );
+            assert!(
+                fetch_reflog(storage.as_ref(), &storage_settings, &users, None)
+                    .await?
+                    .is_empty()
+            );
+
+            // deleting a namespace parent doesn't affect its children
+            delete_branch(storage.as_ref(), &storage_settings, "users", None).await?;
+            assert_eq!(page("users", None, 10).await.len(), 3);
+
+            Ok(())
+        })
+        .await;
+        res1?;
+        res2?;
+        Ok(())
+    }
 }
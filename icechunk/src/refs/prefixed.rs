@@ -0,0 +1,121 @@
+//! Mutable objects that are not refs, stored by name under their own top level directory
+//!
+//! They use the same storage primitives as refs, but live outside of the `refs` directory,
+//! where clients that don't know them would fail to parse their names.
+
+use bytes::Bytes;
+use futures::StreamExt as _;
+
+use crate::{
+    Storage,
+    storage::{self, GetRefResult, VersionInfo, WriteRefResult},
+};
+
+use super::{RefErrorKind, RefResult, validate_ref_name};
+
+/// Attempts of a last write wins update before giving up
+const OVERWRITE_ATTEMPTS: usize = 10;
+
+/// The objects under the top level directory `root`
+#[derive(Clone, Copy)]
+pub(crate) struct PrefixedObjects<'a> {
+    storage: &'a (dyn Storage + Send + Sync),
+    storage_settings: &'a storage::Settings,
+    root: &'static str,
+}
+
+impl<'a> PrefixedObjects<'a> {
+    pub(crate) fn new(
+        storage: &'a (dyn Storage + Send + Sync),
+        storage_settings: &'a storage::Settings,
+        root: &'static str,
+    ) -> Self {
+        Self { storage, storage_settings, root }
+    }
+
+    /// Key of the object `key_name` for `name`, names follow the same rules as ref names
+    pub(crate) fn key(name: &str, key_name: &str) -> RefResult<String> {
+        validate_ref_name(name)?;
+        Ok(format!("{}/{}", name, key_name))
+    }
+
+    pub(crate) async fn get(&self, key: &str) -> RefResult<Option<Bytes>> {
+        match self.storage.get_ref_in(self.storage_settings, self.root, key).await? {
+            GetRefResult::Found { bytes, .. } => Ok(Some(bytes)),
+            GetRefResult::NotFound => Ok(None),
+        }
+    }
+
+    /// Write `bytes` to `key`, replacing any previous version of the object, last write wins
+    pub(crate) async fn overwrite(&self, key: &str, bytes: Bytes) -> RefResult<()> {
+        for _ in 0..OVERWRITE_ATTEMPTS {
+            let version = match self
+                .storage
+                .get_ref_in(self.storage_settings, self.root, key)
+                .await?
+            {
+                GetRefResult::Found { version, .. } => version,
+                GetRefResult::NotFound => VersionInfo::for_creation(),
+            };
+            match self
+                .storage
+                .write_ref_in(
+                    self.storage_settings,
+                    self.root,
+                    key,
+                    bytes.clone(),
+                    &version,
+                )
+                .await?
+            {
+                WriteRefResult::Written => return Ok(()),
+                // somebody else wrote the object since we checked, we try again
+                WriteRefResult::WontOverwrite => {}
+            }
+        }
+        Err(RefErrorKind::TooManyConcurrentUpdates(format!("{}/{}", self.root, key))
+            .into())
+    }
+
+    /// Write `bytes` to `key` unless the object already exists
+    pub(crate) async fn create(
+        &self,
+        key: &str,
+        bytes: Bytes,
+    ) -> RefResult<WriteRefResult> {
+        Ok(self
+            .storage
+            .write_ref_in(
+                self.storage_settings,
+                self.root,
+                key,
+                bytes,
+                &VersionInfo::for_creation(),
+            )
+            .await?)
+    }
+
+    /// Names of the objects under `dir_prefix`, relative to it, in key order
+    pub(crate) async fn names(&self, dir_prefix: &str) -> RefResult<Vec<String>> {
+        let names = self
+            .storage
+            .ref_names_in(self.storage_settings, self.root, dir_prefix, None, None)
+            .await?;
+        Ok(names
+            .iter()
+            .filter_map(|path| path.strip_prefix(dir_prefix))
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    pub(crate) async fn delete(&self, keys: Vec<String>) -> RefResult<u64> {
+        Ok(self
+            .storage
+            .delete_refs_in(
+                self.storage_settings,
+                self.root,
+                futures::stream::iter(keys).boxed(),
+            )
+            .await?)
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 };
 
 use async_recursion::async_recursion;
+use async_trait::async_trait;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use err_into::ErrorInto as _;
@@ -32,15 +33,25 @@ use crate::{
         },
         transaction_log::{Diff, DiffBuilder},
     },
+    ops::gc::{ExpireResult, ExpiredRefAction, GCResult, expire_keeping_branches},
     refs::{
-        Ref, RefError, RefErrorKind, create_tag, delete_branch, delete_tag,
-        fetch_branch_tip, fetch_tag, list_branches, list_tags, update_branch,
+        BranchGuard, Ref, RefError, RefErrorKind, RefOperation, RefResult, ReflogEntry,
+        TagAnnotation, TagInfo, create_annotated_tag, delete_branch, delete_tag,
+        fetch_branch_tip, fetch_reflog, fetch_tag, list_branches, list_branches_page,
+        list_tags, list_tags_page, record_reflog, update_branch,
+    },
+    session::{
+        SavedSession, Session, SessionErrorKind, SessionResult,
+        lease::{LeaseError, LeaseErrorKind},
+        saved::{delete_saved_session, list_saved_sessions},
     },
-    session::{Session, SessionErrorKind, SessionResult},
     storage::{self, FetchConfigResult, StorageErrorKind, UpdateConfigResult},
+    validation::CommitValidator,
     virtual_chunks::{ContainerName, VirtualChunkResolver},
 };
 
+pub use crate::refs::BranchPolicyViolation;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum VersionInfo {
@@ -59,6 +70,8 @@ pub enum RepositoryErrorKind {
     FormatError(IcechunkFormatErrorKind),
     #[error(transparent)]
     Ref(RefErrorKind),
+    #[error(transparent)]
+    Lease(LeaseErrorKind),
     #[error("snapshot not found: `{id}`")]
     SnapshotNotFound { id: SnapshotId },
     #[error("branch {branch} does not have a snapshots before or at {at}")]
@@ -93,6 +106,10 @@ pub enum RepositoryErrorKind {
     CannotDeleteMain,
     #[error("the storage used by this Icechunk repository is read-only: {0}")]
     ReadonlyStorage(String),
+    #[error("branch `{branch}` is protected: {violation}")]
+    BranchPolicyViolation { branch: String, violation: BranchPolicyViolation },
+    #[error("saved session not found `{0}`")]
+    SavedSessionNotFound(String),
 }
 
 pub type RepositoryError = ICError<RepositoryErrorKind>;
@@ -116,7 +133,19 @@ impl From<StorageError> for RepositoryError {
 
 impl From<RefError> for RepositoryError {
     fn from(value: RefError) -> Self {
-        Self::with_context(RepositoryErrorKind::Ref(value.kind), value.context)
+        let kind = match value.kind {
+            RefErrorKind::BranchPolicyViolation { branch, violation } => {
+                RepositoryErrorKind::BranchPolicyViolation { branch, violation }
+            }
+            kind => RepositoryErrorKind::Ref(kind),
+        };
+        Self::with_context(kind, value.context)
+    }
+}
+
+impl From<LeaseError> for RepositoryError {
+    fn from(value: LeaseError) -> Self {
+        Self::with_context(RepositoryErrorKind::Lease(value.kind), value.context)
     }
 }
 
@@ -138,6 +167,12 @@ pub struct Repository {
     virtual_resolver: Arc<VirtualChunkResolver>,
     virtual_chunk_credentials: HashMap<ContainerName, Credentials>,
     default_commit_metadata: SnapshotProperties,
+    // validators are code, only their names are serialized, sessions created from a
+    // deserialized repository refuse to commit until the validators are added again
+    #[serde(skip)]
+    commit_validators: Vec<Arc<dyn CommitValidator>>,
+    #[serde(default)]
+    commit_validator_names: Vec<String>,
 }
 
 impl Repository {
@@ -188,8 +223,21 @@ impl Repository {
                     Ref::DEFAULT_BRANCH,
                     new_snapshot.id().clone(),
                     None,
+                    None,
                 )
                 .await?;
+                record_reflog(
+                    storage_c.as_ref(),
+                    &storage_settings,
+                    &Ref::Branch(Ref::DEFAULT_BRANCH.to_string()),
+                    ReflogEntry::new(
+                        RefOperation::Create,
+                        None,
+                        Some(new_snapshot.id().clone()),
+                        &SnapshotProperties::default(),
+                    ),
+                )
+                .await;
                 Ok::<(), RepositoryError>(())
             }
             .in_current_span(),
@@ -310,6 +358,8 @@ impl Repository {
             asset_manager,
             virtual_chunk_credentials,
             default_commit_metadata: SnapshotProperties::default(),
+            commit_validators: Vec::new(),
+            commit_validator_names: Vec::new(),
         })
     }
 
@@ -336,13 +386,19 @@ impl Repository {
             .map(|c| self.config().merge(c))
             .unwrap_or_else(|| self.config().clone());
 
-        Self::new(
+        let mut repo = Self::new(
             config,
             self.config_version.clone(),
             Arc::clone(&self.storage),
             virtual_chunk_credentials
                 .unwrap_or_else(|| self.virtual_chunk_credentials.clone()),
-        )
+        )?;
+        repo.commit_validators = self.commit_validators.clone();
+        repo.commit_validator_names = self.commit_validator_names.clone();
+        if let Some((archive, archive_settings)) = self.asset_manager.archive() {
+            repo.asset_manager.set_archive(archive, archive_settings);
+        }
+        Ok(repo)
     }
 
     #[instrument(skip(bytes))]
@@ -388,6 +444,48 @@ impl Repository {
         &self.default_commit_metadata
     }
 
+    /// Register a check to run on every commit of the sessions created from now on
+    ///
+    /// See [`crate::validation`].
+    #[instrument(skip_all)]
+    pub fn add_commit_validator(&mut self, validator: Arc<dyn CommitValidator>) {
+        let name = validator.name().to_string();
+        if !self.commit_validator_names.contains(&name) {
+            self.commit_validator_names.push(name);
+        }
+        self.commit_validators.push(validator);
+    }
+
+    /// Read the chunks that are not in the repository storage from `archive`
+    ///
+    /// Needed to read the versions whose chunks were moved by
+    /// [`crate::ops::archive::archive_chunks`]. It applies to the open sessions too, they
+    /// share the repository caches. The archive is kept by [`Repository::reopen`], but it's
+    /// not part of the repository config, it must be set every time the repository is opened.
+    #[instrument(skip_all)]
+    pub fn set_archive_storage(&self, archive: Arc<dyn Storage + Send + Sync>) {
+        let archive_settings = archive.default_settings();
+        self.asset_manager.set_archive(archive, archive_settings);
+    }
+
+    pub fn archive_storage(&self) -> Option<Arc<dyn Storage + Send + Sync>> {
+        self.asset_manager.archive().map(|(archive, _)| archive)
+    }
+
+    #[instrument(skip_all)]
+    pub fn commit_validators(&self) -> &[Arc<dyn CommitValidator>] {
+        &self.commit_validators
+    }
+
+    /// Deserialize a session created by [`Session::as_bytes`], attaching the validators of
+    /// this repository to it
+    #[instrument(skip_all)]
+    pub fn session_from_bytes(&self, bytes: Vec<u8>) -> SessionResult<Session> {
+        let mut session = Session::from_bytes(bytes)?;
+        session.attach_commit_validators(self.commit_validators.clone());
+        Ok(session)
+    }
+
     #[instrument(skip(storage, config))]
     pub(crate) async fn store_config(
         storage: &(dyn Storage + Send + Sync),
@@ -495,15 +593,27 @@ impl Repository {
             branch_name,
             snapshot_id.clone(),
             None,
+            None,
         )
         .await
         .map_err(|e| match e {
             RefError {
                 kind: RefErrorKind::Conflict { expected_parent, actual_parent },
                 ..
-            } => RepositoryErrorKind::Conflict { expected_parent, actual_parent }.into(),
+            } => RepositoryError::from(RepositoryErrorKind::Conflict {
+                expected_parent,
+                actual_parent,
+            }),
             err => err.into(),
-        })
+        })?;
+        self.record_reflog(
+            Ref::Branch(branch_name.to_string()),
+            RefOperation::Create,
+            None,
+            Some(snapshot_id.clone()),
+        )
+        .await;
+        Ok(())
     }
 
     /// List all branches in the repository.
@@ -514,6 +624,30 @@ impl Repository {
         Ok(branches)
     }
 
+    /// List at most `limit` branches whose name starts with `prefix`.
+    ///
+    /// Branch names can use `/` to create namespaces, like `users/alice/experiment`,
+    /// `prefix` can be used to list only one of them. Names are sorted by their key in
+    /// storage, so `users/alice/experiment` comes before `users`. To get the next page, pass
+    /// the last name returned as `start_after`.
+    #[instrument(skip(self))]
+    pub async fn list_branches_page(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> RepositoryResult<Vec<String>> {
+        let branches = list_branches_page(
+            self.storage.as_ref(),
+            &self.storage_settings,
+            prefix,
+            start_after,
+            limit,
+        )
+        .await?;
+        Ok(branches)
+    }
+
     /// Get the snapshot id of the tip of a branch
     #[instrument(skip(self))]
     pub async fn lookup_branch(&self, branch: &str) -> RepositoryResult<SnapshotId> {
@@ -559,9 +693,56 @@ impl Repository {
             branch,
             snapshot_id.clone(),
             Some(&branch_tip),
+            self.branch_guard(branch).as_ref().map(|guard| guard as &dyn BranchGuard),
         )
-        .await
-        .err_into()
+        .await?;
+        self.record_reflog(
+            Ref::Branch(branch.to_string()),
+            RefOperation::Reset,
+            Some(branch_tip),
+            Some(snapshot_id.clone()),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Checks resets of `branch` against its policy, if it forbids non fast-forward moves
+    pub(crate) fn branch_guard(&self, branch: &str) -> Option<FastForwardGuard<'_>> {
+        self.config
+            .branch_policy(branch)
+            .filter(|policy| policy.forbid_non_fast_forward())
+            .map(|_| FastForwardGuard { asset_manager: &self.asset_manager })
+    }
+
+    /// History of the movements of a branch or tag, newest first, at most `limit` entries.
+    ///
+    /// The reflog is kept after the ref is deleted, so it can be used to find the
+    /// snapshot a deleted or reset ref used to point to.
+    #[instrument(skip(self))]
+    pub async fn reflog(
+        &self,
+        reference: &Ref,
+        limit: Option<usize>,
+    ) -> RepositoryResult<Vec<ReflogEntry>> {
+        Ok(fetch_reflog(self.storage.as_ref(), &self.storage_settings, reference, limit)
+            .await?)
+    }
+
+    async fn record_reflog(
+        &self,
+        reference: Ref,
+        operation: RefOperation,
+        old_snapshot: Option<SnapshotId>,
+        new_snapshot: Option<SnapshotId>,
+    ) {
+        let entry = ReflogEntry::new(
+            operation,
+            old_snapshot,
+            new_snapshot,
+            &self.default_commit_metadata,
+        );
+        record_reflog(self.storage.as_ref(), &self.storage_settings, &reference, entry)
+            .await
     }
 
     /// Delete a branch from the repository.
@@ -576,13 +757,52 @@ impl Repository {
             .into());
         }
         if branch != Ref::DEFAULT_BRANCH {
-            delete_branch(self.storage.as_ref(), &self.storage_settings, branch).await?;
+            let tip = self.lookup_branch(branch).await?;
+            delete_branch(
+                self.storage.as_ref(),
+                &self.storage_settings,
+                branch,
+                self.config.branch_policy(branch),
+            )
+            .await?;
+            self.record_reflog(
+                Ref::Branch(branch.to_string()),
+                RefOperation::Delete,
+                Some(tip),
+                None,
+            )
+            .await;
             Ok(())
         } else {
             Err(RepositoryErrorKind::CannotDeleteMain.into())
         }
     }
 
+    /// Expire the snapshots older than `older_than`, see [`expire`](crate::ops::gc::expire)
+    ///
+    /// Expired branches whose policy forbids deleting them are kept.
+    #[instrument(skip(self))]
+    pub async fn expire_snapshots(
+        &self,
+        older_than: DateTime<Utc>,
+        expired_branches: ExpiredRefAction,
+        expired_tags: ExpiredRefAction,
+    ) -> GCResult<ExpireResult> {
+        let keep_branch = |branch: &str| {
+            self.config.branch_policy(branch).is_some_and(|policy| policy.forbid_delete())
+        };
+        expire_keeping_branches(
+            self.storage.as_ref(),
+            &self.storage_settings,
+            Arc::clone(&self.asset_manager),
+            older_than,
+            expired_branches,
+            expired_tags,
+            &keep_branch,
+        )
+        .await
+    }
+
     /// Delete a tag from the repository.
     /// This will remove the tag reference. It will not remove the
     /// chunks or snapshots associated with the tag.
@@ -594,7 +814,16 @@ impl Repository {
             )
             .into());
         }
-        Ok(delete_tag(self.storage.as_ref(), &self.storage_settings, tag).await?)
+        let snapshot = self.lookup_tag(tag).await?;
+        delete_tag(self.storage.as_ref(), &self.storage_settings, tag).await?;
+        self.record_reflog(
+            Ref::Tag(tag.to_string()),
+            RefOperation::Delete,
+            Some(snapshot),
+            None,
+        )
+        .await;
+        Ok(())
     }
 
     /// Create a new tag in the repository at the given snapshot id
@@ -603,6 +832,27 @@ impl Repository {
         &self,
         tag_name: &str,
         snapshot_id: &SnapshotId,
+    ) -> RepositoryResult<()> {
+        self.write_tag(tag_name, snapshot_id, None).await
+    }
+
+    /// Create a new tag in the repository at the given snapshot id, storing a message
+    /// and metadata with it
+    #[instrument(skip(self, annotation))]
+    pub async fn create_annotated_tag(
+        &self,
+        tag_name: &str,
+        snapshot_id: &SnapshotId,
+        annotation: TagAnnotation,
+    ) -> RepositoryResult<()> {
+        self.write_tag(tag_name, snapshot_id, Some(annotation)).await
+    }
+
+    async fn write_tag(
+        &self,
+        tag_name: &str,
+        snapshot_id: &SnapshotId,
+        annotation: Option<TagAnnotation>,
     ) -> RepositoryResult<()> {
         if !self.storage.can_write() {
             return Err(RepositoryErrorKind::ReadonlyStorage(
@@ -617,13 +867,21 @@ impl Repository {
         )
         .await?;
 
-        create_tag(
+        create_annotated_tag(
             self.storage.as_ref(),
             &self.storage_settings,
             tag_name,
             snapshot_id.clone(),
+            annotation,
         )
         .await?;
+        self.record_reflog(
+            Ref::Tag(tag_name.to_string()),
+            RefOperation::Create,
+            None,
+            Some(snapshot_id.clone()),
+        )
+        .await;
         Ok(())
     }
 
@@ -634,6 +892,28 @@ impl Repository {
         Ok(tags)
     }
 
+    /// List at most `limit` tags whose name starts with `prefix`.
+    ///
+    /// Names are sorted as in [`Repository::list_branches_page`]. To get the next page,
+    /// pass the last name returned as `start_after`.
+    #[instrument(skip(self))]
+    pub async fn list_tags_page(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> RepositoryResult<Vec<String>> {
+        let tags = list_tags_page(
+            self.storage.as_ref(),
+            &self.storage_settings,
+            prefix,
+            start_after,
+            limit,
+        )
+        .await?;
+        Ok(tags)
+    }
+
     #[instrument(skip(self))]
     pub async fn lookup_tag(&self, tag: &str) -> RepositoryResult<SnapshotId> {
         let ref_data =
@@ -641,6 +921,35 @@ impl Repository {
         Ok(ref_data.snapshot)
     }
 
+    /// Get the snapshot id of a tag, together with its annotation if it has one
+    #[instrument(skip(self))]
+    pub async fn lookup_tag_info(&self, tag: &str) -> RepositoryResult<TagInfo> {
+        let ref_data =
+            fetch_tag(self.storage.as_ref(), &self.storage_settings, tag).await?;
+        Ok(TagInfo {
+            name: tag.to_string(),
+            snapshot: ref_data.snapshot,
+            annotation: ref_data.annotation,
+        })
+    }
+
+    /// List all tags in the repository with their annotations, sorted by name.
+    #[instrument(skip(self))]
+    pub async fn list_tags_info(&self) -> RepositoryResult<Vec<TagInfo>> {
+        // tags are fetched a few at a time, repositories can have many
+        let concurrency = self
+            .storage_settings
+            .concurrency()
+            .max_concurrent_requests_for_object()
+            .get() as usize;
+        let tags = self.list_tags().await?;
+        futures::stream::iter(tags.iter())
+            .map(|tag| self.lookup_tag_info(tag.as_str()))
+            .buffered(concurrency)
+            .try_collect()
+            .await
+    }
+
     #[instrument(skip(self))]
     pub async fn resolve_version(
         &self,
@@ -779,7 +1088,7 @@ impl Repository {
         let ref_data =
             fetch_branch_tip(self.storage.as_ref(), &self.storage_settings, branch)
                 .await?;
-        let session = Session::create_writable_session(
+        let mut session = Session::create_writable_session(
             self.config.clone(),
             self.storage_settings.clone(),
             self.storage.clone(),
@@ -788,13 +1097,62 @@ impl Repository {
             branch.to_string(),
             ref_data.snapshot.clone(),
             self.default_commit_metadata.clone(),
-        );
+        )
+        .with_commit_validators(self.commit_validators.clone())
+        .require_commit_validators(self.commit_validator_names.clone());
+        session.acquire_lease().await?;
 
         self.preload_manifests(ref_data.snapshot);
 
         Ok(session)
     }
 
+    /// Names of the sessions saved with [`Session::save`], sorted
+    #[instrument(skip(self))]
+    pub async fn saved_sessions(&self) -> RepositoryResult<Vec<String>> {
+        list_saved_sessions(self.storage.as_ref(), &self.storage_settings).await
+    }
+
+    /// Continue working on a session saved with [`Session::save`]
+    ///
+    /// The session keeps its name, committing it deletes the saved state.
+    #[instrument(skip(self))]
+    pub async fn resume_session(&self, name: &str) -> RepositoryResult<Session> {
+        if !self.storage.can_write() {
+            return Err(RepositoryErrorKind::ReadonlyStorage(
+                "Cannot resume session".to_string(),
+            )
+            .into());
+        }
+        let saved =
+            SavedSession::fetch(self.storage.as_ref(), &self.storage_settings, name)
+                .await?;
+        let mut session = Session::create_writable_session(
+            self.config.clone(),
+            self.storage_settings.clone(),
+            self.storage.clone(),
+            Arc::clone(&self.asset_manager),
+            self.virtual_resolver.clone(),
+            saved.branch_name.clone(),
+            saved.snapshot_id.clone(),
+            self.default_commit_metadata.clone(),
+        )
+        .with_commit_validators(self.commit_validators.clone())
+        .require_commit_validators(self.commit_validator_names.clone());
+        self.preload_manifests(saved.snapshot_id.clone());
+        session.resume(name, saved).await?;
+        session.acquire_lease().await?;
+        Ok(session)
+    }
+
+    /// Delete the state of a session saved with [`Session::save`]
+    ///
+    /// The chunks written by the session are no longer protected from garbage collection.
+    #[instrument(skip(self))]
+    pub async fn discard_saved_session(&self, name: &str) -> RepositoryResult<()> {
+        delete_saved_session(self.storage.as_ref(), &self.storage_settings, name).await
+    }
+
     #[instrument(skip(self))]
     fn preload_manifests(&self, snapshot_id: SnapshotId) {
         debug!("Preloading manifests");
@@ -929,6 +1287,46 @@ pub async fn raise_if_invalid_snapshot_id(
     Ok(())
 }
 
+/// Rejects moves of a branch to snapshots that don't descend from its tip
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FastForwardGuard<'a> {
+    asset_manager: &'a Arc<AssetManager>,
+}
+
+#[async_trait]
+impl BranchGuard for FastForwardGuard<'_> {
+    async fn check(
+        &self,
+        branch: &str,
+        tip: &SnapshotId,
+        new_snapshot: &SnapshotId,
+    ) -> RefResult<()> {
+        let is_fast_forward = async {
+            Arc::clone(self.asset_manager)
+                .snapshot_ancestry(new_snapshot)
+                .await?
+                .try_any(|info| ready(&info.id == tip))
+                .await
+        }
+        .await
+        .map_err(|err: RepositoryError| RefErrorKind::BranchPolicyCheck {
+            branch: branch.to_string(),
+            source: Box::new(err),
+        })?;
+        if !is_fast_forward {
+            return Err(RefErrorKind::BranchPolicyViolation {
+                branch: branch.to_string(),
+                violation: BranchPolicyViolation::NonFastForwardReset {
+                    tip: tip.clone(),
+                    target: new_snapshot.clone(),
+                },
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::panic, clippy::unwrap_used, clippy::expect_used)]
 mod tests {
@@ -940,10 +1338,12 @@ mod tests {
     use crate::{
         Repository, Storage,
         config::{
-            CachingConfig, ManifestConfig, ManifestPreloadConfig, RepositoryConfig,
+            BranchPolicy, CachingConfig, ManifestConfig, ManifestPreloadConfig,
+            RepositoryConfig,
         },
         format::{ChunkIndices, manifest::ChunkPayload, snapshot::ArrayShape},
         new_local_filesystem_storage,
+        session::SessionError,
         storage::new_in_memory_storage,
     };
 
@@ -1094,6 +1494,265 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_branch_policies() -> Result<(), Box<dyn Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let mut config = RepositoryConfig::default();
+        config.set_branch_policy(
+            "prod",
+            BranchPolicy {
+                forbid_delete: Some(true),
+                forbid_non_fast_forward: Some(true),
+                required_metadata_keys: Some(vec!["author".to_string()]),
+            },
+        );
+        let repo = Repository::create(Some(config), Arc::clone(&storage), HashMap::new())
+            .await?;
+
+        let initial_snapshot = repo.lookup_branch("main").await?;
+        repo.create_branch("prod", &initial_snapshot).await?;
+
+        // commits without the required metadata are rejected
+        let mut session = repo.writable_session("prod").await?;
+        session.add_group(Path::root(), Bytes::copy_from_slice(b"")).await?;
+        assert!(matches!(
+            session.commit("no author", None).await,
+            Err(SessionError {
+                kind: SessionErrorKind::RepositoryError(
+                    RepositoryErrorKind::BranchPolicyViolation {
+                        violation: BranchPolicyViolation::MissingCommitMetadata(_),
+                        ..
+                    }
+                ),
+                ..
+            })
+        ));
+        let props: SnapshotProperties =
+            [("author".to_string(), "jane".into())].into_iter().collect();
+        let new_snapshot = session.commit("with author", Some(props)).await?;
+
+        // moving the branch backwards is not a fast-forward
+        assert!(matches!(
+            repo.reset_branch("prod", &initial_snapshot).await,
+            Err(RepositoryError {
+                kind: RepositoryErrorKind::BranchPolicyViolation {
+                    violation: BranchPolicyViolation::NonFastForwardReset { .. },
+                    ..
+                },
+                ..
+            })
+        ));
+        assert_eq!(repo.lookup_branch("prod").await?, new_snapshot);
+
+        // resetting to the same snapshot is trivially a fast-forward
+        repo.reset_branch("prod", &new_snapshot).await?;
+
+        assert!(matches!(
+            repo.delete_branch("prod").await,
+            Err(RepositoryError {
+                kind: RepositoryErrorKind::BranchPolicyViolation {
+                    violation: BranchPolicyViolation::DeleteForbidden,
+                    ..
+                },
+                ..
+            })
+        ));
+
+        // unprotected branches are not affected
+        repo.create_branch("scratch", &new_snapshot).await?;
+        let mut session = repo.writable_session("scratch").await?;
+        session.add_group("/a".try_into().unwrap(), Bytes::new()).await?;
+        let no_author = session.commit("no author", None).await?;
+
+        // the fast-forward policy is enforced by the branch update itself
+        let guard = repo.branch_guard("prod");
+        assert!(matches!(
+            update_branch(
+                storage.as_ref(),
+                &repo.storage_settings,
+                "prod",
+                initial_snapshot.clone(),
+                Some(&new_snapshot),
+                guard.as_ref().map(|guard| guard as &dyn BranchGuard),
+            )
+            .await,
+            Err(RefError {
+                kind: RefErrorKind::BranchPolicyViolation {
+                    violation: BranchPolicyViolation::NonFastForwardReset { .. },
+                    ..
+                },
+                ..
+            })
+        ));
+        assert_eq!(repo.lookup_branch("prod").await?, new_snapshot);
+
+        // resets to a child of the tip are fast-forwards, commit metadata is only checked
+        // on commit
+        repo.reset_branch("prod", &no_author).await?;
+        assert_eq!(repo.lookup_branch("prod").await?, no_author);
+
+        repo.reset_branch("scratch", &initial_snapshot).await?;
+        repo.delete_branch("scratch").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reflog() -> Result<(), Box<dyn Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let mut repo =
+            Repository::create(None, Arc::clone(&storage), HashMap::new()).await?;
+        repo.set_default_commit_metadata(SnapshotProperties::from_iter([(
+            "user".to_string(),
+            "jane".into(),
+        )]));
+
+        let initial_snapshot = repo.lookup_branch("main").await?;
+        let mut session = repo.writable_session("main").await?;
+        session.add_group(Path::root(), Bytes::copy_from_slice(b"")).await?;
+        let new_snapshot = session.commit("first", None).await?;
+
+        repo.reset_branch("main", &initial_snapshot).await?;
+        repo.create_branch("feature", &new_snapshot).await?;
+        repo.delete_branch("feature").await?;
+        repo.create_tag("v1", &new_snapshot).await?;
+
+        let log = repo.reflog(&Ref::Branch("main".to_string()), None).await?;
+        let summary: Vec<_> = log
+            .iter()
+            .map(|e| (e.operation, e.old_snapshot.clone(), e.new_snapshot.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    RefOperation::Reset,
+                    Some(new_snapshot.clone()),
+                    Some(initial_snapshot.clone())
+                ),
+                (
+                    RefOperation::Commit,
+                    Some(initial_snapshot.clone()),
+                    Some(new_snapshot.clone())
+                ),
+                (RefOperation::Create, None, Some(initial_snapshot.clone())),
+            ]
+        );
+        assert_eq!(log[0].user, Some("jane".to_string()));
+        // the repository creation doesn't have a user
+        assert_eq!(log[2].user, None);
+
+        // the reflog survives branch deletion, allowing recovery
+        let log = repo.reflog(&Ref::Branch("feature".to_string()), None).await?;
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].operation, RefOperation::Delete);
+        assert_eq!(log[0].old_snapshot, Some(new_snapshot.clone()));
+        assert!(!repo.list_branches().await?.contains("feature"));
+
+        let log = repo.reflog(&Ref::Tag("v1".to_string()), None).await?;
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].operation, RefOperation::Create);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_annotated_tags() -> Result<(), Box<dyn Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let repo = Repository::create(None, Arc::clone(&storage), HashMap::new()).await?;
+        let snapshot = repo.lookup_branch("main").await?;
+
+        let annotation = TagAnnotation::new(
+            "First release",
+            Some("jane".to_string()),
+            SnapshotProperties::from_iter([("doi".to_string(), "10.1234/abcd".into())]),
+        );
+        repo.create_annotated_tag("v1", &snapshot, annotation.clone()).await?;
+        repo.create_tag("v0", &snapshot).await?;
+
+        // annotated tags behave like any other tag
+        assert_eq!(repo.lookup_tag("v1").await?, snapshot);
+        assert!(
+            repo.create_annotated_tag("v1", &snapshot, annotation.clone()).await.is_err()
+        );
+
+        let info = repo.lookup_tag_info("v1").await?;
+        assert_eq!(
+            info,
+            TagInfo {
+                name: "v1".to_string(),
+                snapshot: snapshot.clone(),
+                annotation: Some(annotation.clone())
+            }
+        );
+        assert_eq!(repo.lookup_tag_info("v0").await?.annotation, None);
+
+        let all = repo.list_tags_info().await?;
+        assert_eq!(
+            all.iter()
+                .map(|t| (t.name.as_str(), t.annotation.is_some()))
+                .collect::<Vec<_>>(),
+            vec![("v0", false), ("v1", true)]
+        );
+
+        repo.delete_tag("v1").await?;
+        assert!(repo.lookup_tag_info("v1").await.is_err());
+        assert_eq!(repo.list_tags_info().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_as_of() -> Result<(), Box<dyn Error>> {
+        let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+        let repo = Repository::create(None, Arc::clone(&storage), HashMap::new()).await?;
+
+        let mut snapshots = vec![repo.lookup_branch("main").await?];
+        for i in 0..3 {
+            let mut session = repo.writable_session("main").await?;
+            let path: Path = format!("/group{i}").try_into().unwrap();
+            session.add_group(path, Bytes::copy_from_slice(b"")).await?;
+            snapshots.push(session.commit("commit", None).await?);
+        }
+
+        let mut times = Vec::new();
+        for snap in snapshots.iter() {
+            times.push(repo.lookup_snapshot(snap).await?.flushed_at);
+        }
+
+        let as_of = |at| VersionInfo::AsOf { branch: "main".to_string(), at };
+        for (snap, time) in snapshots.iter().zip(times.iter()) {
+            // the exact commit time resolves to the commit itself
+            assert_eq!(&repo.resolve_version(&as_of(*time)).await?, snap);
+            // and a time right after it too
+            let after = *time + chrono::TimeDelta::microseconds(1);
+            assert_eq!(&repo.resolve_version(&as_of(after)).await?, snap);
+        }
+
+        // far in the future we get the tip
+        let future = Utc::now() + chrono::TimeDelta::days(1);
+        assert_eq!(
+            &repo.resolve_version(&as_of(future)).await?,
+            snapshots.last().unwrap()
+        );
+
+        // before the repository existed there is nothing to resolve
+        let before = times[0] - chrono::TimeDelta::seconds(1);
+        assert!(matches!(
+            repo.resolve_version(&as_of(before)).await,
+            Err(RepositoryError {
+                kind: RepositoryErrorKind::InvalidAsOfSpec { .. },
+                ..
+            })
+        ));
+
+        // a session can be opened at a point in time
+        let session = repo.readonly_session(&as_of(times[1])).await?;
+        assert_eq!(session.snapshot_id(), &snapshots[1]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_manifest_preload_default_condition() {
         let condition =
@@ -1,19 +1,22 @@
 use std::{
     cmp::min,
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     convert::Infallible,
     future::{Future, ready},
     ops::Range,
+    path::PathBuf,
     pin::Pin,
     sync::Arc,
+    time::Duration,
 };
 
 use async_stream::try_stream;
 use bytes::Bytes;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use err_into::ErrorInto;
 use futures::{FutureExt, Stream, StreamExt, TryStreamExt, future::Either, stream};
 use itertools::Itertools as _;
+use rand::distr::{Alphanumeric, SampleString as _};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::task::JoinError;
@@ -22,8 +25,10 @@ use tracing::{Instrument, debug, info, instrument, trace, warn};
 use crate::{
     RepositoryConfig, Storage, StorageError,
     asset_manager::AssetManager,
-    change_set::{ArrayData, ChangeSet},
-    conflicts::{Conflict, ConflictResolution, ConflictSolver},
+    change_set::{ArrayData, ChangeSet, ChangeSetOverlap, CheckpointedArray},
+    conflicts::{
+        Conflict, ConflictResolution, ConflictSolver, detector::ConflictDetector,
+    },
     error::ICError,
     format::{
         ByteRange, ChunkIndices, ChunkOffset, IcechunkFormatError,
@@ -39,12 +44,25 @@ use crate::{
         },
         transaction_log::{Diff, DiffBuilder, TransactionLog},
     },
-    refs::{RefError, RefErrorKind, fetch_branch_tip, update_branch},
-    repository::{RepositoryError, RepositoryErrorKind},
+    refs::{
+        Ref, RefError, RefErrorKind, RefOperation, ReflogEntry, fetch_branch_tip,
+        record_reflog, update_branch,
+    },
+    repository::{BranchPolicyViolation, RepositoryError, RepositoryErrorKind},
     storage::{self, StorageErrorKind},
+    validation::{CommitValidator, ProposedCommit, ValidationFailure, validate_commit},
     virtual_chunks::{VirtualChunkContainer, VirtualChunkResolver},
 };
 
+pub mod lease;
+pub mod saved;
+
+use lease::{
+    LeaseError, LeaseErrorKind, LeaseResult, SessionLease, delete_lease, write_lease,
+};
+pub(crate) use saved::SavedSession;
+use saved::{SavedSessionRef, delete_saved_session, write_saved_session};
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum SessionErrorKind {
@@ -58,6 +76,8 @@ pub enum SessionErrorKind {
     Ref(RefErrorKind),
     #[error(transparent)]
     VirtualReferenceError(VirtualReferenceErrorKind),
+    #[error(transparent)]
+    Lease(LeaseErrorKind),
 
     #[error("Read only sessions cannot modify the repository")]
     ReadOnlySession,
@@ -106,6 +126,20 @@ pub enum SessionErrorKind {
     InvalidIndex { coords: ChunkIndices, path: Path },
     #[error("`to` snapshot ancestry doesn't include `from`")]
     BadSnapshotChainForDiff,
+    #[error(
+        "cannot merge a session based on snapshot `{other}` into a session based on `{ours}`"
+    )]
+    MergeBaseMismatch { ours: SnapshotId, other: SnapshotId },
+    #[error("cannot merge sessions with overlapping writes: {0:?}")]
+    OverlappingWrites(Vec<OverlappingWrite>),
+    #[error("error writing or reading chunk references spilled to disk")]
+    SpillError(#[from] std::io::Error),
+    #[error("commit rejected by validators: {}", .0.iter().join("; "))]
+    CommitRejected(Vec<ValidationFailure>),
+    #[error(
+        "commit validators {0:?} are not attached to the session, attach them again after deserializing it"
+    )]
+    MissingCommitValidators(Vec<String>),
 }
 
 pub type SessionError = ICError<SessionErrorKind>;
@@ -139,6 +173,12 @@ impl From<RefError> for SessionError {
     }
 }
 
+impl From<LeaseError> for SessionError {
+    fn from(value: LeaseError) -> Self {
+        Self::with_context(SessionErrorKind::Lease(value.kind), value.context)
+    }
+}
+
 impl From<IcechunkFormatError> for SessionError {
     fn from(value: IcechunkFormatError) -> Self {
         Self::with_context(SessionErrorKind::FormatError(value.kind), value.context)
@@ -156,6 +196,70 @@ impl From<VirtualReferenceError> for SessionError {
 
 pub type SessionResult<T> = Result<T, SessionError>;
 
+/// How [`Session::commit_rebasing`] retries commits that conflict with the branch tip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebaseRetryPolicy {
+    /// Maximum number of commit attempts, including the first one
+    pub max_attempts: u16,
+    /// Upper bound for the wait before the first retry, it doubles on every attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the wait between any two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RebaseRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RebaseRetryPolicy {
+    /// Random wait before retrying after `failed_attempts` attempts.
+    ///
+    /// Using a random value in the full range spreads concurrent writers apart,
+    /// making it less likely they conflict again.
+    pub fn backoff(&self, failed_attempts: u16) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(31) as u32;
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff);
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// The outcome of a successful [`Session::commit_rebasing`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebasingCommitResult {
+    pub snapshot_id: SnapshotId,
+    /// How many times the session had to be rebased before the commit succeeded
+    pub rebase_count: u16,
+}
+
+/// A change two sessions being merged made differently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlappingWrite {
+    /// The node was created, updated or deleted in both sessions
+    Node(Path),
+    /// The same chunks were written in both sessions
+    Chunks { path: Path, coords: Vec<ChunkIndices> },
+}
+
+/// What rebasing over one commit would do, as reported by [`Session::preview_rebase`]
+#[derive(Debug)]
+pub struct RebasePreviewStep {
+    /// The commit the session changes would be rebased over
+    pub snapshot_id: SnapshotId,
+    /// All conflicts between the session changes and the commit
+    pub conflicts: Vec<Conflict>,
+    /// The outcome of the conflict solver, including the patched changes if it succeeds
+    pub resolution: ConflictResolution,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Session {
     config: RepositoryConfig,
@@ -167,6 +271,28 @@ pub struct Session {
     snapshot_id: SnapshotId,
     change_set: ChangeSet,
     default_commit_metadata: SnapshotProperties,
+    // true if the session was rebased since its last commit, used to annotate the reflog
+    #[serde(default)]
+    rebased: bool,
+    // name the session was saved with, its saved state is deleted on commit
+    #[serde(default)]
+    saved_as: Option<String>,
+    // lease protecting the objects written by the session from garbage collection, with its id
+    #[serde(default)]
+    lease: Option<(String, SessionLease)>,
+    // validators are code, they are not preserved when the session is serialized, only their
+    // names are, commits fail until validators with those names are attached again
+    #[serde(skip)]
+    commit_validators: Vec<Arc<dyn CommitValidator>>,
+    #[serde(default)]
+    required_commit_validators: BTreeSet<String>,
+    // the chunk writer returns inline payloads instead of uploading the data, used for
+    // previews
+    #[serde(skip)]
+    discard_chunk_writes: bool,
+    // local to the process, so it's not preserved when the session is serialized
+    #[serde(skip)]
+    spill_directory: Option<PathBuf>,
 }
 
 impl Session {
@@ -188,6 +314,13 @@ impl Session {
             snapshot_id,
             change_set: ChangeSet::default(),
             default_commit_metadata: SnapshotProperties::default(),
+            rebased: false,
+            saved_as: None,
+            lease: None,
+            commit_validators: Vec::new(),
+            required_commit_validators: BTreeSet::new(),
+            discard_chunk_writes: false,
+            spill_directory: None,
         }
     }
 
@@ -212,12 +345,65 @@ impl Session {
             snapshot_id,
             change_set: ChangeSet::default(),
             default_commit_metadata,
+            rebased: false,
+            saved_as: None,
+            lease: None,
+            commit_validators: Vec::new(),
+            required_commit_validators: BTreeSet::new(),
+            discard_chunk_writes: false,
+            spill_directory: None,
         }
     }
 
+    /// Checks to run before committing, see [`crate::validation`]
+    pub fn with_commit_validators(
+        mut self,
+        validators: Vec<Arc<dyn CommitValidator>>,
+    ) -> Self {
+        self.attach_commit_validators(validators);
+        self
+    }
+
+    /// Set the checks to run before committing, see [`crate::validation`]
+    ///
+    /// Validators are not serialized with the session, only their names. A deserialized
+    /// session refuses to commit until validators with the same names are attached again,
+    /// for example with [`Repository::session_from_bytes`](crate::Repository::session_from_bytes).
+    pub fn attach_commit_validators(
+        &mut self,
+        validators: Vec<Arc<dyn CommitValidator>>,
+    ) {
+        self.required_commit_validators
+            .extend(validators.iter().map(|validator| validator.name().to_string()));
+        self.commit_validators = validators;
+    }
+
+    /// Refuse to commit unless validators with these names are attached
+    pub(crate) fn require_commit_validators(
+        mut self,
+        names: impl IntoIterator<Item = String>,
+    ) -> Self {
+        self.required_commit_validators.extend(names);
+        self
+    }
+
+    /// Names of the validators the session needs that are not attached
+    pub fn missing_commit_validators(&self) -> Vec<String> {
+        let attached: HashSet<&str> =
+            self.commit_validators.iter().map(|validator| validator.name()).collect();
+        self.required_commit_validators
+            .iter()
+            .filter(|name| !attached.contains(name.as_str()))
+            .cloned()
+            .collect()
+    }
+
     #[instrument(skip(bytes))]
     pub fn from_bytes(bytes: Vec<u8>) -> SessionResult<Self> {
-        rmp_serde::from_slice(&bytes).err_into()
+        let mut session: Self = rmp_serde::from_slice(&bytes)?;
+        // spilled changes are serialized inline, they go back to disk
+        session.spill_chunk_refs_if_needed_blocking()?;
+        Ok(session)
     }
 
     #[instrument(skip(self))]
@@ -259,7 +445,7 @@ impl Session {
     /// Compute an overview of the current session changes
     pub async fn status(&self) -> SessionResult<Diff> {
         // it doesn't really matter what Id we give to the tx log, it's not going to be persisted
-        let tx_log = TransactionLog::new(&SnapshotId::random(), &self.change_set);
+        let tx_log = TransactionLog::new(&SnapshotId::random(), &self.change_set)?;
         let from_session = Self::create_readonly_session(
             self.config().clone(),
             self.storage_settings.as_ref().clone(),
@@ -455,7 +641,8 @@ impl Session {
         if let NodeData::Array { shape, .. } = node.node_data {
             if shape.valid_chunk_coord(&coord) {
                 self.change_set.set_chunk_ref(node.id, coord, data);
-                Ok(())
+                self.spill_chunk_refs_if_needed().await?;
+                self.renew_lease_if_needed().await
             } else {
                 Err(SessionErrorKind::InvalidIndex {
                     coords: coord,
@@ -549,9 +736,7 @@ impl Session {
             .into()),
             NodeData::Array { manifests, .. } => {
                 // check the chunks modified in this session first
-                // TODO: I hate rust forces me to clone to search in a hashmap. How to do better?
-                let session_chunk =
-                    self.change_set.get_chunk_ref(&node.id, coords).cloned();
+                let session_chunk = self.change_set.get_chunk_ref(&node.id, coords)?;
 
                 // If session_chunk is not None we have to return it, because is the update the
                 // user made in the current session
@@ -600,48 +785,53 @@ impl Session {
         byte_range: &ByteRange,
     ) -> SessionResult<Option<Pin<Box<dyn Future<Output = SessionResult<Bytes>> + Send>>>>
     {
-        match self.get_chunk_ref(path, coords).await? {
-            Some(ChunkPayload::Ref(ChunkRef { id, offset, length })) => {
+        Ok(self
+            .get_chunk_ref(path, coords)
+            .await?
+            .map(|payload| self.chunk_payload_reader(payload, byte_range)))
+    }
+
+    /// Get a future that reads the bytes pointed to by a [`ChunkPayload`]
+    ///
+    /// This is the payload-level counterpart of [`Session::get_chunk_reader`], useful when the
+    /// payload doesn't come from this session, for example, while solving conflicts.
+    #[allow(clippy::type_complexity)]
+    pub fn chunk_payload_reader(
+        &self,
+        payload: ChunkPayload,
+        byte_range: &ByteRange,
+    ) -> Pin<Box<dyn Future<Output = SessionResult<Bytes>> + Send>> {
+        match payload {
+            ChunkPayload::Ref(ChunkRef { id, offset, length }) => {
                 let byte_range = byte_range.clone();
                 let asset_manager = Arc::clone(&self.asset_manager);
                 let byte_range = construct_valid_byte_range(&byte_range, offset, length);
-                Ok(Some(
-                    async move {
-                        // TODO: we don't have a way to distinguish if we want to pass a range or not
-                        asset_manager
-                            .fetch_chunk(&id, &byte_range)
-                            .await
-                            .map_err(|e| e.into())
-                    }
-                    .boxed(),
-                ))
-            }
-            Some(ChunkPayload::Inline(bytes)) => {
-                Ok(Some(ready(Ok(byte_range.slice(bytes))).boxed()))
+                async move {
+                    // TODO: we don't have a way to distinguish if we want to pass a range or not
+                    asset_manager
+                        .fetch_chunk(&id, &byte_range)
+                        .await
+                        .map_err(|e| e.into())
+                }
+                .boxed()
             }
-            Some(ChunkPayload::Virtual(VirtualChunkRef {
+            ChunkPayload::Inline(bytes) => ready(Ok(byte_range.slice(bytes))).boxed(),
+            ChunkPayload::Virtual(VirtualChunkRef {
                 location,
                 offset,
                 length,
                 checksum,
-            })) => {
+            }) => {
                 let byte_range = construct_valid_byte_range(byte_range, offset, length);
                 let resolver = Arc::clone(&self.virtual_resolver);
-                Ok(Some(
-                    async move {
-                        resolver
-                            .fetch_chunk(
-                                location.0.as_str(),
-                                &byte_range,
-                                checksum.as_ref(),
-                            )
-                            .await
-                            .map_err(|e| e.into())
-                    }
-                    .boxed(),
-                ))
+                async move {
+                    resolver
+                        .fetch_chunk(location.0.as_str(), &byte_range, checksum.as_ref())
+                        .await
+                        .map_err(|e| e.into())
+                }
+                .boxed()
             }
-            None => Ok(None),
         }
     }
 
@@ -668,9 +858,11 @@ impl Session {
     + use<> {
         let threshold = self.config().inline_chunk_threshold_bytes() as usize;
         let asset_manager = Arc::clone(&self.asset_manager);
+        let discard = self.discard_chunk_writes;
         move |data: Bytes| {
             async move {
-                let payload = if data.len() > threshold {
+                // discarded chunks are kept inline, so later steps of a preview can read them
+                let payload = if data.len() > threshold && !discard {
                     new_materialized_chunk(asset_manager.as_ref(), data).await?
                 } else {
                     new_inline_chunk(data)
@@ -705,6 +897,8 @@ impl Session {
         manifests: &[ManifestRef],
         coords: &ChunkIndices,
     ) -> SessionResult<Option<ChunkPayload>> {
+        let manifests =
+            self.change_set.checkpointed_manifests(&node).unwrap_or(manifests);
         // FIXME: use manifest extents
         for manifest in manifests {
             let manifest = self.fetch_manifest(&manifest.object_id).await?;
@@ -723,7 +917,13 @@ impl Session {
     }
 
     async fn fetch_manifest(&self, id: &ManifestId) -> SessionResult<Arc<Manifest>> {
-        fetch_manifest(id, self.snapshot_id(), self.asset_manager.as_ref()).await
+        fetch_manifest(
+            id,
+            self.snapshot_id(),
+            &self.change_set,
+            self.asset_manager.as_ref(),
+        )
+        .await
     }
 
     #[instrument(skip(self))]
@@ -758,10 +958,10 @@ impl Session {
 
         let res = try_stream! {
             let new_chunks = stream::iter(
-                self.change_set
-                    .new_array_chunk_iterator(&node.id, array_path)
-                    .map(|chunk_info| Ok::<ChunkIndices, SessionError>(chunk_info.coord)),
-            );
+                self.change_set.new_array_chunk_iterator(&node.id, array_path),
+            )
+            .map_ok(|chunk_info| chunk_info.coord)
+            .map_err(SessionError::from);
 
             for await maybe_coords in updated_chunks.chain(new_chunks) {
                 match maybe_coords {
@@ -791,16 +991,368 @@ impl Session {
         std::mem::take(&mut self.change_set)
     }
 
-    /// Merge a set of `ChangeSet`s into the repository without committing them
+    /// Create a child session to write from a different task, thread or process
+    ///
+    /// The child is based on the same snapshot and sees the nodes created, updated or deleted
+    /// in this session, but not its uncommitted chunk writes, so forking is cheap even for
+    /// large sessions. It can be serialized with [`Session::as_bytes`] and sent to a remote
+    /// writer. Once done, its changes are collected with [`Session::merge_session`].
+    #[instrument(skip(self))]
+    pub fn fork(&self) -> SessionResult<Session> {
+        if self.read_only() {
+            return Err(SessionErrorKind::ReadOnlySession.into());
+        }
+        Ok(Session {
+            config: self.config.clone(),
+            storage_settings: Arc::clone(&self.storage_settings),
+            storage: Arc::clone(&self.storage),
+            asset_manager: Arc::clone(&self.asset_manager),
+            virtual_resolver: Arc::clone(&self.virtual_resolver),
+            branch_name: self.branch_name.clone(),
+            snapshot_id: self.snapshot_id.clone(),
+            change_set: self.change_set.fork(),
+            default_commit_metadata: self.default_commit_metadata.clone(),
+            rebased: false,
+            saved_as: None,
+            // the parent lease already protects the objects of its forks, which are merged
+            // back into it, a fork releasing or renewing it would change the parent lease
+            lease: None,
+            commit_validators: self.commit_validators.clone(),
+            required_commit_validators: self.required_commit_validators.clone(),
+            discard_chunk_writes: self.discard_chunk_writes,
+            spill_directory: self.spill_directory.clone(),
+        })
+    }
+
+    /// Merge the changes of a session into this one without committing them
+    ///
+    /// Both sessions must be based on the same snapshot, usually `other` is a
+    /// [`fork`](Session::fork) of this session. The merge fails, without modifying this session,
+    /// if the sessions changed the same nodes or chunks to different values.
+    #[instrument(skip(self, other))]
+    pub async fn merge_session(&mut self, other: Session) -> SessionResult<()> {
+        if self.read_only() {
+            return Err(SessionErrorKind::ReadOnlySession.into());
+        }
+        if other.snapshot_id != self.snapshot_id {
+            return Err(SessionErrorKind::MergeBaseMismatch {
+                ours: self.snapshot_id.clone(),
+                other: other.snapshot_id,
+            }
+            .into());
+        }
+
+        // provisional manifests for the same array cannot be merged, we bring those changes
+        // back to memory, this also allows to check them for overlaps
+        let mut other_changes = other.change_set;
+        let touches = |change_set: &ChangeSet, node_id: &NodeId| {
+            change_set.has_chunk_changes(node_id)
+                || change_set.get_checkpoint(node_id).is_some()
+        };
+        let to_restore: Vec<_> = self
+            .change_set
+            .checkpointed_arrays()
+            .filter(|(node_id, _)| touches(&other_changes, node_id))
+            .chain(
+                other_changes
+                    .checkpointed_arrays()
+                    .filter(|(node_id, _)| touches(&self.change_set, node_id)),
+            )
+            .map(|(node_id, _)| node_id.clone())
+            .collect();
+        // our checkpoints are restored in a copy, a rejected merge must leave them in place
+        let ours = if to_restore.is_empty() {
+            None
+        } else {
+            let mut ours = self.change_set.clone();
+            restore_checkpoints(&self.asset_manager, &mut ours, &to_restore).await?;
+            Some(ours)
+        };
+        restore_checkpoints(&self.asset_manager, &mut other_changes, &to_restore).await?;
+
+        let overlap = ours
+            .as_ref()
+            .unwrap_or(&self.change_set)
+            .overlapping_writes(&other_changes)?;
+        if !overlap.is_empty() {
+            return Err(SessionErrorKind::OverlappingWrites(
+                self.overlapping_writes_paths(&other_changes, overlap).await?,
+            )
+            .into());
+        }
+
+        if let Some(ours) = ours {
+            self.change_set = ours;
+        }
+        self.change_set.merge(other_changes)?;
+        self.spill_chunk_refs_if_needed().await?;
+        Ok(())
+    }
+
+    /// Merge a `ChangeSet` into the repository without committing it
+    ///
+    /// Unlike [`Session::merge_session`], there are no checks, changes in `changes` take
+    /// precedence.
     #[instrument(skip(self, changes))]
     pub async fn merge(&mut self, changes: ChangeSet) -> SessionResult<()> {
         if self.read_only() {
             return Err(SessionErrorKind::ReadOnlySession.into());
         }
-        self.change_set.merge(changes);
+        self.change_set.merge(changes)?;
+        self.spill_chunk_refs_if_needed().await?;
+        Ok(())
+    }
+
+    /// Set the directory for the chunk references spilled to disk, see
+    /// [`SpillConfig`](crate::config::SpillConfig)
+    ///
+    /// If not set, the OS temporary directory is used.
+    pub fn set_spill_directory(&mut self, directory: Option<PathBuf>) {
+        self.spill_directory = directory;
+    }
+
+    /// The directory to spill to, if there are more modified chunk references in memory
+    /// than configured in [`SpillConfig`](crate::config::SpillConfig)
+    fn spill_directory_if_needed(&self) -> Option<PathBuf> {
+        let max = self.config.spill().max_in_memory_chunk_refs()?;
+        (self.change_set.in_memory_chunk_refs() as u64 > max)
+            .then(|| self.spill_directory.clone().unwrap_or_else(std::env::temp_dir))
+    }
+
+    /// Move the modified chunk references to disk if needed, blocking the current thread
+    fn spill_chunk_refs_if_needed_blocking(&mut self) -> std::io::Result<()> {
+        if let Some(directory) = self.spill_directory_if_needed() {
+            self.change_set.spill_chunk_refs(&directory)?;
+        }
+        Ok(())
+    }
+
+    /// Move the modified chunk references to disk if needed
+    ///
+    /// Sorting and writing the changes can take seconds, so it runs in the blocking pool
+    /// instead of stalling the async runtime.
+    async fn spill_chunk_refs_if_needed(&mut self) -> std::io::Result<()> {
+        if let Some(directory) = self.spill_directory_if_needed() {
+            let mut change_set = std::mem::take(&mut self.change_set);
+            let (change_set, res) = tokio::task::spawn_blocking(move || {
+                let res = change_set.spill_chunk_refs(&directory);
+                (change_set, res)
+            })
+            .await?;
+            self.change_set = change_set;
+            res?;
+        }
+        Ok(())
+    }
+
+    async fn overlapping_writes_paths(
+        &self,
+        other: &ChangeSet,
+        overlap: ChangeSetOverlap,
+    ) -> SessionResult<Vec<OverlappingWrite>> {
+        let mut paths: HashMap<NodeId, Path> = HashMap::new();
+        for node in self.list_nodes().await? {
+            let node = node?;
+            paths.insert(node.id, node.path);
+        }
+        for change_set in [&self.change_set, other] {
+            let new_nodes = change_set.new_nodes();
+            let deleted = change_set
+                .deleted_groups()
+                .chain(change_set.deleted_arrays())
+                .map(|(path, id)| (path, id));
+            for (path, id) in new_nodes.chain(deleted) {
+                paths.entry(id.clone()).or_insert_with(|| path.clone());
+            }
+        }
+        let mut res = Vec::with_capacity(overlap.nodes.len() + overlap.chunks.len());
+        for node_id in overlap.nodes {
+            match paths.get(&node_id).cloned() {
+                Some(path) => res.push(OverlappingWrite::Node(path)),
+                None => {
+                    return Err(SessionErrorKind::ConflictingPathNotFound(node_id).into());
+                }
+            }
+        }
+        for (node_id, coords) in overlap.chunks {
+            match paths.get(&node_id).cloned() {
+                Some(path) => res.push(OverlappingWrite::Chunks {
+                    path,
+                    coords: coords.into_iter().collect(),
+                }),
+                None => {
+                    return Err(SessionErrorKind::ConflictingPathNotFound(node_id).into());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Take a lease that protects the objects this session writes from garbage collection
+    ///
+    /// Does nothing unless [`RepositoryConfig::session_lease_seconds`] is set.
+    pub(crate) async fn acquire_lease(&mut self) -> LeaseResult<()> {
+        let id = Alphanumeric.sample_string(&mut rand::rng(), 16);
+        self.store_lease(id, Utc::now()).await
+    }
+
+    /// Extend the lease held by this session, if any, by the configured duration
+    ///
+    /// Sessions running for longer than [`RepositoryConfig::session_lease_seconds`] need to
+    /// renew their lease, otherwise garbage collection can delete the objects they wrote.
+    /// [`Session::checkpoint`], [`Session::save`] and [`Session::rebase`] renew it too, and
+    /// setting chunk references renews it once it is past half its lifetime.
+    #[instrument(skip(self))]
+    pub async fn renew_lease(&mut self) -> SessionResult<()> {
+        if let Some((id, lease)) = self.lease.clone() {
+            self.store_lease(id, lease.acquired_at).await?;
+        }
+        Ok(())
+    }
+
+    /// Renew the lease held by this session, if any, once it is past half its lifetime
+    async fn renew_lease_if_needed(&mut self) -> SessionResult<()> {
+        let (Some((_, lease)), Some(seconds)) =
+            (&self.lease, self.config.session_lease_seconds())
+        else {
+            return Ok(());
+        };
+        if lease.expires_at - Utc::now() < TimeDelta::seconds(seconds.into()) / 2 {
+            self.renew_lease().await?;
+        }
+        Ok(())
+    }
+
+    async fn store_lease(
+        &mut self,
+        id: String,
+        acquired_at: DateTime<Utc>,
+    ) -> LeaseResult<()> {
+        let (Some(branch), Some(seconds)) =
+            (&self.branch_name, self.config.session_lease_seconds())
+        else {
+            return Ok(());
+        };
+        let lease = SessionLease {
+            branch: branch.clone(),
+            snapshot_id: self.snapshot_id.clone(),
+            acquired_at,
+            expires_at: Utc::now() + TimeDelta::seconds(seconds.into()),
+        };
+        write_lease(self.storage.as_ref(), self.storage_settings.as_ref(), &id, &lease)
+            .await?;
+        self.lease = Some((id, lease));
         Ok(())
     }
 
+    /// Write the chunk references modified in this session to provisional manifests
+    ///
+    /// Long running sessions can checkpoint periodically to bound their memory use and the
+    /// work left for [`Session::commit`], that only needs to write the manifests for arrays
+    /// modified after the last checkpoint. The branch is not updated, provisional manifests
+    /// only become part of the repository once the session is committed.
+    #[instrument(skip(self))]
+    pub async fn checkpoint(&mut self) -> SessionResult<()> {
+        if self.read_only() {
+            return Err(SessionErrorKind::ReadOnlySession.into());
+        }
+
+        let nodes: Vec<_> = self
+            .list_nodes()
+            .await?
+            .filter_ok(|node| self.change_set.has_chunk_changes(&node.id))
+            .try_collect()?;
+
+        let mut flush_data = FlushProcess::new(
+            Arc::clone(&self.asset_manager),
+            &self.change_set,
+            &self.snapshot_id,
+        );
+        for node in nodes.iter() {
+            trace!(path=%node.path, "Checkpointing node");
+            flush_data.write_manifest_for_existing_node(node).await?;
+        }
+
+        let snapshot = self.asset_manager.fetch_snapshot(&self.snapshot_id).await?;
+        let FlushProcess { mut manifest_refs, manifest_files, .. } = flush_data;
+        for node in nodes {
+            // arrays with all their chunks deleted have no manifests
+            let manifests = manifest_refs.remove(&node.id).unwrap_or_default();
+            let files = manifest_files
+                .iter()
+                .filter(|info| manifests.iter().any(|mr| mr.object_id == info.id))
+                .cloned()
+                .collect();
+            // the manifests in the snapshot, only needed the first time the array is
+            // checkpointed, later the node already points to provisional manifests
+            let (base, base_files) = match &node.node_data {
+                NodeData::Array { manifests: base, .. }
+                    if self.change_set.get_checkpoint(&node.id).is_none() =>
+                {
+                    let base_files = base
+                        .iter()
+                        .map(|mr| {
+                            snapshot.get_manifest_file(&mr.object_id).ok_or_else(|| {
+                                IcechunkFormatError::from(
+                                    IcechunkFormatErrorKind::ManifestInfoNotFound {
+                                        manifest_id: mr.object_id.clone(),
+                                    },
+                                )
+                            })
+                        })
+                        .try_collect()?;
+                    (base.clone(), base_files)
+                }
+                _ => (Vec::new(), Vec::new()),
+            };
+            self.change_set
+                .checkpoint_array(&node.id, manifests, files, base, base_files)?;
+        }
+        self.renew_lease().await
+    }
+
+    /// Save the state of this session in the repository, under `name`
+    ///
+    /// The session can be resumed later, even from a different process, with
+    /// [`Repository::resume_session`](crate::Repository::resume_session). Saving again
+    /// replaces the previous state. The saved state is deleted when the session is committed,
+    /// until then, garbage collection keeps the chunks and manifests the session wrote.
+    #[instrument(skip(self))]
+    pub async fn save(&mut self, name: &str) -> SessionResult<()> {
+        let Some(branch_name) = &self.branch_name else {
+            return Err(SessionErrorKind::ReadOnlySession.into());
+        };
+        let state = SavedSessionRef {
+            branch_name,
+            snapshot_id: &self.snapshot_id,
+            saved_at: Utc::now(),
+            change_set: &self.change_set,
+        };
+        let bytes = Bytes::from(rmp_serde::to_vec(&state)?);
+        write_saved_session(
+            self.storage.as_ref(),
+            self.storage_settings.as_ref(),
+            name,
+            bytes,
+        )
+        .await?;
+        self.saved_as = Some(name.to_string());
+        self.renew_lease().await
+    }
+
+    /// Resume a session saved with [`Session::save`]
+    pub(crate) async fn resume(
+        &mut self,
+        name: &str,
+        saved: SavedSession,
+    ) -> std::io::Result<()> {
+        self.snapshot_id = saved.snapshot_id;
+        self.change_set = saved.change_set;
+        self.saved_as = Some(name.to_string());
+        // spilled changes are saved inline, they go back to disk
+        self.spill_chunk_refs_if_needed().await
+    }
+
     #[instrument(skip(self, properties))]
     pub async fn commit(
         &mut self,
@@ -820,6 +1372,40 @@ impl Session {
             })
             .unwrap_or(default_metadata);
 
+        // checked before writing anything, the branch update only checks resets
+        if let Some(policy) = self.config.branch_policy(branch_name) {
+            let missing = policy.missing_metadata_keys(&properties);
+            if !missing.is_empty() {
+                return Err(SessionErrorKind::RepositoryError(
+                    RepositoryErrorKind::BranchPolicyViolation {
+                        branch: branch_name.clone(),
+                        violation: BranchPolicyViolation::MissingCommitMetadata(missing),
+                    },
+                )
+                .into());
+            }
+        }
+
+        let missing = self.missing_commit_validators();
+        if !missing.is_empty() {
+            return Err(SessionErrorKind::MissingCommitValidators(missing).into());
+        }
+        if !self.commit_validators.is_empty() {
+            let parent = self.asset_manager.fetch_snapshot(&self.snapshot_id).await?;
+            let proposed = ProposedCommit {
+                branch: branch_name,
+                parent: parent.as_ref(),
+                change_set: &self.change_set,
+                message,
+                properties: &properties,
+            };
+            let failures = validate_commit(&self.commit_validators, &proposed)?;
+            if !failures.is_empty() {
+                return Err(SessionErrorKind::CommitRejected(failures).into());
+            }
+        }
+
+        let user_properties = properties.clone();
         let current = fetch_branch_tip(
             self.storage.as_ref(),
             self.storage_settings.as_ref(),
@@ -866,9 +1452,47 @@ impl Session {
             }
         }?;
 
+        let operation =
+            if self.rebased { RefOperation::Rebase } else { RefOperation::Commit };
+        record_reflog(
+            self.storage.as_ref(),
+            self.storage_settings.as_ref(),
+            &Ref::Branch(branch_name.clone()),
+            ReflogEntry::new(
+                operation,
+                Some(self.snapshot_id.clone()),
+                Some(id.clone()),
+                &user_properties,
+            ),
+        )
+        .await;
+
+        if let Some(name) = self.saved_as.take() {
+            // the commit already happened, a stale saved state only delays garbage collection
+            if let Err(err) = delete_saved_session(
+                self.storage.as_ref(),
+                self.storage_settings.as_ref(),
+                &name,
+            )
+            .await
+            {
+                tracing::error!(error = %err, name, "Cannot delete saved session");
+            }
+        }
+        if let Some((id, _)) = self.lease.take() {
+            // an unreleased lease only delays garbage collection until it expires
+            if let Err(err) =
+                delete_lease(self.storage.as_ref(), self.storage_settings.as_ref(), &id)
+                    .await
+            {
+                tracing::error!(error = %err, id, "Cannot release session lease");
+            }
+        }
+
         // if the commit was successful, we update the session to be
         // a read only session pointed at the new snapshot
         self.change_set = ChangeSet::default();
+        self.rebased = false;
         self.snapshot_id = id.clone();
         // Once committed, the session is now read only, which we control
         // by setting the branch_name to None (you can only write to a branch session)
@@ -877,6 +1501,43 @@ impl Session {
         Ok(id)
     }
 
+    /// Commit, rebasing with `solver` and retrying every time the branch tip moved.
+    ///
+    /// The commit is attempted at most `retry_policy.max_attempts` times, waiting a
+    /// random backoff between attempts. Errors other than a conflict with the branch tip,
+    /// including failed rebases, are returned immediately. When attempts are exhausted the
+    /// last [`SessionErrorKind::Conflict`] is returned and the session is left rebased on
+    /// the latest tip it saw.
+    #[instrument(skip(self, properties, solver))]
+    pub async fn commit_rebasing(
+        &mut self,
+        message: &str,
+        properties: Option<SnapshotProperties>,
+        solver: &dyn ConflictSolver,
+        retry_policy: &RebaseRetryPolicy,
+    ) -> SessionResult<RebasingCommitResult> {
+        let mut rebase_count = 0;
+        let mut attempts = 1;
+        loop {
+            match self.commit(message, properties.clone()).await {
+                Ok(snapshot_id) => {
+                    return Ok(RebasingCommitResult { snapshot_id, rebase_count });
+                }
+                Err(SessionError { kind: SessionErrorKind::Conflict { .. }, .. })
+                    if attempts < retry_policy.max_attempts =>
+                {
+                    let backoff = retry_policy.backoff(attempts);
+                    debug!(attempts, ?backoff, "Commit conflicted, rebasing");
+                    tokio::time::sleep(backoff).await;
+                    self.rebase(solver).await?;
+                    rebase_count += 1;
+                    attempts += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
     /// Detect and optionally fix conflicts between the current [`ChangeSet`] (or session) and
     /// the tip of the branch.
     ///
@@ -940,6 +1601,10 @@ impl Session {
     /// If at some point it finds a conflict it cannot recover from, `rebase` leaves the
     /// `Repository` in a consistent state, that would successfully commit on top
     /// of the latest successfully fast-forwarded commit.
+    ///
+    /// Each commit is solved against the repository as it was right after that commit, the
+    /// `previous_repo` passed to the solver. Later commits are not visible yet, so conflicts
+    /// are reported with the commit that introduced them.
     #[instrument(skip(self, solver))]
     pub async fn rebase(&mut self, solver: &dyn ConflictSolver) -> SessionResult<()> {
         let Some(branch_name) = &self.branch_name else {
@@ -947,14 +1612,8 @@ impl Session {
         };
 
         debug!("Rebase started");
-        let ref_data = fetch_branch_tip(
-            self.storage.as_ref(),
-            self.storage_settings.as_ref(),
-            branch_name,
-        )
-        .await?;
-
-        if ref_data.snapshot == self.snapshot_id {
+        let new_commits = self.commits_to_rebase(branch_name).await?;
+        if new_commits.is_empty() {
             // nothing to do, commit should work without rebasing
             warn!(
                 branch = &self.branch_name,
@@ -962,39 +1621,22 @@ impl Session {
             );
             Ok(())
         } else {
-            let current_snapshot =
-                self.asset_manager.fetch_snapshot(&ref_data.snapshot).await?;
-            let ancestry = Arc::clone(&self.asset_manager)
-                .snapshot_ancestry(&current_snapshot.id())
-                .await?
-                .map_ok(|meta| meta.id);
-            let new_commits =
-                stream::once(ready(Ok(ref_data.snapshot.clone())))
-                    .chain(ancestry.try_take_while(|snap_id| {
-                        ready(Ok(snap_id != &self.snapshot_id))
-                    }))
-                    .try_collect::<Vec<_>>()
-                    .await?;
-            trace!("Found {} commits to rebase", new_commits.len());
-
             // TODO: this clone is expensive
             // we currently need it to be able to process commits one by one without modifying the
             // changeset in case of failure
             // let mut changeset = self.change_set.clone();
 
-            // we need to reverse the iterator to process them in order of oldest first
-            for snap_id in new_commits.into_iter().rev() {
+            // conflicts are detected and solved on the changes in memory, checkpointed arrays
+            // the new commits didn't modify keep their provisional manifests
+            let touched = self.checkpoints_modified_by(&new_commits).await?;
+            restore_checkpoints(&self.asset_manager, &mut self.change_set, &touched)
+                .await?;
+
+            for snap_id in new_commits {
                 debug!("Rebasing snapshot {}", &snap_id);
                 let tx_log = self.asset_manager.fetch_transaction_log(&snap_id).await?;
-
-                let session = Self::create_readonly_session(
-                    self.config.clone(),
-                    self.storage_settings.as_ref().clone(),
-                    Arc::clone(&self.storage),
-                    Arc::clone(&self.asset_manager),
-                    Arc::clone(&self.virtual_resolver),
-                    ref_data.snapshot.clone(),
-                );
+                // the commit being rebased on, not the tip of the branch
+                let session = self.readonly_session_at(snap_id.clone());
 
                 let change_set = std::mem::take(&mut self.change_set);
                 // TODO: this should probably execute in a worker thread
@@ -1003,10 +1645,13 @@ impl Session {
                         trace!("Snapshot rebased");
                         self.change_set = patched_changeset;
                         self.snapshot_id = snap_id;
+                        self.rebased = true;
                     }
                     ConflictResolution::Unsolvable { reason, unmodified } => {
                         warn!("Snapshot cannot be rebased. Aborting rebase.");
                         self.change_set = unmodified;
+                        // the lease follows the commits already rebased over
+                        self.renew_lease().await?;
                         return Err(SessionErrorKind::RebaseFailed {
                             snapshot: snap_id,
                             conflicts: reason,
@@ -1016,25 +1661,217 @@ impl Session {
                 }
             }
             debug!("Rebase done");
-            Ok(())
+            // the lease records the new base snapshot
+            self.renew_lease().await
         }
     }
-}
 
-/// Warning: The presence of a single error may mean multiple missing items
-async fn updated_chunk_iterator<'a>(
-    asset_manager: &'a AssetManager,
-    change_set: &'a ChangeSet,
-    snapshot_id: &'a SnapshotId,
-) -> SessionResult<impl Stream<Item = SessionResult<(Path, ChunkInfo)>> + 'a> {
-    let snapshot = asset_manager.fetch_snapshot(snapshot_id).await?;
-    let nodes = futures::stream::iter(snapshot.iter_arc());
-    let res = nodes.and_then(move |node| async move {
-        Ok(updated_node_chunks_iterator(asset_manager, change_set, snapshot_id, node)
-            .await)
-    });
-    Ok(res.try_flatten())
-}
+    /// Report what [`Session::rebase`] would do, without modifying the session
+    ///
+    /// Returns one step per commit in the branch since this session's snapshot, oldest
+    /// first. Each step has every conflict detected with that commit, and the resolution
+    /// `solver` produces for them. Unlike `rebase`, the preview doesn't stop at unsolvable
+    /// conflicts, the following steps are computed with the changes left unmodified.
+    ///
+    /// Nothing is written to storage. Chunks that solvers create, like merged chunks, are not
+    /// uploaded, the resolution holds them as inline payloads whatever their size.
+    #[instrument(skip(self, solver))]
+    pub async fn preview_rebase(
+        &self,
+        solver: &dyn ConflictSolver,
+    ) -> SessionResult<Vec<RebasePreviewStep>> {
+        let Some(branch_name) = &self.branch_name else {
+            return Err(SessionErrorKind::ReadOnlySession.into());
+        };
+
+        let new_commits = self.commits_to_rebase(branch_name).await?;
+        let mut steps = Vec::with_capacity(new_commits.len());
+        // solvers expect the current session to not hold the changes being rebased
+        let mut base = self.readonly_session_at(self.snapshot_id.clone());
+        base.discard_chunk_writes = true;
+        let mut change_set = self.change_set.clone();
+        let touched = self.checkpoints_modified_by(&new_commits).await?;
+        restore_checkpoints(&self.asset_manager, &mut change_set, &touched).await?;
+        for snap_id in new_commits {
+            let tx_log = self.asset_manager.fetch_transaction_log(&snap_id).await?;
+            let session = self.readonly_session_at(snap_id.clone());
+
+            let conflicts =
+                match ConflictDetector.solve(&tx_log, &session, change_set, &base).await?
+                {
+                    ConflictResolution::Patched(unmodified) => {
+                        change_set = unmodified;
+                        Vec::new()
+                    }
+                    ConflictResolution::Unsolvable { reason, unmodified } => {
+                        change_set = unmodified;
+                        reason
+                    }
+                };
+
+            let resolution =
+                solver.solve(&tx_log, &session, change_set.clone(), &base).await?;
+            if let ConflictResolution::Patched(patched) = &resolution {
+                change_set = patched.clone();
+            }
+
+            base.snapshot_id = snap_id.clone();
+            steps.push(RebasePreviewStep { snapshot_id: snap_id, conflicts, resolution });
+        }
+        Ok(steps)
+    }
+
+    /// Checkpointed arrays that any of `commits` modified
+    ///
+    /// Their chunk changes must be brought back to memory to rebase, the provisional manifests
+    /// of the rest still hold every chunk the array has at the tip of the branch.
+    async fn checkpoints_modified_by(
+        &self,
+        commits: &[SnapshotId],
+    ) -> SessionResult<Vec<NodeId>> {
+        let Some(tip) = commits.last() else {
+            return Ok(Vec::new());
+        };
+        let mut modified = HashSet::new();
+        for snap_id in commits {
+            let tx_log = self.asset_manager.fetch_transaction_log(snap_id).await?;
+            modified.extend(
+                self.change_set
+                    .checkpointed_arrays()
+                    .filter(|(node_id, _)| {
+                        tx_log.array_updated(node_id)
+                            || tx_log.array_deleted(node_id)
+                            || tx_log.chunks_updated(node_id)
+                    })
+                    .map(|(node_id, _)| node_id.clone()),
+            );
+        }
+        // commits that rewrite manifests don't record chunk changes
+        let tip = self.asset_manager.fetch_snapshot(tip).await?;
+        for node in tip.iter() {
+            let node = node?;
+            if let (Some(checkpoint), NodeData::Array { manifests, .. }) =
+                (self.change_set.get_checkpoint(&node.id), &node.node_data)
+                && manifests != &checkpoint.base_manifests
+            {
+                modified.insert(node.id);
+            }
+        }
+        Ok(modified.into_iter().collect())
+    }
+
+    /// The commits in the branch since this session's snapshot, oldest first
+    async fn commits_to_rebase(
+        &self,
+        branch_name: &str,
+    ) -> SessionResult<Vec<SnapshotId>> {
+        let ref_data = fetch_branch_tip(
+            self.storage.as_ref(),
+            self.storage_settings.as_ref(),
+            branch_name,
+        )
+        .await?;
+        if ref_data.snapshot == self.snapshot_id {
+            return Ok(Vec::new());
+        }
+
+        // the ancestry starts with the tip itself
+        let mut new_commits = Arc::clone(&self.asset_manager)
+            .snapshot_ancestry(&ref_data.snapshot)
+            .await?
+            .map_ok(|meta| meta.id)
+            .try_take_while(|snap_id| ready(Ok(snap_id != &self.snapshot_id)))
+            .try_collect::<Vec<_>>()
+            .await?;
+        trace!("Found {} commits to rebase", new_commits.len());
+        new_commits.reverse();
+        Ok(new_commits)
+    }
+
+    fn readonly_session_at(&self, snapshot_id: SnapshotId) -> Self {
+        Self::create_readonly_session(
+            self.config.clone(),
+            self.storage_settings.as_ref().clone(),
+            Arc::clone(&self.storage),
+            Arc::clone(&self.asset_manager),
+            Arc::clone(&self.virtual_resolver),
+            snapshot_id,
+        )
+    }
+}
+
+/// Bring the chunk changes of checkpointed arrays back to memory, dropping their provisional
+/// manifests
+async fn restore_checkpoints(
+    asset_manager: &AssetManager,
+    change_set: &mut ChangeSet,
+    node_ids: &[NodeId],
+) -> SessionResult<()> {
+    for node_id in node_ids {
+        let Some(checkpoint) = change_set.take_checkpoint(node_id) else {
+            continue;
+        };
+        for (coord, payload) in
+            checkpointed_changes(asset_manager, node_id, &checkpoint).await?
+        {
+            // changes made after the checkpoint are newer
+            if change_set.get_chunk_ref(node_id, &coord)?.is_none() {
+                change_set.set_chunk_ref(node_id.clone(), coord, payload);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Chunk changes of a checkpointed array, the payloads of the written chunks are read from
+/// its provisional manifests
+async fn checkpointed_changes(
+    asset_manager: &AssetManager,
+    node_id: &NodeId,
+    checkpoint: &CheckpointedArray,
+) -> SessionResult<Vec<(ChunkIndices, Option<ChunkPayload>)>> {
+    let mut manifests = Vec::with_capacity(checkpoint.manifest_files.len());
+    for info in checkpoint.manifest_files.iter() {
+        manifests.push(asset_manager.fetch_manifest(&info.id, info.size_bytes).await?);
+    }
+    let lookup = |coord: &ChunkIndices| {
+        for manifest in manifests.iter() {
+            match manifest.get_chunk_payload(node_id, coord) {
+                Ok(payload) => return Ok(Some(payload)),
+                Err(IcechunkFormatError {
+                    kind: IcechunkFormatErrorKind::ChunkCoordinatesNotFound { .. },
+                    ..
+                }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    };
+
+    let mut changes =
+        Vec::with_capacity(checkpoint.written.len() + checkpoint.deleted.len());
+    for coord in checkpoint.written.iter() {
+        changes.push((coord.clone(), lookup(coord)?));
+    }
+    changes.extend(checkpoint.deleted.iter().map(|coord| (coord.clone(), None)));
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(changes)
+}
+
+/// Warning: The presence of a single error may mean multiple missing items
+async fn updated_chunk_iterator<'a>(
+    asset_manager: &'a AssetManager,
+    change_set: &'a ChangeSet,
+    snapshot_id: &'a SnapshotId,
+) -> SessionResult<impl Stream<Item = SessionResult<(Path, ChunkInfo)>> + 'a> {
+    let snapshot = asset_manager.fetch_snapshot(snapshot_id).await?;
+    let nodes = futures::stream::iter(snapshot.iter_arc());
+    let res = nodes.and_then(move |node| async move {
+        Ok(updated_node_chunks_iterator(asset_manager, change_set, snapshot_id, node)
+            .await)
+    });
+    Ok(res.try_flatten())
+}
 
 async fn updated_node_chunks_iterator<'a>(
     asset_manager: &'a AssetManager,
@@ -1084,31 +1921,26 @@ async fn verified_node_chunk_iterator<'a>(
     match node.node_data {
         NodeData::Group => futures::future::Either::Left(futures::stream::empty()),
         NodeData::Array { manifests, .. } => {
-            let new_chunk_indices: Box<HashSet<&ChunkIndices>> = Box::new(
-                change_set
-                    .array_chunks_iterator(&node.id, &node.path)
-                    .map(|(idx, _)| idx)
-                    .collect(),
-            );
-
+            // checkpointed arrays have all their chunks in the provisional manifests
+            let manifests = change_set
+                .checkpointed_manifests(&node.id)
+                .map(|manifests| manifests.to_vec())
+                .unwrap_or(manifests);
             let node_id_c = node.id.clone();
             let new_chunks = change_set
                 .array_chunks_iterator(&node.id, &node.path)
-                .filter_map(move |(idx, payload)| {
-                    payload.as_ref().map(|payload| {
-                        Ok(ChunkInfo {
-                            node: node_id_c.clone(),
-                            coord: idx.clone(),
-                            payload: payload.clone(),
-                        })
+                .filter_map_ok(move |(coord, payload)| {
+                    payload.map(|payload| ChunkInfo {
+                        node: node_id_c.clone(),
+                        coord,
+                        payload,
                     })
                 });
 
             futures::future::Either::Right(
-                futures::stream::iter(new_chunks).chain(
+                futures::stream::iter(new_chunks).map_err(SessionError::from).chain(
                     futures::stream::iter(manifests)
                         .then(move |manifest_ref| {
-                            let new_chunk_indices = new_chunk_indices.clone();
                             let node_id_c = node.id.clone();
                             let node_id_c2 = node.id.clone();
                             let node_id_c3 = node.id.clone();
@@ -1116,16 +1948,14 @@ async fn verified_node_chunk_iterator<'a>(
                                 let manifest = fetch_manifest(
                                     &manifest_ref.object_id,
                                     snapshot_id,
+                                    change_set,
                                     asset_manager,
                                 )
                                 .await;
                                 match manifest {
                                     Ok(manifest) => {
                                         let old_chunks = manifest
-                                            .iter(node_id_c.clone())
-                                            .filter_ok(move |(coord, _)| {
-                                                !new_chunk_indices.contains(coord)
-                                            })
+                                            .iter(node_id_c2.clone())
                                             .map_ok(move |(coord, payload)| ChunkInfo {
                                                 node: node_id_c2.clone(),
                                                 coord,
@@ -1138,7 +1968,24 @@ async fn verified_node_chunk_iterator<'a>(
                                             );
                                         futures::future::Either::Left(
                                             futures::stream::iter(old_chunks)
-                                                .map_err(|e| e.into()),
+                                                .map_err(SessionError::from)
+                                                // chunks modified in the session were
+                                                // already yielded
+                                                .try_filter_map(move |chunk| {
+                                                    ready(
+                                                        change_set
+                                                            .get_chunk_ref(
+                                                                &node_id_c,
+                                                                &chunk.coord,
+                                                            )
+                                                            .map(|modified| {
+                                                                modified
+                                                                    .is_none()
+                                                                    .then_some(chunk)
+                                                            })
+                                                            .map_err(SessionError::from),
+                                                    )
+                                                }),
                                         )
                                     }
                                     // if we cannot even fetch the manifest, we generate a
@@ -1313,8 +2160,21 @@ async fn all_chunks<'a>(
     let existing_array_chunks =
         updated_chunk_iterator(asset_manager, change_set, snapshot_id).await?;
     let new_array_chunks =
-        futures::stream::iter(change_set.new_arrays_chunk_iterator().map(Ok));
-    Ok(existing_array_chunks.chain(new_array_chunks))
+        futures::stream::iter(change_set.new_arrays_chunk_iterator().filter_ok(
+            |(_, chunk)| change_set.checkpointed_manifests(&chunk.node).is_none(),
+        ))
+        .map_err(SessionError::from);
+    // new arrays that were checkpointed also have chunks in their provisional manifests
+    let checkpointed_new_array_chunks = futures::stream::iter(
+        change_set
+            .new_nodes_iterator()
+            .filter(|node| change_set.checkpointed_manifests(&node.id).is_some()),
+    )
+    .then(move |node| {
+        updated_node_chunks_iterator(asset_manager, change_set, snapshot_id, node)
+    })
+    .flatten();
+    Ok(existing_array_chunks.chain(new_array_chunks).chain(checkpointed_new_array_chunks))
 }
 
 pub async fn raise_if_invalid_snapshot_id(
@@ -1394,14 +2254,12 @@ impl<'a> FlushProcess<'a> {
     ) -> SessionResult<()> {
         let mut from = vec![];
         let mut to = vec![];
-        let chunks = stream::iter(
-            self.change_set
-                .new_array_chunk_iterator(node_id, node_path)
-                .map(Ok::<ChunkInfo, Infallible>),
-        );
+        let chunks =
+            stream::iter(self.change_set.new_array_chunk_iterator(node_id, node_path))
+                .map_err(SessionError::from);
         let chunks = aggregate_extents(&mut from, &mut to, chunks, |ci| &ci.coord);
 
-        if let Some(new_manifest) = Manifest::from_stream(chunks).await.unwrap() {
+        if let Some(new_manifest) = Manifest::from_stream(chunks).await? {
             let new_manifest = Arc::new(new_manifest);
             let new_manifest_size =
                 self.asset_manager.write_manifest(Arc::clone(&new_manifest)).await?;
@@ -1464,6 +2322,19 @@ impl<'a> FlushProcess<'a> {
         Ok(())
     }
 
+    /// Record the provisional manifests for an array with no changes since its checkpoint
+    fn copy_checkpointed_manifests(
+        &mut self,
+        node_id: &NodeId,
+        checkpoint: &CheckpointedArray,
+    ) {
+        self.manifest_files.extend(checkpoint.manifest_files.iter().cloned());
+        self.manifest_refs
+            .entry(node_id.clone())
+            .or_default()
+            .extend(checkpoint.manifests.iter().cloned());
+    }
+
     /// Record the previous manifests for an array that was not modified in the session
     fn copy_previous_manifest(&mut self, node: &NodeSnapshot, old_snapshot: &Snapshot) {
         match &node.node_data {
@@ -1521,6 +2392,9 @@ async fn flush(
             trace!(path=%node.path, "Node has changes, writing a new manifest");
             // Array wasn't deleted and has changes in this session
             flush_data.write_manifest_for_existing_node(&node).await?;
+        } else if let Some(checkpoint) = flush_data.change_set.get_checkpoint(node_id) {
+            trace!(path=%node.path, "Node has no changes since checkpoint, keeping its manifest");
+            flush_data.copy_checkpointed_manifests(node_id, checkpoint);
         } else {
             trace!(path=%node.path, "Node has no changes, keeping the previous manifest");
             // Array wasn't deleted but has no changes in this session
@@ -1533,8 +2407,26 @@ async fn flush(
     // Now we need to go through all the new arrays, and generate manifests for them
 
     for (node_path, node_id) in flush_data.change_set.new_arrays() {
-        trace!(path=%node_path, "New node, writing a manifest");
-        flush_data.write_manifest_for_new_node(node_id, node_path).await?;
+        match flush_data.change_set.get_checkpoint(node_id) {
+            None => {
+                trace!(path=%node_path, "New node, writing a manifest");
+                flush_data.write_manifest_for_new_node(node_id, node_path).await?;
+            }
+            Some(checkpoint) if !flush_data.change_set.has_chunk_changes(node_id) => {
+                trace!(path=%node_path, "New node with no changes since checkpoint");
+                flush_data.copy_checkpointed_manifests(node_id, checkpoint);
+            }
+            Some(_) => {
+                trace!(path=%node_path, "New node with changes since checkpoint");
+                // we need the node to read the chunks in its provisional manifests
+                #[allow(clippy::expect_used)]
+                let node = flush_data
+                    .change_set
+                    .get_new_array(node_path)
+                    .expect("Bug in flush function, new array not found");
+                flush_data.write_manifest_for_existing_node(&node).await?;
+            }
+        }
     }
 
     trace!("Building new snapshot");
@@ -1609,7 +2501,7 @@ async fn flush(
     trace!(transaction_log_id = %new_snapshot.id(), "Creating transaction log");
     let new_snapshot_id = new_snapshot.id();
     // FIXME: this should execute in a non-blocking context
-    let tx_log = TransactionLog::new(&new_snapshot_id, flush_data.change_set);
+    let tx_log = TransactionLog::new(&new_snapshot_id, flush_data.change_set)?;
 
     flush_data
         .asset_manager
@@ -1653,16 +2545,19 @@ async fn do_commit(
     info!(branch_name, old_snapshot_id=%snapshot_id, "Commit started");
     let parent_snapshot = snapshot_id.clone();
     let properties = properties.unwrap_or_default();
-    let flush_data = FlushProcess::new(asset_manager, change_set, snapshot_id);
+    let flush_data =
+        FlushProcess::new(Arc::clone(&asset_manager), change_set, snapshot_id);
     let new_snapshot = flush(flush_data, message, properties).await?;
 
     debug!(branch_name, new_snapshot_id=%new_snapshot, "Updating branch");
+    // commits are fast-forwards, their metadata was checked by `Session::commit`
     let id = match update_branch(
         storage,
         storage_settings,
         branch_name,
         new_snapshot.clone(),
         Some(&parent_snapshot),
+        None,
     )
     .await
     {
@@ -1683,8 +2578,15 @@ async fn do_commit(
 async fn fetch_manifest(
     manifest_id: &ManifestId,
     snapshot_id: &SnapshotId,
+    change_set: &ChangeSet,
     asset_manager: &AssetManager,
 ) -> SessionResult<Arc<Manifest>> {
+    // provisional manifests are not in the snapshot yet
+    if let Some(manifest_info) = change_set.checkpointed_manifest_file(manifest_id) {
+        return Ok(asset_manager
+            .fetch_manifest(manifest_id, manifest_info.size_bytes)
+            .await?);
+    }
     let snapshot = asset_manager.fetch_snapshot(snapshot_id).await?;
     let manifest_info = snapshot.manifest_info(manifest_id).ok_or_else(|| {
         IcechunkFormatError::from(IcechunkFormatErrorKind::ManifestInfoNotFound {
@@ -1758,17 +2660,21 @@ mod tests {
 
     use crate::{
         ObjectStorage, Repository,
+        config::SpillConfig,
         conflicts::{
             basic_solver::{BasicConflictSolver, VersionSelection},
+            chunk_merge_solver::ChunkMergeSolver,
             detector::ConflictDetector,
         },
-        format::manifest::ManifestExtents,
+        format::{IcechunkResult, manifest::ManifestExtents},
         refs::{Ref, fetch_tag},
         repository::VersionInfo,
-        storage::new_in_memory_storage,
+        storage::{new_in_memory_storage, new_local_filesystem_storage},
         strategies::{
-            ShapeDim, chunk_indices, empty_writable_session, node_paths, shapes_and_dims,
+            ShapeDim, chunk_indices, empty_repositories, empty_writable_session,
+            node_paths, shapes_and_dims,
         },
+        validation::{ChunksWithinShape, DimensionNamesRequired, RequiredAttributes},
     };
 
     use super::*;
@@ -1933,6 +2839,120 @@ mod tests {
         prop_assert!(session.delete_group(path.clone()).await.is_ok());
     }
 
+    #[proptest(async = "tokio")]
+    /// Rebasing a group delete and a concurrent node creation never leaves orphan nodes
+    async fn test_rebase_never_creates_orphans(
+        #[strategy(proptest::collection::vec("[a-z]{1,4}", 0..4))] group: Vec<String>,
+        #[strategy(empty_repositories())] repo: Repository,
+        theirs_delete: bool,
+        under_deleted_group: bool,
+    ) {
+        let group: Path = format!("/{}", group.join("/")).as_str().try_into().unwrap();
+        let mut ds = repo.writable_session("main").await.unwrap();
+        for path in group.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            ds.add_group(path, Bytes::new()).await.unwrap();
+        }
+        let group_id = ds.get_node(&group).await.unwrap().id;
+        ds.commit("create groups", None).await.unwrap();
+
+        // the new node goes either under the deleted group or next to it
+        let parent = group
+            .ancestors()
+            .nth(if under_deleted_group { 0 } else { 1 })
+            .unwrap_or(Path::root());
+        let new_path: Path = if parent == Path::root() {
+            "/new-node".try_into().unwrap()
+        } else {
+            format!("{}/new-node", parent).as_str().try_into().unwrap()
+        };
+        let orphaned = new_path.starts_with(&group);
+
+        let mut deleting = repo.writable_session("main").await.unwrap();
+        deleting.delete_group(group.clone()).await.unwrap();
+        let mut adding = repo.writable_session("main").await.unwrap();
+        adding.add_group(new_path.clone(), Bytes::new()).await.unwrap();
+
+        let (mut first, mut second) =
+            if theirs_delete { (deleting, adding) } else { (adding, deleting) };
+        first.commit("first", None).await.unwrap();
+        second.commit("second", None).await.unwrap_err();
+
+        match second.rebase(&BasicConflictSolver::default()).await {
+            Ok(()) => {
+                prop_assert!(!orphaned);
+                second.commit("rebased", None).await.unwrap();
+            }
+            Err(SessionError {
+                kind: SessionErrorKind::RebaseFailed { conflicts, .. },
+                ..
+            }) => {
+                prop_assert!(orphaned);
+                let expected = if theirs_delete {
+                    Conflict::NewNodeInDeletedGroup(group.clone())
+                } else {
+                    Conflict::DeleteOfGroupWithNewChildren {
+                        path: group.clone(),
+                        node_id: group_id,
+                    }
+                };
+                prop_assert_eq!(conflicts, vec![expected]);
+            }
+            Err(err) => panic!("unexpected rebase error {:?}", err),
+        }
+
+        let tip = repo
+            .readonly_session(&VersionInfo::BranchTipRef("main".to_string()))
+            .await
+            .unwrap();
+        let nodes: Vec<_> = tip.list_nodes().await.unwrap().try_collect().unwrap();
+        let paths: HashSet<_> = nodes.iter().map(|node| node.path.clone()).collect();
+        for node in nodes {
+            if let Some(parent) = node.path.ancestors().nth(1) {
+                prop_assert!(paths.contains(&parent), "orphan node {}", node.path);
+            }
+        }
+    }
+
+    #[tokio::test]
+    /// The shrunk case of `test_rebase_never_creates_orphans`, the deleted group is the root
+    async fn test_rebase_root_group_delete_with_new_children()
+    -> Result<(), Box<dyn std::error::Error>> {
+        for theirs_delete in [true, false] {
+            let repo = create_memory_store_repository().await;
+            let mut ds = repo.writable_session("main").await?;
+            ds.add_group(Path::root(), Bytes::new()).await?;
+            let root_id = ds.get_node(&Path::root()).await?.id;
+            ds.commit("create root", None).await?;
+
+            let mut deleting = repo.writable_session("main").await?;
+            deleting.delete_group(Path::root()).await?;
+            let mut adding = repo.writable_session("main").await?;
+            adding.add_group("/new-node".try_into().unwrap(), Bytes::new()).await?;
+
+            let (mut first, mut second) =
+                if theirs_delete { (deleting, adding) } else { (adding, deleting) };
+            first.commit("first", None).await?;
+            assert!(second.commit("second", None).await.is_err());
+
+            let expected = if theirs_delete {
+                Conflict::NewNodeInDeletedGroup(Path::root())
+            } else {
+                Conflict::DeleteOfGroupWithNewChildren {
+                    path: Path::root(),
+                    node_id: root_id,
+                }
+            };
+            assert!(matches!(
+                second.rebase(&BasicConflictSolver::default()).await,
+                Err(SessionError {
+                    kind: SessionErrorKind::RebaseFailed { conflicts, .. },
+                    ..
+                }) if conflicts == vec![expected]
+            ));
+        }
+        Ok(())
+    }
+
     #[proptest(async = "tokio")]
     async fn test_aggregate_extents(
         #[strategy(proptest::collection::vec(chunk_indices(3, 0..1_000_000), 1..50))]
@@ -2011,6 +3031,139 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_commit_validators() -> Result<(), Box<dyn Error>> {
+        #[derive(Debug)]
+        struct RequireTicket;
+        impl CommitValidator for RequireTicket {
+            fn name(&self) -> &str {
+                "require_ticket"
+            }
+            fn validate(
+                &self,
+                commit: &ProposedCommit<'_>,
+            ) -> IcechunkResult<Vec<String>> {
+                Ok(if commit.properties.contains_key("ticket") {
+                    vec![]
+                } else {
+                    vec![format!("commit `{}` has no ticket", commit.message)]
+                })
+            }
+        }
+
+        // sessions are serialized below, in memory storage would lose its content
+        let dir = tempfile::tempdir()?;
+        let storage = new_local_filesystem_storage(dir.path()).await?;
+        let mut repo = Repository::create(None, storage, HashMap::new()).await?;
+        repo.add_commit_validator(Arc::new(RequiredAttributes::new(["units"])));
+        repo.add_commit_validator(Arc::new(DimensionNamesRequired));
+        repo.add_commit_validator(Arc::new(ChunksWithinShape));
+        repo.add_commit_validator(Arc::new(RequireTicket));
+        let ticket = SnapshotProperties::from_iter([("ticket".to_string(), 42.into())]);
+        let path: Path = "/array".try_into().unwrap();
+        let shape = ArrayShape::new(vec![(10, 1)]).unwrap();
+        let dims = Some(vec!["x".into()]);
+        let meta = Bytes::from_static(br#"{"attributes":{"units":"m"}}"#);
+
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_array(path.clone(), shape.clone(), None, Bytes::new()).await?;
+        ds.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![8]),
+            Some(ChunkPayload::Inline("x".into())),
+        )
+        .await?;
+        let res = ds.commit("first", None).await;
+        let Err(SessionError {
+            kind: SessionErrorKind::CommitRejected(failures), ..
+        }) = res
+        else {
+            panic!("commit should be rejected: {res:?}");
+        };
+        let rejected_by: Vec<_> = failures.iter().map(|f| f.validator.as_str()).collect();
+        assert_eq!(
+            rejected_by,
+            ["required_attributes", "dimension_names_required", "require_ticket"]
+        );
+
+        ds.update_array(&path, shape, dims.clone(), meta.clone()).await?;
+        let first = ds.commit("first", Some(ticket.clone())).await?;
+
+        // shrinking the array leaves the chunk written before outside of it
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![9]),
+            Some(ChunkPayload::Inline("x".into())),
+        )
+        .await?;
+        ds.update_array(
+            &path,
+            ArrayShape::new(vec![(5, 1)]).unwrap(),
+            dims.clone(),
+            meta.clone(),
+        )
+        .await?;
+        let res = ds.commit("second", Some(ticket.clone())).await;
+        assert!(matches!(
+            res,
+            Err(SessionError { kind: SessionErrorKind::CommitRejected(ref failures), .. })
+                if failures == &[ValidationFailure {
+                    validator: "chunks_within_shape".to_string(),
+                    reason: "array /array has 1 chunks outside its shape".to_string(),
+                }]
+        ));
+        // nothing was published
+        assert_eq!(repo.lookup_branch("main").await?, first);
+
+        // chunks moved to provisional manifests by a checkpoint are validated too
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![9]),
+            Some(ChunkPayload::Inline("x".into())),
+        )
+        .await?;
+        ds.checkpoint().await?;
+        ds.update_array(&path, ArrayShape::new(vec![(5, 1)]).unwrap(), dims, meta)
+            .await?;
+        let res = ds.commit("third", Some(ticket.clone())).await;
+        assert!(matches!(
+            res,
+            Err(SessionError { kind: SessionErrorKind::CommitRejected(ref failures), .. })
+                if failures == &[ValidationFailure {
+                    validator: "chunks_within_shape".to_string(),
+                    reason: "array /array has 1 chunks outside its shape".to_string(),
+                }]
+        ));
+
+        // serialized sessions lose the validators, they can't commit until they get them back
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![1]),
+            Some(ChunkPayload::Inline("x".into())),
+        )
+        .await?;
+        let bytes = ds.as_bytes()?;
+        let res = Session::from_bytes(bytes.clone())?
+            .commit("fourth", Some(ticket.clone()))
+            .await;
+        assert!(matches!(
+            res,
+            Err(SessionError {
+                kind: SessionErrorKind::MissingCommitValidators(ref missing), ..
+            }) if missing.len() == 4
+        ));
+        let mut ds = repo.session_from_bytes(bytes)?;
+        ds.commit("fourth", Some(ticket)).await?;
+
+        // reopening keeps the validators
+        let repo = repo.reopen(None, None)?;
+        assert_eq!(repo.commit_validators().len(), 4);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_repository_with_updates() -> Result<(), Box<dyn Error>> {
         let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
@@ -2091,6 +3244,7 @@ mod tests {
             "main",
             snapshot.id().clone(),
             None,
+            None,
         )
         .await?;
         Repository::store_config(
@@ -3192,6 +4346,47 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test()]
+    /// Test rebase over several commits
+    ///
+    /// This session: add array
+    /// First previous commit: add an unrelated group
+    /// Second previous commit: add group in the same path as the session array
+    ///
+    /// The first commit is solved without seeing the second one, the conflict is reported
+    /// with the second commit
+    async fn test_rebase_solves_each_commit_at_its_snapshot() -> Result<(), Box<dyn Error>>
+    {
+        let repo = get_repo_for_conflict().await?;
+        let conflict_path: Path = "/foo/bar/conflict".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_array(conflict_path.clone(), basic_shape(), None, user_data()).await?;
+
+        let mut ds1 = repo.writable_session("main").await?;
+        ds1.add_group("/foo/unrelated".try_into().unwrap(), user_data()).await?;
+        let first = ds1.commit("unrelated group", None).await?;
+        let mut ds1 = repo.writable_session("main").await?;
+        ds1.add_group(conflict_path.clone(), user_data()).await?;
+        let second = ds1.commit("create group", None).await?;
+
+        match ds.rebase(&BasicConflictSolver::default()).await {
+            Err(SessionError {
+                kind: SessionErrorKind::RebaseFailed { snapshot, conflicts },
+                ..
+            }) => {
+                assert_eq!(snapshot, second);
+                assert_eq!(
+                    conflicts,
+                    vec![Conflict::NewNodeConflictsWithExistingNode(conflict_path)]
+                );
+            }
+            other => panic!("expected a rebase failure, got {other:?}"),
+        }
+        // the session can still commit on top of the first commit
+        assert_eq!(ds.snapshot_id(), &first);
+        Ok(())
+    }
+
     #[tokio::test()]
     /// Test conflict detection
     ///
@@ -3334,22 +4529,77 @@ mod tests {
     }
 
     #[tokio::test()]
-    async fn test_rebase_without_fast_forward() -> Result<(), Box<dyn Error>> {
-        let repo = create_memory_store_repository().await;
+    /// Test conflict detection
+    ///
+    /// This session: delete group
+    /// Previous commit: add a new array under the same group
+    async fn test_conflict_detection_delete_of_group_with_new_children()
+    -> Result<(), Box<dyn Error>> {
+        let (mut ds1, mut ds2) = get_sessions_for_conflict().await?;
 
-        let mut ds = repo.writable_session("main").await?;
+        let path: Path = "/foo/bar".try_into().unwrap();
+        ds1.add_array(
+            "/foo/bar/new-array".try_into().unwrap(),
+            basic_shape(),
+            None,
+            user_data(),
+        )
+        .await?;
+        ds1.commit("add array", None).await?;
 
-        ds.add_group("/".try_into().unwrap(), user_data()).await?;
+        let node = ds2.get_node(&path).await.unwrap();
+        ds2.delete_group(path.clone()).await?;
+        ds2.commit("delete group", None).await.unwrap_err();
+        assert_has_conflict(
+            &Conflict::DeleteOfGroupWithNewChildren { path, node_id: node.id },
+            ds2.rebase(&BasicConflictSolver::default()).await,
+        );
+        Ok(())
+    }
 
-        let new_array_path: Path = "/array".try_into().unwrap();
-        ds.add_array(new_array_path.clone(), basic_shape(), None, user_data()).await?;
-        ds.commit("create array", None).await?;
+    #[tokio::test()]
+    /// Test conflict detection
+    ///
+    /// This session: add a new group nested under an existing group
+    /// Previous commit: delete the existing group
+    async fn test_conflict_detection_new_node_in_deleted_group()
+    -> Result<(), Box<dyn Error>> {
+        let (mut ds1, mut ds2) = get_sessions_for_conflict().await?;
 
-        // one writer sets chunks
-        // other writer sets the same chunks, generating a conflict
+        let path: Path = "/foo/bar".try_into().unwrap();
+        ds1.delete_group(path.clone()).await?;
+        ds1.commit("delete group", None).await?;
 
-        let mut ds1 = repo.writable_session("main").await?;
-        let mut ds2 = repo.writable_session("main").await?;
+        ds2.add_group("/foo/bar/new-group".try_into().unwrap(), user_data()).await?;
+        ds2.add_group("/foo/bar/new-group/child".try_into().unwrap(), user_data())
+            .await?;
+        ds2.commit("add groups", None).await.unwrap_err();
+        let res = ds2.rebase(&BasicConflictSolver::default()).await;
+        assert!(matches!(
+            res,
+            Err(SessionError{kind: SessionErrorKind::RebaseFailed { conflicts, .. }, ..})
+                if conflicts == vec![Conflict::NewNodeInDeletedGroup("/foo/bar".try_into().unwrap())]
+        ));
+        Ok(())
+    }
+
+    #[tokio::test()]
+    async fn test_rebase_without_fast_forward() -> Result<(), Box<dyn Error>> {
+        let repo = create_memory_store_repository().await;
+
+        let mut ds = repo.writable_session("main").await?;
+
+        ds.add_group("/".try_into().unwrap(), user_data()).await?;
+
+        let new_array_path: Path = "/array".try_into().unwrap();
+        ds.add_array(new_array_path.clone(), basic_shape(), None, user_data()).await?;
+        ds.commit("create array", None).await?;
+
+        // one writer sets chunks
+        // other writer sets the same chunks, generating a conflict
+
+        let mut ds1 = repo.writable_session("main").await?;
+        let mut ds2 = repo.writable_session("main").await?;
 
         ds1.set_chunk_ref(
             new_array_path.clone(),
@@ -3394,6 +4644,94 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test()]
+    async fn test_commit_rebasing() -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let policy = RebaseRetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+
+        // without conflicts no rebase is needed
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_group("/a".try_into().unwrap(), user_data()).await?;
+        let res = ds.commit_rebasing("a", None, &ConflictDetector, &policy).await?;
+        assert_eq!(res.rebase_count, 0);
+        assert_eq!(repo.lookup_branch("main").await?, res.snapshot_id);
+
+        // two commits land on the branch after the session started
+        let mut ds = repo.writable_session("main").await?;
+        for path in ["/b", "/c"] {
+            let mut other = repo.writable_session("main").await?;
+            other.add_group(path.try_into().unwrap(), user_data()).await?;
+            other.commit(path, None).await?;
+        }
+        ds.add_group("/d".try_into().unwrap(), user_data()).await?;
+        let res = ds.commit_rebasing("d", None, &ConflictDetector, &policy).await?;
+        // a single rebase goes over all new commits
+        assert_eq!(res.rebase_count, 1);
+        assert_eq!(repo.lookup_branch("main").await?, res.snapshot_id);
+        let reflog =
+            repo.reflog(&crate::refs::Ref::Branch("main".to_string()), None).await?;
+        assert_eq!(reflog[0].operation, crate::refs::RefOperation::Rebase);
+
+        // attempts are bounded
+        let mut ds = repo.writable_session("main").await?;
+        let mut other = repo.writable_session("main").await?;
+        other.add_group("/e".try_into().unwrap(), user_data()).await?;
+        other.commit("e", None).await?;
+        ds.add_group("/f".try_into().unwrap(), user_data()).await?;
+        let policy = RebaseRetryPolicy { max_attempts: 1, ..policy };
+        assert!(matches!(
+            ds.commit_rebasing("f", None, &ConflictDetector, &policy).await,
+            Err(SessionError { kind: SessionErrorKind::Conflict { .. }, .. })
+        ));
+
+        // unsolvable conflicts are reported without retrying
+        let (mut ds1, mut ds2) = get_sessions_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        ds1.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![0]),
+            Some(ChunkPayload::Inline("a".into())),
+        )
+        .await?;
+        ds1.commit("first", None).await?;
+        ds2.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![0]),
+            Some(ChunkPayload::Inline("b".into())),
+        )
+        .await?;
+        assert!(matches!(
+            ds2.commit_rebasing(
+                "second",
+                None,
+                &ConflictDetector,
+                &RebaseRetryPolicy::default()
+            )
+            .await,
+            Err(SessionError { kind: SessionErrorKind::RebaseFailed { .. }, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebase_retry_backoff() {
+        let policy = RebaseRetryPolicy {
+            max_attempts: 100,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        };
+        for _ in 0..100 {
+            assert!(policy.backoff(1) <= Duration::from_millis(10));
+            assert!(policy.backoff(3) <= Duration::from_millis(40));
+            assert!(policy.backoff(50) <= Duration::from_millis(100));
+        }
+    }
+
     #[tokio::test()]
     async fn test_rebase_fast_forwarding_over_chunk_writes() -> Result<(), Box<dyn Error>>
     {
@@ -3487,6 +4825,7 @@ mod tests {
             "main",
             conflicting_snap.clone(),
             Some(&current_snap),
+            None,
         )
         .await?;
         Ok(())
@@ -3688,6 +5027,752 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_fork_and_merge() -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_group("/foo/bar/new-group".try_into().unwrap(), user_data()).await?;
+        ds.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![2]),
+            Some(ChunkPayload::Inline("parent".into())),
+        )
+        .await?;
+
+        let mut forks = vec![ds.fork()?, ds.fork()?];
+        // forks see the parent's nodes, but don't carry its chunk writes
+        assert!(
+            forks[0].get_group(&"/foo/bar/new-group".try_into().unwrap()).await.is_ok()
+        );
+        assert_eq!(forks[0].changes().in_memory_chunk_refs(), 0);
+        for (coord, fork) in forks.iter_mut().enumerate() {
+            fork.set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![coord as u32]),
+                Some(ChunkPayload::Inline(format!("fork {}", coord).into())),
+            )
+            .await?;
+        }
+
+        // forks that wrote the same chunk with different data cannot be merged
+        let mut overlapping = ds.fork()?;
+        overlapping
+            .set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![0]),
+                Some(ChunkPayload::Inline("overlapping".into())),
+            )
+            .await?;
+
+        for fork in forks {
+            // the forks share the new group, which is not an overlap
+            ds.merge_session(Session::from_bytes(fork.as_bytes()?)?).await?;
+        }
+        let changes = ds.changes().clone();
+        let err = ds.merge_session(overlapping).await.unwrap_err();
+        assert!(matches!(
+            err.kind,
+            SessionErrorKind::OverlappingWrites(overlaps)
+                if overlaps == vec![OverlappingWrite::Chunks {
+                    path: path.clone(),
+                    coords: vec![ChunkIndices(vec![0])]
+                }]
+        ));
+        assert_eq!(ds.changes(), &changes);
+
+        ds.commit("merged forks", None).await?;
+        for coord in [0u32, 1] {
+            let payload = ds.get_chunk_ref(&path, &ChunkIndices(vec![coord])).await?;
+            assert_eq!(
+                payload,
+                Some(ChunkPayload::Inline(format!("fork {}", coord).into()))
+            );
+        }
+        assert_eq!(
+            ds.get_chunk_ref(&path, &ChunkIndices(vec![2])).await?,
+            Some(ChunkPayload::Inline("parent".into()))
+        );
+        assert!(ds.get_group(&"/foo/bar/new-group".try_into().unwrap()).await.is_ok());
+
+        // sessions based on other snapshots cannot be merged
+        let mut ds = repo.writable_session("main").await?;
+        let fork = ds.fork()?;
+        ds.delete_array(path.clone()).await?;
+        ds.commit("delete array", None).await?;
+        let mut newer = repo.writable_session("main").await?;
+        assert!(matches!(
+            newer.merge_session(fork).await.unwrap_err().kind,
+            SessionErrorKind::MergeBaseMismatch { .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    /// Changes the parent makes after forking are not overlaps, the fork never touched them
+    async fn test_merge_session_after_parent_updates() -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let group_path: Path = "/foo/bar/new-group".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        ds.update_array(&path, basic_shape(), None, Bytes::from_static(b"before fork"))
+            .await?;
+        ds.add_group(group_path.clone(), user_data()).await?;
+
+        let mut fork = ds.fork()?;
+        ds.update_array(&path, basic_shape(), None, Bytes::from_static(b"after fork"))
+            .await?;
+        fork.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![0]),
+            Some(ChunkPayload::Inline("fork".into())),
+        )
+        .await?;
+        // the fork deletes a group it inherited
+        fork.delete_group(group_path.clone()).await?;
+        ds.merge_session(fork).await?;
+
+        assert_eq!(
+            ds.get_array(&path).await?.user_data,
+            Bytes::from_static(b"after fork")
+        );
+        assert_eq!(
+            ds.get_chunk_ref(&path, &ChunkIndices(vec![0])).await?,
+            Some(ChunkPayload::Inline("fork".into()))
+        );
+        assert!(ds.get_group(&group_path).await.is_err());
+
+        // both sides updating the array after forking is still an overlap
+        let mut fork = ds.fork()?;
+        ds.update_array(&path, basic_shape(), None, Bytes::from_static(b"parent"))
+            .await?;
+        fork.update_array(&path, basic_shape(), None, Bytes::from_static(b"fork"))
+            .await?;
+        assert!(matches!(
+            ds.merge_session(fork).await.unwrap_err().kind,
+            SessionErrorKind::OverlappingWrites(overlaps)
+                if overlaps == vec![OverlappingWrite::Node(path)]
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_session_rejects_group_and_array_at_same_path()
+    -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/new-node".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        let mut fork = ds.fork()?;
+        ds.add_group(path.clone(), user_data()).await?;
+        fork.add_array(path.clone(), basic_shape(), None, user_data()).await?;
+
+        let changes = ds.changes().clone();
+        assert!(matches!(
+            ds.merge_session(fork).await.unwrap_err().kind,
+            SessionErrorKind::OverlappingWrites(overlaps)
+                if overlaps == vec![OverlappingWrite::Node(path)]
+        ));
+        assert_eq!(ds.changes(), &changes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_session_rejects_nodes_under_deleted_group()
+    -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/new-group/child".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        let mut fork = ds.fork()?;
+        ds.delete_group("/foo/bar".try_into().unwrap()).await?;
+        fork.add_group(path.clone(), user_data()).await?;
+
+        let changes = ds.changes().clone();
+        assert!(matches!(
+            ds.merge_session(fork).await.unwrap_err().kind,
+            SessionErrorKind::OverlappingWrites(overlaps)
+                if overlaps == vec![OverlappingWrite::Node(path)]
+        ));
+        assert_eq!(ds.changes(), &changes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() -> Result<(), Box<dyn Error>> {
+        let repo = create_memory_store_repository().await;
+        let path: Path = "/array".try_into().unwrap();
+        let payload = |n: u32| ChunkPayload::Inline(format!("{n}").into());
+        let chunks = |ds: &Session| {
+            let ds = ds.clone();
+            async move {
+                ds.all_chunks()
+                    .await?
+                    .map_ok(|(_, info)| (info.coord.0[0], info.payload))
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map(|chunks| chunks.into_iter().sorted().collect::<Vec<_>>())
+            }
+        };
+
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_array(
+            path.clone(),
+            ArrayShape::new(vec![(20, 1)]).unwrap(),
+            None,
+            user_data(),
+        )
+        .await?;
+        for i in 0..10 {
+            ds.set_chunk_ref(path.clone(), ChunkIndices(vec![i]), Some(payload(i)))
+                .await?;
+        }
+        ds.checkpoint().await?;
+        assert_eq!(ds.changes().in_memory_chunk_refs(), 0);
+        assert_eq!(
+            ds.get_chunk_ref(&path, &ChunkIndices(vec![3])).await?,
+            Some(payload(3))
+        );
+
+        // changes after the checkpoint take precedence
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![2]), Some(payload(42))).await?;
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![4]), None).await?;
+        let expected: Vec<_> = (0..10)
+            .filter(|i| *i != 4)
+            .map(|i| (i, payload(if i == 2 { 42 } else { i })))
+            .collect();
+        assert_eq!(chunks(&ds).await?, expected);
+        ds.checkpoint().await?;
+        assert_eq!(chunks(&ds).await?, expected);
+        // the modified coordinates are recorded, commits don't diff the manifests
+        let node_id = ds.get_array(&path).await?.id;
+        let checkpoint = ds.changes().get_checkpoint(&node_id).unwrap();
+        assert_eq!(
+            checkpoint.written,
+            (0..10).filter(|i| *i != 4).map(|i| ChunkIndices(vec![i])).collect()
+        );
+        assert_eq!(checkpoint.deleted, BTreeSet::from([ChunkIndices(vec![4])]));
+        ds.commit("checkpointed new array", None).await?;
+        assert_eq!(chunks(&ds).await?, expected);
+
+        // commit reuses the provisional manifests of arrays with no changes since checkpoint
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![15]), Some(payload(15))).await?;
+        ds.checkpoint().await?;
+        let node_id = ds.get_array(&path).await?.id;
+        let provisional = ds.changes().checkpointed_manifests(&node_id).unwrap().to_vec();
+        let snap = ds.commit("checkpointed existing array", None).await?;
+        let tx_log = repo.asset_manager().fetch_transaction_log(&snap).await?;
+        assert_eq!(
+            tx_log.updated_chunks_for(&node_id).collect::<Vec<_>>(),
+            vec![ChunkIndices(vec![15])]
+        );
+        match ds.get_array(&path).await?.node_data {
+            NodeData::Array { manifests, .. } => assert_eq!(manifests, provisional),
+            NodeData::Group => panic!("not an array"),
+        }
+        let mut expected_after = expected.clone();
+        expected_after.push((15, payload(15)));
+        assert_eq!(chunks(&ds).await?, expected_after);
+
+        // rebasing a checkpointed session keeps the changes of the other commits
+        let mut ds1 = repo.writable_session("main").await?;
+        let mut ds2 = repo.writable_session("main").await?;
+        ds1.set_chunk_ref(path.clone(), ChunkIndices(vec![16]), Some(payload(16)))
+            .await?;
+        ds1.checkpoint().await?;
+        ds2.set_chunk_ref(path.clone(), ChunkIndices(vec![17]), Some(payload(17)))
+            .await?;
+        ds2.commit("concurrent", None).await?;
+        assert!(ds1.commit("conflicting", None).await.is_err());
+        ds1.rebase(&ConflictDetector).await?;
+        ds1.commit("rebased", None).await?;
+        expected_after.push((16, payload(16)));
+        expected_after.push((17, payload(17)));
+        assert_eq!(chunks(&ds1).await?, expected_after);
+
+        // forks checkpointing the same array can be merged
+        let mut ds = repo.writable_session("main").await?;
+        let mut forks = vec![ds.fork()?, ds.fork()?];
+        for (i, fork) in forks.iter_mut().enumerate() {
+            let coord = 18 + i as u32;
+            fork.set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![coord]),
+                Some(payload(coord)),
+            )
+            .await?;
+            fork.checkpoint().await?;
+        }
+        for fork in forks {
+            ds.merge_session(fork).await?;
+        }
+        ds.commit("merged", None).await?;
+        expected_after.push((18, payload(18)));
+        expected_after.push((19, payload(19)));
+        assert_eq!(chunks(&ds).await?, expected_after);
+
+        // a merge rejected for overlapping writes leaves the checkpoints in place
+        let mut ds = repo.writable_session("main").await?;
+        let mut fork = ds.fork()?;
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![0]), Some(payload(100))).await?;
+        ds.checkpoint().await?;
+        let checkpointed =
+            ds.changes().checkpointed_manifests(&node_id).map(<[_]>::to_vec);
+        assert!(checkpointed.is_some());
+        fork.set_chunk_ref(path.clone(), ChunkIndices(vec![0]), Some(payload(101)))
+            .await?;
+        assert!(matches!(
+            ds.merge_session(fork).await,
+            Err(SessionError { kind: SessionErrorKind::OverlappingWrites(_), .. })
+        ));
+        assert_eq!(
+            ds.changes().checkpointed_manifests(&node_id).map(<[_]>::to_vec),
+            checkpointed
+        );
+        assert!(!ds.changes().has_chunk_changes(&node_id));
+        assert_eq!(
+            ds.get_chunk_ref(&path, &ChunkIndices(vec![0])).await?,
+            Some(payload(100))
+        );
+
+        // rebasing keeps the checkpoints of arrays the new commits didn't modify
+        let other: Path = "/other".try_into().unwrap();
+        let mut ds = repo.writable_session("main").await?;
+        ds.add_array(
+            other.clone(),
+            ArrayShape::new(vec![(20, 1)]).unwrap(),
+            None,
+            user_data(),
+        )
+        .await?;
+        ds.commit("other array", None).await?;
+        let mut ds1 = repo.writable_session("main").await?;
+        let mut ds2 = repo.writable_session("main").await?;
+        ds1.set_chunk_ref(path.clone(), ChunkIndices(vec![3]), None).await?;
+        ds1.checkpoint().await?;
+        ds2.set_chunk_ref(other.clone(), ChunkIndices(vec![0]), Some(payload(0))).await?;
+        ds2.commit("other chunk", None).await?;
+        ds1.rebase(&ConflictDetector).await?;
+        assert!(ds1.changes().checkpointed_manifests(&node_id).is_some());
+        let snap = ds1.commit("deleted after rebase", None).await?;
+        let tx_log = repo.asset_manager().fetch_transaction_log(&snap).await?;
+        assert_eq!(
+            tx_log.updated_chunks_for(&node_id).collect::<Vec<_>>(),
+            vec![ChunkIndices(vec![3])]
+        );
+        assert_eq!(ds1.get_chunk_ref(&path, &ChunkIndices(vec![3])).await?, None);
+        assert_eq!(
+            ds1.get_chunk_ref(&other, &ChunkIndices(vec![0])).await?,
+            Some(payload(0))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spill_chunk_refs() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let config = RepositoryConfig {
+            spill: Some(SpillConfig { max_in_memory_chunk_refs: Some(10) }),
+            ..RepositoryConfig::default()
+        };
+        let storage = new_in_memory_storage().await?;
+        let repo = Repository::create(Some(config), storage, HashMap::new()).await?;
+        let path: Path = "/array".try_into().unwrap();
+        let payload = |n: u32| Some(ChunkPayload::Inline(format!("{n}").into()));
+        let all_chunks = |ds: &Session| {
+            let ds = ds.clone();
+            async move {
+                ds.all_chunks()
+                    .await?
+                    .map_ok(|(_, info)| (info.coord, info.payload))
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map(|chunks| chunks.into_iter().sorted().collect::<Vec<_>>())
+            }
+        };
+
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_spill_directory(Some(dir.path().to_path_buf()));
+        ds.add_array(
+            path.clone(),
+            ArrayShape::new(vec![(100, 1)]).unwrap(),
+            None,
+            user_data(),
+        )
+        .await?;
+        for i in 0..50 {
+            ds.set_chunk_ref(path.clone(), ChunkIndices(vec![i]), payload(i)).await?;
+        }
+        assert!(ds.changes().in_memory_chunk_refs() <= 10);
+        assert_eq!(ds.get_chunk_ref(&path, &ChunkIndices(vec![7])).await?, payload(7));
+        assert_eq!(all_chunks(&ds).await?.len(), 50);
+        ds.commit("spilled new array", None).await?;
+
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![0]), None).await?;
+        for i in 10..30 {
+            ds.set_chunk_ref(path.clone(), ChunkIndices(vec![i]), payload(i * 10))
+                .await?;
+        }
+        ds.set_chunk_ref(path.clone(), ChunkIndices(vec![99]), payload(99)).await?;
+        assert!(ds.changes().in_memory_chunk_refs() <= 10);
+
+        let expected: Vec<_> = (1..50)
+            .map(|i| {
+                (
+                    ChunkIndices(vec![i]),
+                    payload(if (10..30).contains(&i) { i * 10 } else { i }),
+                )
+            })
+            .chain([(ChunkIndices(vec![99]), payload(99))])
+            .map(|(coord, payload)| (coord, payload.unwrap()))
+            .collect();
+        assert_eq!(all_chunks(&ds).await?, expected);
+        ds.commit("spilled updates", None).await?;
+
+        let ds =
+            repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+        assert_eq!(all_chunks(&ds).await?, expected);
+        assert_eq!(ds.get_chunk_ref(&path, &ChunkIndices(vec![0])).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    /// Spilling and compacting run off the runtime workers, reads block in place
+    async fn test_spill_chunk_refs_multi_thread() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let config = RepositoryConfig {
+            spill: Some(SpillConfig { max_in_memory_chunk_refs: Some(2) }),
+            ..RepositoryConfig::default()
+        };
+        let storage = new_in_memory_storage().await?;
+        let repo = Repository::create(Some(config), storage, HashMap::new()).await?;
+        let path: Path = "/array".try_into().unwrap();
+        let payload = |n: u32| Some(ChunkPayload::Inline(format!("{n}").into()));
+
+        let mut ds = repo.writable_session("main").await?;
+        ds.set_spill_directory(Some(dir.path().to_path_buf()));
+        ds.add_array(
+            path.clone(),
+            ArrayShape::new(vec![(100, 1)]).unwrap(),
+            None,
+            user_data(),
+        )
+        .await?;
+        // enough spills to trigger compaction
+        for i in 0..60 {
+            ds.set_chunk_ref(path.clone(), ChunkIndices(vec![i]), payload(i)).await?;
+        }
+        assert!(ds.changes().in_memory_chunk_refs() <= 2);
+        for i in [0, 31, 59] {
+            assert_eq!(
+                ds.get_chunk_ref(&path, &ChunkIndices(vec![i])).await?,
+                payload(i)
+            );
+        }
+        ds.commit("spilled in a multi thread runtime", None).await?;
+
+        let ds =
+            repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+        assert_eq!(ds.all_chunks().await?.count().await, 60);
+        Ok(())
+    }
+
+    #[tokio::test]
+    /// Test conflict resolution merging metadata attributes
+    ///
+    /// Concurrent updates to different attribute keys are merged, the same key updated to
+    /// different values is a conflict
+    async fn test_conflict_resolution_merge_attributes() -> Result<(), Box<dyn Error>> {
+        fn metadata(value: serde_json::Value) -> Bytes {
+            serde_json::to_vec(&value).unwrap().into()
+        }
+        fn parse(bytes: &Bytes) -> serde_json::Value {
+            serde_json::from_slice(bytes).unwrap()
+        }
+
+        let repo = get_repo_for_conflict().await?;
+        let group_path: Path = "/foo/bar".try_into().unwrap();
+        let array_path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let solver = BasicConflictSolver { merge_attributes: true, ..Default::default() };
+
+        let mut ds1 = repo.writable_session("main").await?;
+        let mut ds2 = repo.writable_session("main").await?;
+        ds1.update_group(
+            &group_path,
+            metadata(serde_json::json!({"node_type": "group", "attributes": {"a": 1}})),
+        )
+        .await?;
+        ds1.update_array(
+            &array_path,
+            basic_shape(),
+            None,
+            metadata(serde_json::json!({"attributes": {"source": "job 1", "a": 1}})),
+        )
+        .await?;
+        ds1.commit("update attributes on repo 1", None).await?;
+
+        ds2.update_group(
+            &group_path,
+            metadata(serde_json::json!({"node_type": "group", "attributes": {"b": 2}})),
+        )
+        .await?;
+        ds2.update_array(
+            &array_path,
+            basic_shape(),
+            None,
+            metadata(serde_json::json!({"attributes": {"a": 1, "b": 2}})),
+        )
+        .await?;
+        ds2.commit("update attributes on repo 2", None).await.unwrap_err();
+
+        // without merging the metadata updates conflict
+        assert_has_conflict(
+            &Conflict::ZarrMetadataDoubleUpdate(group_path.clone()),
+            ds2.rebase(&BasicConflictSolver::default()).await,
+        );
+
+        ds2.rebase(&solver).await?;
+        ds2.commit("after conflict", None).await?;
+        assert_eq!(
+            parse(&ds2.get_node(&group_path).await?.user_data),
+            serde_json::json!({"node_type": "group", "attributes": {"a": 1, "b": 2}})
+        );
+        assert_eq!(
+            parse(&ds2.get_node(&array_path).await?.user_data),
+            serde_json::json!({"attributes": {"source": "job 1", "a": 1, "b": 2}})
+        );
+
+        // the same key changed to different values cannot be merged
+        let mut ds1 = repo.writable_session("main").await?;
+        let mut ds2 = repo.writable_session("main").await?;
+        ds1.update_group(
+            &group_path,
+            metadata(serde_json::json!({"node_type": "group", "attributes": {"a": 10, "b": 2}})),
+        )
+        .await?;
+        ds1.commit("update a", None).await?;
+        ds2.update_group(
+            &group_path,
+            metadata(serde_json::json!({"node_type": "group", "attributes": {"a": 20, "b": 2}})),
+        )
+        .await?;
+        ds2.commit("update a again", None).await.unwrap_err();
+        assert_has_conflict(
+            &Conflict::ZarrMetadataDoubleUpdate(group_path.clone()),
+            ds2.rebase(&solver).await,
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    /// Test conflict resolution merging chunks
+    ///
+    /// Two commits and a session accumulate counts into the same chunk, rebase adds them up
+    async fn test_conflict_resolution_chunk_merge() -> Result<(), Box<dyn Error>> {
+        fn counts(values: &[u32]) -> Bytes {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        }
+        fn values(bytes: &[u8]) -> Vec<u32> {
+            bytes.chunks(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect()
+        }
+
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let coord = ChunkIndices(vec![0]);
+        let write = |session: &Session, values: &[u32]| {
+            session.get_chunk_writer()(counts(values))
+        };
+
+        let mut ds = repo.writable_session("main").await?;
+        let payload = write(&ds, &[1, 1]).await?;
+        ds.set_chunk_ref(path.clone(), coord.clone(), Some(payload)).await?;
+        ds.commit("initial counts", None).await?;
+
+        let mut ds2 = repo.writable_session("main").await?;
+        let payload = write(&ds2, &[1, 6]).await?;
+        ds2.set_chunk_ref(path.clone(), coord.clone(), Some(payload)).await?;
+
+        for (i, delta) in [[2u32, 0], [10, 1]].iter().enumerate() {
+            let mut ds1 = repo.writable_session("main").await?;
+            let current = values(
+                &get_chunk(ds1.get_chunk_reader(&path, &coord, &ByteRange::ALL).await?)
+                    .await?
+                    .unwrap(),
+            );
+            let new: Vec<_> = current.iter().zip(delta).map(|(c, d)| c + d).collect();
+            let payload = write(&ds1, &new).await?;
+            ds1.set_chunk_ref(path.clone(), coord.clone(), Some(payload)).await?;
+            ds1.commit(format!("accumulate {}", i).as_str(), None).await?;
+        }
+
+        ds2.commit("accumulate on repo 2", None).await.unwrap_err();
+
+        // a merge function that refuses to merge falls back to the basic solver
+        let refusing =
+            ChunkMergeSolver::new(|_, _, _| None).with_fallback(BasicConflictSolver {
+                on_chunk_conflict: VersionSelection::Fail,
+                ..Default::default()
+            });
+        assert_has_conflict(
+            &Conflict::ChunkDoubleUpdate {
+                path: path.clone(),
+                node_id: ds2.get_array(&path).await?.id,
+                chunk_coordinates: [coord.clone()].into(),
+            },
+            ds2.rebase(&refusing).await,
+        );
+
+        let adding = ChunkMergeSolver::new(|ancestor, ours, theirs| {
+            let (ancestor, ours, theirs) =
+                (values(&ancestor?), values(&ours?), values(&theirs?));
+            let merged: Vec<_> = ancestor
+                .iter()
+                .zip(ours)
+                .zip(theirs)
+                .map(|((a, o), t)| o + t - a)
+                .collect();
+            Some(counts(&merged))
+        });
+        ds2.rebase(&adding).await?;
+        ds2.commit("after conflict", None).await?;
+
+        let data = get_chunk(ds2.get_chunk_reader(&path, &coord, &ByteRange::ALL).await?)
+            .await?;
+        assert_eq!(values(&data.unwrap()), vec![13, 7]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    /// Test rebase preview
+    ///
+    /// Conflicts and resolutions are reported for every commit, and the session is not modified
+    async fn test_preview_rebase() -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let mut ds2 = repo.writable_session("main").await?;
+
+        let mut snapshots = Vec::new();
+        for coord in [0u32, 1] {
+            let mut ds1 = repo.writable_session("main").await?;
+            ds1.set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![coord]),
+                Some(ChunkPayload::Inline("repo 1".into())),
+            )
+            .await?;
+            snapshots.push(
+                ds1.commit(format!("update chunk {}", coord).as_str(), None).await?,
+            );
+        }
+
+        for coord in [0u32, 2] {
+            ds2.set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![coord]),
+                Some(ChunkPayload::Inline("repo 2".into())),
+            )
+            .await?;
+        }
+        let snapshot_before = ds2.snapshot_id().clone();
+        let changes_before = ds2.changes().clone();
+
+        let solver = BasicConflictSolver {
+            on_chunk_conflict: VersionSelection::Fail,
+            ..Default::default()
+        };
+        let steps = ds2.preview_rebase(&solver).await?;
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].snapshot_id, snapshots[0]);
+        assert!(matches!(
+            steps[0].conflicts.as_slice(),
+            [Conflict::ChunkDoubleUpdate { chunk_coordinates, .. }]
+                if chunk_coordinates == &[ChunkIndices(vec![0])].into()
+        ));
+        assert!(matches!(steps[0].resolution, ConflictResolution::Unsolvable { .. }));
+        assert_eq!(steps[1].snapshot_id, snapshots[1]);
+        assert!(steps[1].conflicts.is_empty());
+        assert!(matches!(steps[1].resolution, ConflictResolution::Patched(_)));
+
+        let solver = BasicConflictSolver {
+            on_chunk_conflict: VersionSelection::UseTheirs,
+            ..Default::default()
+        };
+        let steps = ds2.preview_rebase(&solver).await?;
+        assert_eq!(steps[0].conflicts.len(), 1);
+        match &steps[0].resolution {
+            ConflictResolution::Patched(changes) => {
+                let node_id = ds2.get_array(&path).await?.id;
+                assert_eq!(
+                    changes.get_chunk_ref(&node_id, &ChunkIndices(vec![0]))?,
+                    None
+                );
+                assert!(
+                    changes.get_chunk_ref(&node_id, &ChunkIndices(vec![2]))?.is_some()
+                );
+            }
+            other => panic!("expected patched changes, got {:?}", other),
+        }
+
+        // merged chunks are not uploaded by the preview
+        let count_chunks = || async {
+            let storage = repo.storage();
+            storage.list_chunks(&storage.default_settings()).await.unwrap().count().await
+        };
+        let chunks_before = count_chunks().await;
+        let merging = ChunkMergeSolver::new(|_, _, _| Some(Bytes::from(vec![0; 1024])));
+        let steps = ds2.preview_rebase(&merging).await?;
+        assert!(matches!(steps[0].resolution, ConflictResolution::Patched(_)));
+        assert_eq!(count_chunks().await, chunks_before);
+
+        assert_eq!(ds2.snapshot_id(), &snapshot_before);
+        assert_eq!(ds2.changes(), &changes_before);
+        Ok(())
+    }
+
+    #[tokio::test]
+    /// Chunks merged, but not uploaded, by a rebase preview can be merged again by later steps
+    async fn test_preview_rebase_merges_same_chunk_twice() -> Result<(), Box<dyn Error>> {
+        let repo = get_repo_for_conflict().await?;
+        let path: Path = "/foo/bar/some-array".try_into().unwrap();
+        let mut ds2 = repo.writable_session("main").await?;
+
+        for message in ["first", "second"] {
+            let mut ds1 = repo.writable_session("main").await?;
+            ds1.set_chunk_ref(
+                path.clone(),
+                ChunkIndices(vec![0]),
+                Some(ChunkPayload::Inline(message.into())),
+            )
+            .await?;
+            ds1.commit(message, None).await?;
+        }
+        ds2.set_chunk_ref(
+            path.clone(),
+            ChunkIndices(vec![0]),
+            Some(ChunkPayload::Inline("repo 2".into())),
+        )
+        .await?;
+
+        // the merged chunks are larger than the inline threshold
+        let merging = ChunkMergeSolver::new(|_, ours, _| {
+            Some([ours?.as_ref(), &[0; 1024]].concat().into())
+        });
+        let steps = ds2.preview_rebase(&merging).await?;
+        assert_eq!(steps.len(), 2);
+        let node_id = ds2.get_array(&path).await?.id;
+        match &steps[1].resolution {
+            ConflictResolution::Patched(changes) => {
+                let payload = changes.get_chunk_ref(&node_id, &ChunkIndices(vec![0]))?;
+                assert!(matches!(
+                    payload,
+                    Some(Some(ChunkPayload::Inline(bytes))) if bytes.len() == 6 + 2 * 1024
+                ));
+            }
+            other => panic!("expected patched changes, got {:?}", other),
+        }
+        Ok(())
+    }
+
     #[tokio::test]
     /// Rebase over multiple commits with partial failure
     ///
@@ -0,0 +1,135 @@
+//! Leases that protect the objects written by in-flight sessions from garbage collection
+//!
+//! Leases live in their own top level directory, outside of the refs.
+
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use serde_with::{TryFromInto, serde_as};
+use thiserror::Error;
+use tracing::instrument;
+
+use crate::{
+    Storage,
+    error::ICError,
+    format::SnapshotId,
+    refs::{RefError, RefErrorKind, prefixed::PrefixedObjects},
+    storage::{self, LEASE_PREFIX},
+};
+
+const LEASE_KEY_NAME: &str = "lease.json";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LeaseErrorKind {
+    #[error(transparent)]
+    Ref(RefErrorKind),
+    #[error("cannot serialize session lease json")]
+    Serialization(#[from] serde_json::Error),
+}
+
+pub type LeaseError = ICError<LeaseErrorKind>;
+
+// it would be great to define this impl in error.rs, but it conflicts with the blanket
+// `impl From<T> for T`
+impl<E> From<E> for LeaseError
+where
+    E: Into<LeaseErrorKind>,
+{
+    fn from(value: E) -> Self {
+        Self::new(value.into())
+    }
+}
+
+impl From<RefError> for LeaseError {
+    fn from(value: RefError) -> Self {
+        Self::with_context(LeaseErrorKind::Ref(value.kind), value.context)
+    }
+}
+
+pub type LeaseResult<T> = Result<T, LeaseError>;
+
+/// Protects the objects written by an in-flight session from garbage collection
+///
+/// Garbage collection doesn't delete anything created after `acquired_at` while the lease
+/// is active, and treats `snapshot_id` as a root.
+#[serde_as]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionLease {
+    pub branch: String,
+    /// The snapshot the session is based on
+    #[serde_as(as = "TryFromInto<String>")]
+    pub snapshot_id: SnapshotId,
+    pub acquired_at: DateTime<Utc>,
+    /// The lease is ignored after this time, unless the session renews it
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SessionLease {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+fn leases<'a>(
+    storage: &'a (dyn Storage + Send + Sync),
+    storage_settings: &'a storage::Settings,
+) -> PrefixedObjects<'a> {
+    PrefixedObjects::new(storage, storage_settings, LEASE_PREFIX)
+}
+
+/// Store `lease` under `id`, replacing any previous version of it
+#[instrument(skip(storage, storage_settings))]
+pub async fn write_lease(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    id: &str,
+    lease: &SessionLease,
+) -> LeaseResult<()> {
+    let key = PrefixedObjects::key(id, LEASE_KEY_NAME)?;
+    let content = serde_json::to_vec(lease)?;
+    // only the session holding the lease writes it, but retries can race
+    leases(storage, storage_settings).overwrite(key.as_str(), content.into()).await?;
+    Ok(())
+}
+
+/// All the leases in the repository, including the expired ones, with their ids
+#[instrument(skip(storage, storage_settings))]
+pub async fn list_leases(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> LeaseResult<Vec<(String, SessionLease)>> {
+    let objects = leases(storage, storage_settings);
+    let ids = objects.names("").await?;
+    let objects = &objects;
+    let leases: Vec<Option<(String, SessionLease)>> = stream::iter(ids)
+        .map(|id| async move {
+            let key = PrefixedObjects::key(&id, LEASE_KEY_NAME)?;
+            // released while we were listing if it's missing
+            match objects.get(key.as_str()).await? {
+                Some(bytes) => {
+                    LeaseResult::Ok(Some((id, serde_json::from_slice(bytes.as_ref())?)))
+                }
+                None => Ok(None),
+            }
+        })
+        .buffer_unordered(
+            storage_settings.concurrency().max_concurrent_requests_for_object().get()
+                as usize,
+        )
+        .try_collect()
+        .await?;
+    Ok(leases.into_iter().flatten().collect())
+}
+
+/// Delete the lease with `id`, releasing a lease that doesn't exist is not an error
+#[instrument(skip(storage, storage_settings))]
+pub async fn delete_lease(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    id: &str,
+) -> LeaseResult<()> {
+    let key = PrefixedObjects::key(id, LEASE_KEY_NAME)?;
+    leases(storage, storage_settings).delete(vec![key]).await?;
+    Ok(())
+}
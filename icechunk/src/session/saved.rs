@@ -0,0 +1,105 @@
+//! Writable sessions persisted with [`Session::save`](super::Session::save)
+//!
+//! Saved sessions live in their own top level directory, outside of the refs.
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    Storage,
+    change_set::ChangeSet,
+    format::SnapshotId,
+    refs::prefixed::PrefixedObjects,
+    repository::{RepositoryErrorKind, RepositoryResult},
+    storage::{self, SAVED_SESSION_PREFIX},
+};
+
+const SAVED_SESSION_KEY_NAME: &str = "session.msgpack";
+
+/// The state of a writable session persisted with [`Session::save`](super::Session::save)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SavedSession {
+    pub branch_name: String,
+    pub snapshot_id: SnapshotId,
+    pub saved_at: DateTime<Utc>,
+    pub change_set: ChangeSet,
+}
+
+// same layout as `SavedSession`, it allows to serialize the state without cloning the changes
+#[derive(Serialize)]
+pub(super) struct SavedSessionRef<'a> {
+    pub branch_name: &'a str,
+    pub snapshot_id: &'a SnapshotId,
+    pub saved_at: DateTime<Utc>,
+    pub change_set: &'a ChangeSet,
+}
+
+impl SavedSession {
+    pub(crate) async fn fetch(
+        storage: &(dyn Storage + Send + Sync),
+        storage_settings: &storage::Settings,
+        name: &str,
+    ) -> RepositoryResult<Self> {
+        let bytes = fetch_saved_session(storage, storage_settings, name).await?;
+        Ok(rmp_serde::from_slice(&bytes)?)
+    }
+}
+
+fn saved_sessions<'a>(
+    storage: &'a (dyn Storage + Send + Sync),
+    storage_settings: &'a storage::Settings,
+) -> PrefixedObjects<'a> {
+    PrefixedObjects::new(storage, storage_settings, SAVED_SESSION_PREFIX)
+}
+
+/// Store the serialized state of a session under `name`, replacing any previous state.
+#[instrument(skip(storage, storage_settings, bytes))]
+pub async fn write_saved_session(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    name: &str,
+    bytes: Bytes,
+) -> RepositoryResult<()> {
+    let key = PrefixedObjects::key(name, SAVED_SESSION_KEY_NAME)?;
+    saved_sessions(storage, storage_settings).overwrite(key.as_str(), bytes).await?;
+    Ok(())
+}
+
+#[instrument(skip(storage, storage_settings))]
+pub async fn fetch_saved_session(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    name: &str,
+) -> RepositoryResult<Bytes> {
+    let key = PrefixedObjects::key(name, SAVED_SESSION_KEY_NAME)?;
+    saved_sessions(storage, storage_settings)
+        .get(key.as_str())
+        .await?
+        .ok_or_else(|| RepositoryErrorKind::SavedSessionNotFound(name.to_string()).into())
+}
+
+/// Sorted names of the saved sessions
+#[instrument(skip(storage, storage_settings))]
+pub async fn list_saved_sessions(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+) -> RepositoryResult<Vec<String>> {
+    let mut names = saved_sessions(storage, storage_settings).names("").await?;
+    names.sort();
+    Ok(names)
+}
+
+#[instrument(skip(storage, storage_settings))]
+pub async fn delete_saved_session(
+    storage: &(dyn Storage + Send + Sync),
+    storage_settings: &storage::Settings,
+    name: &str,
+) -> RepositoryResult<()> {
+    // we make sure the session exists
+    _ = fetch_saved_session(storage, storage_settings, name).await?;
+    let key = PrefixedObjects::key(name, SAVED_SESSION_KEY_NAME)?;
+    saved_sessions(storage, storage_settings).delete(vec![key]).await?;
+    Ok(())
+}
@@ -175,26 +175,44 @@ impl Storage for LoggingStorage {
         self.backend.write_chunk(settings, id, bytes).await
     }
 
-    async fn get_ref(
+    async fn get_ref_in(
         &self,
         settings: &Settings,
+        root: &str,
         ref_key: &str,
     ) -> StorageResult<GetRefResult> {
-        self.backend.get_ref(settings, ref_key).await
+        self.backend.get_ref_in(settings, root, ref_key).await
     }
 
-    async fn ref_names(&self, settings: &Settings) -> StorageResult<Vec<String>> {
-        self.backend.ref_names(settings).await
+    async fn ref_names_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> StorageResult<Vec<String>> {
+        self.backend.ref_names_in(settings, root, prefix, start_after, limit).await
+    }
+
+    async fn ref_objects_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        prefix: &str,
+    ) -> StorageResult<Vec<ListInfo<String>>> {
+        self.backend.ref_objects_in(settings, root, prefix).await
     }
 
-    async fn write_ref(
+    async fn write_ref_in(
         &self,
         settings: &Settings,
+        root: &str,
         ref_key: &str,
         bytes: Bytes,
         previous_version: &VersionInfo,
     ) -> StorageResult<WriteRefResult> {
-        self.backend.write_ref(settings, ref_key, bytes, previous_version).await
+        self.backend.write_ref_in(settings, root, ref_key, bytes, previous_version).await
     }
 
     async fn list_objects<'a>(
@@ -48,7 +48,7 @@ pub use object_store::ObjectStorage;
 use crate::{
     config::{AzureCredentials, GcsCredentials, S3Credentials, S3Options},
     error::ICError,
-    format::{ChunkId, ChunkOffset, ManifestId, SnapshotId},
+    format::{AttributesId, ChunkId, ChunkOffset, ManifestId, SnapshotId},
     private,
 };
 
@@ -79,6 +79,22 @@ pub enum StorageErrorKind {
 }
 pub type StorageError = ICError<StorageErrorKind>;
 
+impl StorageError {
+    /// The requested object doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        match &self.kind {
+            StorageErrorKind::ObjectStore(::object_store::Error::NotFound { .. }) => true,
+            StorageErrorKind::S3GetObjectError(err) => {
+                err.as_service_error().is_some_and(|err| err.is_no_such_key())
+                    // some S3 compatible stores answer with an unparsed 404
+                    || err.raw_response().is_some_and(|res| res.status().as_u16() == 404)
+            }
+            StorageErrorKind::IOError(err) => err.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+}
+
 // it would be great to define this impl in error.rs, but it conflicts with the blanket
 // `impl From<T> for T`
 impl<E> From<E> for StorageError
@@ -101,9 +117,15 @@ pub struct ListInfo<Id> {
 
 const SNAPSHOT_PREFIX: &str = "snapshots/";
 const MANIFEST_PREFIX: &str = "manifests/";
-// const ATTRIBUTES_PREFIX: &str = "attributes/";
+const ATTRIBUTES_PREFIX: &str = "attributes/";
 const CHUNK_PREFIX: &str = "chunks/";
-const REF_PREFIX: &str = "refs";
+pub(crate) const REF_PREFIX: &str = "refs";
+// mutable objects that are not refs live outside of `refs`, where clients that don't know
+// them would fail to parse their names
+pub(crate) const REFLOG_PREFIX: &str = "reflog";
+pub(crate) const SAVED_SESSION_PREFIX: &str = "sessions";
+pub(crate) const LEASE_PREFIX: &str = "leases";
+pub(crate) const GC_PREFIX: &str = "gc";
 const TRANSACTION_PREFIX: &str = "transactions/";
 const CONFIG_PATH: &str = "config.yaml";
 
@@ -394,14 +416,72 @@ pub trait Storage: fmt::Debug + fmt::Display + private::Sealed + Sync + Send {
         &self,
         settings: &Settings,
         ref_key: &str,
+    ) -> StorageResult<GetRefResult> {
+        self.get_ref_in(settings, REF_PREFIX, ref_key).await
+    }
+
+    /// Like [`Storage::get_ref`], for an object under the top level directory `root`
+    /// instead of the refs root
+    async fn get_ref_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        ref_key: &str,
     ) -> StorageResult<GetRefResult>;
-    async fn ref_names(&self, settings: &Settings) -> StorageResult<Vec<String>>;
+
+    /// Names of all the refs
+    ///
+    /// The name of a ref is the path of its parent directory, relative to the refs root,
+    /// for example `branch.main` or `branch.users/alice/experiment`.
+    async fn ref_names(&self, settings: &Settings) -> StorageResult<Vec<String>> {
+        self.ref_names_in(settings, REF_PREFIX, "", None, None).await
+    }
+
+    /// Names of the objects under the top level directory `root` that start with `prefix`,
+    /// the empty prefix lists all of them
+    ///
+    /// Names are the paths of the parent directories of the objects, relative to `root`, as
+    /// in [`Storage::ref_names`]. They are sorted by the keys of their objects, and the
+    /// objects of a ref are listed together, so its name is returned once. Only objects with
+    /// keys after `start_after`, relative to `root`, are listed, and listing stops once
+    /// `limit` names are found.
+    async fn ref_names_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> StorageResult<Vec<String>>;
+
+    /// Objects under the top level directory `root` with keys that start with `prefix`
+    ///
+    /// Their ids are the keys relative to `root`, for example `tag.v1/ref.json`.
+    async fn ref_objects_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        prefix: &str,
+    ) -> StorageResult<Vec<ListInfo<String>>>;
+
     async fn write_ref(
         &self,
         settings: &Settings,
         ref_key: &str,
         bytes: Bytes,
         previous_version: &VersionInfo,
+    ) -> StorageResult<WriteRefResult> {
+        self.write_ref_in(settings, REF_PREFIX, ref_key, bytes, previous_version).await
+    }
+
+    /// Like [`Storage::write_ref`], for an object under the top level directory `root`
+    async fn write_ref_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        ref_key: &str,
+        bytes: Bytes,
+        previous_version: &VersionInfo,
     ) -> StorageResult<WriteRefResult>;
 
     async fn list_objects<'a>(
@@ -486,6 +566,15 @@ pub trait Storage: fmt::Debug + fmt::Display + private::Sealed + Sync + Send {
         Ok(translate_list_infos(self.list_objects(settings, TRANSACTION_PREFIX).await?))
     }
 
+    /// Attribute files are no longer written, but repositories created by older versions
+    /// can have them
+    async fn list_attributes(
+        &self,
+        settings: &Settings,
+    ) -> StorageResult<BoxStream<StorageResult<ListInfo<AttributesId>>>> {
+        Ok(translate_list_infos(self.list_objects(settings, ATTRIBUTES_PREFIX).await?))
+    }
+
     async fn delete_chunks(
         &self,
         settings: &Settings,
@@ -538,13 +627,36 @@ pub trait Storage: fmt::Debug + fmt::Display + private::Sealed + Sync + Send {
         .await
     }
 
+    async fn delete_attributes(
+        &self,
+        settings: &Settings,
+        attributes: BoxStream<'_, (AttributesId, u64)>,
+    ) -> StorageResult<DeleteObjectsResult> {
+        self.delete_objects(
+            settings,
+            ATTRIBUTES_PREFIX,
+            attributes.map(|(id, size)| (id.to_string(), size)).boxed(),
+        )
+        .await
+    }
+
     async fn delete_refs(
         &self,
         settings: &Settings,
         refs: BoxStream<'_, String>,
+    ) -> StorageResult<u64> {
+        self.delete_refs_in(settings, REF_PREFIX, refs).await
+    }
+
+    /// Like [`Storage::delete_refs`], for objects under the top level directory `root`
+    async fn delete_refs_in(
+        &self,
+        settings: &Settings,
+        root: &str,
+        refs: BoxStream<'_, String>,
     ) -> StorageResult<u64> {
         let refs = refs.map(|s| (s, 0)).boxed();
-        Ok(self.delete_objects(settings, REF_PREFIX, refs).await?.deleted_objects)
+        Ok(self.delete_objects(settings, root, refs).await?.deleted_objects)
     }
 
     async fn get_object_range_buf(
@@ -629,6 +741,28 @@ where
     .boxed()
 }
 
+/// Add `name`, listed in key order, to `names`, unless it was the last name added
+///
+/// The objects of one ref are listed next to each other because ref names can't have path
+/// components that start with `ref.json`.
+///
+/// Returns `false`, without adding it, if `names` already has `limit` names, so listing can
+/// stop.
+pub(crate) fn push_ref_name(
+    names: &mut Vec<String>,
+    name: &str,
+    limit: Option<usize>,
+) -> bool {
+    if names.last().is_some_and(|last| last == name) {
+        return true;
+    }
+    if limit.is_some_and(|limit| names.len() >= limit) {
+        return false;
+    }
+    names.push(name.to_string());
+    true
+}
+
 /// Split an object request into multiple byte range requests
 ///
 /// Returns tuples of Range for each request.
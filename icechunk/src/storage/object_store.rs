@@ -44,9 +44,9 @@ use tracing::instrument;
 
 use super::{
     CHUNK_PREFIX, CONFIG_PATH, ConcurrencySettings, DeleteObjectsResult, ETag,
-    FetchConfigResult, Generation, GetRefResult, ListInfo, MANIFEST_PREFIX, REF_PREFIX,
-    Reader, SNAPSHOT_PREFIX, Settings, Storage, StorageError, StorageErrorKind,
-    StorageResult, TRANSACTION_PREFIX, UpdateConfigResult, VersionInfo, WriteRefResult,
+    FetchConfigResult, Generation, GetRefResult, ListInfo, MANIFEST_PREFIX, Reader,
+    SNAPSHOT_PREFIX, Settings, Storage, StorageError, StorageErrorKind, StorageResult,
+    TRANSACTION_PREFIX, UpdateConfigResult, VersionInfo, WriteRefResult, push_ref_name,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -202,9 +202,9 @@ impl ObjectStorage {
         path.prefix_match(&ObjectPath::from(format!("{}", prefix))).map(|it| it.collect())
     }
 
-    fn ref_key(&self, ref_key: &str) -> ObjectPath {
+    fn ref_key(&self, root: &str, ref_key: &str) -> ObjectPath {
         // ObjectPath knows how to deal with empty path parts: bar//foo
-        ObjectPath::from(format!("{}/{}/{}", self.backend.prefix(), REF_PREFIX, ref_key))
+        ObjectPath::from(format!("{}/{}/{}", self.backend.prefix(), root, ref_key))
     }
 
     async fn get_object_reader(
@@ -242,8 +242,13 @@ impl ObjectStorage {
 
     fn get_ref_name(&self, prefix: &ObjectPath, meta: &ObjectMeta) -> Option<String> {
         let relative_key = self.drop_prefix(prefix, &meta.location)?;
-        let parent = relative_key.parts().next()?;
-        Some(parent.as_ref().to_string())
+        let mut parts: Vec<_> = relative_key.parts().collect();
+        // the last part is the name of the object inside the ref directory
+        parts.pop()?;
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.iter().map(|part| part.as_ref()).collect::<Vec<_>>().join("/"))
     }
 
     fn get_put_mode(
@@ -461,12 +466,13 @@ impl Storage for ObjectStorage {
     }
 
     #[instrument(skip(self, _settings))]
-    async fn get_ref(
+    async fn get_ref_in(
         &self,
         _settings: &Settings,
+        root: &str,
         ref_key: &str,
     ) -> StorageResult<GetRefResult> {
-        let key = self.ref_key(ref_key);
+        let key = self.ref_key(root, ref_key);
         match self.get_client().await.get(&key).await {
             Ok(res) => {
                 let etag = res.meta.e_tag.clone().map(ETag);
@@ -482,33 +488,86 @@ impl Storage for ObjectStorage {
     }
 
     #[instrument(skip(self, _settings))]
-    async fn ref_names(&self, _settings: &Settings) -> StorageResult<Vec<String>> {
-        let prefix = &self.ref_key("");
+    async fn ref_names_in(
+        &self,
+        _settings: &Settings,
+        root: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> StorageResult<Vec<String>> {
+        let list_root = &self.ref_key(root, "");
+        // object_store can only list full path segments, so we list the deepest
+        // directory in the prefix and filter the rest here
+        let dir = prefix.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default();
+        let list_prefix = self.ref_key(root, dir);
+
+        let client = self.get_client().await;
+        let mut objects = match start_after {
+            Some(key) => {
+                client.list_with_offset(Some(&list_prefix), &self.ref_key(root, key))
+            }
+            None => client.list(Some(&list_prefix)),
+        };
+        if self.artificially_sort_refs_in_mem() {
+            let mut all: Vec<_> = objects.try_collect().await?;
+            all.sort_by(|a, b| a.location.cmp(&b.location));
+            objects = stream::iter(all.into_iter().map(Ok)).boxed();
+        }
+
+        let mut res = Vec::new();
+        while let Some(meta) = objects.try_next().await? {
+            match self.get_ref_name(list_root, &meta) {
+                Some(name) if name.starts_with(prefix) => {
+                    if !push_ref_name(&mut res, &name, limit) {
+                        break;
+                    }
+                }
+                Some(_) => {}
+                None => tracing::error!(object = ?meta, "Bad ref name"),
+            }
+        }
+        Ok(res)
+    }
+
+    async fn ref_objects_in(
+        &self,
+        _settings: &Settings,
+        root: &str,
+        prefix: &str,
+    ) -> StorageResult<Vec<ListInfo<String>>> {
+        let list_root = &self.ref_key(root, "");
+        // same as in `ref_names_in`, we can only list full path segments
+        let dir = prefix.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default();
+        let list_prefix = self.ref_key(root, dir);
 
         Ok(self
             .get_client()
             .await
-            .list(Some(prefix.clone()).as_ref())
+            .list(Some(&list_prefix))
             .try_filter_map(|meta| async move {
-                let name = self.get_ref_name(prefix, &meta);
-                if name.is_none() {
-                    tracing::error!(object = ?meta, "Bad ref name")
-                }
-                Ok(name)
+                let info =
+                    self.drop_prefix(list_root, &meta.location).map(|key| ListInfo {
+                        id: key.to_string(),
+                        created_at: meta.last_modified,
+                        size_bytes: meta.size,
+                    });
+                Ok(info.filter(|info| info.id.starts_with(prefix)))
             })
             .try_collect()
             .await?)
     }
 
     #[instrument(skip(self, settings, bytes))]
-    async fn write_ref(
+    async fn write_ref_in(
         &self,
         settings: &Settings,
+        root: &str,
         ref_key: &str,
         bytes: Bytes,
         previous_version: &VersionInfo,
     ) -> StorageResult<WriteRefResult> {
-        let key = self.ref_key(ref_key);
+        let key = self.ref_key(root, ref_key);
         let mode = self.get_put_mode(settings, previous_version);
         let opts = PutOptions { mode, ..PutOptions::default() };
 
@@ -1022,7 +1081,10 @@ mod tests {
 
     use tempfile::TempDir;
 
-    use crate::format::{ChunkId, ManifestId, SnapshotId};
+    use crate::{
+        format::{ChunkId, ManifestId, SnapshotId},
+        storage::{REF_PREFIX, REFLOG_PREFIX},
+    };
 
     use super::ObjectStorage;
 
@@ -1081,8 +1143,10 @@ mod tests {
             .unwrap();
 
         let ref_key = "ref_key";
-        let ref_path = store.ref_key(ref_key);
+        let ref_path = store.ref_key(REF_PREFIX, ref_key);
         assert_eq!(ref_path.to_string(), format!("refs/{ref_key}"));
+        let reflog_path = store.ref_key(REFLOG_PREFIX, ref_key);
+        assert_eq!(reflog_path.to_string(), format!("reflog/{ref_key}"));
 
         let snapshot_id = SnapshotId::random();
         let snapshot_path = store.get_snapshot_path(&snapshot_id);
@@ -42,9 +42,9 @@ use tracing::instrument;
 
 use super::{
     CHUNK_PREFIX, CONFIG_PATH, DeleteObjectsResult, FetchConfigResult, GetRefResult,
-    ListInfo, MANIFEST_PREFIX, REF_PREFIX, Reader, SNAPSHOT_PREFIX, Settings,
-    StorageErrorKind, StorageResult, TRANSACTION_PREFIX, UpdateConfigResult, VersionInfo,
-    WriteRefResult,
+    ListInfo, MANIFEST_PREFIX, Reader, SNAPSHOT_PREFIX, Settings, StorageErrorKind,
+    StorageResult, TRANSACTION_PREFIX, UpdateConfigResult, VersionInfo, WriteRefResult,
+    push_ref_name,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -255,8 +255,8 @@ impl S3Storage {
         self.get_path(TRANSACTION_PREFIX, id)
     }
 
-    fn ref_key(&self, ref_key: &str) -> StorageResult<String> {
-        let path = PathBuf::from_iter([self.prefix.as_str(), REF_PREFIX, ref_key]);
+    fn ref_key(&self, root: &str, ref_key: &str) -> StorageResult<String> {
+        let path = PathBuf::from_iter([self.prefix.as_str(), root, ref_key]);
         let path_str =
             path.into_os_string().into_string().map_err(StorageErrorKind::BadPrefix)?;
 
@@ -301,11 +301,11 @@ impl S3Storage {
         Ok(())
     }
 
-    fn get_ref_name<'a>(&self, key: Option<&'a str>) -> Option<&'a str> {
+    fn get_ref_name<'a>(&self, root: &str, key: Option<&'a str>) -> Option<&'a str> {
         let key = key?;
-        let prefix = self.ref_key("").ok()?;
+        let prefix = self.ref_key(root, "").ok()?;
         let relative_key = key.strip_prefix(&prefix)?;
-        let ref_name = relative_key.split('/').next()?;
+        let (ref_name, _) = relative_key.rsplit_once('/')?;
         Some(ref_name)
     }
 }
@@ -550,12 +550,13 @@ impl Storage for S3Storage {
     }
 
     #[instrument(skip(self, _settings))]
-    async fn get_ref(
+    async fn get_ref_in(
         &self,
         _settings: &Settings,
+        root: &str,
         ref_key: &str,
     ) -> StorageResult<GetRefResult> {
-        let key = self.ref_key(ref_key)?;
+        let key = self.ref_key(root, ref_key)?;
         let res = self
             .get_client()
             .await
@@ -587,24 +588,45 @@ impl Storage for S3Storage {
     }
 
     #[instrument(skip(self, _settings))]
-    async fn ref_names(&self, _settings: &Settings) -> StorageResult<Vec<String>> {
-        let prefix = self.ref_key("")?;
+    async fn ref_names_in(
+        &self,
+        _settings: &Settings,
+        root: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> StorageResult<Vec<String>> {
+        let mut res = Vec::new();
+        if limit == Some(0) {
+            return Ok(res);
+        }
+
+        let prefix = self.ref_key(root, prefix)?;
+        let start_after = match start_after {
+            Some(key) => Some(self.ref_key(root, key)?),
+            None => None,
+        };
+        // one more key than needed, to know the page is complete without another request
+        let max_keys =
+            limit.map(|limit| i32::try_from(limit.saturating_add(1)).unwrap_or(i32::MAX));
         let mut paginator = self
             .get_client()
             .await
             .list_objects_v2()
             .bucket(self.bucket.clone())
             .prefix(prefix.clone())
+            .set_start_after(start_after)
+            .set_max_keys(max_keys)
             .into_paginator()
             .send();
 
-        let mut res = Vec::new();
-
-        while let Some(page) = paginator.try_next().await? {
+        'pages: while let Some(page) = paginator.try_next().await? {
             for obj in page.contents.unwrap_or_else(Vec::new) {
-                let name = self.get_ref_name(obj.key());
+                let name = self.get_ref_name(root, obj.key());
                 if let Some(name) = name {
-                    res.push(name.to_string());
+                    if !push_ref_name(&mut res, name, limit) {
+                        break 'pages;
+                    }
                 } else {
                     tracing::error!(object = ?obj, "Bad ref name")
                 }
@@ -614,15 +636,52 @@ impl Storage for S3Storage {
         Ok(res)
     }
 
+    async fn ref_objects_in(
+        &self,
+        _settings: &Settings,
+        root: &str,
+        prefix: &str,
+    ) -> StorageResult<Vec<ListInfo<String>>> {
+        let root_prefix = self.ref_key(root, "")?;
+        let mut paginator = self
+            .get_client()
+            .await
+            .list_objects_v2()
+            .bucket(self.bucket.clone())
+            .prefix(self.ref_key(root, prefix)?)
+            .into_paginator()
+            .send();
+
+        let mut res = Vec::new();
+        while let Some(page) = paginator.try_next().await? {
+            for obj in page.contents.unwrap_or_else(Vec::new) {
+                let id = obj.key().and_then(|key| key.strip_prefix(&root_prefix));
+                let created_at =
+                    obj.last_modified().and_then(|time| time.to_chrono_utc().ok());
+                if let (Some(id), Some(created_at)) = (id, created_at) {
+                    res.push(ListInfo {
+                        id: id.to_string(),
+                        created_at,
+                        size_bytes: obj.size.unwrap_or(0) as u64,
+                    });
+                } else {
+                    tracing::error!(object = ?obj, "Bad ref object")
+                }
+            }
+        }
+        Ok(res)
+    }
+
     #[instrument(skip(self, settings, bytes))]
-    async fn write_ref(
+    async fn write_ref_in(
         &self,
         settings: &Settings,
+        root: &str,
         ref_key: &str,
         bytes: Bytes,
         previous_version: &VersionInfo,
     ) -> StorageResult<WriteRefResult> {
-        let key = self.ref_key(ref_key)?;
+        let key = self.ref_key(root, ref_key)?;
         let mut builder = self
             .get_client()
             .await
@@ -849,7 +908,10 @@ async fn get_object_range(
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
-    use crate::config::{S3Credentials, S3Options, S3StaticCredentials};
+    use crate::{
+        config::{S3Credentials, S3Options, S3StaticCredentials},
+        storage::{REF_PREFIX, REFLOG_PREFIX},
+    };
 
     use super::*;
 
@@ -909,8 +971,10 @@ mod tests {
         )
         .unwrap();
 
-        let ref_path = storage.ref_key("ref_key").unwrap();
+        let ref_path = storage.ref_key(REF_PREFIX, "ref_key").unwrap();
         assert_eq!(ref_path, "prefix/refs/ref_key");
+        let reflog_path = storage.ref_key(REFLOG_PREFIX, "ref_key").unwrap();
+        assert_eq!(reflog_path, "prefix/reflog/ref_key");
 
         let snapshot_id = SnapshotId::random();
         let snapshot_path = storage.get_snapshot_path(&snapshot_id).unwrap();
@@ -0,0 +1,244 @@
+//! Checks run by [`Session::commit`](crate::session::Session::commit) before a new snapshot is
+//! published.
+//!
+//! Validators are registered on the [`Repository`](crate::Repository) with
+//! [`add_commit_validator`](crate::Repository::add_commit_validator), every writable session
+//! created from it runs them. If any of them finds a problem the commit is rejected with
+//! [`SessionErrorKind::CommitRejected`](crate::session::SessionErrorKind::CommitRejected) and
+//! nothing is written to the repository.
+//!
+//! Validators are not serialized, sessions and repositories only keep their names. A
+//! deserialized session fails to commit with
+//! [`SessionErrorKind::MissingCommitValidators`](crate::session::SessionErrorKind::MissingCommitValidators)
+//! until the validators are attached again, with
+//! [`Repository::session_from_bytes`](crate::Repository::session_from_bytes) or
+//! [`Session::attach_commit_validators`](crate::session::Session::attach_commit_validators).
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    change_set::ChangeSet,
+    format::{
+        IcechunkResult, NodeId, Path,
+        snapshot::{DimensionName, NodeData, NodeSnapshot, Snapshot, SnapshotProperties},
+    },
+};
+
+/// Everything a [`CommitValidator`] gets to see about a commit before it's published
+#[derive(Debug)]
+pub struct ProposedCommit<'a> {
+    pub branch: &'a str,
+    /// The snapshot the session is based on
+    pub parent: &'a Snapshot,
+    /// Chunk changes of checkpointed arrays are only recorded by coordinates, see
+    /// [`ChangeSet::checkpointed_arrays`]. Changes to the same coordinates after the
+    /// checkpoint are newer.
+    pub change_set: &'a ChangeSet,
+    pub message: &'a str,
+    /// Commit metadata, already merged with the repository defaults
+    pub properties: &'a SnapshotProperties,
+}
+
+impl ProposedCommit<'_> {
+    /// Nodes in the snapshot that would be created, with the changes applied
+    pub fn nodes(&self) -> impl Iterator<Item = IcechunkResult<NodeSnapshot>> + '_ {
+        self.parent
+            .iter()
+            .filter_map_ok(|node| self.change_set.update_existing_node(node))
+            .chain(self.change_set.new_nodes_iterator().map(Ok))
+    }
+
+    /// Nodes created, or with metadata updated, by the commit
+    pub fn changed_nodes(
+        &self,
+    ) -> impl Iterator<Item = IcechunkResult<NodeSnapshot>> + '_ {
+        let changed: HashSet<&NodeId> = self
+            .change_set
+            .new_nodes()
+            .map(|(_, node_id)| node_id)
+            .chain(self.change_set.updated_arrays())
+            .chain(self.change_set.updated_groups())
+            .collect();
+        self.nodes().filter_ok(move |node| changed.contains(&node.id))
+    }
+}
+
+/// A check that can reject a commit
+pub trait CommitValidator: Debug + Send + Sync {
+    /// Identifies the validator in [`ValidationFailure`]
+    fn name(&self) -> &str;
+
+    /// Returns a description for each problem found, an empty result accepts the commit
+    fn validate(&self, commit: &ProposedCommit<'_>) -> IcechunkResult<Vec<String>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationFailure {
+    pub validator: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ValidationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.validator, self.reason)
+    }
+}
+
+/// Run all `validators`, collecting the failures of every one of them
+pub fn validate_commit(
+    validators: &[Arc<dyn CommitValidator>],
+    commit: &ProposedCommit<'_>,
+) -> IcechunkResult<Vec<ValidationFailure>> {
+    let mut failures = Vec::new();
+    for validator in validators {
+        failures.extend(validator.validate(commit)?.into_iter().map(|reason| {
+            ValidationFailure { validator: validator.name().to_string(), reason }
+        }));
+    }
+    Ok(failures)
+}
+
+/// Requires the given keys in the attributes of every array created or updated
+#[derive(Debug, Clone)]
+pub struct RequiredAttributes {
+    pub keys: Vec<String>,
+}
+
+impl RequiredAttributes {
+    pub fn new<S: Into<String>>(keys: impl IntoIterator<Item = S>) -> Self {
+        Self { keys: keys.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl CommitValidator for RequiredAttributes {
+    fn name(&self) -> &str {
+        "required_attributes"
+    }
+
+    fn validate(&self, commit: &ProposedCommit<'_>) -> IcechunkResult<Vec<String>> {
+        let mut res = Vec::new();
+        for node in commit.changed_nodes() {
+            let node = node?;
+            if !matches!(node.node_data, NodeData::Array { .. }) {
+                continue;
+            }
+            // user data holds the zarr metadata document
+            let attributes = serde_json::from_slice::<serde_json::Value>(&node.user_data)
+                .ok()
+                .and_then(|meta| meta.get("attributes").cloned());
+            let missing: Vec<_> = self
+                .keys
+                .iter()
+                .filter(|key| {
+                    attributes.as_ref().and_then(|atts| atts.get(key)).is_none()
+                })
+                .collect();
+            if !missing.is_empty() {
+                res.push(format!(
+                    "array {} is missing attributes {missing:?}",
+                    node.path
+                ));
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Requires a name for every dimension of the arrays created or updated
+#[derive(Debug, Clone, Default)]
+pub struct DimensionNamesRequired;
+
+impl CommitValidator for DimensionNamesRequired {
+    fn name(&self) -> &str {
+        "dimension_names_required"
+    }
+
+    fn validate(&self, commit: &ProposedCommit<'_>) -> IcechunkResult<Vec<String>> {
+        let mut res = Vec::new();
+        for node in commit.changed_nodes() {
+            let node = node?;
+            if let NodeData::Array { dimension_names, .. } = &node.node_data {
+                let named = dimension_names.as_ref().is_some_and(|names| {
+                    names.iter().all(|name| matches!(name, DimensionName::Name(_)))
+                });
+                if !named {
+                    res.push(format!("array {} has unnamed dimensions", node.path));
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Rejects chunks written outside of the final shape of their array
+///
+/// Writes are validated against the shape when they happen, but the array can be resized
+/// later in the same session.
+#[derive(Debug, Clone, Default)]
+pub struct ChunksWithinShape;
+
+impl CommitValidator for ChunksWithinShape {
+    fn name(&self) -> &str {
+        "chunks_within_shape"
+    }
+
+    fn validate(&self, commit: &ProposedCommit<'_>) -> IcechunkResult<Vec<String>> {
+        let with_changes: HashSet<&NodeId> = commit
+            .change_set
+            .arrays_with_chunk_changes()
+            .chain(commit.change_set.checkpointed_arrays().map(|(node_id, _)| node_id))
+            .collect();
+        if with_changes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut arrays: HashMap<NodeId, (Path, NodeData)> = HashMap::new();
+        for node in commit.nodes() {
+            let node = node?;
+            if with_changes.contains(&node.id) {
+                arrays.insert(node.id, (node.path, node.node_data));
+            }
+        }
+
+        let mut outside: BTreeMap<&NodeId, usize> = BTreeMap::new();
+        for (node_id, changes) in commit.change_set.chunk_changes() {
+            let Some((_, NodeData::Array { shape, .. })) = arrays.get(node_id) else {
+                // the array was deleted, its chunks won't be committed
+                continue;
+            };
+            for res in changes {
+                let (coords, payload) = res?;
+                if payload.is_some() && !shape.valid_chunk_coord(&coords) {
+                    *outside.entry(node_id).or_default() += 1;
+                }
+            }
+        }
+        for (node_id, checkpoint) in commit.change_set.checkpointed_arrays() {
+            let Some((_, NodeData::Array { shape, .. })) = arrays.get(node_id) else {
+                continue;
+            };
+            for coords in checkpoint.written.iter() {
+                if !shape.valid_chunk_coord(coords)
+                    // already counted if it was written again after the checkpoint
+                    && commit.change_set.get_chunk_ref(node_id, coords)?.is_none()
+                {
+                    *outside.entry(node_id).or_default() += 1;
+                }
+            }
+        }
+
+        Ok(outside
+            .into_iter()
+            .filter_map(|(node_id, outside)| {
+                arrays.get(node_id).map(|(path, _)| {
+                    format!("array {path} has {outside} chunks outside its shape")
+                })
+            })
+            .collect())
+    }
+}
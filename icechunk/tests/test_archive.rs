@@ -0,0 +1,128 @@
+#![allow(clippy::expect_used, clippy::unwrap_used, clippy::panic)]
+
+use std::{collections::HashMap, sync::Arc};
+
+use bytes::Bytes;
+use chrono::Utc;
+use futures::StreamExt;
+use icechunk::{
+    Repository, RepositoryConfig, Storage,
+    format::{ByteRange, ChunkId, ChunkIndices, Path, snapshot::ArrayShape},
+    new_in_memory_storage,
+    ops::archive::{ArchiveConfig, ArchiveSummary, archive_chunks},
+    repository::VersionInfo,
+    session::{Session, get_chunk},
+};
+use pretty_assertions::assert_eq;
+
+async fn read_chunk(
+    session: &Session,
+    path: &Path,
+    idx: u32,
+) -> Result<Option<Bytes>, Box<dyn std::error::Error>> {
+    let reader =
+        session.get_chunk_reader(path, &ChunkIndices(vec![idx]), &ByteRange::ALL).await?;
+    Ok(get_chunk(reader).await?)
+}
+
+#[tokio::test]
+pub async fn test_archive_old_chunks() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let archive: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let archive_settings = archive.default_settings();
+    let repo = Repository::create(
+        Some(RepositoryConfig {
+            inline_chunk_threshold_bytes: Some(0),
+            ..Default::default()
+        }),
+        Arc::clone(&storage),
+        HashMap::new(),
+    )
+    .await?;
+
+    let array_path: Path = "/array".try_into().unwrap();
+    let mut ds = repo.writable_session("main").await?;
+    ds.add_group(Path::root(), Bytes::new()).await?;
+    let shape = ArrayShape::new(vec![(20, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    for idx in 0..20 {
+        let payload = ds.get_chunk_writer()(Bytes::from_static(&[42])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    let first_snap_id = ds.commit("first", None).await?;
+    let threshold = Utc::now();
+
+    // the second version replaces half the chunks
+    let mut ds = repo.writable_session("main").await?;
+    for idx in 0..10 {
+        let payload = ds.get_chunk_writer()(Bytes::from_static(&[0])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    ds.commit("second", None).await?;
+    // garbage is not archived
+    repo.asset_manager().write_chunk(ChunkId::random(), Bytes::new()).await?;
+
+    let archive_repo = || {
+        let storage = Arc::clone(&storage);
+        let storage_settings = storage_settings.clone();
+        let archive = Arc::clone(&archive);
+        let archive_settings = archive_settings.clone();
+        let asset_manager = Arc::clone(repo.asset_manager());
+        async move {
+            archive_chunks(
+                storage.as_ref(),
+                &storage_settings,
+                asset_manager,
+                archive.as_ref(),
+                &archive_settings,
+                &ArchiveConfig::new(threshold),
+            )
+            .await
+        }
+    };
+
+    let summary = archive_repo().await?;
+    assert_eq!(summary, ArchiveSummary { chunks_archived: 10, bytes_archived: 10 });
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 21);
+    assert_eq!(archive.list_chunks(&archive_settings).await?.count().await, 10);
+    // nothing left to do
+    assert_eq!(archive_repo().await?, ArchiveSummary::default());
+
+    // without the archive, only the chunks of the old version are missing
+    let old_version = VersionInfo::SnapshotId(first_snap_id);
+    let old_ds = repo.readonly_session(&old_version).await?;
+    assert!(read_chunk(&old_ds, &array_path, 0).await.is_err());
+    assert_eq!(
+        read_chunk(&old_ds, &array_path, 15).await?,
+        Some(Bytes::from_static(&[42]))
+    );
+    let ds =
+        repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+    for idx in 0..20 {
+        let expected: &[u8] = if idx < 10 { &[0] } else { &[42] };
+        assert_eq!(read_chunk(&ds, &array_path, idx).await?.unwrap(), expected);
+    }
+
+    // the archive is attached to the existing caches, open sessions use it too
+    let asset_manager = Arc::clone(repo.asset_manager());
+    repo.set_archive_storage(Arc::clone(&archive));
+    assert!(Arc::ptr_eq(&asset_manager, repo.asset_manager()));
+    assert_eq!(
+        read_chunk(&old_ds, &array_path, 0).await?,
+        Some(Bytes::from_static(&[42]))
+    );
+    // the archive survives reopening
+    let repo = repo.reopen(None, None)?;
+    assert!(repo.archive_storage().is_some());
+    let ds = repo.readonly_session(&old_version).await?;
+    for idx in 0..20 {
+        assert_eq!(
+            read_chunk(&ds, &array_path, idx).await?,
+            Some(Bytes::from_static(&[42]))
+        );
+    }
+    Ok(())
+}
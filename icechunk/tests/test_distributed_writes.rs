@@ -7,6 +7,7 @@ use bytes::Bytes;
 use icechunk::{
     Repository, Storage,
     format::{ByteRange, ChunkIndices, Path, snapshot::ArrayShape},
+    new_in_memory_storage,
     repository::VersionInfo,
     session::{Session, get_chunk},
 };
@@ -32,7 +33,15 @@ async fn write_chunks(
     xs: Range<u32>,
     ys: Range<u32>,
 ) -> Result<Session, Box<dyn std::error::Error + Send + Sync>> {
-    let mut ds = repo.writable_session("main").await?;
+    let ds = repo.writable_session("main").await?;
+    write_chunks_to(ds, xs, ys).await
+}
+
+async fn write_chunks_to(
+    mut ds: Session,
+    xs: Range<u32>,
+    ys: Range<u32>,
+) -> Result<Session, Box<dyn std::error::Error + Send + Sync>> {
     for x in xs {
         for y in ys.clone() {
             let fx = x as f64;
@@ -113,6 +122,37 @@ async fn test_distributed_writes_in_tigris() -> Result<(), Box<dyn std::error::E
     .await
 }
 
+/// Writers get a [`Session::fork`] of the coordinator session, their changes are collected
+/// with [`Session::merge_session`] and committed once.
+#[tokio::test]
+async fn test_distributed_writes_with_forks() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let repo = mk_repo(storage, true).await?;
+    let mut ds = repo.writable_session("main").await?;
+    let shape = ArrayShape::new(vec![(SIZE as u64, 1), (SIZE as u64, 1)]).unwrap();
+    ds.add_array("/array".try_into().unwrap(), shape, None, Bytes::new()).await?;
+    ds.commit("create array", None).await?;
+
+    let mut ds = repo.writable_session("main").await?;
+    let mut set = JoinSet::new();
+    let size = SIZE as u32;
+    for x in 0..size {
+        let fork = ds.fork()?;
+        set.spawn(async move { write_chunks_to(fork, x..x + 1, 0..size).await });
+    }
+    for fork in set.join_all().await {
+        // simulates the marshalling of the fork from a remote writer
+        let bytes = fork.unwrap().as_bytes()?;
+        ds.merge_session(Session::from_bytes(bytes)?).await?;
+    }
+    ds.commit("distributed commit", None).await?;
+
+    let ds =
+        repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+    verify(ds).await?;
+    Ok(())
+}
+
 /// This test does a distributed write from 4 different [`Repository`] instances, and then commits.
 ///
 /// - We create a repo, and write an empty array to it.
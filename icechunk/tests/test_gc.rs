@@ -1,6 +1,9 @@
 #![allow(clippy::expect_used, clippy::unwrap_used, clippy::panic)]
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use bytes::Bytes;
 use chrono::{DateTime, TimeDelta, Utc};
@@ -8,20 +11,39 @@ use futures::{StreamExt, TryStreamExt};
 use icechunk::{
     Repository, RepositoryConfig, Storage,
     asset_manager::AssetManager,
-    format::{ByteRange, ChunkIndices, Path, snapshot::ArrayShape},
-    new_in_memory_storage,
-    ops::gc::{
-        ExpireRefResult, ExpiredRefAction, GCConfig, GCSummary, expire, expire_ref,
-        garbage_collect,
+    config::{BranchPolicy, RetentionPolicy},
+    conflicts::detector::ConflictDetector,
+    format::{
+        AttributesId, ByteRange, ChunkIndices, Path,
+        snapshot::{ArrayShape, SnapshotInfo},
+    },
+    new_in_memory_storage, new_local_filesystem_storage,
+    ops::{
+        ScanConfig, apply_retention,
+        gc::{
+            Action, ExpireRefResult, ExpiredRefAction, GCConfig, GCError, GCPlan,
+            GCSummary, estimate_expire_and_gc, execute_gc_plan, expire, expire_ref,
+            garbage_collect, plan_garbage_collection,
+        },
+    },
+    refs::{Ref, list_deleted_tags, list_refs, update_branch},
+    repository::{RepositoryError, RepositoryErrorKind, VersionInfo},
+    session::{
+        get_chunk,
+        lease::{SessionLease, list_leases, write_lease},
     },
-    refs::{Ref, update_branch},
-    repository::VersionInfo,
-    session::get_chunk,
+    storage,
 };
 use pretty_assertions::assert_eq;
 
 mod common;
 
+#[tokio::test]
+pub async fn test_gc_in_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    do_test_gc(storage).await
+}
+
 #[tokio::test]
 pub async fn test_gc_in_minio() -> Result<(), Box<dyn std::error::Error>> {
     let prefix = format!("test_gc_{}", Utc::now().timestamp_millis());
@@ -104,7 +126,10 @@ pub async fn do_test_gc(
 
     // verify doing gc without dangling objects doesn't change the repo
     let now = Utc::now();
-    let gc_config = GCConfig::clean_all(now, now, None);
+    // a low limit forces the chunk ids to be sorted in temporary files
+    let gc_config = GCConfig::clean_all(now, now, None).with_scan(
+        ScanConfig::default().with_max_in_memory_chunks(100).with_concurrency(4),
+    );
     let summary = garbage_collect(
         storage.as_ref(),
         &storage_settings,
@@ -131,6 +156,7 @@ pub async fn do_test_gc(
         "main",
         first_snap_id,
         Some(&second_snap_id),
+        None,
     )
     .await?;
 
@@ -337,6 +363,18 @@ pub async fn do_test_expire_ref(
         branch_commit_messages(&repo, "main").await,
         Vec::from(["14", "13", "12", "Repository initialized"])
     );
+    // the edited snapshot records the history it lost
+    let main_ancestry: Vec<SnapshotInfo> = repo
+        .ancestry(&VersionInfo::BranchTipRef("main".to_string()))
+        .await?
+        .try_collect()
+        .await?;
+    assert!(main_ancestry[0].history_edit().is_none());
+    assert!(main_ancestry[1].history_edit().is_none());
+    let edit = main_ancestry[2].history_edit().unwrap();
+    assert_eq!(edit.skipped_snapshots, 4);
+    assert_ne!(Some(&edit.original_parent_id), main_ancestry[2].parent_id.as_ref());
+    assert!(main_ancestry[3].history_edit().is_none());
     assert_eq!(
         branch_commit_messages(&repo, "develop").await,
         Vec::from(["11", "10", "6", "3", "2", "1", "Repository initialized"])
@@ -728,3 +766,720 @@ pub async fn test_expire_and_garbage_collect_deliting_expired_refs()
     assert_eq!(storage.list_snapshots(&storage_settings).await?.count().await, 8);
     Ok(())
 }
+
+#[tokio::test]
+/// Expiration doesn't delete branches whose policy forbids it
+pub async fn test_expire_keeps_protected_branches()
+-> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let mut config = RepositoryConfig::default();
+    config.set_branch_policy(
+        "protected",
+        BranchPolicy { forbid_delete: Some(true), ..Default::default() },
+    );
+    let mut repo =
+        Repository::create(Some(config), Arc::clone(&storage), HashMap::new()).await?;
+
+    let expire_older_than = make_design_doc_repo(&mut repo).await?;
+    // both branches point to an old snapshot, so they are expired
+    let old_snapshot = repo.lookup_tag("tag1").await?;
+    repo.create_branch("protected", &old_snapshot).await?;
+    repo.create_branch("scratch", &old_snapshot).await?;
+
+    let result = repo
+        .expire_snapshots(
+            expire_older_than,
+            ExpiredRefAction::Delete,
+            ExpiredRefAction::Delete,
+        )
+        .await?;
+
+    assert_eq!(
+        result.deleted_refs,
+        HashSet::from([
+            Ref::Tag("tag1".to_string()),
+            Ref::Tag("tag2".to_string()),
+            Ref::Branch("scratch".to_string()),
+        ])
+    );
+    let repo = Repository::open(None, Arc::clone(&storage), HashMap::new()).await?;
+    assert!(repo.lookup_branch("scratch").await.is_err());
+    assert_eq!(
+        branch_commit_messages(&repo, "protected").await,
+        Vec::from(["3", "Repository initialized"])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+/// The estimate for expiration and gc matches what they free when executed
+pub async fn test_estimate_expire_and_gc() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let mut repo = Repository::create(None, Arc::clone(&storage), HashMap::new()).await?;
+
+    let expire_older_than = make_design_doc_repo(&mut repo).await?;
+
+    let asset_manager = Arc::new(AssetManager::new_no_cache(
+        storage.clone(),
+        storage_settings.clone(),
+        1,
+    ));
+    // expiration rewrites the snapshots it edits, gc must run with a threshold after that
+    let after_expire = Utc::now() + TimeDelta::minutes(1);
+    let gc_config = GCConfig::clean_all(after_expire, after_expire, None);
+
+    let estimate = estimate_expire_and_gc(
+        storage.as_ref(),
+        &storage_settings,
+        asset_manager.clone(),
+        expire_older_than,
+        ExpiredRefAction::Ignore,
+        ExpiredRefAction::Ignore,
+        &gc_config,
+    )
+    .await?;
+    // other expired snapshots are pointed by tags
+    assert_eq!(estimate.snapshots.count, 5);
+    assert_eq!(estimate.transaction_logs.count, 5);
+    assert!(estimate.deleted_refs.is_empty());
+
+    let estimate = estimate_expire_and_gc(
+        storage.as_ref(),
+        &storage_settings,
+        asset_manager.clone(),
+        expire_older_than,
+        ExpiredRefAction::Delete,
+        ExpiredRefAction::Delete,
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(estimate.snapshots.count, 7);
+    assert_eq!(estimate.deleted_refs.len(), 2);
+
+    // nothing was changed
+    assert_eq!(storage.list_snapshots(&storage_settings).await?.count().await, 15);
+    assert_eq!(list_refs(storage.as_ref(), &storage_settings).await?.len(), 6);
+    assert_eq!(
+        branch_commit_messages(&repo, "main").await,
+        Vec::from(["14", "13", "12", "5", "4", "2", "1", "Repository initialized"])
+    );
+
+    let result = expire(
+        storage.as_ref(),
+        &storage_settings,
+        asset_manager.clone(),
+        expire_older_than,
+        ExpiredRefAction::Delete,
+        ExpiredRefAction::Delete,
+    )
+    .await?;
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        asset_manager.clone(),
+        &gc_config,
+    )
+    .await?;
+
+    assert_eq!(estimate.deleted_refs, result.deleted_refs);
+    assert_eq!(estimate.snapshots.count, summary.snapshots_deleted);
+    assert_eq!(estimate.transaction_logs.count, summary.transaction_logs_deleted);
+    assert_eq!(estimate.manifests.count, summary.manifests_deleted);
+    assert_eq!(estimate.chunks.count, summary.chunks_deleted);
+    // edited snapshots grow when expiration records the edit in their metadata
+    assert!(estimate.bytes_freed() <= summary.bytes_deleted);
+    assert!(estimate.bytes_freed() > estimate.snapshots.bytes);
+    Ok(())
+}
+
+#[tokio::test]
+/// Collections using a checkpoint delete the same objects as full ones
+pub async fn test_gc_with_checkpoint() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let repo = Repository::create(
+        Some(RepositoryConfig {
+            inline_chunk_threshold_bytes: Some(0),
+            ..Default::default()
+        }),
+        Arc::clone(&storage),
+        HashMap::new(),
+    )
+    .await?;
+
+    let array_path: Path = "/array".try_into().unwrap();
+    let mut ds = repo.writable_session("main").await?;
+    ds.add_group(Path::root(), Bytes::new()).await?;
+    let shape = ArrayShape::new(vec![(20, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    for idx in 0..20 {
+        let payload = ds.get_chunk_writer()(Bytes::from_static(&[42])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    let first_snap_id = ds.commit("first", None).await?;
+
+    let gc = |config: GCConfig| {
+        let storage = Arc::clone(&storage);
+        let storage_settings = storage_settings.clone();
+        let asset_manager = Arc::clone(repo.asset_manager());
+        async move {
+            garbage_collect(storage.as_ref(), &storage_settings, asset_manager, &config)
+                .await
+        }
+    };
+
+    let now = Utc::now();
+    let summary = gc(GCConfig::clean_all(now, now, None).with_checkpoint(true)).await?;
+    assert_eq!(summary, GCSummary::default());
+    // the checkpoint is not a ref, and is not stored with them
+    assert_eq!(
+        list_refs(storage.as_ref(), &storage_settings).await?,
+        [Ref::Branch("main".to_string())].into()
+    );
+    assert_eq!(storage.ref_names(&storage_settings).await?, ["branch.main"]);
+
+    let mut ds = repo.writable_session("main").await?;
+    for idx in 0..10 {
+        let payload = ds.get_chunk_writer()(Bytes::from_static(&[0])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    let second_snap_id = ds.commit("second", None).await?;
+    update_branch(
+        storage.as_ref(),
+        &storage_settings,
+        "main",
+        first_snap_id,
+        Some(&second_snap_id),
+        None,
+    )
+    .await?;
+
+    let now = Utc::now();
+    let plan = plan_garbage_collection(
+        storage.as_ref(),
+        &storage_settings,
+        Arc::clone(repo.asset_manager()),
+        &GCConfig::clean_all(now, now, None),
+    )
+    .await?;
+    let summary = gc(GCConfig::clean_all(now, now, None).with_checkpoint(true)).await?;
+    assert_eq!(summary, plan.summary());
+    assert_eq!(summary.chunks_deleted, 10);
+    assert_eq!(summary.manifests_deleted, 1);
+    assert_eq!(summary.snapshots_deleted, 1);
+
+    let ds =
+        repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+    for idx in 0..20 {
+        let bytes = get_chunk(
+            ds.get_chunk_reader(&array_path, &ChunkIndices(vec![idx]), &ByteRange::ALL)
+                .await?,
+        )
+        .await?
+        .unwrap();
+        assert_eq!(&[42], bytes.as_ref());
+    }
+
+    // nothing left to collect
+    let now = Utc::now();
+    let summary = gc(GCConfig::clean_all(now, now, None).with_checkpoint(true)).await?;
+    assert_eq!(summary, GCSummary::default());
+    Ok(())
+}
+
+#[tokio::test]
+/// Chunks written by a saved session survive gc until the session is committed
+pub async fn test_gc_keeps_saved_sessions() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let config =
+        RepositoryConfig { inline_chunk_threshold_bytes: Some(0), ..Default::default() };
+    let repo =
+        Repository::create(Some(config.clone()), Arc::clone(&storage), HashMap::new())
+            .await?;
+
+    let mut ds = repo.writable_session("main").await?;
+    let array_path: Path = "/array".try_into().unwrap();
+    let shape = ArrayShape::new(vec![(10, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    for idx in 0..5 {
+        let payload = ds.get_chunk_writer()(Bytes::from(vec![idx as u8])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    ds.checkpoint().await?;
+    for idx in 5..10 {
+        let payload = ds.get_chunk_writer()(Bytes::from(vec![idx as u8])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    ds.save("ingest").await?;
+    drop(ds);
+    // saved sessions are not stored with the refs, where older clients would choke on them
+    assert_eq!(storage.ref_names(&storage_settings).await?, ["branch.main"]);
+
+    // a future cutoff makes every unreachable object eligible
+    let gc_config = GCConfig::clean_all(
+        Utc::now() + TimeDelta::hours(1),
+        Utc::now() + TimeDelta::hours(1),
+        None,
+    );
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(summary.chunks_deleted, 0);
+    assert_eq!(summary.manifests_deleted, 0);
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 10);
+
+    // resume from a different instance
+    let repo =
+        Repository::open(Some(config), Arc::clone(&storage), HashMap::new()).await?;
+    assert_eq!(repo.saved_sessions().await?, vec!["ingest".to_string()]);
+    let mut ds = repo.resume_session("ingest").await?;
+    for idx in 0..10 {
+        let bytes = get_chunk(
+            ds.get_chunk_reader(&array_path, &ChunkIndices(vec![idx]), &ByteRange::ALL)
+                .await?,
+        )
+        .await?
+        .unwrap();
+        assert_eq!(bytes.as_ref(), &[idx as u8]);
+    }
+    ds.commit("ingested", None).await?;
+    assert!(repo.saved_sessions().await?.is_empty());
+
+    // discarding a saved session releases its chunks
+    let mut ds = repo.writable_session("main").await?;
+    let payload = ds.get_chunk_writer()(Bytes::from_static(b"x")).await?;
+    ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![0]), Some(payload)).await?;
+    ds.save("abandoned").await?;
+    repo.discard_saved_session("abandoned").await?;
+    assert!(repo.saved_sessions().await?.is_empty());
+    assert!(matches!(
+        repo.resume_session("abandoned").await,
+        Err(RepositoryError { kind: RepositoryErrorKind::SavedSessionNotFound(name), .. })
+            if name == "abandoned"
+    ));
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(summary.chunks_deleted, 1);
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 10);
+    Ok(())
+}
+
+#[tokio::test]
+/// Leases protect the objects written by sessions that didn't commit yet
+pub async fn test_gc_respects_session_leases() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let config = RepositoryConfig {
+        inline_chunk_threshold_bytes: Some(0),
+        session_lease_seconds: Some(3600),
+        ..Default::default()
+    };
+    let repo =
+        Repository::create(Some(config), Arc::clone(&storage), HashMap::new()).await?;
+    // a stale lease left by a session that crashed
+    let stale = SessionLease {
+        branch: "main".to_string(),
+        snapshot_id: repo.lookup_branch("main").await?,
+        acquired_at: Utc::now() - TimeDelta::days(2),
+        expires_at: Utc::now() - TimeDelta::days(1),
+    };
+    write_lease(storage.as_ref(), &storage_settings, "crashed", &stale).await?;
+
+    let mut ds = repo.writable_session("main").await?;
+    let array_path: Path = "/array".try_into().unwrap();
+    let shape = ArrayShape::new(vec![(10, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    for idx in 0..5 {
+        let payload = ds.get_chunk_writer()(Bytes::from(vec![idx as u8])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    ds.checkpoint().await?;
+    let leases = list_leases(storage.as_ref(), &storage_settings).await?;
+    assert_eq!(leases.len(), 2);
+    // leases are not refs, and are not stored with them
+    assert_eq!(
+        list_refs(storage.as_ref(), &storage_settings).await?,
+        [Ref::Branch("main".to_string())].into()
+    );
+    assert_eq!(storage.ref_names(&storage_settings).await?, ["branch.main"]);
+
+    // a future cutoff would delete everything the session wrote, if it wasn't for the lease
+    let gc_config = GCConfig::clean_all(
+        Utc::now() + TimeDelta::hours(1),
+        Utc::now() + TimeDelta::hours(1),
+        None,
+    );
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(summary, GCSummary::default());
+    // the expired lease was cleaned up
+    let leases = list_leases(storage.as_ref(), &storage_settings).await?;
+    assert_eq!(leases.len(), 1);
+    assert!(leases[0].1.is_active(Utc::now()));
+
+    // rebasing moves the lease to the new base snapshot
+    let mut other = repo.writable_session("main").await?;
+    other.add_group("/other".try_into().unwrap(), Bytes::new()).await?;
+    let tip = other.commit("concurrent", None).await?;
+    ds.rebase(&ConflictDetector).await?;
+    let leases = list_leases(storage.as_ref(), &storage_settings).await?;
+    assert_eq!(leases.len(), 1);
+    assert_eq!(leases[0].1.snapshot_id, tip);
+
+    ds.commit("written", None).await?;
+    assert!(list_leases(storage.as_ref(), &storage_settings).await?.is_empty());
+    let ds =
+        repo.readonly_session(&VersionInfo::BranchTipRef("main".to_string())).await?;
+    for idx in 0..5 {
+        let bytes = get_chunk(
+            ds.get_chunk_reader(&array_path, &ChunkIndices(vec![idx]), &ByteRange::ALL)
+                .await?,
+        )
+        .await?
+        .unwrap();
+        assert_eq!(bytes.as_ref(), &[idx as u8]);
+    }
+
+    // forks don't hold the lease, committing one leaves the parent lease in place
+    let parent = repo.writable_session("main").await?;
+    let mut fork = parent.fork()?;
+    fork.renew_lease().await?;
+    fork.add_group("/forked".try_into().unwrap(), Bytes::new()).await?;
+    fork.commit("forked", None).await?;
+    let leases = list_leases(storage.as_ref(), &storage_settings).await?;
+    assert_eq!(leases.len(), 1);
+    assert_eq!(&leases[0].1.snapshot_id, parent.snapshot_id());
+    Ok(())
+}
+
+#[tokio::test]
+/// Setting chunk references renews the lease once it is past half its lifetime
+pub async fn test_session_lease_renewed_by_writes()
+-> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let config = RepositoryConfig {
+        inline_chunk_threshold_bytes: Some(0),
+        session_lease_seconds: Some(2),
+        ..Default::default()
+    };
+    let repo =
+        Repository::create(Some(config), Arc::clone(&storage), HashMap::new()).await?;
+    let mut ds = repo.writable_session("main").await?;
+    let array_path: Path = "/array".try_into().unwrap();
+    let shape = ArrayShape::new(vec![(10, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    let expires_at = |leases: Vec<(String, SessionLease)>| leases[0].1.expires_at;
+    let acquired = expires_at(list_leases(storage.as_ref(), &storage_settings).await?);
+
+    // a fresh lease is not written again
+    let payload = ds.get_chunk_writer()(Bytes::from_static(b"0")).await?;
+    ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![0]), Some(payload)).await?;
+    assert_eq!(
+        expires_at(list_leases(storage.as_ref(), &storage_settings).await?),
+        acquired
+    );
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let payload = ds.get_chunk_writer()(Bytes::from_static(b"1")).await?;
+    ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![1]), Some(payload)).await?;
+    assert!(
+        expires_at(list_leases(storage.as_ref(), &storage_settings).await?) > acquired
+    );
+    Ok(())
+}
+
+#[tokio::test]
+/// A dry-run plan lists what gc would delete, and can only be executed if refs didn't move
+pub async fn test_gc_plan() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let repo = Repository::create(
+        Some(RepositoryConfig {
+            inline_chunk_threshold_bytes: Some(0),
+            ..Default::default()
+        }),
+        Arc::clone(&storage),
+        HashMap::new(),
+    )
+    .await?;
+
+    let array_path: Path = "/array".try_into().unwrap();
+    let mut ds = repo.writable_session("main").await?;
+    let shape = ArrayShape::new(vec![(10, 1)]).unwrap();
+    ds.add_array(array_path.clone(), shape, None, Bytes::new()).await?;
+    for idx in 0..10 {
+        let payload = ds.get_chunk_writer()(Bytes::from(vec![idx as u8])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    let first_snap_id = ds.commit("first", None).await?;
+
+    let mut ds = repo.writable_session("main").await?;
+    for idx in 0..3 {
+        let payload = ds.get_chunk_writer()(Bytes::from(vec![42])).await?;
+        ds.set_chunk_ref(array_path.clone(), ChunkIndices(vec![idx]), Some(payload))
+            .await?;
+    }
+    let second_snap_id = ds.commit("second", None).await?;
+    update_branch(
+        storage.as_ref(),
+        &storage_settings,
+        "main",
+        first_snap_id.clone(),
+        Some(&second_snap_id),
+        None,
+    )
+    .await?;
+
+    let now = Utc::now();
+    let gc_config = GCConfig::clean_all(now, now, None);
+    let plan = plan_garbage_collection(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(plan.snapshots.len(), 1);
+    assert_eq!(plan.snapshots[0].id, second_snap_id);
+    assert_eq!(plan.transaction_logs.len(), 1);
+    assert_eq!(plan.manifests.len(), 1);
+    assert_eq!(plan.chunks.len(), 3);
+    assert!(plan.chunks.iter().all(|chunk| chunk.size_bytes == 1));
+    // nothing was deleted
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 13);
+
+    // plans survive a round trip through their serialized form
+    let plan: GCPlan = serde_json::from_str(&serde_json::to_string(&plan)?)?;
+
+    // moving a ref invalidates the plan
+    repo.create_branch("keep", &second_snap_id).await?;
+    let res = execute_gc_plan(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &plan,
+    )
+    .await;
+    assert!(matches!(res, Err(GCError::PlanOutdated { .. })));
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 13);
+
+    repo.delete_branch("keep").await?;
+    let plan = plan_garbage_collection(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    let summary = execute_gc_plan(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &plan,
+    )
+    .await?;
+    assert_eq!(summary, plan.summary());
+    assert_eq!(summary.chunks_deleted, 3);
+    assert_eq!(storage.list_chunks(&storage_settings).await?.count().await, 10);
+    assert_eq!(storage.list_snapshots(&storage_settings).await?.count().await, 2);
+    Ok(())
+}
+
+#[tokio::test]
+/// Retention policies keep non contiguous snapshots, and shared history stays consistent
+pub async fn test_apply_retention() -> Result<(), Box<dyn std::error::Error>> {
+    let storage: Arc<dyn Storage + Send + Sync> = new_in_memory_storage().await?;
+    let storage_settings = storage.default_settings();
+    let config = RepositoryConfig {
+        retention: Some(RetentionPolicy {
+            keep_last: Some(2),
+            keep_with_metadata_keys: Some(vec!["release".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let repo =
+        Repository::create(Some(config), Arc::clone(&storage), HashMap::new()).await?;
+
+    let mut shared = None;
+    for i in 1..=6 {
+        let mut session = repo.writable_session("main").await?;
+        session.add_group(format!("/g{i}").try_into().unwrap(), Bytes::new()).await?;
+        let properties = (i == 2).then(|| {
+            [("release".to_string(), serde_json::Value::from("v1"))].into_iter().collect()
+        });
+        let snap = session.commit(&format!("main {i}"), properties).await?;
+        if i == 3 {
+            shared = Some(snap);
+        }
+    }
+    repo.create_branch("feature", &shared.unwrap()).await?;
+    for i in 1..=2 {
+        let mut session = repo.writable_session("feature").await?;
+        session.add_group(format!("/f{i}").try_into().unwrap(), Bytes::new()).await?;
+        session.commit(&format!("feature {i}"), None).await?;
+    }
+
+    // there is nothing to apply without a policy in the config
+    assert!(matches!(
+        apply_retention(
+            storage.as_ref(),
+            &storage_settings,
+            repo.asset_manager().clone(),
+            &RepositoryConfig::default(),
+            Utc::now(),
+        )
+        .await,
+        Err(GCError::NoRetentionPolicy)
+    ));
+
+    let result = apply_retention(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        repo.config(),
+        Utc::now(),
+    )
+    .await?;
+    assert_eq!(result.released_snapshots.len(), 3);
+    assert_eq!(result.edited_snapshots.len(), 3);
+
+    let root = "Repository initialized".to_string();
+    assert_eq!(
+        branch_commit_messages(&repo, "main").await,
+        ["main 6", "main 5", "main 2", &root]
+    );
+    assert_eq!(
+        branch_commit_messages(&repo, "feature").await,
+        ["feature 2", "feature 1", "main 2", &root]
+    );
+
+    let feature_ancestry: Vec<SnapshotInfo> = repo
+        .ancestry(&VersionInfo::BranchTipRef("feature".to_string()))
+        .await?
+        .try_collect()
+        .await?;
+    let edits: Vec<_> = feature_ancestry
+        .iter()
+        .map(|info| info.history_edit().map(|e| e.skipped_snapshots))
+        .collect();
+    // feature 1 skips main 3, main 2 skips main 1
+    assert_eq!(edits, [None, Some(1), Some(1), None]);
+
+    // released snapshots can be garbage collected
+    let now = Utc::now();
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &GCConfig::clean_all(now, now, None),
+    )
+    .await?;
+    assert_eq!(summary.snapshots_deleted, 3);
+    Ok(())
+}
+
+#[tokio::test]
+/// Attribute files written by older versions and stale tag delete markers are collected
+pub async fn test_gc_attributes_and_tag_markers() -> Result<(), Box<dyn std::error::Error>>
+{
+    let dir = tempfile::tempdir()?;
+    let storage: Arc<dyn Storage + Send + Sync> =
+        new_local_filesystem_storage(dir.path()).await?;
+    let storage_settings = storage.default_settings();
+    let repo = Repository::create(None, Arc::clone(&storage), HashMap::new()).await?;
+    let snap = repo.lookup_branch("main").await?;
+
+    let attributes_dir = dir.path().join("attributes");
+    std::fs::create_dir(&attributes_dir)?;
+    std::fs::write(attributes_dir.join(AttributesId::random().to_string()), b"{}")?;
+
+    repo.create_tag("deleted", &snap).await?;
+    repo.delete_tag("deleted").await?;
+    // a tag deleted by an older version, with an empty marker
+    repo.create_tag("legacy", &snap).await?;
+    storage
+        .write_ref(
+            &storage_settings,
+            "tag.legacy/ref.json.deleted",
+            Bytes::new(),
+            &storage::VersionInfo::for_creation(),
+        )
+        .await?;
+    repo.create_tag("alive", &snap).await?;
+
+    let deleted: Vec<_> = list_deleted_tags(storage.as_ref(), &storage_settings)
+        .await?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(deleted, ["deleted".to_string(), "legacy".to_string()]);
+
+    // markers are only deleted on request
+    let future = Utc::now() + TimeDelta::hours(1);
+    let plan = plan_garbage_collection(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &GCConfig::clean_all(future, future, None),
+    )
+    .await?;
+    assert!(plan.tag_delete_markers.is_empty());
+
+    // markers written after the cutoff are kept, legacy ones included
+    let past = Utc::now() - TimeDelta::hours(1);
+    let gc_config = GCConfig::clean_all(past, past, None)
+        .with_stale_tag_delete_markers(Action::DeleteIfCreatedBefore(past));
+    let plan = plan_garbage_collection(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert!(plan.tag_delete_markers.is_empty());
+
+    let gc_config = GCConfig::clean_all(future, future, None)
+        .with_stale_tag_delete_markers(Action::DeleteIfCreatedBefore(future));
+    let summary = garbage_collect(
+        storage.as_ref(),
+        &storage_settings,
+        repo.asset_manager().clone(),
+        &gc_config,
+    )
+    .await?;
+    assert_eq!(summary.attributes_deleted, 1);
+    assert_eq!(summary.tag_delete_markers_deleted, 2);
+    assert_eq!(summary.bytes_deleted, 2);
+    assert_eq!(storage.list_attributes(&storage_settings).await?.count().await, 0);
+    assert!(list_deleted_tags(storage.as_ref(), &storage_settings).await?.is_empty());
+    assert_eq!(repo.list_tags().await?, ["alive".to_string()].into());
+
+    // the name of a purged tag can be used again
+    repo.create_tag("deleted", &snap).await?;
+    assert_eq!(repo.lookup_tag("deleted").await?, snap);
+    Ok(())
+}
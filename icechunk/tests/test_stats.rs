@@ -13,7 +13,10 @@ use icechunk::{
         snapshot::ArrayShape,
     },
     new_in_memory_storage,
-    ops::stats::repo_chunks_storage,
+    ops::{
+        ScanConfig,
+        stats::{StatsConfig, repo_chunks_storage, repo_storage_stats},
+    },
 };
 
 mod common;
@@ -192,5 +195,58 @@ pub async fn do_test_repo_chunks_storage(
     .unwrap();
     // 50 native chunks from first commit, 10 from second, 5 from third
     assert_eq!(size, 50 * 6 + 10 * 6 + 5 * 6);
+
+    let stats = repo_storage_stats(
+        storage.as_ref(),
+        &storage_settings,
+        Arc::clone(&asset_manager),
+        &StatsConfig::default(),
+    )
+    .await?;
+    assert_eq!(stats.native_chunk_bytes(), size);
+    let array = &stats.arrays[&array_path];
+    assert_eq!(array.native_chunks.count, 65);
+    // dev rewrote the same inline chunks, they are counted once
+    assert_eq!(array.inline_chunks.count, 10);
+    assert_eq!(array.inline_chunks.bytes, 10);
+    assert_eq!(array.virtual_chunks.count, 10);
+    assert_eq!(array.virtual_chunks.bytes, 10 * 100);
+    assert_eq!(stats.arrays.len(), 1);
+    // the initial snapshot and one per commit
+    assert_eq!(stats.snapshots.count, 4);
+    assert_eq!(stats.manifests.count, 3);
+    assert!(stats.metadata_bytes() > 0);
+
+    // every snapshot in main is also in dev
+    assert_eq!(stats.branches["main"], 0);
+    // the third commit: its 5 chunks, manifest and snapshot
+    assert!(stats.branches["dev"] > 5 * 6);
+    assert!(stats.tags.is_empty());
+
+    // spilling chunks to disk gives the same results
+    let spilled = repo_storage_stats(
+        storage.as_ref(),
+        &storage_settings,
+        Arc::clone(&asset_manager),
+        &StatsConfig::default().with_scan(
+            ScanConfig::default().with_max_in_memory_chunks(4).with_concurrency(2),
+        ),
+    )
+    .await?;
+    assert_eq!(spilled.arrays, stats.arrays);
+    assert_eq!(spilled.branches, stats.branches);
+
+    // a tag on the tip of dev shares everything with it
+    let dev_tip = repo.lookup_branch("dev").await?;
+    repo.create_tag("v1", &dev_tip).await?;
+    let stats = repo_storage_stats(
+        storage.as_ref(),
+        &storage_settings,
+        Arc::clone(&asset_manager),
+        &StatsConfig::default(),
+    )
+    .await?;
+    assert_eq!(stats.branches["dev"], 0);
+    assert_eq!(stats.tags["v1"], 0);
     Ok(())
 }
@@ -276,6 +276,7 @@ pub async fn test_branch_initialization() -> Result<(), Box<dyn std::error::Erro
             "some-branch",
             id.clone(),
             None,
+            None,
         )
         .await?;
 
@@ -300,6 +301,7 @@ pub async fn test_fetch_non_existing_branch() -> Result<(), Box<dyn std::error::
             "some-branch",
             id.clone(),
             None,
+            None,
         )
         .await?;
 
@@ -329,6 +331,7 @@ pub async fn test_branch_update() -> Result<(), Box<dyn std::error::Error>> {
             "some-branch",
             id1.clone(),
             None,
+            None,
         )
         .await?;
 
@@ -338,6 +341,7 @@ pub async fn test_branch_update() -> Result<(), Box<dyn std::error::Error>> {
             "some-branch",
             id2.clone(),
             Some(&id1),
+            None,
         )
         .await?;
 
@@ -347,6 +351,7 @@ pub async fn test_branch_update() -> Result<(), Box<dyn std::error::Error>> {
             "some-branch",
             id3.clone(),
             Some(&id2),
+            None,
         )
         .await?;
 
@@ -366,20 +371,42 @@ pub async fn test_ref_names() -> Result<(), Box<dyn std::error::Error>> {
         let storage_settings = storage.default_settings();
         let id1 = SnapshotId::random();
         let id2 = SnapshotId::random();
-        update_branch(storage.as_ref(), &storage_settings, "main", id1.clone(), None)
-            .await?;
+        update_branch(
+            storage.as_ref(),
+            &storage_settings,
+            "main",
+            id1.clone(),
+            None,
+            None,
+        )
+        .await?;
         update_branch(
             storage.as_ref(),
             &storage_settings,
             "main",
             id2.clone(),
             Some(&id1),
+            None,
+        )
+        .await?;
+        update_branch(
+            storage.as_ref(),
+            &storage_settings,
+            "foo",
+            id1.clone(),
+            None,
+            None,
+        )
+        .await?;
+        update_branch(
+            storage.as_ref(),
+            &storage_settings,
+            "bar",
+            id1.clone(),
+            None,
+            None,
         )
         .await?;
-        update_branch(storage.as_ref(), &storage_settings, "foo", id1.clone(), None)
-            .await?;
-        update_branch(storage.as_ref(), &storage_settings, "bar", id1.clone(), None)
-            .await?;
         create_tag(storage.as_ref(), &storage_settings, "my-tag", id1.clone()).await?;
         create_tag(storage.as_ref(), &storage_settings, "my-other-tag", id1.clone())
             .await?;
@@ -396,6 +423,29 @@ pub async fn test_ref_names() -> Result<(), Box<dyn std::error::Error>> {
                 Ref::Branch("bar".to_string()),
             ])
         );
+
+        // listing can start after a key and stop once it finds enough names
+        assert_eq!(
+            storage
+                .ref_names_in(
+                    &storage_settings,
+                    "refs",
+                    "branch.",
+                    Some("branch.bar/ref.json"),
+                    Some(1)
+                )
+                .await?,
+            ["branch.foo"]
+        );
+
+        let mut tag_objects: Vec<_> = storage
+            .ref_objects_in(&storage_settings, "refs", "tag.")
+            .await?
+            .into_iter()
+            .map(|info| info.id)
+            .collect();
+        tag_objects.sort();
+        assert_eq!(tag_objects, ["tag.my-other-tag/ref.json", "tag.my-tag/ref.json"]);
         Ok(())
     })
     .await?;